@@ -26,7 +26,7 @@ use polkadot_node_subsystem::{
 		AssignmentCheckError, AssignmentCheckResult, ApprovalCheckError, ApprovalCheckResult,
 		ApprovalVotingMessage, RuntimeApiMessage, RuntimeApiRequest, ChainApiMessage,
 		ApprovalDistributionMessage, ValidationFailed, CandidateValidationMessage,
-		AvailabilityRecoveryMessage,
+		AvailabilityRecoveryMessage, ValidationOptions, ValidationSource,
 	},
 	errors::RecoveryError,
 	Subsystem, SubsystemContext, SubsystemError, SubsystemResult, SpawnedSubsystem,
@@ -1059,6 +1059,7 @@ async fn handle_background_request(
 				validation_code,
 				descriptor,
 				pov,
+				ValidationOptions::new(ValidationSource::ApprovalVoting),
 				tx,
 			).into()).await;
 
@@ -2055,7 +2056,7 @@ async fn launch_approval(
 
 		match val_rx.await {
 			Err(_) => return,
-			Ok(Ok(ValidationResult::Valid(_, _))) => {
+			Ok(Ok(ValidationResult::Valid(_, _, _))) => {
 				// Validation checked out. Issue an approval command. If the underlying service is unreachable,
 				// then there isn't anything we can do.
 
@@ -2086,6 +2087,14 @@ async fn launch_approval(
 				// https://github.com/paritytech/polkadot/issues/2176
 				metrics_guard.take().on_approval_invalid();
 			}
+			Ok(Ok(ValidationResult::Unchanged)) => {
+				tracing::error!(
+					target: LOG_TARGET,
+					"Candidate validation reported an unchanged outcome without asking for it",
+				);
+				metrics_guard.take().on_approval_error();
+				return
+			}
 			Ok(Err(e)) => {
 				tracing::error!(
 					target: LOG_TARGET,
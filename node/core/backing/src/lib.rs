@@ -41,7 +41,7 @@ use polkadot_subsystem::{
 		AllMessages, AvailabilityDistributionMessage, AvailabilityStoreMessage,
 		CandidateBackingMessage, CandidateValidationMessage, CollatorProtocolMessage,
 		ProvisionableData, ProvisionerMessage, RuntimeApiRequest,
-		StatementDistributionMessage, ValidationFailed
+		StatementDistributionMessage, ValidationFailed, ValidationOptions, ValidationSource,
 	}
 };
 use polkadot_node_subsystem_util::{
@@ -398,6 +398,7 @@ async fn request_candidate_validation(
 			CandidateValidationMessage::ValidateFromChainState(
 				candidate,
 				pov,
+				ValidationOptions::new(ValidationSource::Backing),
 				tx,
 			)
 		).into()
@@ -479,7 +480,7 @@ async fn validate_and_make_available(
 	let expected_commitments_hash = candidate.commitments_hash;
 
 	let res = match v {
-		ValidationResult::Valid(commitments, validation_data) => {
+		ValidationResult::Valid(commitments, validation_data, _) => {
 			tracing::debug!(
 				target: LOG_TARGET,
 				candidate_hash = ?candidate.hash(),
@@ -530,6 +531,9 @@ async fn validate_and_make_available(
 			);
 			Err(candidate)
 		}
+		ValidationResult::Unchanged => return Err(Error::ValidationFailed(ValidationFailed::Other(
+			"unchanged outcome reported without asking for it".into(),
+		))),
 	};
 
 	tx_command.send(make_command(res)).await.map_err(Into::into)
@@ -1599,6 +1603,7 @@ mod tests {
 					CandidateValidationMessage::ValidateFromChainState(
 						c,
 						pov,
+						_,
 						tx,
 					)
 				) if pov == pov && &c == candidate.descriptor() => {
@@ -1610,7 +1615,7 @@ mod tests {
 							new_validation_code: None,
 							processed_downward_messages: 0,
 							hrmp_watermark: 0,
-						}, test_state.validation_data),
+						}, test_state.validation_data, Default::default()),
 					)).unwrap();
 				}
 			);
@@ -1728,6 +1733,7 @@ mod tests {
 					CandidateValidationMessage::ValidateFromChainState(
 						c,
 						pov,
+						_,
 						tx,
 					)
 				) if pov == pov && &c == candidate_a.descriptor() => {
@@ -1739,7 +1745,7 @@ mod tests {
 							new_validation_code: None,
 							processed_downward_messages: 0,
 							hrmp_watermark: 0,
-						}, test_state.validation_data),
+						}, test_state.validation_data, Default::default()),
 					)).unwrap();
 				}
 			);
@@ -1879,6 +1885,7 @@ mod tests {
 					CandidateValidationMessage::ValidateFromChainState(
 						c,
 						pov,
+						_,
 						tx,
 					)
 				) if pov == pov && &c == candidate_a.descriptor() => {
@@ -2019,6 +2026,7 @@ mod tests {
 					CandidateValidationMessage::ValidateFromChainState(
 						c,
 						pov,
+						_,
 						tx,
 					)
 				) if pov == pov && &c == candidate_a.descriptor() => {
@@ -2030,7 +2038,7 @@ mod tests {
 							new_validation_code: None,
 							processed_downward_messages: 0,
 							hrmp_watermark: 0,
-						}, test_state.validation_data),
+						}, test_state.validation_data, Default::default()),
 					)).unwrap();
 				}
 			);
@@ -2151,6 +2159,7 @@ mod tests {
 					CandidateValidationMessage::ValidateFromChainState(
 						c,
 						pov,
+						_,
 						tx,
 					)
 				) if pov == pov && &c == candidate_a.descriptor() => {
@@ -2179,6 +2188,7 @@ mod tests {
 					CandidateValidationMessage::ValidateFromChainState(
 						c,
 						pov,
+						_,
 						tx,
 					)
 				) if pov == pov && &c == candidate_b.descriptor() => {
@@ -2190,7 +2200,7 @@ mod tests {
 							new_validation_code: None,
 							processed_downward_messages: 0,
 							hrmp_watermark: 0,
-						}, test_state.validation_data),
+						}, test_state.validation_data, Default::default()),
 					)).unwrap();
 				}
 			);
@@ -2288,6 +2298,7 @@ mod tests {
 					CandidateValidationMessage::ValidateFromChainState(
 						c,
 						pov,
+						_,
 						tx,
 					)
 				) if pov == pov && &c == candidate.descriptor() => {
@@ -2337,6 +2348,7 @@ mod tests {
 						_,
 						pov,
 						_,
+						_,
 					)
 				) => {
 					assert_eq!(&*pov, &pov_to_second);
@@ -2409,10 +2421,11 @@ mod tests {
 					CandidateValidationMessage::ValidateFromChainState(
 						c,
 						pov,
+						_,
 						tx,
 					)
 				) if pov == pov && &c == candidate.descriptor() => {
-					tx.send(Err(ValidationFailed("Internal test error".into()))).unwrap();
+					tx.send(Err(ValidationFailed::Other("Internal test error".into()))).unwrap();
 				}
 			);
 
@@ -2755,6 +2768,7 @@ mod tests {
 					CandidateValidationMessage::ValidateFromChainState(
 						c,
 						pov,
+						_,
 						_tx,
 					)
 				) if pov == pov && &c == candidate.descriptor()
@@ -0,0 +1,305 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! An in-memory stand-in for the candidate validation subsystem, responding to validation requests
+//! with canned outcomes instead of executing anything.
+
+use std::collections::HashMap;
+
+use futures::prelude::*;
+
+use polkadot_subsystem::{
+	Subsystem, SubsystemContext, SpawnedSubsystem, FromOverseer, OverseerSignal,
+	messages::{
+		CandidateValidationMessage, ValidationFailed, ValidationLimits, ValidationOutcomeEvent,
+		Readiness, PreCheckOutcome, ValidationHealth,
+	},
+};
+use polkadot_node_primitives::{VALIDATION_CODE_BOMB_LIMIT, POV_BOMB_LIMIT, ValidationResult};
+use polkadot_primitives::v1::{BlakeTwo256, CandidateDescriptor, CandidateHash, HashT};
+
+use super::publish_outcome;
+
+/// A fake candidate validation subsystem, meant for testing the interactions of other subsystems
+/// with candidate validation without running a PVF host.
+///
+/// It responds to each validation request with the outcome configured for the candidate, or a
+/// [`ValidationFailed`] if none is. Candidates validated by their hash are looked up by that hash,
+/// the ones validated along with their descriptor by [`Self::candidate_hash`] of the descriptor.
+pub struct FakeCandidateValidation {
+	outcomes: HashMap<CandidateHash, Result<ValidationResult, ValidationFailed>>,
+}
+
+impl FakeCandidateValidation {
+	/// Create a fake responding with the given outcomes.
+	pub fn new(outcomes: HashMap<CandidateHash, Result<ValidationResult, ValidationFailed>>) -> Self {
+		FakeCandidateValidation { outcomes }
+	}
+
+	/// The hash the outcome of a candidate validated along with the given descriptor is looked up
+	/// by.
+	pub fn candidate_hash(descriptor: &CandidateDescriptor) -> CandidateHash {
+		CandidateHash(BlakeTwo256::hash_of(descriptor))
+	}
+
+	fn outcome(&self, candidate_hash: &CandidateHash) -> Result<ValidationResult, ValidationFailed> {
+		self.outcomes
+			.get(candidate_hash)
+			.cloned()
+			.unwrap_or_else(|| Err(ValidationFailed::Other("no outcome configured for the candidate".into())))
+	}
+
+	async fn run(
+		self,
+		mut ctx: impl SubsystemContext<Message = CandidateValidationMessage>,
+	) -> polkadot_subsystem::SubsystemResult<()> {
+		let mut outcome_subscribers: Vec<futures::channel::mpsc::Sender<ValidationOutcomeEvent>> =
+			Vec::new();
+
+		loop {
+			match ctx.recv().await? {
+				FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
+				FromOverseer::Signal(_) => {}
+				FromOverseer::Communication { msg } => match msg {
+					CandidateValidationMessage::ValidateFromChainState(
+						descriptor,
+						_,
+						_,
+						response_sender,
+					)
+					| CandidateValidationMessage::ValidateFromChainStateStreamed(
+						descriptor,
+						_,
+						_,
+						response_sender,
+					)
+					| CandidateValidationMessage::ValidateFromExhaustive(
+						_,
+						_,
+						descriptor,
+						_,
+						_,
+						response_sender,
+					) => {
+						let outcome = self.outcome(&Self::candidate_hash(&descriptor));
+						publish_outcome(&mut outcome_subscribers, &descriptor, &outcome, None, None);
+						let _ = response_sender.send(outcome);
+					}
+					CandidateValidationMessage::ValidateFromHash(
+						candidate_hash,
+						_,
+						_,
+						response_sender,
+					) => {
+						let _ = response_sender.send(self.outcome(&candidate_hash));
+					}
+					CandidateValidationMessage::ValidateBatch(candidates, response_sender) => {
+						let outcomes = candidates
+							.iter()
+							.map(|(_, _, descriptor, _)| {
+								let outcome = self.outcome(&Self::candidate_hash(descriptor));
+								publish_outcome(&mut outcome_subscribers, descriptor, &outcome, None, None);
+								outcome
+							})
+							.collect();
+						let _ = response_sender.send(outcomes);
+					}
+					CandidateValidationMessage::BasicCheck(_, _, _, _, response_sender) => {
+						let _ = response_sender.send(Ok(()));
+					}
+					CandidateValidationMessage::GetLimits(response_sender) => {
+						let _ = response_sender.send(ValidationLimits {
+							validation_code_bomb_limit: VALIDATION_CODE_BOMB_LIMIT,
+							pov_bomb_limit: POV_BOMB_LIMIT,
+							execution_timeout: polkadot_node_core_pvf::EXECUTION_TIMEOUT,
+							worker_idle_timeout: None,
+							execution_memory_limit: None,
+						});
+					}
+					CandidateValidationMessage::GetReadiness(response_sender) => {
+						let _ = response_sender.send(Readiness::Ready);
+					}
+					CandidateValidationMessage::QueryHealth(response_sender) => {
+						let _ = response_sender.send(ValidationHealth {
+							host_running: true,
+							recent_worker_deaths: 0,
+						});
+					}
+					CandidateValidationMessage::SubscribeOutcomes(subscriber) => {
+						outcome_subscribers.push(subscriber);
+					}
+					CandidateValidationMessage::FlushArtifactCache(response_sender) => {
+						let _ = response_sender.send(0);
+					}
+					CandidateValidationMessage::PreCheck(_, _, response_sender) => {
+						let _ = response_sender.send(PreCheckOutcome::Valid);
+					}
+				},
+			}
+		}
+	}
+}
+
+impl<C> Subsystem<C> for FakeCandidateValidation where
+	C: SubsystemContext<Message = CandidateValidationMessage>,
+{
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		SpawnedSubsystem {
+			name: "fake-candidate-validation-subsystem",
+			future: self.run(ctx).boxed(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use polkadot_node_subsystem_test_helpers as test_helpers;
+	use polkadot_node_primitives::{InvalidCandidate, PoV, BlockData};
+	use polkadot_primitives::v1::{Hash, PersistedValidationData, ValidationCode};
+	use polkadot_subsystem::messages::{CandidateResolvers, PublishedOutcome};
+	use futures::channel::{mpsc, oneshot};
+	use futures::executor;
+	use assert_matches::assert_matches;
+	use sp_core::testing::TaskExecutor;
+	use std::sync::Arc;
+
+	#[test]
+	fn responds_to_each_message_with_the_configured_outcome() {
+		let mut invalid = CandidateDescriptor::default();
+		invalid.para_id = 1.into();
+		let mut valid = CandidateDescriptor::default();
+		valid.para_id = 2.into();
+		let by_hash = CandidateHash(Hash::repeat_byte(3));
+
+		let outcomes = vec![
+			(
+				FakeCandidateValidation::candidate_hash(&invalid),
+				Ok(ValidationResult::Invalid(InvalidCandidate::BadReturn)),
+			),
+			(
+				FakeCandidateValidation::candidate_hash(&valid),
+				Ok(ValidationResult::Valid(Default::default(), Default::default(), Default::default())),
+			),
+			(by_hash, Err(ValidationFailed::Other("canned".into()))),
+		].into_iter().collect();
+
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+		let subsystem = FakeCandidateValidation::new(outcomes).start(ctx);
+
+		let test_fut = async move {
+			let pov = Arc::new(PoV { block_data: BlockData(Vec::new()) });
+
+			let (subscriber, mut events) = mpsc::channel(4);
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::SubscribeOutcomes(subscriber),
+			}).await;
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateFromChainState(
+					invalid,
+					pov.clone(),
+					Default::default(),
+					tx,
+				),
+			}).await;
+			assert_matches!(rx.await.unwrap(), Ok(ValidationResult::Invalid(InvalidCandidate::BadReturn)));
+			assert_matches!(events.next().await.unwrap().outcome, PublishedOutcome::Invalid { .. });
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateFromExhaustive(
+					PersistedValidationData::default(),
+					ValidationCode(Vec::new()),
+					valid.clone(),
+					pov.clone(),
+					Default::default(),
+					tx,
+				),
+			}).await;
+			assert_matches!(rx.await.unwrap(), Ok(ValidationResult::Valid(_, _, _)));
+			assert_matches!(events.next().await.unwrap().outcome, PublishedOutcome::Valid { .. });
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateFromChainStateStreamed(
+					valid,
+					mpsc::channel(1).1,
+					Default::default(),
+					tx,
+				),
+			}).await;
+			assert_matches!(rx.await.unwrap(), Ok(ValidationResult::Valid(_, _, _)));
+			assert_matches!(events.next().await.unwrap().outcome, PublishedOutcome::Valid { .. });
+
+			let resolvers = CandidateResolvers {
+				descriptor: mpsc::channel(1).0,
+				validation_code: mpsc::channel(1).0,
+				persisted_validation_data: mpsc::channel(1).0,
+				pov: mpsc::channel(1).0,
+			};
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateFromHash(by_hash, resolvers, Default::default(), tx),
+			}).await;
+			assert_matches!(rx.await.unwrap(), Err(ValidationFailed::Other(e)) if e == "canned");
+
+			// Unknown candidates fail.
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateFromChainState(
+					CandidateDescriptor::default(),
+					pov,
+					Default::default(),
+					tx,
+				),
+			}).await;
+			assert_matches!(rx.await.unwrap(), Err(ValidationFailed::Other(_)));
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::GetLimits(tx),
+			}).await;
+			assert_eq!(rx.await.unwrap().pov_bomb_limit, POV_BOMB_LIMIT);
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::GetReadiness(tx),
+			}).await;
+			assert_eq!(rx.await.unwrap(), Readiness::Ready);
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::QueryHealth(tx),
+			}).await;
+			assert_eq!(rx.await.unwrap().recent_worker_deaths, 0);
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::FlushArtifactCache(tx),
+			}).await;
+			assert_eq!(rx.await.unwrap(), 0);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (res, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert!(res.is_ok());
+	}
+}
@@ -24,37 +24,113 @@
 #![warn(missing_docs)]
 
 use polkadot_subsystem::{
-	Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemResult, SubsystemError,
-	FromOverseer, OverseerSignal,
+	Subsystem, SubsystemContext, SubsystemSender, SpawnedSubsystem, SubsystemResult, SubsystemError,
+	FromOverseer, OverseerSignal, ActiveLeavesUpdate,
 	messages::{
 		AllMessages, CandidateValidationMessage, RuntimeApiMessage,
-		ValidationFailed, RuntimeApiRequest,
+		ValidationFailed, RuntimeApiRequest, ValidationLimits, ValidationOutcomeEvent,
+		AssumptionCheckFailure,
+		PublishedOutcome, ValidationSource, RuntimeStateSnapshot, DisputeSeverity, PvfExecPriority,
+		CandidateResolvers, ResolveRequest, ValidationStep, Readiness, pov_integrity_checksum,
+		PreCheckOutcome, ProofRequest, ExecutionKind, ValidationHealth, ValidationOptions,
 	},
 };
 use polkadot_node_subsystem_util::metrics::{self, prometheus};
 use polkadot_subsystem::errors::RuntimeApiError;
 use polkadot_node_primitives::{
 	VALIDATION_CODE_BOMB_LIMIT, POV_BOMB_LIMIT, ValidationResult, InvalidCandidate, PoV, BlockData,
+	ValidationProof,
 };
 use polkadot_primitives::v1::{
 	ValidationCode, CandidateDescriptor, PersistedValidationData,
-	OccupiedCoreAssumption, Hash, CandidateCommitments,
+	OccupiedCoreAssumption, Hash, CandidateCommitments, Id as ParaId, HeadData,
+	BlakeTwo256, HashT, CandidateHash, BlockNumber, ValidationCodeHash, CandidateReceipt,
 };
 use polkadot_parachain::primitives::{ValidationParams, ValidationResult as WasmValidationResult};
-use polkadot_node_core_pvf::{Pvf, ValidationHost, ValidationError, InvalidCandidate as WasmInvalidCandidate};
+use polkadot_node_core_pvf::{
+	Pvf, ValidationHost, ValidationError, InvalidCandidate as WasmInvalidCandidate, Priority,
+	PrepareError,
+};
 
 use parity_scale_codec::Encode;
+use sp_keystore::SyncCryptoStorePtr;
 
-use futures::channel::oneshot;
+use futures::channel::{mpsc, oneshot};
 use futures::prelude::*;
+use futures::stream::FuturesUnordered;
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::sync::Arc;
 use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
 
+use async_lock::{Mutex, Semaphore};
 use async_trait::async_trait;
+use tracing::Instrument;
+
+#[cfg(feature = "test-helpers")]
+mod fake;
+#[cfg(feature = "test-helpers")]
+pub use fake::FakeCandidateValidation;
+
+mod native;
+pub use native::{NativeValidationBackend, NativeValidationFunction};
 
 const LOG_TARGET: &'static str = "parachain::candidate-validation";
 
+/// The time allotted for spawning a worker and completing the handshake with it on startup.
+const WORKER_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The number of times the PVF hosts are restarted after exiting unexpectedly, before the
+/// subsystem gives up.
+const MAX_PVF_HOST_RESTARTS: u32 = 3;
+
+/// The recommended time allotted for a single PVF execution. Slower hardware or parachains with
+/// heavier blocks may warrant more, at the cost of slower rejection of candidates that never
+/// conclude.
+pub const DEFAULT_EXECUTION_TIMEOUT: Duration = polkadot_node_core_pvf::EXECUTION_TIMEOUT;
+
+/// The recommended time allotted for a single PVF execution for approval checking or dispute
+/// participation. These aren't as pressed for time as backing, so an honest but slow candidate is
+/// given more time before concluding it timed out.
+pub const DEFAULT_APPROVAL_EXECUTION_TIMEOUT: Duration =
+	Duration::from_secs(polkadot_node_core_pvf::EXECUTION_TIMEOUT.as_secs() * 4);
+
+/// The recommended time allotted for preparing a PVF. Preparation is done once per validation
+/// code, so this can be generous.
+pub const DEFAULT_PREPARATION_TIMEOUT: Duration = polkadot_node_core_pvf::COMPILATION_TIMEOUT;
+
+/// The recommended number of retries of the execution of a PVF after an ambiguous worker death.
+pub const DEFAULT_AMBIGUOUS_WORKER_DEATH_RETRIES: usize = 2;
+
+/// The recommended schedule by which restarting a PVF host is delayed while it keeps exiting.
+pub const DEFAULT_PVF_HOST_RESTART_BACKOFF: polkadot_node_core_pvf::RespawnBackoff =
+	polkadot_node_core_pvf::RespawnBackoff {
+		initial: Duration::from_secs(1),
+		max: Duration::from_secs(30),
+	};
+
+/// The recommended number of validations in flight at once. This exceeds the number of execution
+/// workers of a PVF host, so that they're kept busy while some validations fetch their inputs.
+pub const DEFAULT_MAX_CONCURRENT_VALIDATIONS: usize = 8;
+
+/// The recommended time allotted for the validations in flight to complete once the subsystem is
+/// asked to conclude.
+pub const DEFAULT_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// The maximum number of distinct values observed per label attached to the metrics. Any further
+/// values are observed as `other`.
+pub const MAX_METRIC_LABEL_VALUES: usize = 16;
+
+/// The time allotted for handing a message over to the overseer. Taking longer means the overseer
+/// is saturated, in which case the validation at hand fails rather than stalling the subsystem.
+const OVERSEER_SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The target of the detailed events emitted for validations requested to be verbose.
+const VERBOSE_LOG_TARGET: &'static str = "parachain::candidate-validation::verbose";
+
 /// Configuration for the candidate validation subsystem
 #[derive(Clone)]
 pub struct Config {
@@ -63,865 +139,10518 @@ pub struct Config {
 	/// The path to the executable which can be used for spawning PVF compilation & validation
 	/// workers.
 	pub program_path: PathBuf,
+	/// The time after which a PVF execution worker that hasn't executed anything is terminated
+	/// to reclaim its resources. It is respawned on demand. `None` keeps idle workers around.
+	pub worker_idle_timeout: Option<Duration>,
+	/// The number of PVF execution workers of each PVF host, i.e. the number of candidates it
+	/// executes in parallel. Every worker is a process of its own, holding the compiled PVF and the
+	/// memory of the execution, which may be hundreds of megabytes for a heavy PVF, so raise this
+	/// only with the RAM to match. `None` keeps the default of the PVF host.
+	pub max_workers: Option<usize>,
+	/// The time allotted for a single PVF execution for backing, and for requests of no specific
+	/// [`ExecutionKind`]. An execution that doesn't conclude in time renders the candidate invalid
+	/// with [`InvalidCandidate::Timeout`].
+	///
+	/// See [`DEFAULT_EXECUTION_TIMEOUT`] for the recommended value.
+	pub execution_timeout: Duration,
+	/// The time allotted for a single PVF execution for approval checking and dispute
+	/// participation. The PVF hosts are configured with the longer of this and
+	/// `execution_timeout`, and shorter executions are timed out by the subsystem.
+	///
+	/// See [`DEFAULT_APPROVAL_EXECUTION_TIMEOUT`] for the recommended value.
+	pub approval_execution_timeout: Duration,
+	/// The time allotted for preparing a PVF. A PVF that doesn't prepare in time can't be
+	/// executed, rendering the candidates using it invalid.
+	///
+	/// See [`DEFAULT_PREPARATION_TIMEOUT`] for the recommended value.
+	pub preparation_timeout: Duration,
+	/// Whether to prepare the current validation code of the paras assigned to availability cores
+	/// once the first leaf is activated, so that the first candidates of each para after a
+	/// restart don't pay for compiling their code.
+	pub prewarm_on_startup: bool,
+	/// The maximum total size, in bytes, of the compiled artifacts each PVF host keeps on disk.
+	/// Once it's exceeded, the least recently needed artifacts are pruned. The artifacts prepared
+	/// by [`Config::prewarm_on_startup`] count towards it as well, so a limit too small for the
+	/// code of all the paras assigned to cores leaves some of them to be compiled again when
+	/// needed. `None` only prunes the artifacts that weren't needed for a day.
+	pub max_artifacts_cache_size: Option<u64>,
+	/// The amount of memory in bytes a single PVF execution may use on top of what the worker
+	/// used before it. Exceeding it fails the validation for internal reasons rather than
+	/// rendering the candidate invalid, as the peak memory of a worker also depends on the
+	/// executions before. Such a failure is no verdict on the candidate, so dispute participation
+	/// doesn't vote on it. Only supported on Linux: elsewhere, the limit is ignored apart from a
+	/// warning logged as the PVF host starts. `None` doesn't limit the memory beyond the heap pages
+	/// of the PVF.
+	pub execution_memory_limit: Option<u64>,
+	/// If set, a `PersistedValidationData` the runtime reports as missing is requested once more
+	/// after this delay before giving up. This papers over the data being briefly unavailable
+	/// while the relay-parent is being imported.
+	pub validation_data_retry_delay: Option<Duration>,
+	/// The maximum size of the head data included in the events published to outcome
+	/// subscribers. Larger head data is truncated, while its hash always refers to the full head
+	/// data. The result returned to the requester is never affected. `None` publishes the full
+	/// head data.
+	pub published_head_data_limit: Option<usize>,
+	/// Limits on the rate of validation requests accepted from each source. Requests above the
+	/// limit are answered with a `ValidationFailed`. Sources without a limit are not throttled, and
+	/// neither are approval voting and dispute participation, whose limits are ignored.
+	pub rate_limits: HashMap<ValidationSource, RateLimit>,
+	/// Whether to defer the checks of the outputs of candidates validated from chain state to the
+	/// next block boundary, for requests providing a callback for the outcome. The checks are
+	/// then performed together, and the requesters receive a provisionally valid result right
+	/// away.
+	pub defer_output_checks: bool,
+	/// The maximum number of occupied core assumptions to try when looking for the persisted
+	/// validation data a candidate is based on, before concluding it has a bad parent. `None`
+	/// means all of them are tried.
+	pub max_assumptions_to_try: Option<usize>,
+	/// Whether to also try the `Free` occupied core assumption, after the `TimedOut` one didn't
+	/// match. The runtime is expected to answer both alike, so this only costs an extra request
+	/// to the runtime API for every candidate with a bad parent, unless the runtime drifted.
+	pub check_free_assumption: bool,
+	/// Whether to request the validation code under an occupied core assumption along with the
+	/// persisted validation data, rather than only once the data turned out to match the
+	/// candidate. This saves a round-trip to the runtime API on the common path, at the cost of a
+	/// discarded request for every assumption that doesn't match.
+	pub prefetch_validation_code: bool,
+	/// The policy mapping the reasons of invalidity to the severity published along with the
+	/// outcome of a validation. `None` treats every invalid candidate as disputable.
+	pub dispute_policy: Option<DisputePolicy>,
+	/// The maximum number of results of `ValidateFromExhaustive` requests to keep around when
+	/// their requester hung up before receiving them. An identical request arriving later is then
+	/// served without validating the candidate again. `None` discards such results.
+	pub unclaimed_results_capacity: Option<usize>,
+	/// The maximum total size in bytes of the outcomes of PVF executions to keep around, keyed by
+	/// the hashes of the validation code, the PoV and the persisted validation data they were
+	/// executed with. Validating a candidate with inputs seen before then skips decompressing and
+	/// executing it. Only deterministic outcomes are kept. `None` disables the cache.
+	pub execution_cache_size: Option<usize>,
+	/// The maximum total size in bytes of the validation code decompressed while pre-checking PVFs
+	/// to keep around, so that the first validation of a candidate with that code doesn't
+	/// decompress it again. Only code found valid is kept. `None` disables the cache.
+	pub precheck_code_cache_size: Option<usize>,
+	/// The number of PVF hosts to start. Validations are dispatched to them in a round-robin
+	/// fashion. With more than one host, each keeps its artifacts in a dedicated subdirectory of
+	/// `artifacts_cache_path`.
+	pub pvf_hosts: usize,
+	/// The maximum number of validations in flight at once. Once reached, further validation
+	/// requests are rejected with [`ValidationFailed::Overloaded`] until validations complete,
+	/// except for those of approval voting and dispute participation, which are queued instead. At
+	/// least one validation is always let in flight. Each candidate of a batch counts as a
+	/// validation, and a batch is rejected as a whole unless all of them fit.
+	pub max_concurrent_validations: usize,
+	/// The time allotted for the validations in flight to complete once the subsystem is asked to
+	/// conclude. Those still running afterwards are cancelled, and their requesters answered with
+	/// [`ValidationFailed::ShuttingDown`].
+	pub shutdown_grace_period: Duration,
+	/// The number of leading bytes of a PoV to log, in hex, if it fails to decompress. This helps
+	/// diagnosing mismatches of the compression format. `0` disables the preview.
+	pub pov_preview_len: usize,
+	/// If set, a compact record of the durations of the phases of each validation is appended to
+	/// this file, for profiling validations without a tracing backend.
+	pub span_log: Option<SpanLogConfig>,
+	/// The maximum estimated cost of preparing the validation code of a candidate. Validation of
+	/// candidates whose code exceeds it fails without attempting the preparation, bounding the
+	/// time spent compiling huge PVFs. `None` doesn't limit the cost.
+	///
+	/// See [`estimate_preparation_cost`] for how the cost is estimated.
+	pub max_preparation_cost: Option<u64>,
+	/// The time allotted for decompressing the PoV of a candidate, separately from the execution
+	/// timeout. If set, the decompression runs on a dedicated thread and a PoV that doesn't
+	/// decompress in time renders the candidate invalid, like one that doesn't decompress at all.
+	/// `None` decompresses without a time limit.
+	pub pov_decompression_timeout: Option<Duration>,
+	/// The maximum size in bytes the validation code of a candidate may decompress to. Code
+	/// decompressing to more renders the candidate invalid. `None` applies
+	/// [`VALIDATION_CODE_BOMB_LIMIT`], which only test networks should raise.
+	pub validation_code_bomb_limit: Option<usize>,
+	/// The maximum size in bytes of the validation code of a candidate as provided, i.e. before
+	/// decompressing it. Larger code renders the candidate invalid with
+	/// [`InvalidCandidate::CodeTooLarge`] without hashing or decompressing it. `None` leaves the
+	/// size of compressed code to the bomb limit, which only applies while decompressing it.
+	pub max_compressed_code_size: Option<usize>,
+	/// The maximum size in bytes the PoV of a candidate may decompress to. A PoV decompressing to
+	/// more renders the candidate invalid. `None` applies [`POV_BOMB_LIMIT`], which only test
+	/// networks should raise.
+	pub pov_bomb_limit: Option<usize>,
+	/// The maximum number of candidates whose validation code and PoV may be decompressed at the
+	/// same time. Decompression happens in the subsystem process rather than in a worker, so this
+	/// bounds the memory it takes up. Must not be zero. `None` doesn't limit the decompressions.
+	pub max_concurrent_decompressions: Option<usize>,
+	/// The schedule by which respawning PVF execution workers is delayed while they keep dying,
+	/// so that a crash loop ramps down the respawn frequency. `None` respawns them right away.
+	pub worker_respawn_backoff: Option<polkadot_node_core_pvf::RespawnBackoff>,
+	/// The schedule by which restarting a PVF host that exited unexpectedly is delayed, by the
+	/// number of restarts so far. `None` restarts it right away.
+	///
+	/// See [`DEFAULT_PVF_HOST_RESTART_BACKOFF`] for the recommended value.
+	pub pvf_host_restart_backoff: Option<polkadot_node_core_pvf::RespawnBackoff>,
+	/// The number of times the execution of a PVF is retried after the worker died ambiguously,
+	/// before concluding that the candidate is invalid. Such deaths are often caused by the node
+	/// itself, e.g. by the OOM killer, rather than by the candidate.
+	///
+	/// See [`DEFAULT_AMBIGUOUS_WORKER_DEATH_RETRIES`] for the recommended value.
+	pub ambiguous_worker_death_retries: usize,
+	/// Whether to execute the PVF of every candidate twice and compare the outputs, rendering the
+	/// candidate invalid with [`InvalidCandidate::NonDeterministic`] if they differ. This doubles
+	/// the cost of validation, so it is meant for a few canary validators watching out for
+	/// non-deterministic parachain runtimes.
+	pub differential_execution: bool,
+	/// The names of the host functions withheld from PVFs, e.g. `ext_logging_log_version_1`, to
+	/// lock down the surface exposed to them. A PVF calling any of them renders the candidate
+	/// invalid with [`InvalidCandidate::ExecutionError`].
+	pub disabled_host_functions: Vec<String>,
+	/// The keys of the labels supplied with validation requests which are attached to the metrics.
+	/// Other labels only appear in the logs. At most [`MAX_METRIC_LABEL_VALUES`] distinct values
+	/// are observed per key, keeping the cardinality of the metrics bounded.
+	pub metric_labels: HashSet<String>,
+	/// Reports whether the node is currently an active validator, e.g. by querying the validator
+	/// set of the current session. While it reports `false`, validations for backing are declined
+	/// to save resources, whereas those for approval voting and dispute participation are still
+	/// served. `None` serves all validations.
+	pub active_validator_check: Option<ActiveValidatorCheck>,
+	/// The keystore proofs of the outcomes of validations are signed with, if requested. `None`
+	/// doesn't answer such requests.
+	pub keystore: Option<SyncCryptoStorePtr>,
+	/// The sink every completed validation is recorded to, e.g. for persisting the outcomes for
+	/// audits. [`NoopRecordSink`] discards them.
+	pub record_sink: Arc<dyn ValidationRecordSink>,
 }
 
-/// The candidate validation subsystem.
-pub struct CandidateValidationSubsystem {
-	metrics: Metrics,
-	config: Config,
+/// A predicate reporting whether the node is currently an active validator.
+pub type ActiveValidatorCheck = Arc<dyn Fn() -> bool + Send + Sync>;
+
+/// A sink for the records of completed validations, allowing to persist them durably.
+///
+/// Records are passed synchronously, so an implementation backed by slow storage should buffer
+/// them rather than block the subsystem.
+pub trait ValidationRecordSink: Send + Sync {
+	/// Record the outcome of a completed validation. The head data of a valid candidate is never
+	/// truncated.
+	fn record(&self, outcome: &ValidationOutcomeEvent);
 }
 
-impl CandidateValidationSubsystem {
-	/// Create a new `CandidateValidationSubsystem` with the given task spawner and isolation
-	/// strategy.
-	///
-	/// Check out [`IsolationStrategy`] to get more details.
-	pub fn with_config(config: Config, metrics: Metrics) -> Self {
-		CandidateValidationSubsystem { config, metrics, }
-	}
-}
+/// A [`ValidationRecordSink`] discarding all records.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopRecordSink;
 
-impl<C> Subsystem<C> for CandidateValidationSubsystem where
-	C: SubsystemContext<Message = CandidateValidationMessage>,
-{
-	fn start(self, ctx: C) -> SpawnedSubsystem {
-		let future = run(ctx, self.metrics, self.config.artifacts_cache_path, self.config.program_path)
-			.map_err(|e| SubsystemError::with_origin("candidate-validation", e))
-			.boxed();
-		SpawnedSubsystem {
-			name: "candidate-validation-subsystem",
-			future,
-		}
-	}
+impl ValidationRecordSink for NoopRecordSink {
+	fn record(&self, _outcome: &ValidationOutcomeEvent) {}
 }
 
-async fn run(
-	mut ctx: impl SubsystemContext<Message = CandidateValidationMessage>,
-	metrics: Metrics,
-	cache_path: PathBuf,
-	program_path: PathBuf,
-) -> SubsystemResult<()> {
-	let (mut validation_host, task) = polkadot_node_core_pvf::start(
-		polkadot_node_core_pvf::Config::new(cache_path, program_path),
-	);
-	ctx.spawn_blocking("pvf-validation-host", task.boxed()).await?;
+/// Configuration of the file records of validation spans are appended to.
+#[derive(Debug, Clone)]
+pub struct SpanLogConfig {
+	/// The path of the file.
+	pub path: PathBuf,
+	/// The size in bytes beyond which the file is rotated. The previous records are kept in a
+	/// single file with the `.1` suffix appended, replacing any earlier one.
+	pub max_file_size: u64,
+}
 
-	loop {
-		match ctx.recv().await? {
-			FromOverseer::Signal(OverseerSignal::ActiveLeaves(_)) => {}
-			FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {}
-			FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
-			FromOverseer::Communication { msg } => match msg {
-				CandidateValidationMessage::ValidateFromChainState(
-					descriptor,
-					pov,
-					response_sender,
-				) => {
-					let _timer = metrics.time_validate_from_chain_state();
+/// Validation code decompressed for the candidates of a batch, by its hash.
+type DecompressedCodeCache = Arc<Mutex<HashMap<ValidationCodeHash, Arc<Vec<u8>>>>>;
 
-					let res = spawn_validate_from_chain_state(
-						&mut ctx,
-						&mut validation_host,
-						descriptor,
-						pov,
-						&metrics,
-					).await;
+/// Validation code decompressed while pre-checking it, by its hash. The total size of the code is
+/// bounded, evicting the code used least recently first.
+#[derive(Debug)]
+struct PrecheckedCode {
+	max_size: usize,
+	size: usize,
+	codes: VecDeque<(ValidationCodeHash, Arc<Vec<u8>>)>,
+}
 
-					match res {
-						Ok(x) => {
-							metrics.on_validation_event(&x);
-							let _ = response_sender.send(x);
-						}
-						Err(e) => return Err(e),
-					}
-				}
-				CandidateValidationMessage::ValidateFromExhaustive(
-					persisted_validation_data,
-					validation_code,
-					descriptor,
-					pov,
-					response_sender,
-				) => {
-					let _timer = metrics.time_validate_from_exhaustive();
+impl PrecheckedCode {
+	fn new(max_size: usize) -> Self {
+		PrecheckedCode { max_size, size: 0, codes: VecDeque::new() }
+	}
 
-					let res = validate_candidate_exhaustive(
-						&mut validation_host,
-						persisted_validation_data,
-						validation_code,
-						descriptor,
-						pov,
-						&metrics,
-					).await;
+	/// Keep the given decompressed code with the given hash, evicting other code as needed.
+	fn insert(&mut self, hash: ValidationCodeHash, code: Arc<Vec<u8>>) {
+		if code.len() > self.max_size {
+			return;
+		}
 
-					match res {
-						Ok(x) => {
-							metrics.on_validation_event(&x);
-							if let Err(_e) = response_sender.send(x) {
-								tracing::warn!(
-									target: LOG_TARGET,
-									"Requester of candidate validation dropped",
-								)
-							}
-						},
-						Err(e) => return Err(e),
-					}
-				}
+		let _ = self.remove(&hash);
+		while self.size + code.len() > self.max_size {
+			match self.codes.pop_front() {
+				Some((_, evicted)) => self.size -= evicted.len(),
+				None => break,
 			}
 		}
+		self.size += code.len();
+		self.codes.push_back((hash, code));
 	}
-}
 
-async fn runtime_api_request<T>(
-	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
-	relay_parent: Hash,
-	request: RuntimeApiRequest,
-	receiver: oneshot::Receiver<Result<T, RuntimeApiError>>,
-) -> SubsystemResult<Result<T, RuntimeApiError>> {
-	ctx.send_message(
-		AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-			relay_parent,
-			request,
-		))
-	).await;
+	/// The decompressed code with the given hash, if kept.
+	fn get(&mut self, hash: &ValidationCodeHash) -> Option<Arc<Vec<u8>>> {
+		let code = self.remove(hash)?;
+		self.size += code.len();
+		self.codes.push_back((*hash, code.clone()));
+		Some(code)
+	}
 
-	receiver.await.map_err(Into::into)
+	fn remove(&mut self, hash: &ValidationCodeHash) -> Option<Arc<Vec<u8>>> {
+		let index = self.codes.iter().position(|(h, _)| h == hash)?;
+		let (_, code) = self.codes.remove(index)?;
+		self.size -= code.len();
+		Some(code)
+	}
 }
 
-#[derive(Debug)]
-enum AssumptionCheckOutcome {
-	Matches(PersistedValidationData, ValidationCode),
-	DoesNotMatch,
-	BadRequest,
+/// The parts of the [`Config`], of the subsystem state and of the request affecting the validation
+/// of a candidate once its inputs are known.
+#[derive(Debug, Clone, Default)]
+pub struct ExhaustiveOptions {
+	span_log: Option<SpanLogConfig>,
+	max_preparation_cost: Option<u64>,
+	pov_decompression_timeout: Option<Duration>,
+	/// The maximum decompressed size of the validation code, if not the default one.
+	validation_code_bomb_limit: Option<usize>,
+	/// The maximum size of the validation code before decompressing it, if limited.
+	max_compressed_code_size: Option<usize>,
+	/// The maximum decompressed size of the PoV, if not the default one.
+	pov_bomb_limit: Option<usize>,
+	/// The permits to hold while decompressing, if the decompressions are limited.
+	decompression_permits: Option<Arc<Semaphore>>,
+	/// The outcomes of earlier executions, if cached.
+	execution_cache: Option<Arc<Mutex<ExecutionCache>>>,
+	/// The number of the highest active leaf seen so far, if any.
+	best_known_number: Option<BlockNumber>,
+	/// The priority with which the PVF is executed.
+	priority: PvfExecPriority,
+	/// The number of times the execution is retried after an ambiguous worker death.
+	ambiguous_worker_death_retries: usize,
+	/// Whether the PVF is executed twice to compare the outputs.
+	differential_execution: bool,
+	/// The kind of validation the PVF is executed for.
+	execution_kind: ExecutionKind,
+	/// The time allotted for the execution for backing, if timed out by the subsystem.
+	backing_execution_timeout: Option<Duration>,
+	/// The time allotted for the execution for approval checking and dispute participation, if
+	/// timed out by the subsystem.
+	approval_execution_timeout: Option<Duration>,
+	/// The known hash of the decompressed validation code, if provided with the request.
+	decompressed_code_hash: Option<Hash>,
+	/// The hash of the decompressed validation code of the PVF to execute from its prepared
+	/// artifact, if provided with the request.
+	prepared_artifact: Option<ValidationCodeHash>,
+	/// The validation code decompressed for other candidates of the same batch, if validated as
+	/// part of a batch.
+	decompressed_code_cache: Option<DecompressedCodeCache>,
+	/// The validation code decompressed while pre-checking it, if kept.
+	prechecked_code: Option<Arc<Mutex<PrecheckedCode>>>,
+	/// The recent ambiguous deaths of execution workers.
+	worker_deaths: Arc<Mutex<WorkerDeaths>>,
+	/// The seed the PVF draws its randomness from, if provided with the request.
+	execution_seed: Option<[u8; 32]>,
 }
 
-async fn check_assumption_validation_data(
-	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
-	descriptor: &CandidateDescriptor,
-	assumption: OccupiedCoreAssumption,
-) -> SubsystemResult<AssumptionCheckOutcome> {
-	let validation_data = {
-		let (tx, rx) = oneshot::channel();
-		let d = runtime_api_request(
-			ctx,
-			descriptor.relay_parent,
-			RuntimeApiRequest::PersistedValidationData(
-				descriptor.para_id,
-				assumption,
-				tx,
-			),
-			rx,
-		).await?;
-
-		match d {
-			Ok(None) | Err(_) => {
-				return Ok(AssumptionCheckOutcome::BadRequest);
-			}
-			Ok(Some(d)) => d,
+impl ExhaustiveOptions {
+	fn from_config(config: &Config) -> Self {
+		ExhaustiveOptions {
+			span_log: config.span_log.clone(),
+			max_preparation_cost: config.max_preparation_cost,
+			pov_decompression_timeout: config.pov_decompression_timeout,
+			validation_code_bomb_limit: config.validation_code_bomb_limit,
+			max_compressed_code_size: config.max_compressed_code_size,
+			pov_bomb_limit: config.pov_bomb_limit,
+			decompression_permits: config.max_concurrent_decompressions
+				.map(|limit| Arc::new(Semaphore::new(limit))),
+			execution_cache: config.execution_cache_size
+				.map(|size| Arc::new(Mutex::new(ExecutionCache::new(size)))),
+			best_known_number: None,
+			priority: PvfExecPriority::Normal,
+			ambiguous_worker_death_retries: config.ambiguous_worker_death_retries,
+			differential_execution: config.differential_execution,
+			execution_kind: ExecutionKind::Backing,
+			backing_execution_timeout: Some(config.execution_timeout),
+			approval_execution_timeout: Some(config.approval_execution_timeout),
+			decompressed_code_hash: None,
+			prepared_artifact: None,
+			decompressed_code_cache: None,
+			prechecked_code: config.precheck_code_cache_size
+				.map(|size| Arc::new(Mutex::new(PrecheckedCode::new(size)))),
+			worker_deaths: Default::default(),
+			execution_seed: None,
 		}
-	};
+	}
 
-	let persisted_validation_data_hash = validation_data.hash();
+	/// The options for validating a candidate requested with the given priority.
+	fn with_priority(&self, priority: PvfExecPriority) -> Self {
+		ExhaustiveOptions { priority, ..self.clone() }
+	}
 
-	SubsystemResult::Ok(if descriptor.persisted_validation_data_hash == persisted_validation_data_hash {
-		let (code_tx, code_rx) = oneshot::channel();
-		let validation_code = runtime_api_request(
-			ctx,
-			descriptor.relay_parent,
-			RuntimeApiRequest::ValidationCode(
-				descriptor.para_id,
-				assumption,
-				code_tx,
-			),
-			code_rx,
-		).await?;
+	/// The options for validating a candidate for the given kind of validation.
+	fn with_execution_kind(self, execution_kind: ExecutionKind) -> Self {
+		ExhaustiveOptions { execution_kind, ..self }
+	}
 
-		match validation_code {
-			Ok(None) | Err(_) => AssumptionCheckOutcome::BadRequest,
-			Ok(Some(v)) => AssumptionCheckOutcome::Matches(validation_data, v),
-		}
-	} else {
-		AssumptionCheckOutcome::DoesNotMatch
-	})
-}
+	/// The options for validating a candidate whose validation code is known to decompress to code
+	/// with the given hash, if any.
+	fn with_decompressed_code_hash(self, decompressed_code_hash: Option<Hash>) -> Self {
+		ExhaustiveOptions { decompressed_code_hash, ..self }
+	}
 
-async fn find_assumed_validation_data(
-	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
-	descriptor: &CandidateDescriptor,
-) -> SubsystemResult<AssumptionCheckOutcome> {
-	// The candidate descriptor has a `persisted_validation_data_hash` which corresponds to
-	// one of up to two possible values that we can derive from the state of the
-	// relay-parent. We can fetch these values by getting the persisted validation data
-	// based on the different `OccupiedCoreAssumption`s.
+	/// The options for validating a candidate whose PVF is executed from the artifact prepared for
+	/// the decompressed validation code with the given hash, if any.
+	fn with_prepared_artifact(self, prepared_artifact: Option<ValidationCodeHash>) -> Self {
+		ExhaustiveOptions { prepared_artifact, ..self }
+	}
 
-	const ASSUMPTIONS: &[OccupiedCoreAssumption] = &[
-		OccupiedCoreAssumption::Included,
-		OccupiedCoreAssumption::TimedOut,
-		// `TimedOut` and `Free` both don't perform any speculation and therefore should be the same
-		// for our purposes here. In other words, if `TimedOut` matched then the `Free` must be
-		// matched as well.
-	];
+	/// The options for validating a candidate whose PVF draws its randomness from the given seed,
+	/// if any.
+	fn with_execution_seed(self, execution_seed: Option<[u8; 32]>) -> Self {
+		ExhaustiveOptions { execution_seed, ..self }
+	}
 
-	// Consider running these checks in parallel to reduce validation latency.
-	for assumption in ASSUMPTIONS {
-		let outcome = check_assumption_validation_data(ctx, descriptor, *assumption).await?;
+	/// The options for validating a candidate sharing the decompressed validation code with the
+	/// other candidates of its batch through the given cache.
+	fn with_decompressed_code_cache(self, cache: DecompressedCodeCache) -> Self {
+		ExhaustiveOptions { decompressed_code_cache: Some(cache), ..self }
+	}
 
-		match outcome {
-			AssumptionCheckOutcome::Matches(_, _) => return Ok(outcome),
-			AssumptionCheckOutcome::BadRequest => return Ok(outcome),
-			AssumptionCheckOutcome::DoesNotMatch => continue,
+	/// The time allotted for the execution, if timed out by the subsystem.
+	fn execution_timeout(&self) -> Option<Duration> {
+		match self.execution_kind {
+			ExecutionKind::Backing => self.backing_execution_timeout,
+			ExecutionKind::Approval | ExecutionKind::Dispute => self.approval_execution_timeout,
 		}
 	}
+}
 
-	Ok(AssumptionCheckOutcome::DoesNotMatch)
+/// Estimate the cost of preparing the given decompressed validation code.
+///
+/// The time it takes to compile a PVF roughly grows with the size of its code, so the estimate is
+/// simply that size in bytes.
+pub fn estimate_preparation_cost(raw_validation_code: &[u8]) -> u64 {
+	raw_validation_code.len() as u64
 }
 
-async fn spawn_validate_from_chain_state(
-	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
-	validation_host: &mut ValidationHost,
-	descriptor: CandidateDescriptor,
-	pov: Arc<PoV>,
-	metrics: &Metrics,
-) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
-	let (validation_data, validation_code) =
-		match find_assumed_validation_data(ctx, &descriptor).await? {
-			AssumptionCheckOutcome::Matches(validation_data, validation_code) => {
-				(validation_data, validation_code)
-			}
-			AssumptionCheckOutcome::DoesNotMatch => {
-				// If neither the assumption of the occupied core having the para included or the assumption
-				// of the occupied core timing out are valid, then the persisted_validation_data_hash in the descriptor
-				// is not based on the relay parent and is thus invalid.
-				return Ok(Ok(ValidationResult::Invalid(InvalidCandidate::BadParent)));
-			}
-			AssumptionCheckOutcome::BadRequest => {
-				return Ok(Err(ValidationFailed("Assumption Check: Bad request".into())));
-			}
+/// A policy mapping the reason a candidate is invalid to the severity with which it should be
+/// treated by the dispute logic.
+pub type DisputePolicy = Arc<dyn Fn(&InvalidCandidate) -> DisputeSeverity + Send + Sync>;
+
+/// Determine the dispute severity of the given invalidity reason under the given policy.
+fn dispute_severity(policy: Option<&DisputePolicy>, reason: &InvalidCandidate) -> DisputeSeverity {
+	policy.map_or_else(DisputeSeverity::default, |policy| policy(reason))
+}
+
+/// A limit on the number of validation requests accepted from a single source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+	/// The maximum number of requests accepted within a window.
+	pub max_requests: u32,
+	/// The length of a window.
+	pub window: Duration,
+}
+
+/// Throttles validation requests per source according to the configured [`RateLimit`]s.
+struct RateLimiter {
+	limits: HashMap<ValidationSource, RateLimit>,
+	/// The start of the current window and the number of requests accepted within it, per source.
+	windows: HashMap<ValidationSource, (Instant, u32)>,
+}
+
+impl RateLimiter {
+	fn new(limits: HashMap<ValidationSource, RateLimit>) -> Self {
+		RateLimiter { limits, windows: HashMap::new() }
+	}
+
+	/// Account for a request from the given source. Returns `false` if the source is over its
+	/// limit and the request should be rejected. Essential sources are never throttled.
+	fn try_acquire(&mut self, source: ValidationSource, now: Instant) -> bool {
+		let limit = match self.limits.get(&source) {
+			Some(limit) if !is_essential(source) => limit,
+			_ => return true,
 		};
 
-	let validation_result = validate_candidate_exhaustive(
-		validation_host,
-		validation_data,
-		validation_code,
-		descriptor.clone(),
-		pov,
-		metrics,
-	)
-	.await;
+		let (window_start, accepted) = self.windows.entry(source).or_insert((now, 0));
+		if now.saturating_duration_since(*window_start) >= limit.window {
+			*window_start = now;
+			*accepted = 0;
+		}
 
-	if let Ok(Ok(ValidationResult::Valid(ref outputs, _))) = validation_result {
-		let (tx, rx) = oneshot::channel();
-		match runtime_api_request(
-			ctx,
-			descriptor.relay_parent,
-			RuntimeApiRequest::CheckValidationOutputs(descriptor.para_id, outputs.clone(), tx),
-			rx,
-		)
-		.await?
-		{
-			Ok(true) => {}
-			Ok(false) => {
-				return Ok(Ok(ValidationResult::Invalid(
-					InvalidCandidate::InvalidOutputs,
-				)));
-			}
-			Err(_) => {
-				return Ok(Err(ValidationFailed("Check Validation Outputs: Bad request".into())));
-			}
+		if *accepted < limit.max_requests {
+			*accepted += 1;
+			true
+		} else {
+			false
 		}
 	}
-
-	validation_result
 }
 
-async fn validate_candidate_exhaustive(
-	mut validation_backend: impl ValidationBackend,
-	persisted_validation_data: PersistedValidationData,
-	validation_code: ValidationCode,
-	descriptor: CandidateDescriptor,
-	pov: Arc<PoV>,
-	metrics: &Metrics,
-) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
-	let _timer = metrics.time_validate_candidate_exhaustive();
+/// Results of validations whose requesters hung up, kept to serve identical requests arriving
+/// later. The oldest results are evicted first.
+struct UnclaimedResults {
+	capacity: usize,
+	results: VecDeque<(Hash, ValidationResult)>,
+}
 
-	if let Err(e) = perform_basic_checks(
-		&descriptor,
-		persisted_validation_data.max_pov_size,
-		&*pov,
-		&validation_code,
-	) {
-		return Ok(Ok(ValidationResult::Invalid(e)));
+impl UnclaimedResults {
+	fn new(capacity: usize) -> Self {
+		UnclaimedResults { capacity, results: VecDeque::new() }
 	}
 
-	let raw_validation_code = match sp_maybe_compressed_blob::decompress(
-		&validation_code.0,
-		VALIDATION_CODE_BOMB_LIMIT,
-	) {
-		Ok(code) => code,
-		Err(e) => {
-			tracing::debug!(target: LOG_TARGET, err=?e, "Invalid validation code");
+	fn insert(&mut self, key: Hash, result: ValidationResult) {
+		if self.capacity == 0 {
+			return;
+		}
 
-			// If the validation code is invalid, the candidate certainly is.
-			return Ok(Ok(ValidationResult::Invalid(InvalidCandidate::CodeDecompressionFailure)));
+		if self.results.len() == self.capacity {
+			self.results.pop_front();
 		}
-	};
+		self.results.push_back((key, result));
+	}
 
-	let raw_block_data = match sp_maybe_compressed_blob::decompress(
-		&pov.block_data.0,
-		POV_BOMB_LIMIT,
-	) {
-		Ok(block_data) => BlockData(block_data.to_vec()),
-		Err(e) => {
-			tracing::debug!(target: LOG_TARGET, err=?e, "Invalid PoV code");
+	/// Take the result stored under the given key, if any.
+	fn take(&mut self, key: &Hash) -> Option<ValidationResult> {
+		let index = self.results.iter().position(|(k, _)| k == key)?;
+		self.results.remove(index).map(|(_, result)| result)
+	}
 
-			// If the PoV is invalid, the candidate certainly is.
-			return Ok(Ok(ValidationResult::Invalid(InvalidCandidate::PoVDecompressionFailure)));
-		}
-	};
+	/// Like [`Self::take`], but also accounts for the lookup in the metrics.
+	fn lookup(&mut self, key: &Hash, metrics: &Metrics) -> Option<ValidationResult> {
+		let result = self.take(key);
+		metrics.on_result_cache_lookup(result.is_some());
+		result
+	}
+}
 
-	let params = ValidationParams {
-		parent_head: persisted_validation_data.parent_head.clone(),
-		block_data: raw_block_data,
-		relay_parent_number: persisted_validation_data.relay_parent_number,
-		relay_parent_storage_root: persisted_validation_data.relay_parent_storage_root,
-	};
+/// The hashes of the validation code, the PoV and the persisted validation data a PVF is executed
+/// with.
+type ExecutionCacheKey = (ValidationCodeHash, Hash, Hash);
 
-	let result =
-		validation_backend.validate_candidate(
-			raw_validation_code.to_vec(),
-			params
-		)
-		.await;
+/// The outcomes of earlier executions of PVFs, keyed by the hashes of their inputs. The total
+/// encoded size of the outcomes is bounded, evicting the ones used least recently first.
+#[derive(Debug)]
+struct ExecutionCache {
+	max_size: usize,
+	size: usize,
+	outcomes: VecDeque<(ExecutionCacheKey, Result<WasmValidationResult, ValidationError>, usize)>,
+}
 
-	if let Err(ref e) = result {
-		tracing::debug!(
-			target: LOG_TARGET,
-			error = ?e,
-			"Failed to validate candidate",
-		);
+impl ExecutionCache {
+	fn new(max_size: usize) -> Self {
+		ExecutionCache { max_size, size: 0, outcomes: VecDeque::new() }
 	}
 
-	let result = match result {
-		Err(ValidationError::InternalError(e)) => Err(ValidationFailed(e)),
+	/// Whether the given outcome of an execution is the same for every execution with the same
+	/// inputs. Timeouts and worker deaths depend on the circumstances of the execution instead,
+	/// and internal errors don't concern the candidate at all.
+	fn is_deterministic(outcome: &Result<WasmValidationResult, ValidationError>) -> bool {
+		match outcome {
+			Ok(_) => true,
+			Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::WorkerReportedError(_))) => true,
+			Err(_) => false,
+		}
+	}
 
-		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::HardTimeout)) =>
-			Ok(ValidationResult::Invalid(InvalidCandidate::Timeout)),
-		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::WorkerReportedError(e))) =>
-			Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError(e))),
-		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::AmbigiousWorkerDeath)) =>
-			Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError("ambigious worker death".to_string()))),
+	/// Note the outcome of an execution with the given inputs, unless it isn't deterministic.
+	fn insert(&mut self, key: ExecutionCacheKey, outcome: Result<WasmValidationResult, ValidationError>) {
+		if !Self::is_deterministic(&outcome) {
+			return;
+		}
 
-		Ok(res) => {
-			if res.head_data.hash() != descriptor.para_head {
-				Ok(ValidationResult::Invalid(InvalidCandidate::ParaHeadHashMismatch))
-			} else {
-				let outputs = CandidateCommitments {
-					head_data: res.head_data,
-					upward_messages: res.upward_messages,
-					horizontal_messages: res.horizontal_messages,
-					new_validation_code: res.new_validation_code,
-					processed_downward_messages: res.processed_downward_messages,
-					hrmp_watermark: res.hrmp_watermark,
-				};
-				Ok(ValidationResult::Valid(outputs, persisted_validation_data))
+		let size = match outcome {
+			Ok(ref result) => result.encoded_size(),
+			// Of the errors kept, only the message reported by the worker varies in size.
+			Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::WorkerReportedError(
+				ref message,
+			))) => std::mem::size_of::<ValidationError>() + message.len(),
+			Err(_) => std::mem::size_of::<ValidationError>(),
+		};
+		if size > self.max_size {
+			return;
+		}
+
+		self.remove(&key);
+		while self.size + size > self.max_size {
+			match self.outcomes.pop_front() {
+				Some((_, _, evicted)) => self.size -= evicted,
+				None => break,
 			}
 		}
-	};
+		self.size += size;
+		self.outcomes.push_back((key, outcome, size));
+	}
 
-	Ok(result)
-}
+	/// The outcome of an earlier execution with the given inputs, if any.
+	fn get(&mut self, key: &ExecutionCacheKey) -> Option<Result<WasmValidationResult, ValidationError>> {
+		let entry = self.remove(key)?;
+		let outcome = entry.1.clone();
+		self.size += entry.2;
+		self.outcomes.push_back(entry);
+		Some(outcome)
+	}
 
-#[async_trait]
-trait ValidationBackend {
-	async fn validate_candidate(
+	/// Like [`Self::get`], but also accounts for the lookup in the metrics.
+	fn lookup(
 		&mut self,
-		raw_validation_code: Vec<u8>,
-		params: ValidationParams
-	) -> Result<WasmValidationResult, ValidationError>;
-}
+		key: &ExecutionCacheKey,
+		metrics: &Metrics,
+	) -> Option<Result<WasmValidationResult, ValidationError>> {
+		let outcome = self.get(key);
+		metrics.on_execution_cache_lookup(outcome.is_some());
+		outcome
+	}
 
-#[async_trait]
-impl ValidationBackend for &'_ mut ValidationHost {
-	async fn validate_candidate(
+	fn remove(
 		&mut self,
-		raw_validation_code: Vec<u8>,
-		params: ValidationParams
-	) -> Result<WasmValidationResult, ValidationError> {
-		let (tx, rx) = oneshot::channel();
-		if let Err(err) = self.execute_pvf(
-			Pvf::from_code(raw_validation_code),
-			params.encode(),
-			polkadot_node_core_pvf::Priority::Normal,
-			tx,
-		).await {
-			return Err(ValidationError::InternalError(format!("cannot send pvf to the validation host: {:?}", err)));
-		}
+		key: &ExecutionCacheKey,
+	) -> Option<(ExecutionCacheKey, Result<WasmValidationResult, ValidationError>, usize)> {
+		let index = self.outcomes.iter().position(|(k, _, _)| k == key)?;
+		let entry = self.outcomes.remove(index)?;
+		self.size -= entry.2;
+		Some(entry)
+	}
+}
 
-		let validation_result = rx
-			.await
-			.map_err(|_| ValidationError::InternalError("validation was cancelled".into()))?;
+/// The period within which deaths of execution workers are reported as recent.
+const RECENT_WORKER_DEATHS_PERIOD: Duration = Duration::from_secs(10 * 60);
 
-		validation_result
-	}
+/// The times of the ambiguous deaths of execution workers within the
+/// [`RECENT_WORKER_DEATHS_PERIOD`], oldest first.
+#[derive(Debug, Default)]
+struct WorkerDeaths {
+	times: VecDeque<Instant>,
 }
 
-/// Does basic checks of a candidate. Provide the encoded PoV-block. Returns `Ok` if basic checks
-/// are passed, `Err` otherwise.
-fn perform_basic_checks(
-	candidate: &CandidateDescriptor,
-	max_pov_size: u32,
-	pov: &PoV,
-	validation_code: &ValidationCode,
-) -> Result<(), InvalidCandidate> {
-	let pov_hash = pov.hash();
-	let validation_code_hash = validation_code.hash();
+impl WorkerDeaths {
+	/// Note that a worker died at the given time.
+	fn note(&mut self, now: Instant) {
+		self.prune(now);
+		self.times.push_back(now);
+	}
 
-	let encoded_pov_size = pov.encoded_size();
-	if encoded_pov_size > max_pov_size as usize {
-		return Err(InvalidCandidate::ParamsTooLarge(encoded_pov_size as u64));
+	/// The number of workers that died within the period ending at the given time.
+	fn recent(&mut self, now: Instant) -> usize {
+		self.prune(now);
+		self.times.len()
 	}
 
-	if pov_hash != candidate.pov_hash {
-		return Err(InvalidCandidate::PoVHashMismatch);
+	fn prune(&mut self, now: Instant) {
+		while let Some(time) = self.times.front() {
+			if now.saturating_duration_since(*time) <= RECENT_WORKER_DEATHS_PERIOD {
+				break;
+			}
+			let _ = self.times.pop_front();
+		}
 	}
+}
+
+/// The number of candidates whose last validated inputs are remembered for requests asking to
+/// validate them only if their inputs changed.
+const LAST_VALIDATED_CAPACITY: usize = 1024;
+
+/// The inputs candidates were last validated with on behalf of requests asking to validate them
+/// only if their inputs changed, keyed by the hash of the candidate descriptor. The candidates
+/// validated least recently are forgotten first.
+struct LastValidatedInputs {
+	inputs: VecDeque<(Hash, Hash)>,
+}
 
-	if validation_code_hash != candidate.validation_code_hash {
-		return Err(InvalidCandidate::CodeHashMismatch);
+impl LastValidatedInputs {
+	fn new() -> Self {
+		LastValidatedInputs { inputs: VecDeque::new() }
 	}
 
-	if let Err(()) = candidate.check_collator_signature() {
-		return Err(InvalidCandidate::BadSignature);
+	/// Whether the candidate was last validated with the given inputs.
+	fn is_unchanged(&self, candidate: &Hash, inputs: &Hash) -> bool {
+		self.inputs.iter().any(|(c, i)| c == candidate && i == inputs)
 	}
 
-	Ok(())
+	/// Note that the candidate was validated with the given inputs.
+	fn note(&mut self, candidate: Hash, inputs: Hash) {
+		self.inputs.retain(|(c, _)| *c != candidate);
+		if self.inputs.len() == LAST_VALIDATED_CAPACITY {
+			self.inputs.pop_front();
+		}
+		self.inputs.push_back((candidate, inputs));
+	}
 }
 
-#[derive(Clone)]
-struct MetricsInner {
-	validation_requests: prometheus::CounterVec<prometheus::U64>,
-	validate_from_chain_state: prometheus::Histogram,
-	validate_from_exhaustive: prometheus::Histogram,
-	validate_candidate_exhaustive: prometheus::Histogram,
+/// The key identifying the inputs of a `ValidateFromExhaustive` request.
+fn exhaustive_request_key(
+	persisted_validation_data: &PersistedValidationData,
+	validation_code: &ValidationCode,
+	descriptor: &CandidateDescriptor,
+	pov: &PoV,
+) -> Hash {
+	let digest = InputDigest::new(validation_code, pov, descriptor);
+	BlakeTwo256::hash_of(&(digest.0, persisted_validation_data.hash()))
 }
 
-/// Candidate validation metrics.
-#[derive(Default, Clone)]
-pub struct Metrics(Option<MetricsInner>);
+/// The number of most recent validations the readiness of the subsystem is derived from.
+const READINESS_WINDOW: usize = 20;
 
-impl Metrics {
-	fn on_validation_event(&self, event: &Result<ValidationResult, ValidationFailed>) {
-		if let Some(metrics) = &self.0 {
-			match event {
-				Ok(ValidationResult::Valid(_, _)) => {
-					metrics.validation_requests.with_label_values(&["valid"]).inc();
-				},
-				Ok(ValidationResult::Invalid(_)) => {
-					metrics.validation_requests.with_label_values(&["invalid"]).inc();
-				},
-				Err(_) => {
-					metrics.validation_requests.with_label_values(&["validation failure"]).inc();
-				},
-			}
+/// Tracks the readiness of the subsystem from its startup progress and the failure rate of the
+/// most recent validations.
+struct ReadinessTracker {
+	hosts_started: bool,
+	hosts_running: bool,
+	/// Whether each of the most recent validations failed, oldest first.
+	recent_failures: VecDeque<bool>,
+}
+
+impl ReadinessTracker {
+	fn new() -> Self {
+		ReadinessTracker {
+			hosts_started: false,
+			hosts_running: false,
+			recent_failures: VecDeque::with_capacity(READINESS_WINDOW),
 		}
 	}
 
-	/// Provide a timer for `validate_from_chain_state` which observes on drop.
-	fn time_validate_from_chain_state(&self) -> Option<metrics::prometheus::prometheus::HistogramTimer> {
-		self.0.as_ref().map(|metrics| metrics.validate_from_chain_state.start_timer())
+	fn current(&self) -> Readiness {
+		if !self.hosts_started {
+			return Readiness::Starting;
+		}
+
+		if !self.hosts_running {
+			return Readiness::Unavailable;
+		}
+
+		let failures = self.recent_failures.iter().filter(|failed| **failed).count();
+		if failures * 2 > self.recent_failures.len() {
+			Readiness::Degraded
+		} else {
+			Readiness::Ready
+		}
 	}
 
-	/// Provide a timer for `validate_from_exhaustive` which observes on drop.
-	fn time_validate_from_exhaustive(&self) -> Option<metrics::prometheus::prometheus::HistogramTimer> {
-		self.0.as_ref().map(|metrics| metrics.validate_from_exhaustive.start_timer())
+	/// Note that the validation hosts were started.
+	fn on_hosts_started(&mut self, metrics: &Metrics) {
+		self.hosts_started = true;
+		self.hosts_running = true;
+		metrics.on_readiness(self.current());
 	}
 
-	/// Provide a timer for `validate_candidate_exhaustive` which observes on drop.
-	fn time_validate_candidate_exhaustive(&self) -> Option<metrics::prometheus::prometheus::HistogramTimer> {
-		self.0.as_ref().map(|metrics| metrics.validate_candidate_exhaustive.start_timer())
+	/// Note whether any of the validation hosts is still running.
+	fn on_hosts_checked(&mut self, any_running: bool, metrics: &Metrics) {
+		self.hosts_running = any_running;
+		metrics.on_readiness(self.current());
 	}
-}
 
-impl metrics::Metrics for Metrics {
-	fn try_register(registry: &prometheus::Registry) -> Result<Self, prometheus::PrometheusError> {
-		let metrics = MetricsInner {
-			validation_requests: prometheus::register(
-				prometheus::CounterVec::new(
-					prometheus::Opts::new(
-						"parachain_validation_requests_total",
-						"Number of validation requests served.",
-					),
-					&["validity"],
-				)?,
-				registry,
-			)?,
-			validate_from_chain_state: prometheus::register(
-				prometheus::Histogram::with_opts(
-					prometheus::HistogramOpts::new(
-						"parachain_candidate_validation_validate_from_chain_state",
-						"Time spent within `candidate_validation::validate_from_chain_state`",
-					)
-				)?,
-				registry,
-			)?,
-			validate_from_exhaustive: prometheus::register(
-				prometheus::Histogram::with_opts(
-					prometheus::HistogramOpts::new(
-						"parachain_candidate_validation_validate_from_exhaustive",
-						"Time spent within `candidate_validation::validate_from_exhaustive`",
-					)
-				)?,
-				registry,
-			)?,
-			validate_candidate_exhaustive: prometheus::register(
-				prometheus::Histogram::with_opts(
-					prometheus::HistogramOpts::new(
-						"parachain_candidate_validation_validate_candidate_exhaustive",
-						"Time spent within `candidate_validation::validate_candidate_exhaustive`",
-					)
-				)?,
-				registry,
-			)?,
-		};
-		Ok(Metrics(Some(metrics)))
+	/// Note the outcome of a validation.
+	fn on_validation(&mut self, res: &Result<ValidationResult, ValidationFailed>, metrics: &Metrics) {
+		if self.recent_failures.len() == READINESS_WINDOW {
+			self.recent_failures.pop_front();
+		}
+		self.recent_failures.push_back(res.is_err());
+		metrics.on_readiness(self.current());
 	}
 }
 
-#[cfg(test)]
-mod tests {
-	use super::*;
-	use polkadot_node_subsystem_test_helpers as test_helpers;
-	use polkadot_primitives::v1::{HeadData, UpwardMessage};
-	use sp_core::testing::TaskExecutor;
-	use futures::executor;
-	use assert_matches::assert_matches;
-	use sp_keyring::Sr25519Keyring;
+/// The candidate validation subsystem.
+pub struct CandidateValidationSubsystem {
+	metrics: Metrics,
+	config: Config,
+	backend: Option<Box<dyn ValidationBackend + Send>>,
+}
 
-	fn collator_sign(descriptor: &mut CandidateDescriptor, collator: Sr25519Keyring) {
-		descriptor.collator = collator.public().into();
-		let payload = polkadot_primitives::v1::collator_signature_payload(
-			&descriptor.relay_parent,
-			&descriptor.para_id,
-			&descriptor.persisted_validation_data_hash,
-			&descriptor.pov_hash,
-			&descriptor.validation_code_hash,
-		);
+impl CandidateValidationSubsystem {
+	/// Create a new `CandidateValidationSubsystem` with the given task spawner and isolation
+	/// strategy.
+	///
+	/// Check out [`IsolationStrategy`] to get more details.
+	pub fn with_config(config: Config, metrics: Metrics) -> Self {
+		CandidateValidationSubsystem { config, metrics, backend: None }
+	}
 
-		descriptor.signature = collator.sign(&payload[..]).into();
-		assert!(descriptor.check_collator_signature().is_ok());
+	/// Create a new `CandidateValidationSubsystem` executing PVFs with the given backend instead of
+	/// starting PVF validation hosts. The parts of the config concerning the hosts are ignored.
+	pub fn with_backend(
+		config: Config,
+		backend: impl ValidationBackend + Send + 'static,
+		metrics: Metrics,
+	) -> Self {
+		CandidateValidationSubsystem { config, metrics, backend: Some(Box::new(backend)) }
 	}
+}
 
-	#[test]
-	fn correctly_checks_included_assumption() {
-		let validation_data: PersistedValidationData = Default::default();
-		let validation_code: ValidationCode = vec![1, 2, 3].into();
+impl<C> Subsystem<C> for CandidateValidationSubsystem where
+	C: SubsystemContext<Message = CandidateValidationMessage>,
+{
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		let future = run(ctx, self.metrics, self.config, self.backend)
+			.map_err(|e| SubsystemError::with_origin("candidate-validation", e))
+			.boxed();
+		SpawnedSubsystem {
+			name: "candidate-validation-subsystem",
+			future,
+		}
+	}
+}
 
-		let persisted_validation_data_hash = validation_data.hash();
-		let relay_parent = [2; 32].into();
-		let para_id = 5.into();
+/// Derive the configuration of the PVF validation host from the subsystem configuration.
+fn pvf_host_config(config: &Config) -> polkadot_node_core_pvf::Config {
+	let mut pvf_config = polkadot_node_core_pvf::Config::new(
+		config.artifacts_cache_path.clone(),
+		config.program_path.clone(),
+	);
+	pvf_config.execute_worker_idle_timeout = config.worker_idle_timeout;
+	if let Some(max_workers) = config.max_workers {
+		pvf_config.execute_workers_max_num = max_workers.max(1);
+	}
+	pvf_config.execute_timeout = config.execution_timeout.max(config.approval_execution_timeout);
+	pvf_config.prepare_timeout = config.preparation_timeout;
+	pvf_config.execute_memory_limit = config.execution_memory_limit;
+	pvf_config.execute_worker_respawn_backoff = config.worker_respawn_backoff;
+	pvf_config.execute_disabled_host_functions = config.disabled_host_functions.clone();
+	pvf_config.max_artifacts_cache_size = config.max_artifacts_cache_size;
+	pvf_config
+}
 
-		let mut candidate = CandidateDescriptor::default();
-		candidate.relay_parent = relay_parent;
-		candidate.persisted_validation_data_hash = persisted_validation_data_hash;
-		candidate.para_id = para_id;
+/// The configurations of the PVF hosts to start under the given configuration.
+fn pvf_host_configs(config: &Config) -> Vec<polkadot_node_core_pvf::Config> {
+	let num_hosts = config.pvf_hosts.max(1);
+	if num_hosts == 1 {
+		return vec![pvf_host_config(config)];
+	}
 
-		let pool = TaskExecutor::new();
-		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+	// A host clears its cache directory on startup, so the hosts must not share one.
+	(0..num_hosts)
+		.map(|index| {
+			let mut pvf_config = pvf_host_config(config);
+			pvf_config.cache_path = pvf_config.cache_path.join(format!("host-{}", index));
+			pvf_config
+		})
+		.collect()
+}
 
-		let (check_fut, check_result) = check_assumption_validation_data(
-			&mut ctx,
-			&candidate,
-			OccupiedCoreAssumption::Included,
-		).remote_handle();
+/// The hosts validations are dispatched to, in a round-robin fashion.
+struct HostPool<H> {
+	hosts: Vec<H>,
+	next: usize,
+}
 
-		let test_fut = async move {
-			assert_matches!(
-				ctx_handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					rp,
-					RuntimeApiRequest::PersistedValidationData(
-						p,
-						OccupiedCoreAssumption::Included,
-						tx
-					),
-				)) => {
-					assert_eq!(rp, relay_parent);
-					assert_eq!(p, para_id);
+impl<H> HostPool<H> {
+	fn new(hosts: Vec<H>) -> Self {
+		assert!(!hosts.is_empty(), "at least one host is always started; qed");
+		HostPool { hosts, next: 0 }
+	}
 
-					let _ = tx.send(Ok(Some(validation_data.clone())));
-				}
-			);
+	/// The host to dispatch the next validation to.
+	fn next(&mut self) -> &mut H {
+		let index = self.next;
+		self.next = (self.next + 1) % self.hosts.len();
+		&mut self.hosts[index]
+	}
 
-			assert_matches!(
-				ctx_handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					rp,
-					RuntimeApiRequest::ValidationCode(p, OccupiedCoreAssumption::Included, tx)
-				)) => {
-					assert_eq!(rp, relay_parent);
-					assert_eq!(p, para_id);
+	fn iter(&self) -> impl Iterator<Item = &H> {
+		self.hosts.iter()
+	}
 
-					let _ = tx.send(Ok(Some(validation_code.clone())));
-				}
-			);
+	fn iter_mut(&mut self) -> impl Iterator<Item = &mut H> {
+		self.hosts.iter_mut()
+	}
+}
 
-			assert_matches!(check_result.await.unwrap(), AssumptionCheckOutcome::Matches(o, v) => {
-				assert_eq!(o, validation_data);
-				assert_eq!(v, validation_code);
-			});
-		};
+/// Run the task of the PVF host with the given index, reporting through `exits` once it exits.
+///
+/// The task only exits once all the handles to the host are dropped, so an exit while the
+/// subsystem runs means the host died.
+async fn supervise_pvf_host(
+	index: usize,
+	task: impl Future<Output = ()>,
+	exits: mpsc::UnboundedSender<usize>,
+) {
+	if std::panic::AssertUnwindSafe(task).catch_unwind().await.is_err() {
+		tracing::error!(target: LOG_TARGET, index, "The PVF validation host panicked");
+	}
 
-		let test_fut = future::join(test_fut, check_fut);
-		executor::block_on(test_fut);
+	let _ = exits.unbounded_send(index);
+}
+
+/// Note that the PVF host with the given index exited, returning the delay to restart it after.
+/// Fails if the hosts were restarted [`MAX_PVF_HOST_RESTARTS`] times already, in which case the
+/// subsystem concludes.
+fn pvf_host_exited(
+	index: usize,
+	restarts: &mut u32,
+	backoff: Option<polkadot_node_core_pvf::RespawnBackoff>,
+) -> SubsystemResult<Duration> {
+	tracing::error!(
+		target: LOG_TARGET,
+		index,
+		restarts = *restarts,
+		"The PVF validation host exited unexpectedly, validations fail until it is restarted",
+	);
+
+	if *restarts >= MAX_PVF_HOST_RESTARTS {
+		return Err(SubsystemError::Context("the PVF validation host keeps exiting".into()));
 	}
+	*restarts += 1;
 
-	#[test]
-	fn correctly_checks_timed_out_assumption() {
-		let validation_data: PersistedValidationData = Default::default();
-		let validation_code: ValidationCode = vec![1, 2, 3].into();
+	Ok(backoff.map_or(Duration::from_secs(0), |backoff| backoff.delay(*restarts)))
+}
 
-		let persisted_validation_data_hash = validation_data.hash();
-		let relay_parent = [2; 32].into();
-		let para_id = 5.into();
+/// Restart the PVF host with the given index after its task exited, with the same configuration
+/// it was started with.
+async fn restart_pvf_host(
+	ctx: &mut impl SubsystemContext,
+	config: &Config,
+	hosts: &mut HostPool<ValidationHost>,
+	index: usize,
+	exits: &mpsc::UnboundedSender<usize>,
+	metrics: &Metrics,
+) -> SubsystemResult<()> {
+	tracing::info!(target: LOG_TARGET, index, "Restarting the PVF validation host");
+	metrics.on_pvf_host_restart();
+
+	let pvf_config = pvf_host_configs(config)
+		.into_iter()
+		.nth(index)
+		.expect("exits are only reported by the started hosts; qed");
+	let (validation_host, task) = polkadot_node_core_pvf::start(pvf_config);
+	let task = supervise_pvf_host(index, task, exits.clone());
+	ctx.spawn_blocking("pvf-validation-host", task.boxed()).await?;
+	hosts.hosts[index] = validation_host;
 
-		let mut candidate = CandidateDescriptor::default();
-		candidate.relay_parent = relay_parent;
-		candidate.persisted_validation_data_hash = persisted_validation_data_hash;
-		candidate.para_id = para_id;
+	Ok(())
+}
 
-		let pool = TaskExecutor::new();
-		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+/// An event of the lifecycle of a PVF host, by the index of the host.
+enum HostEvent {
+	/// The task of the host exited.
+	Exited(usize),
+	/// The host is to be restarted now that the backoff after its exit elapsed.
+	RestartDue(usize),
+}
 
-		let (check_fut, check_result) = check_assumption_validation_data(
-			&mut ctx,
-			&candidate,
-			OccupiedCoreAssumption::TimedOut,
-		).remote_handle();
+/// What the subsystem executes PVFs with.
+enum Backends {
+	/// The PVF validation hosts started by the subsystem.
+	Hosts(HostPool<ValidationHost>),
+	/// A backend injected with [`CandidateValidationSubsystem::with_backend`].
+	Injected(Arc<Mutex<Box<dyn ValidationBackend + Send>>>),
+}
 
-		let test_fut = async move {
-			assert_matches!(
-				ctx_handle.recv().await,
+impl Backends {
+	/// The backend to dispatch the next validation to.
+	fn next(&mut self) -> BackendHandle {
+		match self {
+			Backends::Hosts(hosts) => BackendHandle::Host(hosts.next().clone()),
+			Backends::Injected(backend) => BackendHandle::Injected(backend.clone()),
+		}
+	}
+
+	/// Whether any of the backends is running. An injected backend is assumed to be.
+	fn is_running(&mut self) -> bool {
+		match self {
+			Backends::Hosts(hosts) => hosts.iter_mut().any(|host| host.is_running()),
+			Backends::Injected(_) => true,
+		}
+	}
+
+	/// Flush the artifact caches of the hosts, returning the number of artifacts removed. An
+	/// injected backend keeps no artifacts on behalf of the subsystem. The flush holds handles of
+	/// its own to the hosts, so that it runs alongside the validations.
+	fn flush_artifacts(&self) -> impl Future<Output = usize> {
+		let hosts: Vec<_> = match self {
+			Backends::Hosts(hosts) => hosts.iter().cloned().collect(),
+			Backends::Injected(_) => Vec::new(),
+		};
+
+		async move {
+			let mut removed = 0;
+			for mut validation_host in hosts {
+				let (tx, rx) = oneshot::channel();
+				if let Err(e) = validation_host.flush_artifacts(tx).await {
+					tracing::warn!(
+						target: LOG_TARGET,
+						err = %e,
+						"Failed to request flushing the artifact cache",
+					);
+					continue;
+				}
+
+				removed += rx.await.unwrap_or(0);
+			}
+
+			removed
+		}
+	}
+}
+
+/// The backend a single validation is dispatched to, owned by the validation so that it can run
+/// alongside others.
+enum BackendHandle {
+	Host(ValidationHost),
+	/// The injected backend, which serves one validation at a time.
+	Injected(Arc<Mutex<Box<dyn ValidationBackend + Send>>>),
+}
+
+#[async_trait]
+impl ValidationBackend for BackendHandle {
+	async fn validate_candidate(
+		&mut self,
+		raw_validation_code: Vec<u8>,
+		params: ValidationParams,
+		priority: Priority,
+	) -> Result<WasmValidationResult, ValidationError> {
+		match self {
+			BackendHandle::Host(host) =>
+				host.validate_candidate(raw_validation_code, params, priority).await,
+			BackendHandle::Injected(backend) =>
+				backend.lock().await.validate_candidate(raw_validation_code, params, priority).await,
+		}
+	}
+
+	async fn precheck_pvf(&mut self, raw_validation_code: Vec<u8>) -> Result<(), PrepareError> {
+		match self {
+			BackendHandle::Host(host) => ValidationBackend::precheck_pvf(host, raw_validation_code).await,
+			BackendHandle::Injected(backend) => backend.lock().await.precheck_pvf(raw_validation_code).await,
+		}
+	}
+
+	async fn validate_candidate_prepared(
+		&mut self,
+		code_hash: ValidationCodeHash,
+		params: ValidationParams,
+		priority: Priority,
+	) -> Option<Result<WasmValidationResult, ValidationError>> {
+		match self {
+			BackendHandle::Host(host) =>
+				host.validate_candidate_prepared(code_hash, params, priority).await,
+			BackendHandle::Injected(backend) =>
+				backend.lock().await.validate_candidate_prepared(code_hash, params, priority).await,
+		}
+	}
+
+	async fn validate_candidate_seeded(
+		&mut self,
+		raw_validation_code: Vec<u8>,
+		params: ValidationParams,
+		priority: Priority,
+		seed: [u8; 32],
+	) -> Result<WasmValidationResult, ValidationError> {
+		match self {
+			BackendHandle::Host(host) =>
+				host.validate_candidate_seeded(raw_validation_code, params, priority, seed).await,
+			BackendHandle::Injected(backend) => backend
+				.lock()
+				.await
+				.validate_candidate_seeded(raw_validation_code, params, priority, seed)
+				.await,
+		}
+	}
+}
+
+/// Summarize the limits the subsystem runs with under the given configuration.
+fn validation_limits(config: &Config) -> ValidationLimits {
+	ValidationLimits {
+		validation_code_bomb_limit: config.validation_code_bomb_limit
+			.unwrap_or(VALIDATION_CODE_BOMB_LIMIT),
+		pov_bomb_limit: config.pov_bomb_limit.unwrap_or(POV_BOMB_LIMIT),
+		execution_timeout: config.execution_timeout,
+		worker_idle_timeout: config.worker_idle_timeout,
+		execution_memory_limit: config.execution_memory_limit,
+	}
+}
+
+async fn run(
+	mut ctx: impl SubsystemContext<Message = CandidateValidationMessage>,
+	metrics: Metrics,
+	config: Config,
+	backend: Option<Box<dyn ValidationBackend + Send>>,
+) -> SubsystemResult<()> {
+	let mut readiness = ReadinessTracker::new();
+	metrics.on_readiness(readiness.current());
+
+	let (host_exits_tx, mut host_exits) = mpsc::unbounded();
+	let mut host_restarts = 0;
+	let mut restarts_due: FuturesUnordered<future::BoxFuture<'static, usize>> =
+		FuturesUnordered::new();
+	let mut backends = match backend {
+		Some(backend) => Backends::Injected(Arc::new(Mutex::new(backend))),
+		None => {
+			// Fail early and clearly rather than with the first validation if the workers can't be
+			// used.
+			if let Err(e) = polkadot_node_core_pvf::check_worker_compatibility(
+				config.program_path.clone(),
+				WORKER_HANDSHAKE_TIMEOUT,
+			).await {
+				tracing::error!(
+					target: LOG_TARGET,
+					program_path = ?config.program_path,
+					err = %e,
+					"PVF workers can't be spawned with the configured program",
+				);
+
+				return Err(SubsystemError::Context(format!("start the PVF host: {}", e)));
+			}
+
+			let mut validation_hosts = Vec::new();
+			for (index, pvf_config) in pvf_host_configs(&config).into_iter().enumerate() {
+				let (validation_host, task) = polkadot_node_core_pvf::start(pvf_config);
+				let task = supervise_pvf_host(index, task, host_exits_tx.clone());
+				ctx.spawn_blocking("pvf-validation-host", task.boxed()).await?;
+				validation_hosts.push(validation_host);
+			}
+			Backends::Hosts(HostPool::new(validation_hosts))
+		}
+	};
+	readiness.on_hosts_started(&metrics);
+
+	let mut outcome_subscribers = Vec::new();
+	let mut rate_limiter = RateLimiter::new(config.rate_limits.clone());
+	let mut deferred_output_checks = Vec::new();
+	let mut unclaimed_results = config.unclaimed_results_capacity.map(UnclaimedResults::new);
+	let mut last_validated = LastValidatedInputs::new();
+	let mut metric_labels = MetricLabels::new(config.metric_labels.clone());
+	let mut exhaustive_options = ExhaustiveOptions::from_config(&config);
+	let mut in_flight: FuturesUnordered<future::BoxFuture<'static, Option<CompletedValidation>>> =
+		FuturesUnordered::new();
+	let mut coalesced: HashMap<Hash, Followers> = HashMap::new();
+	let mut relay_parent_aborts = RelayParentAborts::default();
+	let max_in_flight = config.max_concurrent_validations.max(1);
+	let mut prewarm_pending = config.prewarm_on_startup;
+	let (shutdown_tx, shutdown) = oneshot::channel();
+	let shutdown = shutdown.shared();
+	let mut awaiting_capacity = VecDeque::new();
+
+	loop {
+		// Requests waiting for room among the validations in flight are served before new ones.
+		let queued = if in_flight.len() < max_in_flight {
+			awaiting_capacity.pop_front()
+		} else {
+			None
+		};
+
+		let next = match queued {
+			Some((msg, pending)) =>
+				future::Either::Right(future::Either::Left((FromOverseer::Communication { msg }, pending))),
+			None => futures::select! {
+				completed = in_flight.select_next_some() => future::Either::Left(completed),
+				message = ctx.recv().fuse() => {
+					let message = message?;
+					let pending = match message {
+						FromOverseer::Communication { .. } => metrics.on_request_received(),
+						FromOverseer::Signal(_) => None,
+					};
+					future::Either::Right(future::Either::Left((message, pending)))
+				}
+				index = host_exits.select_next_some() =>
+					future::Either::Right(future::Either::Right(HostEvent::Exited(index))),
+				index = restarts_due.select_next_some() =>
+					future::Either::Right(future::Either::Right(HostEvent::RestartDue(index))),
+			},
+		};
+
+		let (message, pending) = match next {
+			// The requester of the validation hung up before it completed.
+			future::Either::Left(None) => continue,
+			future::Either::Left(Some(CompletedValidation {
+				descriptor,
+				result,
+				options,
+				pov,
+				deferred_output_check,
+				request_key,
+				response_sender,
+				_pending,
+			})) => {
+				match result {
+					Ok(x) => {
+						if let (Ok(ValidationResult::Valid(outputs, _, _)), Some(callback)) =
+							(&x, deferred_output_check)
+						{
+							deferred_output_checks.push(DeferredOutputCheck {
+								relay_parent: descriptor.relay_parent,
+								para_id: descriptor.para_id,
+								outputs: outputs.clone(),
+								callback,
+							});
+						}
+
+						// Validation failures are transient, so only actual results are noted.
+						if let (true, Some(key), Ok(_)) = (options.if_changed, request_key, &x) {
+							last_validated.note(BlakeTwo256::hash_of(&descriptor), key);
+						}
+
+						trace_validation_outcome(options.verbose, &descriptor, &x);
+						if let Some(pov) = pov {
+							log_pov_preview(&pov, config.pov_preview_len, &x);
+						}
+						metrics.on_validation_event(Some(descriptor.para_id), &x);
+						observe_labels(&metrics, &mut metric_labels, &descriptor, &options.labels, &x);
+						send_validation_proof(config.keystore.as_ref(), &descriptor, options.proof, &x).await;
+						readiness.on_validation(&x, &metrics);
+						record_outcome(
+							config.record_sink.as_ref(),
+							&descriptor,
+							&x,
+							config.dispute_policy.as_ref(),
+						);
+						publish_outcome(
+							&mut outcome_subscribers,
+							&descriptor,
+							&x,
+							config.published_head_data_limit,
+							config.dispute_policy.as_ref(),
+						);
+						if let Err(x) = response_sender.send(x) {
+							tracing::warn!(
+								target: LOG_TARGET,
+								candidate_hash = ?x.as_ref().ok().and_then(|res| candidate_hash_of(&descriptor, res)),
+								para_id = ?descriptor.para_id,
+								relay_parent = ?descriptor.relay_parent,
+								"Requester of candidate validation dropped",
+							);
+
+							// Validation failures are transient, so only actual results are kept.
+							if let (Some(unclaimed), Some(key), Ok(result)) =
+								(unclaimed_results.as_mut(), request_key, x)
+							{
+								unclaimed.insert(key, result);
+							}
+						}
+					}
+					Err(e) => fail_on_error(&metrics, &descriptor, e, response_sender),
+				}
+
+				continue;
+			}
+			future::Either::Right(future::Either::Right(HostEvent::Exited(index))) => {
+				let delay = pvf_host_exited(
+					index,
+					&mut host_restarts,
+					config.pvf_host_restart_backoff,
+				)?;
+				restarts_due.push(futures_timer::Delay::new(delay).map(move |()| index).boxed());
+				readiness.on_hosts_checked(backends.is_running(), &metrics);
+
+				continue;
+			}
+			future::Either::Right(future::Either::Right(HostEvent::RestartDue(index))) => {
+				if let Backends::Hosts(ref mut hosts) = backends {
+					restart_pvf_host(&mut ctx, &config, hosts, index, &host_exits_tx, &metrics).await?;
+				}
+				readiness.on_hosts_checked(backends.is_running(), &metrics);
+
+				continue;
+			}
+			future::Either::Right(future::Either::Left(message)) => message,
+		};
+
+		match message {
+			FromOverseer::Signal(OverseerSignal::ActiveLeaves(update)) => {
+				exhaustive_options.best_known_number = update.activated
+					.iter()
+					.map(|leaf| leaf.number)
+					.chain(exhaustive_options.best_known_number)
+					.max();
+
+				relay_parent_aborts.on_active_leaves(&update);
+				readiness.on_hosts_checked(backends.is_running(), &metrics);
+
+				if let (true, Some(leaf)) =
+					(prewarm_pending, update.activated.iter().max_by_key(|leaf| leaf.number))
+				{
+					prewarm_pending = false;
+
+					let prewarm = prewarm_artifacts(ctx.sender().clone(), backends.next(), leaf.hash);
+					ctx.spawn("candidate-validation-prewarm", prewarm.boxed()).await?;
+				}
+
+				resolve_deferred_output_checks(
+					ctx.sender(),
+					std::mem::take(&mut deferred_output_checks),
+				).await?;
+			}
+			FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {
+				resolve_deferred_output_checks(
+					ctx.sender(),
+					std::mem::take(&mut deferred_output_checks),
+				).await?;
+			}
+			FromOverseer::Signal(OverseerSignal::Conclude) => {
+				drain_in_flight(
+					in_flight,
+					deferred_output_checks,
+					shutdown_tx,
+					config.shutdown_grace_period,
+					&metrics,
+				).await;
+
+				// Dropping the handles of the PVF hosts stops their tasks.
+				return Ok(());
+			}
+			FromOverseer::Communication { msg } if awaits_capacity(&msg, in_flight.len(), max_in_flight) => {
+				tracing::debug!(
+					target: LOG_TARGET,
+					queued = awaiting_capacity.len() + 1,
+					"Validation request queued until a validation in flight completes",
+				);
+
+				awaiting_capacity.push_back((msg, pending));
+			}
+			FromOverseer::Communication { msg } => match msg {
+				CandidateValidationMessage::ValidateFromChainState(
+					descriptor,
+					pov,
+					mut options,
+					mut response_sender,
+				) => {
+					let received_at = match admit(
+						&metrics,
+						1,
+						in_flight.len(),
+						max_in_flight,
+						&mut rate_limiter,
+						config.active_validator_check.as_ref(),
+						&options,
+						Some(&*pov),
+						&response_sender,
+					) {
+						Admission::Admitted(received_at) => received_at,
+						Admission::Rejected(failure) => {
+							reject(&metrics, Some(descriptor.para_id), failure, response_sender);
+							continue;
+						}
+						Admission::RequesterDropped => continue,
+					};
+
+					trace_validation_start(options.verbose, &descriptor);
+
+					let deferred_output_check = options.deferred_output_check
+						.take()
+						.filter(|_| config.defer_output_checks);
+
+					let mut sender = ValidationSender::new(ctx.sender().clone(), options.source);
+					let validation_backend = backends.next();
+					let retry_delay = config.validation_data_retry_delay;
+					let max_assumptions = config.max_assumptions_to_try;
+					let check_free_assumption = config.check_free_assumption;
+					let prefetch_code = config.prefetch_validation_code;
+					let request_options = exhaustive_options
+						.with_priority(options.priority)
+						.with_execution_kind(options.execution_kind)
+						.with_decompressed_code_hash(options.decompressed_code_hash)
+						.with_execution_seed(options.execution_seed);
+					let abort = relay_parent_aborts.signal(descriptor.relay_parent, options.execution_kind);
+					let metrics = metrics.clone();
+					let shutdown = shutdown.clone();
+
+					in_flight.push(async move {
+						let _timer = metrics.time_validate_from_chain_state();
+
+						let mut steps = Vec::new();
+						let validation = spawn_validate_from_chain_state(
+							&mut sender,
+							validation_backend,
+							descriptor.clone(),
+							pov.clone(),
+							retry_delay,
+							max_assumptions,
+							check_free_assumption,
+							prefetch_code,
+							options.state_snapshot.as_ref(),
+							deferred_output_check.is_some(),
+							received_at,
+							&request_options,
+							&mut steps,
+							&metrics,
+						);
+						let validation = unless_relay_parent_deactivated(abort, &metrics, validation);
+						let validation = unless_shutting_down(shutdown, validation);
+						let result =
+							unless_requester_dropped(&mut response_sender, &metrics, validation).await?;
+						report_steps(&mut options, steps, &metrics);
+
+						Some(CompletedValidation {
+							descriptor,
+							result,
+							options,
+							pov: Some(pov),
+							deferred_output_check,
+							request_key: None,
+							response_sender,
+							_pending: pending,
+						})
+					}.boxed());
+				}
+				CandidateValidationMessage::ValidateFromChainStateStreamed(
+					descriptor,
+					chunks,
+					mut options,
+					mut response_sender,
+				) => {
+					let received_at = match admit(
+						&metrics,
+						1,
+						in_flight.len(),
+						max_in_flight,
+						&mut rate_limiter,
+						config.active_validator_check.as_ref(),
+						&options,
+						// The chunks of the PoV are checked as they are assembled.
+						None,
+						&response_sender,
+					) {
+						Admission::Admitted(received_at) => received_at,
+						Admission::Rejected(failure) => {
+							reject(&metrics, Some(descriptor.para_id), failure, response_sender);
+							continue;
+						}
+						Admission::RequesterDropped => continue,
+					};
+
+					trace_validation_start(options.verbose, &descriptor);
+
+					let deferred_output_check = options.deferred_output_check
+						.take()
+						.filter(|_| config.defer_output_checks);
+
+					let mut sender = ValidationSender::new(ctx.sender().clone(), options.source);
+					let validation_backend = backends.next();
+					let retry_delay = config.validation_data_retry_delay;
+					let max_assumptions = config.max_assumptions_to_try;
+					let check_free_assumption = config.check_free_assumption;
+					let prefetch_code = config.prefetch_validation_code;
+					let pov_bomb_limit = config.pov_bomb_limit.unwrap_or(POV_BOMB_LIMIT);
+					let request_options = exhaustive_options
+						.with_priority(options.priority)
+						.with_execution_kind(options.execution_kind)
+						.with_decompressed_code_hash(options.decompressed_code_hash)
+						.with_execution_seed(options.execution_seed);
+					let abort = relay_parent_aborts.signal(descriptor.relay_parent, options.execution_kind);
+					let metrics = metrics.clone();
+					let shutdown = shutdown.clone();
+
+					in_flight.push(async move {
+						let _timer = metrics.time_validate_from_chain_state();
+
+						let mut steps = Vec::new();
+						let validation = validate_from_chain_state_with(
+							&mut sender,
+							validation_backend,
+							descriptor.clone(),
+							assemble_pov(chunks, pov_bomb_limit, options.pov_checksum),
+							retry_delay,
+							max_assumptions,
+							check_free_assumption,
+							prefetch_code,
+							options.state_snapshot.as_ref(),
+							deferred_output_check.is_some(),
+							received_at,
+							&request_options,
+							&mut steps,
+							&metrics,
+						);
+						let validation = unless_relay_parent_deactivated(abort, &metrics, validation);
+						let validation = unless_shutting_down(shutdown, validation);
+						let result =
+							unless_requester_dropped(&mut response_sender, &metrics, validation).await?;
+						report_steps(&mut options, steps, &metrics);
+
+						Some(CompletedValidation {
+							descriptor,
+							result,
+							options,
+							pov: None,
+							deferred_output_check,
+							request_key: None,
+							response_sender,
+							_pending: pending,
+						})
+					}.boxed());
+				}
+				CandidateValidationMessage::ValidateFromExhaustive(
+					persisted_validation_data,
+					validation_code,
+					descriptor,
+					pov,
+					mut options,
+					mut response_sender,
+				) => {
+					let inputs_key = exhaustive_request_key(
+						&persisted_validation_data,
+						&validation_code,
+						&descriptor,
+						&*pov,
+					);
+
+					// The outputs being checked, the time allotted to the execution and the seed of
+					// the execution bear on the result as much as the inputs.
+					let coalesce_key = BlakeTwo256::hash_of(&(
+						inputs_key,
+						options.check_outputs,
+						options.execution_kind as u8,
+						options.execution_seed,
+					));
+					coalesced.retain(|_, followers| {
+						followers.try_lock().map_or(true, |followers| followers.is_some())
+					});
+					let leader = coalesced.get(&coalesce_key).filter(|_| can_follow(&options)).cloned();
+
+					// Following an identical validation in flight doesn't add one.
+					let received_at = match admit(
+						&metrics,
+						if leader.is_some() { 0 } else { 1 },
+						in_flight.len(),
+						max_in_flight,
+						&mut rate_limiter,
+						config.active_validator_check.as_ref(),
+						&options,
+						Some(&*pov),
+						&response_sender,
+					) {
+						Admission::Admitted(received_at) => received_at,
+						Admission::Rejected(failure) => {
+							reject(&metrics, Some(descriptor.para_id), failure, response_sender);
+							continue;
+						}
+						Admission::RequesterDropped => continue,
+					};
+
+					trace_validation_start(options.verbose, &descriptor);
+
+					let timer = metrics.time_validate_from_exhaustive();
+
+					// Seeded executions are one-offs, which neither serve nor leave results keyed by
+					// their inputs.
+					let request_key = Some(inputs_key).filter(|_| {
+						options.execution_seed.is_none() &&
+							(unclaimed_results.is_some() || options.if_changed)
+					});
+
+					if let (Some(unclaimed), Some(key)) = (unclaimed_results.as_mut(), request_key) {
+						if let Some(result) = unclaimed.lookup(&key, &metrics) {
+							tracing::debug!(
+								target: LOG_TARGET,
+								?key,
+								candidate_hash = ?candidate_hash_of(&descriptor, &result),
+								para_id = ?descriptor.para_id,
+								relay_parent = ?descriptor.relay_parent,
+								"Serving the unclaimed result of an identical earlier request",
+							);
+
+							// Concluded like a validation of its own, so that it's observed and
+							// published alike.
+							report_steps(&mut options, Vec::new(), &metrics);
+							in_flight.push(future::ready(Some(CompletedValidation {
+								descriptor,
+								result: Ok(Ok(result)),
+								options,
+								pov: Some(pov),
+								deferred_output_check: None,
+								request_key,
+								response_sender,
+								_pending: pending,
+							})).boxed());
+							continue;
+						}
+					}
+
+					if let (true, Some(key)) = (options.if_changed, request_key) {
+						if last_validated.is_unchanged(&BlakeTwo256::hash_of(&descriptor), &key) {
+							tracing::debug!(
+								target: LOG_TARGET,
+								?key,
+								para_id = ?descriptor.para_id,
+								relay_parent = ?descriptor.relay_parent,
+								"Inputs of the candidate are unchanged, skipping its validation",
+							);
+
+							let res = Ok(ValidationResult::Unchanged);
+							metrics.on_validation_event(Some(descriptor.para_id), &res);
+							publish_outcome(
+								&mut outcome_subscribers,
+								&descriptor,
+								&res,
+								config.published_head_data_limit,
+								config.dispute_policy.as_ref(),
+							);
+							let _ = response_sender.send(res);
+							continue;
+						}
+					}
+
+					if let Some(leader) = leader {
+						if let Some(ref mut followers) = *leader.lock().await {
+							tracing::debug!(
+								target: LOG_TARGET,
+								key = ?coalesce_key,
+								para_id = ?descriptor.para_id,
+								relay_parent = ?descriptor.relay_parent,
+								"Awaiting an identical validation in flight",
+							);
+
+							followers.push(Follower { response_sender, _pending: pending });
+							continue;
+						}
+					}
+
+					let followers: Followers = Arc::new(Mutex::new(Some(Vec::new())));
+					let _ = coalesced.insert(coalesce_key, followers.clone());
+
+					metrics.on_admission(received_at);
+
+					// Checking the outputs is the only part of this validation querying the runtime.
+					let mut sender = if options.check_outputs {
+						Some(ValidationSender::new(ctx.sender().clone(), options.source))
+					} else {
+						None
+					};
+					let keep_unclaimed = unclaimed_results.is_some();
+					let validation_backend = backends.next();
+					let request_options = exhaustive_options
+						.with_priority(options.priority)
+						.with_execution_kind(options.execution_kind)
+						.with_decompressed_code_hash(options.decompressed_code_hash)
+						.with_prepared_artifact(options.prepared_artifact)
+						.with_execution_seed(options.execution_seed);
+					let abort = relay_parent_aborts.signal(descriptor.relay_parent, options.execution_kind);
+					let metrics = metrics.clone();
+					let shutdown = shutdown.clone();
+
+					in_flight.push(async move {
+						let _timer = timer;
+
+						let mut steps = Vec::new();
+						let validation = async {
+							let result = validate_candidate_exhaustive_explained(
+								validation_backend,
+								persisted_validation_data,
+								validation_code,
+								descriptor.clone(),
+								pov.clone(),
+								&request_options,
+								&mut steps,
+								&metrics,
+							).await;
+
+							match (result, sender.as_mut()) {
+								(Ok(x), Some(sender)) =>
+									check_validation_outputs(sender, &descriptor, x, &mut steps).await,
+								(result, _) => result,
+							}
+						};
+
+						let validation = unless_relay_parent_deactivated(abort, &metrics, validation);
+						let validation = unless_shutting_down(shutdown, validation);
+
+						// A result kept for an identical request is worth completing even if the
+						// requester hung up.
+						let result = if keep_unclaimed {
+							validation.await
+						} else {
+							unless_followers_dropped(&mut response_sender, &followers, &metrics, validation)
+								.await?
+						};
+						answer_followers(&followers, &metrics, &descriptor, &result).await;
+						report_steps(&mut options, steps, &metrics);
+
+						Some(CompletedValidation {
+							descriptor,
+							result,
+							options,
+							pov: Some(pov),
+							deferred_output_check: None,
+							request_key,
+							response_sender,
+							_pending: pending,
+						})
+					}.boxed());
+				}
+				CandidateValidationMessage::ValidateBatch(candidates, mut response_sender) => {
+					// A batch is admitted as a whole, so that its candidates share the decompressed
+					// validation code. Each of them is in flight on its own, though.
+					let received_at = match admit(
+						&metrics,
+						candidates.len(),
+						in_flight.len(),
+						max_in_flight,
+						&mut rate_limiter,
+						config.active_validator_check.as_ref(),
+						&ValidationOptions::default(),
+						None,
+						&response_sender,
+					) {
+						Admission::Admitted(received_at) => received_at,
+						Admission::Rejected(failure) => {
+							reject_batch(&metrics, &candidates, failure, response_sender);
+							continue;
+						}
+						Admission::RequesterDropped => continue,
+					};
+
+					metrics.on_admission(received_at);
+
+					let code_cache = DecompressedCodeCache::default();
+					let mut results = Vec::with_capacity(candidates.len());
+					for (persisted_validation_data, validation_code, descriptor, pov) in candidates {
+						// Each candidate is completed like a request of its own, answered to the
+						// batch.
+						let (mut item_sender, item_receiver) = oneshot::channel();
+						results.push(item_receiver);
+
+						let timer = metrics.time_validate_from_exhaustive();
+						let validation_backend = backends.next();
+						let mut options = ValidationOptions::default();
+						let request_options = exhaustive_options
+							.with_priority(options.priority)
+							.with_execution_kind(options.execution_kind)
+							.with_decompressed_code_cache(code_cache.clone());
+						let abort =
+							relay_parent_aborts.signal(descriptor.relay_parent, options.execution_kind);
+						let metrics = metrics.clone();
+						let shutdown = shutdown.clone();
+
+						in_flight.push(async move {
+							let _timer = timer;
+
+							let mut steps = Vec::new();
+							let validation = validate_candidate_exhaustive_explained(
+								validation_backend,
+								persisted_validation_data,
+								validation_code,
+								descriptor.clone(),
+								pov.clone(),
+								&request_options,
+								&mut steps,
+								&metrics,
+							);
+							let validation =
+								unless_relay_parent_deactivated(abort, &metrics, validation);
+							let validation = unless_shutting_down(shutdown, validation);
+							let result =
+								unless_requester_dropped(&mut item_sender, &metrics, validation).await?;
+							report_steps(&mut options, steps, &metrics);
+
+							Some(CompletedValidation {
+								descriptor,
+								result,
+								options,
+								pov: Some(pov),
+								deferred_output_check: None,
+								request_key: None,
+								response_sender: item_sender,
+								_pending: None,
+							})
+						}.boxed());
+					}
+
+					let metrics = metrics.clone();
+					in_flight.push(async move {
+						let _pending = pending;
+
+						// Hanging up drops the receivers, canceling the validations of the batch.
+						let results = future::join_all(results.into_iter().map(|result| {
+							result.map(|result| result.unwrap_or(Err(ValidationFailed::Cancelled)))
+						}));
+						if let Some(results) =
+							unless_requester_dropped(&mut response_sender, &metrics, results).await
+						{
+							let _ = response_sender.send(results);
+						}
+
+						None
+					}.boxed());
+				}
+				CandidateValidationMessage::ValidateFromHash(
+					candidate_hash,
+					mut resolvers,
+					mut options,
+					mut response_sender,
+				) => {
+					let received_at = match admit(
+						&metrics,
+						1,
+						in_flight.len(),
+						max_in_flight,
+						&mut rate_limiter,
+						config.active_validator_check.as_ref(),
+						&options,
+						None,
+						&response_sender,
+					) {
+						Admission::Admitted(received_at) => received_at,
+						Admission::Rejected(failure) => {
+							reject(&metrics, None, failure, response_sender);
+							continue;
+						}
+						Admission::RequesterDropped => continue,
+					};
+
+					let validation_backend = backends.next();
+					let request_options = exhaustive_options
+						.with_priority(options.priority)
+						.with_execution_kind(options.execution_kind)
+						.with_decompressed_code_hash(options.decompressed_code_hash)
+						.with_execution_seed(options.execution_seed);
+					// The relay-parent is only known once the descriptor is resolved.
+					let mut aborts = relay_parent_aborts.signals(options.execution_kind);
+					let metrics = metrics.clone();
+					let shutdown = shutdown.clone();
+
+					in_flight.push(async move {
+						let resolution = resolve(&mut resolvers.descriptor, candidate_hash, "descriptor")
+							.map(Ok::<_, SubsystemError>);
+						let resolution = unless_shutting_down(shutdown.clone(), resolution);
+						let resolution =
+							unless_requester_dropped(&mut response_sender, &metrics, resolution).await?;
+						let descriptor = match resolution.unwrap_or_else(|e| Err(subsystem_failure(&e))) {
+							Ok(descriptor) => descriptor,
+							Err(e) => {
+								let res = Err(e);
+								metrics.on_validation_event(None, &res);
+								let _ = response_sender.send(res);
+								return None;
+							}
+						};
+
+						trace_validation_start(options.verbose, &descriptor);
+
+						let abort = aborts.remove(&descriptor.relay_parent);
+						let mut steps = Vec::new();
+						let validation = validate_from_resolvers(
+							validation_backend,
+							candidate_hash,
+							descriptor.clone(),
+							resolvers,
+							options.pov_checksum,
+							received_at,
+							&request_options,
+							&mut steps,
+							&metrics,
+						);
+						let validation = unless_relay_parent_deactivated(abort, &metrics, validation);
+						let validation = unless_shutting_down(shutdown, validation);
+						let result =
+							unless_requester_dropped(&mut response_sender, &metrics, validation).await?;
+						report_steps(&mut options, steps, &metrics);
+
+						Some(CompletedValidation {
+							descriptor,
+							result,
+							options,
+							pov: None,
+							deferred_output_check: None,
+							request_key: None,
+							response_sender,
+							_pending: pending,
+						})
+					}.boxed());
+				}
+				CandidateValidationMessage::GetLimits(response_sender) => {
+					let _ = response_sender.send(validation_limits(&config));
+				}
+				CandidateValidationMessage::GetReadiness(response_sender) => {
+					readiness.on_hosts_checked(backends.is_running(), &metrics);
+					let _ = response_sender.send(readiness.current());
+				}
+				CandidateValidationMessage::QueryHealth(response_sender) => {
+					let recent_worker_deaths =
+						exhaustive_options.worker_deaths.lock().await.recent(Instant::now());
+					let _ = response_sender.send(ValidationHealth {
+						host_running: backends.is_running(),
+						recent_worker_deaths,
+					});
+				}
+				CandidateValidationMessage::BasicCheck(
+					descriptor,
+					pov,
+					validation_code,
+					max_pov_size,
+					response_sender,
+				) => {
+					let _ = response_sender.send(perform_basic_checks(
+						&descriptor,
+						max_pov_size,
+						&*pov,
+						&validation_code,
+					));
+				}
+				CandidateValidationMessage::FlushArtifactCache(response_sender) => {
+					let flush = backends.flush_artifacts();
+					in_flight.push(async move {
+						let _ = response_sender.send(flush.await);
+						None
+					}.boxed());
+				}
+				CandidateValidationMessage::SubscribeOutcomes(subscriber) => {
+					outcome_subscribers.push(subscriber);
+				}
+				CandidateValidationMessage::PreCheck(
+					relay_parent,
+					validation_code_hash,
+					response_sender,
+				) => {
+					let mut sender = ctx.sender().clone();
+					let validation_backend = backends.next();
+					let prechecked_code = exhaustive_options.prechecked_code.clone();
+					let shutdown = shutdown.clone();
+
+					in_flight.push(async move {
+						let precheck = precheck_pvf(
+							&mut sender,
+							validation_backend,
+							relay_parent,
+							validation_code_hash,
+							prechecked_code.as_deref(),
+						);
+
+						// A pre-check cut short as the subsystem concludes says nothing about the code.
+						futures::pin_mut!(precheck);
+						let outcome = match future::select(precheck, shutdown).await {
+							future::Either::Left((outcome, _)) => outcome,
+							future::Either::Right((_, _)) => PreCheckOutcome::Failed,
+						};
+
+						let _ = response_sender.send(outcome);
+						None
+					}.boxed());
+				}
+			}
+		}
+	}
+}
+
+/// A validation that ran alongside others, with what's left to do for responding to its request.
+struct CompletedValidation {
+	descriptor: CandidateDescriptor,
+	result: SubsystemResult<Result<ValidationResult, ValidationFailed>>,
+	options: ValidationOptions,
+	/// The PoV of the candidate, if it was known before the validation.
+	pov: Option<Arc<PoV>>,
+	deferred_output_check: Option<oneshot::Sender<Result<bool, ValidationFailed>>>,
+	/// The key of the request for exhaustive validation, if its result is kept for identical
+	/// requests or for skipping the validation of unchanged inputs.
+	request_key: Option<Hash>,
+	response_sender: oneshot::Sender<Result<ValidationResult, ValidationFailed>>,
+	/// Keeps the request accounted for as pending until it's responded to.
+	_pending: Option<PendingRequest>,
+}
+
+/// Report the steps a validation took, and what is derived from them, to the channels of its
+/// request asking for them.
+fn report_steps(options: &mut ValidationOptions, steps: Vec<ValidationStep>, metrics: &Metrics) {
+	if let (Some(tx), Some(assumption)) =
+		(options.matched_assumption.take(), matched_assumption(&steps))
+	{
+		let _ = tx.send(assumption);
+	}
+
+	if let (Some(tx), Some(validation_data)) =
+		(options.validation_data.take(), found_validation_data(&steps))
+	{
+		let _ = tx.send(validation_data.clone());
+	}
+
+	if let (Some(tx), Some(fingerprint)) =
+		(options.fingerprint.take(), execution_fingerprint(&steps))
+	{
+		let _ = tx.send(fingerprint);
+	}
+
+	if let Some(fetched_at) = options.fetched_at {
+		metrics.on_fetch_to_done(fetched_at);
+	}
+
+	if let Some(explain) = options.explain.take() {
+		let _ = explain.send(steps);
+	}
+}
+
+/// Whether a request for validation is admitted.
+enum Admission {
+	/// The request is admitted, as received at the given instant.
+	Admitted(Instant),
+	/// The request is rejected with the given failure, which is yet to be answered to it.
+	Rejected(ValidationFailed),
+	/// The requester hung up already, so there is nobody to answer.
+	RequesterDropped,
+}
+
+/// Decide whether to admit a request for the given number of validations, checking the validations
+/// in flight, whether the node is an active validator, whether the requester still awaits the
+/// result, the integrity of the PoV, if known upfront, and the rate limit of the source of the
+/// request, in that order. Only an admitted request counts towards the rate limit.
+fn admit<R>(
+	metrics: &Metrics,
+	validations: usize,
+	in_flight: usize,
+	max_in_flight: usize,
+	rate_limiter: &mut RateLimiter,
+	active_validator_check: Option<&ActiveValidatorCheck>,
+	options: &ValidationOptions,
+	pov: Option<&PoV>,
+	response_sender: &oneshot::Sender<R>,
+) -> Admission {
+	if in_flight + validations > max_in_flight {
+		tracing::debug!(
+			target: LOG_TARGET,
+			validations,
+			"Validation request rejected due to too many validations in flight",
+		);
+
+		metrics.on_overloaded();
+		return Admission::Rejected(ValidationFailed::Overloaded);
+	}
+
+	let received_at = Instant::now();
+
+	if declined_while_inactive(active_validator_check, options) {
+		tracing::debug!(
+			target: LOG_TARGET,
+			"Validation request for backing declined as the node is not an active validator",
+		);
+
+		return Admission::Rejected(ValidationFailed::Other("node not active validator".into()));
+	}
+
+	if requester_dropped(metrics, response_sender) {
+		return Admission::RequesterDropped;
+	}
+
+	if let Some(Err(e)) = pov.map(|pov| check_pov_integrity(pov, options.pov_checksum)) {
+		return Admission::Rejected(e);
+	}
+
+	if !rate_limiter.try_acquire(options.source, received_at) {
+		tracing::debug!(
+			target: LOG_TARGET,
+			source = ?options.source,
+			"Validation request rejected due to the source's rate limit",
+		);
+
+		return Admission::Rejected(ValidationFailed::Other("rate limited".into()));
+	}
+
+	Admission::Admitted(received_at)
+}
+
+/// Whether a request for validation is to wait for room among the validations in flight rather
+/// than be rejected as overloaded.
+fn awaits_capacity(msg: &CandidateValidationMessage, in_flight: usize, max_in_flight: usize) -> bool {
+	let options = match msg {
+		CandidateValidationMessage::ValidateFromChainState(_, _, options, _) |
+		CandidateValidationMessage::ValidateFromChainStateStreamed(_, _, options, _) |
+		CandidateValidationMessage::ValidateFromExhaustive(_, _, _, _, options, _) |
+		CandidateValidationMessage::ValidateFromHash(_, _, options, _) => options,
+		_ => return false,
+	};
+
+	in_flight >= max_in_flight && is_essential(options.source)
+}
+
+/// Whether validations requested by the given source are essential to the security of the relay
+/// chain, so that they must not be shed under load: a validation failing for approval voting or
+/// dispute participation isn't retried by backing another candidate.
+fn is_essential(source: ValidationSource) -> bool {
+	match source {
+		ValidationSource::ApprovalVoting | ValidationSource::DisputeParticipation => true,
+		ValidationSource::Backing | ValidationSource::Other => false,
+	}
+}
+
+/// Answer a request for validation that isn't admitted.
+fn reject(
+	metrics: &Metrics,
+	para_id: Option<ParaId>,
+	failure: ValidationFailed,
+	response_sender: oneshot::Sender<Result<ValidationResult, ValidationFailed>>,
+) {
+	let res = Err(failure);
+	metrics.on_validation_event(para_id, &res);
+	let _ = response_sender.send(res);
+}
+
+/// Reject all candidates of a batch with the given failure.
+fn reject_batch(
+	metrics: &Metrics,
+	candidates: &[(PersistedValidationData, ValidationCode, CandidateDescriptor, Arc<PoV>)],
+	failure: ValidationFailed,
+	response_sender: oneshot::Sender<Vec<Result<ValidationResult, ValidationFailed>>>,
+) {
+	tracing::debug!(
+		target: LOG_TARGET,
+		candidates = candidates.len(),
+		reason = %failure,
+		"Batch validation request rejected",
+	);
+
+	let results = candidates
+		.iter()
+		.map(|(_, _, descriptor, _)| {
+			let res = Err(failure.clone());
+			metrics.on_validation_event(Some(descriptor.para_id), &res);
+			res
+		})
+		.collect();
+	let _ = response_sender.send(results);
+}
+
+/// Whether a validation requested with the given options is declined because the node is not an
+/// active validator. Only validations for backing are, as approval voting and dispute
+/// participation must be served regardless.
+fn declined_while_inactive(
+	active_validator_check: Option<&ActiveValidatorCheck>,
+	options: &ValidationOptions,
+) -> bool {
+	options.execution_kind == ExecutionKind::Backing &&
+		!is_essential(options.source) &&
+		active_validator_check.map_or(false, |is_active| !is_active())
+}
+
+/// Answer a validation that couldn't be completed due to an error of the subsystem, e.g. a
+/// request to the runtime API being dropped. The error only concerns this validation, so the
+/// subsystem keeps serving the others.
+fn fail_on_error(
+	metrics: &Metrics,
+	descriptor: &CandidateDescriptor,
+	err: SubsystemError,
+	response_sender: oneshot::Sender<Result<ValidationResult, ValidationFailed>>,
+) {
+	tracing::warn!(
+		target: LOG_TARGET,
+		para_id = ?descriptor.para_id,
+		err = ?err,
+		"Failed to validate candidate due to an internal error",
+	);
+
+	let res = Err(subsystem_failure(&err));
+	metrics.on_validation_event(Some(descriptor.para_id), &res);
+	let _ = response_sender.send(res);
+}
+
+/// The failure a validation is answered with if it couldn't be completed due to an error of the
+/// subsystem. Failures raised on purpose along the way, e.g. by a send to the overseer timing out,
+/// are passed on as they are.
+fn subsystem_failure(err: &SubsystemError) -> ValidationFailed {
+	match err {
+		SubsystemError::FromOrigin { source, .. } => match source.downcast_ref::<ValidationFailed>() {
+			Some(failure) => failure.clone(),
+			None => ValidationFailed::Other(format!("internal error: {}", err)),
+		},
+		err => ValidationFailed::Other(format!("internal error: {}", err)),
+	}
+}
+
+/// The labels supplied with validation requests which are attached to the metrics, along with the
+/// values observed so far for each of them.
+struct MetricLabels {
+	observed: HashMap<String, HashSet<String>>,
+}
+
+impl MetricLabels {
+	fn new(keys: HashSet<String>) -> Self {
+		MetricLabels { observed: keys.into_iter().map(|key| (key, HashSet::new())).collect() }
+	}
+
+	/// Pick the labels to attach to the metrics out of the ones supplied with a request. Values
+	/// beyond the first [`MAX_METRIC_LABEL_VALUES`] observed for a label are replaced by `other`.
+	fn bound<'a>(&mut self, labels: &'a BTreeMap<String, String>) -> Vec<(&'a str, &'a str)> {
+		labels.iter()
+			.filter_map(|(key, value)| {
+				let observed = self.observed.get_mut(key)?;
+				if !observed.contains(value) && observed.len() >= MAX_METRIC_LABEL_VALUES {
+					return Some((key.as_str(), "other"));
+				}
+
+				let _ = observed.insert(value.clone());
+				Some((key.as_str(), value.as_str()))
+			})
+			.collect()
+	}
+}
+
+/// Observe the outcome of a validation under the labels supplied with its request.
+fn observe_labels(
+	metrics: &Metrics,
+	metric_labels: &mut MetricLabels,
+	descriptor: &CandidateDescriptor,
+	labels: &BTreeMap<String, String>,
+	result: &Result<ValidationResult, ValidationFailed>,
+) {
+	if labels.is_empty() {
+		return;
+	}
+
+	tracing::debug!(
+		target: LOG_TARGET,
+		para_id = ?descriptor.para_id,
+		?labels,
+		outcome = validity_label(result),
+		"Labelled validation concluded",
+	);
+
+	metrics.on_labelled_validation_event(&metric_labels.bound(labels), result);
+}
+
+/// Whether the requester of a validation hung up before the validation started, in which case the
+/// validation is canceled.
+fn requester_dropped<R>(metrics: &Metrics, response_sender: &oneshot::Sender<R>) -> bool {
+	if !response_sender.is_canceled() {
+		return false;
+	}
+
+	tracing::debug!(
+		target: LOG_TARGET,
+		"Validation canceled, since its requester hung up",
+	);
+
+	metrics.on_canceled("requester_dropped");
+	true
+}
+
+/// Drive the given validation to completion, unless its requester hangs up before. The validation
+/// is dropped then, which cancels the execution of its PVF, if any.
+async fn unless_requester_dropped<R, T>(
+	response_sender: &mut oneshot::Sender<R>,
+	metrics: &Metrics,
+	validation: impl Future<Output = T>,
+) -> Option<T> {
+	futures::pin_mut!(validation);
+	match future::select(validation, response_sender.cancellation()).await {
+		future::Either::Left((result, _)) => Some(result),
+		future::Either::Right(((), _)) => {
+			tracing::debug!(
+				target: LOG_TARGET,
+				"Validation canceled mid-flight, since its requester hung up",
+			);
+
+			metrics.on_canceled("requester_dropped");
+			None
+		}
+	}
+}
+
+/// A request awaiting the result of an identical exhaustive validation in flight rather than
+/// validating the candidate once more.
+struct Follower {
+	response_sender: oneshot::Sender<Result<ValidationResult, ValidationFailed>>,
+	/// Keeps the request accounted for as pending until it's responded to.
+	_pending: Option<PendingRequest>,
+}
+
+/// The requests following an exhaustive validation in flight. `None` once the validation
+/// concluded or was canceled, so that identical requests arriving afterwards are validated afresh.
+type Followers = Arc<Mutex<Option<Vec<Follower>>>>;
+
+/// Whether a request can follow an identical validation in flight, which only answers its result.
+/// Requests asking for anything derived from the validation itself are validated on their own.
+fn can_follow(options: &ValidationOptions) -> bool {
+	options.explain.is_none() &&
+		options.fingerprint.is_none() &&
+		options.matched_assumption.is_none() &&
+		options.validation_data.is_none() &&
+		options.proof.is_none() &&
+		options.labels.is_empty() &&
+		!options.if_changed
+}
+
+/// Like [`unless_requester_dropped`], but the validation is driven to completion regardless if
+/// requests following it still await its result.
+async fn unless_followers_dropped<T>(
+	response_sender: &mut oneshot::Sender<Result<ValidationResult, ValidationFailed>>,
+	followers: &Followers,
+	metrics: &Metrics,
+	validation: impl Future<Output = T>,
+) -> Option<T> {
+	futures::pin_mut!(validation);
+	if let future::Either::Left((result, _)) =
+		future::select(validation.as_mut(), response_sender.cancellation()).await
+	{
+		return Some(result);
+	}
+
+	let mut followers = followers.lock().await;
+	let awaited = followers.as_ref().map_or(false, |followers| {
+		followers.iter().any(|follower| !follower.response_sender.is_canceled())
+	});
+	if awaited {
+		drop(followers);
+		return Some(validation.await);
+	}
+
+	*followers = None;
+	tracing::debug!(
+		target: LOG_TARGET,
+		"Validation canceled mid-flight, since its requester hung up",
+	);
+
+	metrics.on_canceled("requester_dropped");
+	None
+}
+
+/// Answer the requests following a validation with its result.
+async fn answer_followers(
+	followers: &Followers,
+	metrics: &Metrics,
+	descriptor: &CandidateDescriptor,
+	result: &SubsystemResult<Result<ValidationResult, ValidationFailed>>,
+) {
+	let followers = followers.lock().await.take().unwrap_or_default();
+	if followers.is_empty() {
+		return;
+	}
+
+	let res = match result {
+		Ok(res) => res.clone(),
+		Err(e) => Err(subsystem_failure(e)),
+	};
+	for follower in followers {
+		metrics.on_validation_event(Some(descriptor.para_id), &res);
+		let _ = follower.response_sender.send(res.clone());
+	}
+}
+
+/// Resolves once the validations for backing in flight on a relay-parent are to be aborted.
+type Abort = future::Shared<oneshot::Receiver<()>>;
+
+/// The signals aborting the validations for backing in flight on each active leaf, raised once the
+/// leaf is deactivated, as backing a candidate on it is pointless then. Validations for approval
+/// checking or disputes conclude regardless, and so do the ones whose relay-parent isn't an active
+/// leaf to begin with.
+///
+/// Whether the leaf left the canonical chain or is merely superseded by a child doesn't matter: a
+/// validation for backing lives as long as the backing job on its relay-parent, which concludes
+/// once the leaf is deactivated for either reason.
+#[derive(Default)]
+struct RelayParentAborts {
+	signals: HashMap<Hash, (oneshot::Sender<()>, Abort)>,
+}
+
+impl RelayParentAborts {
+	/// Track the activated leaves and abort the validations on the deactivated ones.
+	fn on_active_leaves(&mut self, update: &ActiveLeavesUpdate) {
+		for leaf in &update.activated {
+			let (tx, rx) = oneshot::channel();
+			let _ = self.signals.insert(leaf.hash, (tx, rx.shared()));
+		}
+
+		for relay_parent in &update.deactivated {
+			if let Some((tx, _)) = self.signals.remove(relay_parent) {
+				let _ = tx.send(());
+			}
+		}
+	}
+
+	/// The signal aborting a validation of the given kind on the given relay-parent, if any.
+	fn signal(&self, relay_parent: Hash, execution_kind: ExecutionKind) -> Option<Abort> {
+		if execution_kind != ExecutionKind::Backing {
+			return None;
+		}
+
+		self.signals.get(&relay_parent).map(|(_, abort)| abort.clone())
+	}
+
+	/// The signals aborting a validation of the given kind on each active leaf, for a validation
+	/// whose relay-parent is yet to be known.
+	fn signals(&self, execution_kind: ExecutionKind) -> HashMap<Hash, Abort> {
+		if execution_kind != ExecutionKind::Backing {
+			return HashMap::new();
+		}
+
+		self.signals.iter().map(|(relay_parent, (_, abort))| (*relay_parent, abort.clone())).collect()
+	}
+}
+
+/// Drive the given validation to completion, unless it's aborted before as its relay-parent is
+/// deactivated, failing it with [`ValidationFailed::Cancelled`] then. This drops the validation,
+/// which cancels the execution of its PVF, if any.
+async fn unless_relay_parent_deactivated(
+	abort: Option<Abort>,
+	metrics: &Metrics,
+	validation: impl Future<Output = SubsystemResult<Result<ValidationResult, ValidationFailed>>>,
+) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
+	let abort = match abort {
+		Some(abort) => abort,
+		None => return validation.await,
+	};
+
+	futures::pin_mut!(validation);
+	match future::select(validation, abort).await {
+		future::Either::Left((result, _)) => result,
+		future::Either::Right((_, _)) => {
+			tracing::debug!(
+				target: LOG_TARGET,
+				"Validation aborted mid-flight, since its relay-parent was deactivated",
+			);
+
+			metrics.on_canceled("relay_parent_deactivated");
+			Ok(Err(ValidationFailed::Cancelled))
+		}
+	}
+}
+
+/// Resolves once the validations in flight are to be cancelled, as the subsystem concludes.
+type Shutdown = future::Shared<oneshot::Receiver<()>>;
+
+/// Drive the given validation to completion, unless the subsystem concludes before and the grace
+/// period for the validations in flight elapses, failing it with
+/// [`ValidationFailed::ShuttingDown`] then.
+async fn unless_shutting_down<T>(
+	shutdown: Shutdown,
+	validation: impl Future<Output = SubsystemResult<Result<T, ValidationFailed>>>,
+) -> SubsystemResult<Result<T, ValidationFailed>> {
+	futures::pin_mut!(validation);
+	match future::select(validation, shutdown).await {
+		future::Either::Left((result, _)) => result,
+		future::Either::Right((_, _)) => Ok(Err(ValidationFailed::ShuttingDown)),
+	}
+}
+
+/// Wind down the validations in flight as the subsystem concludes, so that no requester is left
+/// waiting for an answer that never comes. They are given the grace period to complete, after
+/// which those still running are cancelled through the shutdown signal. The pending deferred output
+/// checks are answered with [`ValidationFailed::ShuttingDown`] as well.
+async fn drain_in_flight(
+	mut in_flight: FuturesUnordered<future::BoxFuture<'static, Option<CompletedValidation>>>,
+	deferred_output_checks: Vec<DeferredOutputCheck>,
+	shutdown: oneshot::Sender<()>,
+	grace_period: Duration,
+	metrics: &Metrics,
+) {
+	tracing::debug!(
+		target: LOG_TARGET,
+		in_flight = in_flight.len(),
+		"Concluding, draining the validations in flight",
+	);
+
+	let mut grace_period = futures_timer::Delay::new(grace_period).fuse();
+	let mut shutdown = Some(shutdown);
+	while !in_flight.is_empty() {
+		let completed = futures::select! {
+			completed = in_flight.next() => match completed {
+				Some(completed) => completed,
+				None => break,
+			},
+			() = grace_period => {
+				if let Some(shutdown) = shutdown.take() {
+					let _ = shutdown.send(());
+				}
+
+				continue;
+			}
+		};
+
+		let CompletedValidation { descriptor, result, deferred_output_check, response_sender, .. } =
+			match completed {
+				Some(completed) => completed,
+				// The requester of the validation hung up before it completed.
+				None => continue,
+			};
+
+		if let Some(callback) = deferred_output_check {
+			let _ = callback.send(Err(ValidationFailed::ShuttingDown));
+		}
+
+		match result {
+			Ok(x) => {
+				metrics.on_validation_event(Some(descriptor.para_id), &x);
+				let _ = response_sender.send(x);
+			}
+			Err(e) => fail_on_error(metrics, &descriptor, e, response_sender),
+		}
+	}
+
+	for check in deferred_output_checks {
+		let _ = check.callback.send(Err(ValidationFailed::ShuttingDown));
+	}
+}
+
+/// Emit the details of a validation about to start, if the validation was requested to be verbose.
+fn trace_validation_start(verbose: bool, descriptor: &CandidateDescriptor) {
+	if !verbose {
+		return;
+	}
+
+	tracing::trace!(
+		target: VERBOSE_LOG_TARGET,
+		para_id = ?descriptor.para_id,
+		relay_parent = ?descriptor.relay_parent,
+		pov_hash = ?descriptor.pov_hash,
+		validation_code_hash = ?descriptor.validation_code_hash,
+		persisted_validation_data_hash = ?descriptor.persisted_validation_data_hash,
+		"Validating candidate",
+	);
+}
+
+/// Emit the outcome of a validation, if the validation was requested to be verbose.
+fn trace_validation_outcome(
+	verbose: bool,
+	descriptor: &CandidateDescriptor,
+	result: &Result<ValidationResult, ValidationFailed>,
+) {
+	if !verbose {
+		return;
+	}
+
+	tracing::trace!(
+		target: VERBOSE_LOG_TARGET,
+		para_id = ?descriptor.para_id,
+		pov_hash = ?descriptor.pov_hash,
+		?result,
+		"Candidate validation concluded",
+	);
+}
+
+/// Log the leading bytes of a PoV that failed to decompress, if a preview is configured.
+fn log_pov_preview(
+	pov: &PoV,
+	preview_len: usize,
+	result: &Result<ValidationResult, ValidationFailed>,
+) {
+	if preview_len == 0 {
+		return;
+	}
+
+	if let Ok(ValidationResult::Invalid(InvalidCandidate::PoVDecompressionFailure)) = result {
+		let block_data = &pov.block_data.0;
+		let preview = block_data[..block_data.len().min(preview_len)]
+			.iter()
+			.map(|byte| format!("{:02x}", byte))
+			.collect::<String>();
+
+		tracing::debug!(
+			target: LOG_TARGET,
+			pov_hash = ?pov.hash(),
+			pov_preview = %preview,
+			"PoV failed to decompress",
+		);
+	}
+}
+
+/// Verify the PoV against the integrity checksum attached to the request, if any.
+fn check_pov_integrity(pov: &PoV, checksum: Option<Hash>) -> Result<(), ValidationFailed> {
+	match checksum {
+		Some(checksum) if pov_integrity_checksum(pov) != checksum => {
+			tracing::debug!(
+				target: LOG_TARGET,
+				pov_hash = ?pov.hash(),
+				"PoV doesn't match the attached integrity checksum",
+			);
+
+			Err(ValidationFailed::Other("pov integrity checksum mismatch".into()))
+		}
+		_ => Ok(()),
+	}
+}
+
+/// Build the event describing the outcome of a validation for the outcome subscribers.
+fn outcome_event(
+	descriptor: &CandidateDescriptor,
+	result: &Result<ValidationResult, ValidationFailed>,
+	head_data_limit: Option<usize>,
+	dispute_policy: Option<&DisputePolicy>,
+) -> ValidationOutcomeEvent {
+	let outcome = match result {
+		Ok(ValidationResult::Valid(outputs, _, _)) => {
+			let mut head_data = outputs.head_data.clone();
+			if let Some(limit) = head_data_limit {
+				head_data.0.truncate(limit);
+			}
+
+			PublishedOutcome::Valid {
+				head_data,
+				head_data_hash: outputs.head_data.hash(),
+			}
+		}
+		Ok(ValidationResult::Invalid(e)) => PublishedOutcome::Invalid {
+			reason: format!("{:?}", e),
+			severity: dispute_severity(dispute_policy, e),
+		},
+		Ok(ValidationResult::Unchanged) => PublishedOutcome::Unchanged,
+		Err(e) => PublishedOutcome::Failed(e.to_string()),
+	};
+
+	ValidationOutcomeEvent {
+		para_id: descriptor.para_id,
+		relay_parent: descriptor.relay_parent,
+		pov_hash: descriptor.pov_hash,
+		outcome,
+		completed_at: SystemTime::now(),
+	}
+}
+
+/// Answer a request for a proof of the outcome of a validation, signed with the requested key.
+async fn send_validation_proof(
+	keystore: Option<&SyncCryptoStorePtr>,
+	descriptor: &CandidateDescriptor,
+	request: Option<ProofRequest>,
+	result: &Result<ValidationResult, ValidationFailed>,
+) {
+	let (keystore, request) = match (keystore, request) {
+		(Some(keystore), Some(request)) => (keystore, request),
+		_ => return,
+	};
+
+	let (valid, validation_code_hash) = match result {
+		Ok(ValidationResult::Valid(commitments, _, validation_code_hash)) => {
+			let receipt = CandidateReceipt {
+				descriptor: descriptor.clone(),
+				commitments_hash: commitments.hash(),
+			};
+
+			// Never vouch for a candidate other than the one that was validated.
+			if receipt.hash() != request.candidate_hash {
+				tracing::debug!(
+					target: LOG_TARGET,
+					candidate_hash = ?request.candidate_hash,
+					"Not signing a validation proof for a candidate with different commitments",
+				);
+				return;
+			}
+
+			(true, *validation_code_hash)
+		}
+		Ok(ValidationResult::Invalid(_)) => (false, descriptor.validation_code_hash),
+		Ok(ValidationResult::Unchanged) | Err(_) => return,
+	};
+
+	match ValidationProof::sign(
+		keystore,
+		request.candidate_hash,
+		valid,
+		validation_code_hash,
+		request.validator,
+	).await {
+		Ok(Some(proof)) => {
+			let _ = request.response.send(proof);
+		}
+		Ok(None) => tracing::debug!(
+			target: LOG_TARGET,
+			candidate_hash = ?request.candidate_hash,
+			"Key requested for signing a validation proof is not in the keystore",
+		),
+		Err(e) => tracing::warn!(
+			target: LOG_TARGET,
+			candidate_hash = ?request.candidate_hash,
+			err = ?e,
+			"Failed to sign a validation proof",
+		),
+	}
+}
+
+/// Record the outcome of a validation to the given sink.
+fn record_outcome(
+	sink: &dyn ValidationRecordSink,
+	descriptor: &CandidateDescriptor,
+	result: &Result<ValidationResult, ValidationFailed>,
+	dispute_policy: Option<&DisputePolicy>,
+) {
+	sink.record(&outcome_event(descriptor, result, None, dispute_policy));
+}
+
+/// Publish the outcome of a validation to all subscribers. Subscribers that hung up are removed,
+/// the ones that don't keep up miss the event.
+fn publish_outcome(
+	subscribers: &mut Vec<mpsc::Sender<ValidationOutcomeEvent>>,
+	descriptor: &CandidateDescriptor,
+	result: &Result<ValidationResult, ValidationFailed>,
+	head_data_limit: Option<usize>,
+	dispute_policy: Option<&DisputePolicy>,
+) {
+	if subscribers.is_empty() {
+		return;
+	}
+
+	let event = outcome_event(descriptor, result, head_data_limit, dispute_policy);
+	for subscriber in subscribers.iter_mut() {
+		let _ = subscriber.try_send(event.clone());
+	}
+	subscribers.retain(|subscriber| !subscriber.is_closed());
+}
+
+/// The sender of the messages to the overseer on behalf of a validation. Those of validations
+/// requested by an essential source are handed to the unbounded queues of their recipients, so
+/// that they never wait for the overseer, and thus never time out. Their number is bounded by the
+/// protocol rather than by the load.
+#[derive(Clone)]
+struct ValidationSender<S> {
+	sender: S,
+	unbounded: bool,
+}
+
+impl<S: SubsystemSender> ValidationSender<S> {
+	fn new(sender: S, source: ValidationSource) -> Self {
+		ValidationSender { sender, unbounded: is_essential(source) }
+	}
+}
+
+#[async_trait]
+impl<S: SubsystemSender> SubsystemSender for ValidationSender<S> {
+	async fn send_message(&mut self, msg: AllMessages) {
+		if self.unbounded {
+			self.sender.send_unbounded_message(msg);
+		} else {
+			self.sender.send_message(msg).await;
+		}
+	}
+
+	async fn send_messages<T>(&mut self, msgs: T)
+	where
+		T: IntoIterator<Item = AllMessages> + Send,
+		T::IntoIter: Send,
+	{
+		if self.unbounded {
+			for msg in msgs {
+				self.sender.send_unbounded_message(msg);
+			}
+		} else {
+			self.sender.send_messages(msgs).await;
+		}
+	}
+
+	fn send_unbounded_message(&mut self, msg: AllMessages) {
+		self.sender.send_unbounded_message(msg);
+	}
+}
+
+/// Send a message to the overseer, giving up once the timeout expires. The message is dropped then,
+/// along with any response channel it carries.
+async fn send_with_timeout(
+	sender: &mut impl SubsystemSender,
+	msg: AllMessages,
+	timeout: Duration,
+) -> Result<(), ValidationFailed> {
+	match future::select(sender.send_message(msg), futures_timer::Delay::new(timeout)).await {
+		future::Either::Left(((), _)) => Ok(()),
+		future::Either::Right(((), _)) => {
+			tracing::warn!(
+				target: LOG_TARGET,
+				?timeout,
+				"Sending a message to the overseer timed out",
+			);
+
+			Err(ValidationFailed::Other("overseer send timed out".into()))
+		}
+	}
+}
+
+/// Await the execution of a PVF for at most the given time. An execution that doesn't conclude in
+/// time is treated like one the PVF host timed out.
+async fn execute_with_timeout(
+	execution: impl Future<Output = Result<WasmValidationResult, ValidationError>> + Unpin,
+	timeout: Duration,
+) -> Result<WasmValidationResult, ValidationError> {
+	match future::select(execution, futures_timer::Delay::new(timeout)).await {
+		future::Either::Left((result, _)) => result,
+		future::Either::Right(((), _)) => {
+			Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::HardTimeout))
+		}
+	}
+}
+
+async fn runtime_api_request<T>(
+	sender: &mut impl SubsystemSender,
+	relay_parent: Hash,
+	request: RuntimeApiRequest,
+	receiver: oneshot::Receiver<Result<T, RuntimeApiError>>,
+) -> SubsystemResult<Result<T, RuntimeApiError>> {
+	send_with_timeout(
+		sender,
+		AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+			relay_parent,
+			request,
+		)),
+		OVERSEER_SEND_TIMEOUT,
+	).await.map_err(|e| SubsystemError::with_origin("candidate-validation", e))?;
+
+	receiver.await.map_err(runtime_api_dropped)
+}
+
+/// The error of a request to the runtime API being dropped without an answer.
+fn runtime_api_dropped(_: oneshot::Canceled) -> SubsystemError {
+	SubsystemError::with_origin(
+		"candidate-validation",
+		ValidationFailed::RuntimeApi("runtime API request dropped".into()),
+	)
+}
+
+/// The latest version of the `ParachainHost` runtime API whose requests this subsystem knows how
+/// to shape. Relay-parents exposing a later version are queried like ones exposing this version.
+const LATEST_KNOWN_RUNTIME_API_VERSION: u32 = 1;
+
+/// Detect the version of the `ParachainHost` runtime API exposed by the given relay-parent, if any.
+///
+/// The version is detected per relay-parent, since relay-parents on either side of a runtime
+/// upgrade may expose different versions.
+async fn request_runtime_api_version(
+	sender: &mut impl SubsystemSender,
+	relay_parent: Hash,
+) -> SubsystemResult<Option<u32>> {
+	let (tx, rx) = oneshot::channel();
+	let version = runtime_api_request(sender, relay_parent, RuntimeApiRequest::Version(tx), rx).await?;
+
+	Ok(match version {
+		Ok(version) => Some(version),
+		Err(e) => {
+			tracing::debug!(
+				target: LOG_TARGET,
+				?relay_parent,
+				err = ?e,
+				"The relay-parent doesn't expose the parachain host runtime API",
+			);
+
+			None
+		}
+	})
+}
+
+#[derive(Debug)]
+enum AssumptionCheckOutcome {
+	Matches(PersistedValidationData, ValidationCode),
+	/// None of the attempted assumptions yields the persisted validation data hash committed to
+	/// in the descriptor. Contains the hash computed under each attempted assumption.
+	DoesNotMatch(Vec<(OccupiedCoreAssumption, Hash)>),
+	/// A runtime API request checking an assumption failed, as described.
+	BadRequest(AssumptionCheckFailure),
+}
+
+async fn request_persisted_validation_data(
+	sender: &mut impl SubsystemSender,
+	descriptor: &CandidateDescriptor,
+	assumption: OccupiedCoreAssumption,
+	snapshot: Option<&RuntimeStateSnapshot>,
+) -> SubsystemResult<Result<Option<PersistedValidationData>, RuntimeApiError>> {
+	if let Some(snapshot) = snapshot {
+		return Ok(Ok(snapshot.persisted_validation_data(assumption).cloned()));
+	}
+
+	let (tx, rx) = oneshot::channel();
+	runtime_api_request(
+		sender,
+		descriptor.relay_parent,
+		RuntimeApiRequest::PersistedValidationData(
+			descriptor.para_id,
+			assumption,
+			tx,
+		),
+		rx,
+	).await
+}
+
+async fn request_validation_code(
+	sender: &mut impl SubsystemSender,
+	descriptor: &CandidateDescriptor,
+	assumption: OccupiedCoreAssumption,
+	snapshot: Option<&RuntimeStateSnapshot>,
+) -> SubsystemResult<Result<Option<ValidationCode>, RuntimeApiError>> {
+	if let Some(snapshot) = snapshot {
+		return Ok(Ok(snapshot.validation_code(assumption).cloned()));
+	}
+
+	let (tx, rx) = oneshot::channel();
+	runtime_api_request(
+		sender,
+		descriptor.relay_parent,
+		RuntimeApiRequest::ValidationCode(
+			descriptor.para_id,
+			assumption,
+			tx,
+		),
+		rx,
+	).await
+}
+
+/// The runtime API requests checking an occupied core assumption, dispatched but not answered yet.
+struct PendingAssumptionCheck {
+	assumption: OccupiedCoreAssumption,
+	validation_data: oneshot::Receiver<Result<Option<PersistedValidationData>, RuntimeApiError>>,
+	prefetched_code: Option<oneshot::Receiver<Result<Option<ValidationCode>, RuntimeApiError>>>,
+}
+
+/// Dispatch the runtime API requests checking the given occupied core assumption without waiting
+/// for the answers, so that several assumptions can be checked concurrently.
+async fn dispatch_assumption_check(
+	sender: &mut impl SubsystemSender,
+	descriptor: &CandidateDescriptor,
+	assumption: OccupiedCoreAssumption,
+	prefetch_code: bool,
+	snapshot: Option<&RuntimeStateSnapshot>,
+) -> PendingAssumptionCheck {
+	// A snapshot is read without a round-trip, so there is nothing to gain from prefetching.
+	let prefetched_code = if prefetch_code && snapshot.is_none() {
+		// If the request can't be sent, the receiver is canceled and the check fails when concluded.
+		let (tx, rx) = oneshot::channel();
+		let _ = send_with_timeout(
+			sender,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				descriptor.relay_parent,
+				RuntimeApiRequest::ValidationCode(descriptor.para_id, assumption, tx),
+			)),
+			OVERSEER_SEND_TIMEOUT,
+		).await;
+
+		Some(rx)
+	} else {
+		None
+	};
+
+	let (tx, validation_data) = oneshot::channel();
+	match snapshot {
+		Some(snapshot) => {
+			let _ = tx.send(Ok(snapshot.persisted_validation_data(assumption).cloned()));
+		}
+		None => {
+			let _ = send_with_timeout(
+				sender,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					descriptor.relay_parent,
+					RuntimeApiRequest::PersistedValidationData(descriptor.para_id, assumption, tx),
+				)),
+				OVERSEER_SEND_TIMEOUT,
+			).await;
+		}
+	}
+
+	PendingAssumptionCheck { assumption, validation_data, prefetched_code }
+}
+
+/// Wait for the answers to the requests of a dispatched assumption check and conclude it.
+async fn conclude_assumption_check(
+	sender: &mut impl SubsystemSender,
+	descriptor: &CandidateDescriptor,
+	pending: PendingAssumptionCheck,
+	retry_delay: Option<Duration>,
+	snapshot: Option<&RuntimeStateSnapshot>,
+) -> SubsystemResult<AssumptionCheckOutcome> {
+	let PendingAssumptionCheck { assumption, validation_data, prefetched_code } = pending;
+
+	let validation_data = {
+		let mut d = validation_data.await.map_err(runtime_api_dropped)?;
+
+		// A snapshot doesn't change, so there is no point in retrying.
+		match retry_delay {
+			Some(retry_delay) if snapshot.is_none() && matches!(d, Ok(None)) => {
+				tracing::debug!(
+					target: LOG_TARGET,
+					?assumption,
+					"Persisted validation data is unavailable, retrying after {:?}",
+					retry_delay,
+				);
+
+				futures_timer::Delay::new(retry_delay).await;
+				d = request_persisted_validation_data(sender, descriptor, assumption, None).await?;
+			}
+			_ => {}
+		}
+
+		match d {
+			Ok(None) => {
+				return Ok(AssumptionCheckOutcome::BadRequest(AssumptionCheckFailure::ValidationDataMissing));
+			}
+			Err(e) => {
+				tracing::debug!(
+					target: LOG_TARGET,
+					?assumption,
+					err = ?e,
+					"Requesting the persisted validation data failed",
+				);
+
+				return Ok(AssumptionCheckOutcome::BadRequest(AssumptionCheckFailure::ValidationDataError));
+			}
+			Ok(Some(d)) => d,
+		}
+	};
+
+	let persisted_validation_data_hash = validation_data.hash();
+
+	SubsystemResult::Ok(if descriptor.persisted_validation_data_hash == persisted_validation_data_hash {
+		let validation_code = match prefetched_code {
+			Some(rx) => rx.await.map_err(runtime_api_dropped)?,
+			None => request_validation_code(sender, descriptor, assumption, snapshot).await?,
+		};
+
+		match validation_code {
+			Ok(None) => AssumptionCheckOutcome::BadRequest(AssumptionCheckFailure::ValidationCodeMissing),
+			Err(e) => {
+				tracing::debug!(
+					target: LOG_TARGET,
+					?assumption,
+					err = ?e,
+					"Requesting the validation code failed",
+				);
+
+				AssumptionCheckOutcome::BadRequest(AssumptionCheckFailure::ValidationCodeError)
+			}
+			Ok(Some(v)) => AssumptionCheckOutcome::Matches(validation_data, v),
+		}
+	} else {
+		if prefetched_code.is_some() {
+			tracing::trace!(
+				target: LOG_TARGET,
+				?assumption,
+				"Discarding the validation code prefetched under a mismatching assumption",
+			);
+		}
+
+		AssumptionCheckOutcome::DoesNotMatch(vec![(assumption, persisted_validation_data_hash)])
+	})
+}
+
+async fn find_assumed_validation_data(
+	sender: &mut impl SubsystemSender,
+	descriptor: &CandidateDescriptor,
+	retry_delay: Option<Duration>,
+	max_assumptions: Option<usize>,
+	check_free_assumption: bool,
+	prefetch_code: bool,
+	snapshot: Option<&RuntimeStateSnapshot>,
+	steps: &mut Vec<ValidationStep>,
+	metrics: &Metrics,
+) -> SubsystemResult<AssumptionCheckOutcome> {
+	// The candidate descriptor has a `persisted_validation_data_hash` which corresponds to
+	// one of up to two possible values that we can derive from the state of the
+	// relay-parent. We can fetch these values by getting the persisted validation data
+	// based on the different `OccupiedCoreAssumption`s.
+
+	const ASSUMPTIONS: &[OccupiedCoreAssumption] = &[
+		OccupiedCoreAssumption::Included,
+		OccupiedCoreAssumption::TimedOut,
+		// `TimedOut` and `Free` both don't perform any speculation and therefore should be the same
+		// for our purposes here. In other words, if `TimedOut` matched then the `Free` must be
+		// matched as well. That is up to the runtime though, so `Free` is only tried if asked to,
+		// and only if a snapshot the candidate is validated from captured it.
+		OccupiedCoreAssumption::Free,
+	];
+
+	let try_free = check_free_assumption && snapshot.map_or(true, |snapshot| {
+		snapshot.persisted_validation_data(OccupiedCoreAssumption::Free).is_some()
+	});
+	let assumptions = if try_free { ASSUMPTIONS } else { &ASSUMPTIONS[..ASSUMPTIONS.len() - 1] };
+
+	let to_try = max_assumptions.map_or(assumptions.len(), |max| max.min(assumptions.len()));
+	let mut attempted = Vec::with_capacity(to_try);
+
+	// A snapshot was taken with the shape of the requests this subsystem knows, so only the live
+	// state of the relay-parent needs its version detected.
+	if snapshot.is_none() {
+		match request_runtime_api_version(sender, descriptor.relay_parent).await? {
+			// Requests shaped for an unknown version would be misinterpreted.
+			None => return Ok(AssumptionCheckOutcome::BadRequest(AssumptionCheckFailure::VersionError)),
+			Some(version) if version > LATEST_KNOWN_RUNTIME_API_VERSION => {
+				tracing::debug!(
+					target: LOG_TARGET,
+					relay_parent = ?descriptor.relay_parent,
+					version,
+					"Querying a later parachain host runtime API version like version {}",
+					LATEST_KNOWN_RUNTIME_API_VERSION,
+				);
+			}
+			Some(_) => {}
+		}
+	}
+
+	// All the checks are dispatched upfront, so that the runtime API answers them concurrently.
+	// They are concluded in order though, so that an earlier assumption takes precedence.
+	let mut pending = Vec::with_capacity(to_try);
+	for assumption in &assumptions[..to_try] {
+		pending.push(dispatch_assumption_check(
+			sender,
+			descriptor,
+			*assumption,
+			prefetch_code,
+			snapshot,
+		).await);
+	}
+
+	for check in pending {
+		let assumption = check.assumption;
+		let outcome = conclude_assumption_check(sender, descriptor, check, retry_delay, snapshot).await?;
+
+		match outcome {
+			AssumptionCheckOutcome::Matches(ref validation_data, _) => {
+				if assumption == OccupiedCoreAssumption::Free {
+					tracing::warn!(
+						target: LOG_TARGET,
+						relay_parent = ?descriptor.relay_parent,
+						para_id = ?descriptor.para_id,
+						"The `Free` occupied core assumption matched where `TimedOut` didn't",
+					);
+				}
+
+				steps.push(ValidationStep::AssumptionMatched(assumption));
+				steps.push(ValidationStep::ValidationDataFound(validation_data.clone()));
+				return Ok(outcome);
+			}
+			AssumptionCheckOutcome::BadRequest(_) => return Ok(outcome),
+			AssumptionCheckOutcome::DoesNotMatch(attempts) => attempted.extend(attempts),
+		}
+	}
+
+	if to_try < assumptions.len() {
+		metrics.on_assumption_cap_reached();
+	}
+
+	steps.push(ValidationStep::NoAssumptionMatched);
+	Ok(AssumptionCheckOutcome::DoesNotMatch(attempted))
+}
+
+/// The occupied core assumption the persisted validation data was found under, according to the
+/// steps taken while validating a candidate.
+fn matched_assumption(steps: &[ValidationStep]) -> Option<OccupiedCoreAssumption> {
+	steps.iter().find_map(|step| match step {
+		ValidationStep::AssumptionMatched(assumption) => Some(*assumption),
+		_ => None,
+	})
+}
+
+/// The persisted validation data the candidate is based on, according to the steps taken while
+/// validating it.
+fn found_validation_data(steps: &[ValidationStep]) -> Option<&PersistedValidationData> {
+	steps.iter().find_map(|step| match step {
+		ValidationStep::ValidationDataFound(validation_data) => Some(validation_data),
+		_ => None,
+	})
+}
+
+/// The fingerprint of the execution of the PVF, according to the steps taken while validating a
+/// candidate.
+fn execution_fingerprint(steps: &[ValidationStep]) -> Option<Hash> {
+	steps.iter().find_map(|step| match step {
+		ValidationStep::Fingerprinted(fingerprint) => Some(*fingerprint),
+		_ => None,
+	})
+}
+
+/// A check of the outputs of a provisionally valid candidate, deferred to the next block boundary.
+struct DeferredOutputCheck {
+	relay_parent: Hash,
+	para_id: ParaId,
+	outputs: CandidateCommitments,
+	callback: oneshot::Sender<Result<bool, ValidationFailed>>,
+}
+
+/// Perform the given deferred output checks and report the outcomes to their callbacks.
+async fn resolve_deferred_output_checks(
+	sender: &mut impl SubsystemSender,
+	checks: Vec<DeferredOutputCheck>,
+) -> SubsystemResult<()> {
+	// Dispatch all the checks before awaiting any of the answers.
+	let mut pending = Vec::with_capacity(checks.len());
+	for check in checks {
+		let (tx, rx) = oneshot::channel();
+		sender.send_message(
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				check.relay_parent,
+				RuntimeApiRequest::CheckValidationOutputs(check.para_id, check.outputs, tx),
+			))
+		).await;
+
+		pending.push((rx, check.callback));
+	}
+
+	for (rx, callback) in pending {
+		let verdict = match rx.await.map_err(runtime_api_dropped)? {
+			Ok(accepted) => Ok(accepted),
+			Err(_) => Err(ValidationFailed::OutputsCheck),
+		};
+
+		let _ = callback.send(verdict);
+	}
+
+	Ok(())
+}
+
+/// Assemble the PoV of a candidate from the chunks of its block data as they arrive, until the
+/// sender hangs up.
+///
+/// A PoV growing larger than `limit` renders the candidate invalid as soon as it does, in which
+/// case the outcome is returned instead of the PoV, as it is if the PoV fails the integrity check.
+async fn assemble_pov(
+	mut chunks: mpsc::Receiver<Vec<u8>>,
+	limit: usize,
+	checksum: Option<Hash>,
+) -> Result<Arc<PoV>, Result<ValidationResult, ValidationFailed>> {
+	let mut block_data = Vec::new();
+	while let Some(chunk) = chunks.next().await {
+		let size = block_data.len() + chunk.len();
+		if size > limit {
+			return Err(Ok(ValidationResult::Invalid(InvalidCandidate::ParamsTooLarge(size as u64))));
+		}
+
+		block_data.extend_from_slice(&chunk);
+	}
+
+	let pov = PoV { block_data: BlockData(block_data) };
+	check_pov_integrity(&pov, checksum).map_err(Err)?;
+
+	Ok(Arc::new(pov))
+}
+
+async fn spawn_validate_from_chain_state(
+	sender: &mut impl SubsystemSender,
+	validation_backend: impl ValidationBackend,
+	descriptor: CandidateDescriptor,
+	pov: Arc<PoV>,
+	retry_delay: Option<Duration>,
+	max_assumptions: Option<usize>,
+	check_free_assumption: bool,
+	prefetch_code: bool,
+	snapshot: Option<&RuntimeStateSnapshot>,
+	defer_output_check: bool,
+	received_at: Instant,
+	options: &ExhaustiveOptions,
+	steps: &mut Vec<ValidationStep>,
+	metrics: &Metrics,
+) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
+	validate_from_chain_state_with(
+		sender,
+		validation_backend,
+		descriptor,
+		future::ready(Ok(pov)),
+		retry_delay,
+		max_assumptions,
+		check_free_assumption,
+		prefetch_code,
+		snapshot,
+		defer_output_check,
+		received_at,
+		options,
+		steps,
+		metrics,
+	).await
+}
+
+/// Like [`spawn_validate_from_chain_state`], but with the PoV still to be obtained through the given
+/// future, which runs concurrently with the lookup of the validation data. The future resolves to
+/// the outcome of the validation instead if the PoV turns out to be unusable.
+async fn validate_from_chain_state_with(
+	sender: &mut impl SubsystemSender,
+	validation_backend: impl ValidationBackend,
+	descriptor: CandidateDescriptor,
+	pov: impl Future<Output = Result<Arc<PoV>, Result<ValidationResult, ValidationFailed>>>,
+	retry_delay: Option<Duration>,
+	max_assumptions: Option<usize>,
+	check_free_assumption: bool,
+	prefetch_code: bool,
+	snapshot: Option<&RuntimeStateSnapshot>,
+	defer_output_check: bool,
+	received_at: Instant,
+	options: &ExhaustiveOptions,
+	steps: &mut Vec<ValidationStep>,
+	metrics: &Metrics,
+) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
+	let span = tracing::debug_span!(
+		target: LOG_TARGET,
+		"validate-from-chain-state",
+		para_id = ?descriptor.para_id,
+		relay_parent = ?descriptor.relay_parent,
+		pov_hash = ?descriptor.pov_hash,
+		candidate_hash = tracing::field::Empty,
+	);
+
+	let result = validate_from_chain_state_inner(
+		sender,
+		validation_backend,
+		descriptor.clone(),
+		pov,
+		retry_delay,
+		max_assumptions,
+		check_free_assumption,
+		prefetch_code,
+		snapshot,
+		defer_output_check,
+		received_at,
+		options,
+		steps,
+		metrics,
+	).instrument(span.clone()).await;
+
+	record_candidate_hash(&span, &descriptor, &result);
+	result
+}
+
+async fn validate_from_chain_state_inner(
+	sender: &mut impl SubsystemSender,
+	validation_backend: impl ValidationBackend,
+	descriptor: CandidateDescriptor,
+	pov: impl Future<Output = Result<Arc<PoV>, Result<ValidationResult, ValidationFailed>>>,
+	retry_delay: Option<Duration>,
+	max_assumptions: Option<usize>,
+	check_free_assumption: bool,
+	prefetch_code: bool,
+	snapshot: Option<&RuntimeStateSnapshot>,
+	defer_output_check: bool,
+	received_at: Instant,
+	options: &ExhaustiveOptions,
+	steps: &mut Vec<ValidationStep>,
+	metrics: &Metrics,
+) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
+	let (assumption_check, pov) = future::join(
+		find_assumed_validation_data(
+			sender,
+			&descriptor,
+			retry_delay,
+			max_assumptions,
+			check_free_assumption,
+			prefetch_code,
+			snapshot,
+			steps,
+			metrics,
+		),
+		pov,
+	).await;
+
+	let (validation_data, validation_code) =
+		match assumption_check? {
+			AssumptionCheckOutcome::Matches(validation_data, validation_code) => {
+				(validation_data, validation_code)
+			}
+			AssumptionCheckOutcome::DoesNotMatch(attempted) => {
+				// If neither the assumption of the occupied core having the para included or the assumption
+				// of the occupied core timing out are valid, then the persisted_validation_data_hash in the descriptor
+				// is not based on the relay parent and is thus invalid.
+				tracing::debug!(
+					target: LOG_TARGET,
+					expected = ?descriptor.persisted_validation_data_hash,
+					?attempted,
+					"No occupied core assumption matches the persisted validation data hash",
+				);
+
+				return Ok(Ok(ValidationResult::Invalid(InvalidCandidate::BadParent)));
+			}
+			AssumptionCheckOutcome::BadRequest(failure) => {
+				return Ok(Err(ValidationFailed::AssumptionCheck(failure)));
+			}
+		};
+
+	let pov = match pov {
+		Ok(pov) => pov,
+		Err(outcome) => return Ok(outcome),
+	};
+
+	metrics.on_admission(received_at);
+
+	let validation_result = validate_candidate_exhaustive_explained(
+		validation_backend,
+		validation_data,
+		validation_code,
+		descriptor.clone(),
+		pov,
+		options,
+		steps,
+		metrics,
+	)
+	.await;
+
+	if defer_output_check {
+		return validation_result;
+	}
+
+	check_validation_outputs(sender, &descriptor, validation_result?, steps).await
+}
+
+/// Check the outputs of a valid candidate against the acceptance criteria of the runtime at its
+/// relay-parent, turning the result invalid if they don't pass. Other results are returned as
+/// they are.
+async fn check_validation_outputs(
+	sender: &mut impl SubsystemSender,
+	descriptor: &CandidateDescriptor,
+	validation_result: Result<ValidationResult, ValidationFailed>,
+	steps: &mut Vec<ValidationStep>,
+) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
+	if let Ok(ValidationResult::Valid(ref outputs, _, _)) = validation_result {
+		let (tx, rx) = oneshot::channel();
+		match runtime_api_request(
+			sender,
+			descriptor.relay_parent,
+			RuntimeApiRequest::CheckValidationOutputs(descriptor.para_id, outputs.clone(), tx),
+			rx,
+		)
+		.await?
+		{
+			Ok(true) => steps.push(ValidationStep::OutputsChecked(true)),
+			Ok(false) => {
+				steps.push(ValidationStep::OutputsChecked(false));
+				return Ok(Ok(ValidationResult::Invalid(
+					InvalidCandidate::InvalidOutputs,
+				)));
+			}
+			Err(_) => {
+				return Ok(Err(ValidationFailed::OutputsCheck));
+			}
+		}
+	}
+
+	Ok(validation_result)
+}
+
+/// Record the hash of a valid candidate on the span of its validation. The hash commits to the
+/// outputs of the candidate, so it is only known once the candidate was found valid.
+fn record_candidate_hash(
+	span: &tracing::Span,
+	descriptor: &CandidateDescriptor,
+	result: &SubsystemResult<Result<ValidationResult, ValidationFailed>>,
+) {
+	if let Ok(Ok(result)) = result {
+		if let Some(candidate_hash) = candidate_hash_of(descriptor, result) {
+			let _ = span.record("candidate_hash", &tracing::field::debug(candidate_hash));
+		}
+	}
+}
+
+/// The hash of the candidate with the given descriptor, if known from the result of validating
+/// it. It commits to the outputs of the candidate, so it's only known for valid ones.
+fn candidate_hash_of(
+	descriptor: &CandidateDescriptor,
+	result: &ValidationResult,
+) -> Option<CandidateHash> {
+	match result {
+		ValidationResult::Valid(commitments, _, _) => Some(CandidateReceipt {
+			descriptor: descriptor.clone(),
+			commitments_hash: commitments.hash(),
+		}.hash()),
+		_ => None,
+	}
+}
+
+/// A stable digest of the inputs of a candidate validation.
+///
+/// It is logged along with the outcome of the validation, so that the same candidate can be
+/// recognized when it is validated again, including across node restarts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InputDigest(pub Hash);
+
+impl InputDigest {
+	/// Compute the digest of the given validation inputs.
+	pub fn new(
+		validation_code: &ValidationCode,
+		pov: &PoV,
+		descriptor: &CandidateDescriptor,
+	) -> Self {
+		InputDigest(BlakeTwo256::hash_of(&(validation_code.hash(), pov.hash(), descriptor)))
+	}
+}
+
+impl fmt::Display for InputDigest {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		// The `Display` implementation of the hash abbreviates it.
+		write!(f, "{:?}", self.0)
+	}
+}
+
+/// The durations of the phases of a single candidate validation. Phases that weren't reached are
+/// `None`.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct ValidationPhases {
+	basic_checks: Option<Duration>,
+	code_decompression: Option<Duration>,
+	pov_decompression: Option<Duration>,
+	execution: Option<Duration>,
+}
+
+/// Run `f` and record the time it took in `phase`.
+fn time_phase<T>(phase: &mut Option<Duration>, f: impl FnOnce() -> T) -> T {
+	let start = Instant::now();
+	let res = f();
+	*phase = Some(start.elapsed());
+	res
+}
+
+/// Format a record of a validation span, as appended to the span log.
+///
+/// A record is a single line of space-separated `key=value` pairs. Durations are in microseconds,
+/// with `-` standing for phases that weren't reached.
+fn span_record(
+	digest: &InputDigest,
+	para_id: ParaId,
+	outcome: &str,
+	phases: &ValidationPhases,
+	total: Duration,
+) -> String {
+	let micros = |phase: Option<Duration>| phase.map_or_else(
+		|| "-".to_owned(),
+		|d| d.as_micros().to_string(),
+	);
+
+	format!(
+		"digest={} para_id={} outcome={} basic_checks_us={} code_decompression_us={} \
+			pov_decompression_us={} execution_us={} total_us={}",
+		digest,
+		u32::from(para_id),
+		outcome,
+		micros(phases.basic_checks),
+		micros(phases.code_decompression),
+		micros(phases.pov_decompression),
+		micros(phases.execution),
+		total.as_micros(),
+	)
+}
+
+/// Append a record to the span log, rotating the file first if it grew beyond its maximum size.
+fn append_span_record(config: &SpanLogConfig, record: &str) -> std::io::Result<()> {
+	use std::io::Write as _;
+
+	if std::fs::metadata(&config.path).map_or(false, |m| m.len() >= config.max_file_size) {
+		let mut rotated = config.path.clone().into_os_string();
+		rotated.push(".1");
+		std::fs::rename(&config.path, rotated)?;
+	}
+
+	let mut file = std::fs::OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(&config.path)?;
+	writeln!(file, "{}", record)
+}
+
+/// Decompress the block data of the PoV to at most `bomb_limit` bytes. With a timeout, the
+/// decompression runs on a dedicated thread and `None` is returned if it doesn't finish in time.
+async fn decompress_pov(
+	pov: &Arc<PoV>,
+	bomb_limit: usize,
+	timeout: Option<Duration>,
+) -> Option<Result<Vec<u8>, sp_maybe_compressed_blob::Error>> {
+	let decompress = {
+		let pov = pov.clone();
+		move || sp_maybe_compressed_blob::decompress(&pov.block_data.0, bomb_limit)
+			.map(|block_data| block_data.into_owned())
+	};
+
+	match timeout {
+		None => Some(decompress()),
+		Some(timeout) => run_blocking_with_timeout(decompress, timeout).await,
+	}
+}
+
+/// Run `f` on a dedicated thread, returning `None` if it doesn't finish within the timeout.
+///
+/// A thread that overruns the timeout can't be interrupted. It runs to completion and its result
+/// is discarded.
+async fn run_blocking_with_timeout<T: Send + 'static>(
+	f: impl FnOnce() -> T + Send + 'static,
+	timeout: Duration,
+) -> Option<T> {
+	let (tx, rx) = oneshot::channel();
+	let _ = std::thread::spawn(move || {
+		let _ = tx.send(f());
+	});
+
+	match future::select(rx, futures_timer::Delay::new(timeout)).await {
+		future::Either::Left((Ok(res), _)) => Some(res),
+		_ => None,
+	}
+}
+
+/// Validate a candidate with the given validation backend, given all of its inputs. The options
+/// of requests with default [`ValidationOptions`](polkadot_subsystem::messages::ValidationOptions)
+/// are the default ones.
+pub async fn validate_candidate_exhaustive(
+	validation_backend: impl ValidationBackend,
+	persisted_validation_data: PersistedValidationData,
+	validation_code: ValidationCode,
+	descriptor: CandidateDescriptor,
+	pov: Arc<PoV>,
+	options: &ExhaustiveOptions,
+	metrics: &Metrics,
+) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
+	validate_candidate_exhaustive_explained(
+		validation_backend,
+		persisted_validation_data,
+		validation_code,
+		descriptor,
+		pov,
+		options,
+		&mut Vec::new(),
+		metrics,
+	).await
+}
+
+/// Like [`validate_candidate_exhaustive`], but also records the steps taken in `steps`.
+async fn validate_candidate_exhaustive_explained(
+	validation_backend: impl ValidationBackend,
+	persisted_validation_data: PersistedValidationData,
+	validation_code: ValidationCode,
+	descriptor: CandidateDescriptor,
+	pov: Arc<PoV>,
+	options: &ExhaustiveOptions,
+	steps: &mut Vec<ValidationStep>,
+	metrics: &Metrics,
+) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
+	let _timer = metrics.time_validate_candidate_exhaustive();
+	let start = Instant::now();
+
+	let digest = InputDigest::new(&validation_code, &*pov, &descriptor);
+	let para_id = descriptor.para_id;
+	let mut phases = ValidationPhases::default();
+
+	let span = tracing::debug_span!(
+		target: LOG_TARGET,
+		"validate-candidate-exhaustive",
+		?para_id,
+		relay_parent = ?descriptor.relay_parent,
+		pov_hash = ?descriptor.pov_hash,
+		candidate_hash = tracing::field::Empty,
+	);
+
+	let result = validate_candidate_exhaustive_inner(
+		validation_backend,
+		persisted_validation_data,
+		validation_code,
+		descriptor.clone(),
+		pov,
+		options,
+		&mut phases,
+		steps,
+		metrics,
+	).instrument(span.clone()).await;
+
+	record_candidate_hash(&span, &descriptor, &result);
+	let _span = span.enter();
+
+	if let Ok(ref result) = result {
+		let outcome = match result {
+			Ok(ValidationResult::Valid(_, _, _)) => "valid".to_owned(),
+			Ok(ValidationResult::Invalid(reason)) => format!("invalid: {:?}", reason),
+			Ok(ValidationResult::Unchanged) => "unchanged".to_owned(),
+			Err(e) => format!("failed: {}", e.description()),
+		};
+
+		tracing::debug!(
+			target: LOG_TARGET,
+			%digest,
+			?para_id,
+			relay_parent = ?descriptor.relay_parent,
+			candidate_hash = ?result.as_ref().ok().and_then(|res| candidate_hash_of(&descriptor, res)),
+			%outcome,
+			"Candidate validation concluded",
+		);
+
+		if let Some(span_log) = &options.span_log {
+			// The outcome is kept to a single token, so that records remain trivially parseable.
+			let outcome = match result {
+				Ok(ValidationResult::Valid(_, _, _)) => "valid",
+				Ok(ValidationResult::Invalid(_)) => "invalid",
+				Ok(ValidationResult::Unchanged) => "unchanged",
+				Err(_) => "failed",
+			};
+
+			let record = span_record(&digest, para_id, outcome, &phases, start.elapsed());
+			if let Err(e) = append_span_record(span_log, &record) {
+				tracing::warn!(
+					target: LOG_TARGET,
+					err = %e,
+					path = ?span_log.path,
+					?para_id,
+					relay_parent = ?descriptor.relay_parent,
+					"Failed to append to the validation span log",
+				);
+			}
+		}
+	}
+
+	result
+}
+
+async fn validate_candidate_exhaustive_inner(
+	mut validation_backend: impl ValidationBackend,
+	persisted_validation_data: PersistedValidationData,
+	validation_code: ValidationCode,
+	descriptor: CandidateDescriptor,
+	pov: Arc<PoV>,
+	options: &ExhaustiveOptions,
+	phases: &mut ValidationPhases,
+	steps: &mut Vec<ValidationStep>,
+	metrics: &Metrics,
+) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
+	// A relay-parent beyond the best block known to this node can't have been built upon.
+	if let Some(best_known_number) = options.best_known_number {
+		if persisted_validation_data.relay_parent_number > best_known_number {
+			tracing::debug!(
+				target: LOG_TARGET,
+				para_id = ?descriptor.para_id,
+				relay_parent = ?descriptor.relay_parent,
+				relay_parent_number = persisted_validation_data.relay_parent_number,
+				best_known_number,
+				"Candidate is based on a relay-parent in the future",
+			);
+
+			return Ok(Ok(ValidationResult::Invalid(InvalidCandidate::BadParent)));
+		}
+	}
+
+	metrics.on_pov_size(pov.encoded_size());
+
+	// Oversized code is rejected before the basic checks hash it, let alone decompress it.
+	if let Some(max_size) = options.max_compressed_code_size {
+		let code_size = validation_code.0.len();
+		if code_size > max_size {
+			tracing::debug!(
+				target: LOG_TARGET,
+				para_id = ?descriptor.para_id,
+				relay_parent = ?descriptor.relay_parent,
+				code_size,
+				max_size,
+				"Validation code is too large to decompress",
+			);
+
+			return Ok(Ok(ValidationResult::Invalid(InvalidCandidate::CodeTooLarge(code_size as u64))));
+		}
+	}
+
+	let basic_checks = time_phase(&mut phases.basic_checks, || perform_basic_checks(
+		&descriptor,
+		persisted_validation_data.max_pov_size,
+		&*pov,
+		&validation_code,
+	));
+	steps.push(ValidationStep::BasicChecks(basic_checks.as_ref().err().map(|e| format!("{:?}", e))));
+
+	if let Err(e) = basic_checks {
+		return Ok(Ok(ValidationResult::Invalid(e)));
+	}
+
+	// The outcome of an execution only depends on the code, the PoV and the persisted validation
+	// data, all of which the basic checks matched against the descriptor. Seeded executions depend
+	// on their seed too, so they bypass the cache.
+	let execution_cache_key = (
+		descriptor.validation_code_hash,
+		descriptor.pov_hash,
+		persisted_validation_data.hash(),
+	);
+	let execution_cache = options.execution_cache.as_ref().filter(|_| options.execution_seed.is_none());
+	let cached = match execution_cache {
+		Some(cache) => cache.lock().await.lookup(&execution_cache_key, metrics),
+		None => None,
+	};
+
+	let result = match cached {
+		Some(result) => {
+			tracing::debug!(
+				target: LOG_TARGET,
+				para_id = ?descriptor.para_id,
+				relay_parent = ?descriptor.relay_parent,
+				"Reusing the outcome of an earlier execution with the same inputs",
+			);
+
+			result
+		}
+		None => {
+			// The permit is held until the PoV is decompressed as well, so that both decompressed
+			// payloads count towards the limit.
+			let decompression_permit = match options.decompression_permits {
+				Some(ref permits) => Some(permits.acquire().await),
+				None => None,
+			};
+
+			let code_bomb_limit = options.validation_code_bomb_limit
+				.unwrap_or(VALIDATION_CODE_BOMB_LIMIT);
+
+			// The basic checks matched the code against its hash, so code decompressed for another
+			// candidate of the batch or while pre-checking it with the same hash can be reused.
+			let mut shared_code = match options.decompressed_code_cache {
+				Some(ref cache) => cache.lock().await.get(&descriptor.validation_code_hash).cloned(),
+				None => None,
+			};
+			if let (None, Some(ref prechecked)) = (&shared_code, &options.prechecked_code) {
+				// Pre-checking decompresses under the default limit, which the configured one may
+				// be below.
+				shared_code = prechecked.lock().await
+					.get(&descriptor.validation_code_hash)
+					.filter(|code| code.len() <= code_bomb_limit);
+			}
+			let raw_validation_code = match shared_code {
+				Some(ref code) => Ok(Cow::Borrowed(&code[..])),
+				None => {
+					let raw_validation_code = time_phase(&mut phases.code_decompression, || {
+						sp_maybe_compressed_blob::decompress(&validation_code.0, code_bomb_limit)
+					});
+					if let Some(duration) = phases.code_decompression {
+						metrics.on_decompression_time("code", duration);
+					}
+					metrics.on_code_decompression(
+						validation_code.0.len(),
+						raw_validation_code.as_ref().ok().map(|code| code.len()),
+					);
+					raw_validation_code
+				}
+			};
+			steps.push(ValidationStep::CodeDecompressed {
+				compressed_size: validation_code.0.len(),
+				decompressed_size: raw_validation_code.as_ref().ok().map(|code| code.len()),
+			});
+
+			let raw_validation_code = match raw_validation_code {
+				Ok(code) => code,
+				Err(e) => {
+					// The basic checks passed, so the code matches the hash committed to in the
+					// descriptor. A payload that is broken nonetheless rather hints at a compression
+					// bug on the collator side than at an attack.
+					tracing::warn!(
+						target: LOG_TARGET,
+						err = ?e,
+						para_id = ?descriptor.para_id,
+						relay_parent = ?descriptor.relay_parent,
+						validation_code_hash = ?descriptor.validation_code_hash,
+						"Validation code matching the committed hash fails to decompress",
+					);
+					metrics.on_code_decompression_failure_with_matching_hash();
+
+					// If the validation code is invalid, the candidate certainly is.
+					return Ok(Ok(ValidationResult::Invalid(InvalidCandidate::CodeDecompressionFailure)));
+				}
+			};
+			debug_assert!(raw_validation_code.len() <= code_bomb_limit);
+
+			if let (Some(ref cache), None) = (&options.decompressed_code_cache, &shared_code) {
+				let _ = cache.lock().await.insert(
+					descriptor.validation_code_hash,
+					Arc::new(raw_validation_code.to_vec()),
+				);
+			}
+
+			if let Some(expected) = options.decompressed_code_hash {
+				let actual = BlakeTwo256::hash(&raw_validation_code);
+				if actual != expected {
+					// The compressed code matches the committed hash, so what it decompresses to is
+					// determined by it. Another result hints at a bug on this side, for which the
+					// candidate is not to be blamed.
+					tracing::warn!(
+						target: LOG_TARGET,
+						para_id = ?descriptor.para_id,
+						relay_parent = ?descriptor.relay_parent,
+						validation_code_hash = ?descriptor.validation_code_hash,
+						?expected,
+						?actual,
+						"Validation code decompressed to unexpected code",
+					);
+
+					return Ok(Err(ValidationFailed::Other(
+						"decompressed validation code hash mismatch".into(),
+					)));
+				}
+			}
+
+			// The artifact is only trusted to be the code of the candidate if the hashes match.
+			let prepared_artifact = options.prepared_artifact.filter(|expected| {
+				let actual = ValidationCodeHash::from(BlakeTwo256::hash(&raw_validation_code));
+				if actual != *expected {
+					tracing::debug!(
+						target: LOG_TARGET,
+						para_id = ?descriptor.para_id,
+						relay_parent = ?descriptor.relay_parent,
+						?expected,
+						?actual,
+						"Ignoring the prepared artifact of different validation code",
+					);
+				}
+
+				actual == *expected
+			});
+
+			if let Some(max_cost) = options.max_preparation_cost {
+				let cost = estimate_preparation_cost(&raw_validation_code);
+				if cost > max_cost {
+					tracing::debug!(
+						target: LOG_TARGET,
+						para_id = ?descriptor.para_id,
+						relay_parent = ?descriptor.relay_parent,
+						cost,
+						max_cost,
+						"Refusing to prepare validation code above the preparation cost limit",
+					);
+
+					return Ok(Err(ValidationFailed::Other("preparation cost exceeds policy".into())));
+				}
+			}
+
+			let pov_decompression_start = Instant::now();
+			let raw_block_data = decompress_pov(
+				&pov,
+				options.pov_bomb_limit.unwrap_or(POV_BOMB_LIMIT),
+				options.pov_decompression_timeout,
+			).await;
+			phases.pov_decompression = Some(pov_decompression_start.elapsed());
+			metrics.on_decompression_time("pov", pov_decompression_start.elapsed());
+			drop(decompression_permit);
+			steps.push(ValidationStep::PoVDecompressed {
+				compressed_size: pov.block_data.0.len(),
+				decompressed_size: match raw_block_data {
+					Some(Ok(ref block_data)) => Some(block_data.len()),
+					_ => None,
+				},
+			});
+
+			let raw_block_data = match raw_block_data {
+				Some(Ok(block_data)) => {
+					metrics.on_pov_decompressed(block_data.len());
+					BlockData(block_data)
+				}
+				Some(Err(e)) => {
+					tracing::debug!(
+						target: LOG_TARGET,
+						err = ?e,
+						para_id = ?descriptor.para_id,
+						relay_parent = ?descriptor.relay_parent,
+						"Invalid PoV code",
+					);
+
+					// If the PoV is invalid, the candidate certainly is.
+					return Ok(Ok(ValidationResult::Invalid(InvalidCandidate::PoVDecompressionFailure)));
+				}
+				None => {
+					tracing::debug!(
+						target: LOG_TARGET,
+						pov_hash = ?descriptor.pov_hash,
+						para_id = ?descriptor.para_id,
+						relay_parent = ?descriptor.relay_parent,
+						"PoV decompression timed out",
+					);
+
+					// A PoV that is too costly to decompress is treated like one that doesn't
+					// decompress.
+					return Ok(Ok(ValidationResult::Invalid(InvalidCandidate::PoVDecompressionFailure)));
+				}
+			};
+
+			// The basic checks only bound the compressed PoV, which may decompress to far more
+			// than the para allows.
+			let block_data_size = raw_block_data.0.len();
+			if block_data_size > persisted_validation_data.max_pov_size as usize {
+				tracing::debug!(
+					target: LOG_TARGET,
+					para_id = ?descriptor.para_id,
+					relay_parent = ?descriptor.relay_parent,
+					block_data_size,
+					max_pov_size = persisted_validation_data.max_pov_size,
+					"PoV decompresses beyond the maximum PoV size",
+				);
+
+				return Ok(Ok(ValidationResult::Invalid(
+					InvalidCandidate::PoVSizeExceeded(block_data_size as u64),
+				)));
+			}
+
+			let params = ValidationParams {
+				parent_head: persisted_validation_data.parent_head.clone(),
+				block_data: raw_block_data,
+				relay_parent_number: persisted_validation_data.relay_parent_number,
+				relay_parent_storage_root: persisted_validation_data.relay_parent_storage_root,
+			};
+
+			let execution_start = Instant::now();
+			let mut result = execute_with_retries(
+				&mut validation_backend,
+				&raw_validation_code,
+				prepared_artifact,
+				&params,
+				options,
+				descriptor.para_id,
+			).await;
+
+			// The PVF host dispatches the second execution to any idle worker, so that a worker
+			// whose state leaks between executions is caught as well.
+			if options.differential_execution {
+				if let Ok(first) = result {
+					let second = execute_with_retries(
+						&mut validation_backend,
+						&raw_validation_code,
+						prepared_artifact,
+						&params,
+						options,
+						descriptor.para_id,
+					).await;
+
+					result = match second {
+						Ok(second) if second.encode() == first.encode() => Ok(first),
+						Err(ValidationError::InternalError(e)) => Err(ValidationError::InternalError(e)),
+						second => {
+							let receipt = CandidateReceipt {
+								descriptor: descriptor.clone(),
+								commitments_hash: commitments_of(&first).hash(),
+							};
+							tracing::warn!(
+								target: LOG_TARGET,
+								para_id = ?descriptor.para_id,
+								relay_parent = ?descriptor.relay_parent,
+								candidate_hash = ?receipt.hash(),
+								first = ?BlakeTwo256::hash_of(&first),
+								second = ?second.as_ref().map(BlakeTwo256::hash_of),
+								"Executing the PVF of a candidate twice yields different outputs",
+							);
+
+							phases.execution = Some(execution_start.elapsed());
+							steps.push(ValidationStep::Executed(None));
+							return Ok(Ok(ValidationResult::Invalid(InvalidCandidate::NonDeterministic)));
+						}
+					};
+				}
+			}
+
+			phases.execution = Some(execution_start.elapsed());
+			steps.push(ValidationStep::Executed(result.as_ref().err().map(|e| format!("{:?}", e))));
+			if let Ok(ref outputs) = result {
+				steps.push(ValidationStep::Fingerprinted(BlakeTwo256::hash_of(outputs)));
+			}
+
+			if let Err(ref e) = result {
+				tracing::debug!(
+					target: LOG_TARGET,
+					error = ?e,
+					para_id = ?descriptor.para_id,
+					relay_parent = ?descriptor.relay_parent,
+					"Failed to validate candidate",
+				);
+			}
+
+			if let Some(cache) = execution_cache {
+				cache.lock().await.insert(execution_cache_key, result.clone());
+			}
+
+			result
+		}
+	};
+
+	let result = match result {
+		Err(ValidationError::InternalError(e)) => Err(internal_validation_failure(e)),
+
+		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::HardTimeout)) =>
+			Ok(ValidationResult::Invalid(InvalidCandidate::Timeout)),
+		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::WorkerReportedError(e))) =>
+			Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError(e))),
+		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::AmbigiousWorkerDeath)) =>
+			Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError("ambigious worker death".to_string()))),
+
+		Ok(res) => {
+			if res.head_data.hash() != descriptor.para_head {
+				Ok(ValidationResult::Invalid(InvalidCandidate::ParaHeadHashMismatch))
+			} else {
+				let outputs = commitments_of(&res);
+				metrics.on_commitments(descriptor.para_id, &outputs);
+				Ok(ValidationResult::Valid(outputs, persisted_validation_data, validation_code.hash()))
+			}
+		}
+	};
+
+	Ok(result)
+}
+
+/// Execute the PVF of a candidate, retrying after ambiguous worker deaths as configured.
+///
+/// If the hash of a prepared artifact is given, the PVF is executed from it, unless the backend
+/// doesn't have it prepared or the execution is seeded.
+async fn execute_with_retries(
+	validation_backend: &mut impl ValidationBackend,
+	raw_validation_code: &[u8],
+	prepared_artifact: Option<ValidationCodeHash>,
+	params: &ValidationParams,
+	options: &ExhaustiveOptions,
+	para_id: ParaId,
+) -> Result<WasmValidationResult, ValidationError> {
+	let mut retries_left = options.ambiguous_worker_death_retries;
+	loop {
+		let execution = Box::pin(async {
+			let priority = pvf_priority(options.priority);
+			if let Some(seed) = options.execution_seed {
+				return validation_backend
+					.validate_candidate_seeded(raw_validation_code.to_vec(), params.clone(), priority, seed)
+					.await;
+			}
+
+			if let Some(code_hash) = prepared_artifact {
+				let prepared = validation_backend
+					.validate_candidate_prepared(code_hash, params.clone(), priority)
+					.await;
+				if let Some(result) = prepared {
+					return result;
+				}
+
+				tracing::debug!(
+					target: LOG_TARGET,
+					?para_id,
+					?code_hash,
+					"The prepared artifact is gone, executing the PVF from its code",
+				);
+			}
+
+			validation_backend
+				.validate_candidate(raw_validation_code.to_vec(), params.clone(), priority)
+				.await
+		});
+		let result = match options.execution_timeout() {
+			Some(timeout) => execute_with_timeout(execution, timeout).await,
+			None => execution.await,
+		};
+
+		if let Err(ValidationError::InvalidCandidate(
+			WasmInvalidCandidate::AmbigiousWorkerDeath,
+		)) = result {
+			options.worker_deaths.lock().await.note(Instant::now());
+		}
+
+		match result {
+			Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::AmbigiousWorkerDeath))
+				if retries_left > 0 =>
+			{
+				retries_left -= 1;
+				tracing::debug!(
+					target: LOG_TARGET,
+					?para_id,
+					retries_left,
+					"Retrying the execution of a candidate after an ambiguous worker death",
+				);
+			}
+			result => return result,
+		}
+	}
+}
+
+/// The commitments of a candidate yielding the given outputs of its PVF.
+fn commitments_of(outputs: &WasmValidationResult) -> CandidateCommitments {
+	CandidateCommitments {
+		head_data: outputs.head_data.clone(),
+		upward_messages: outputs.upward_messages.clone(),
+		horizontal_messages: outputs.horizontal_messages.clone(),
+		new_validation_code: outputs.new_validation_code.clone(),
+		processed_downward_messages: outputs.processed_downward_messages,
+		hrmp_watermark: outputs.hrmp_watermark,
+	}
+}
+
+/// Request an input of the candidate with the given hash through the given resolver.
+async fn resolve<T>(
+	resolver: &mut mpsc::Sender<ResolveRequest<T>>,
+	candidate_hash: CandidateHash,
+	input: &str,
+) -> Result<T, ValidationFailed> {
+	let (tx, rx) = oneshot::channel();
+	let resolved = match resolver.send((candidate_hash, tx)).await {
+		Ok(()) => rx.await.ok().flatten(),
+		Err(_) => None,
+	};
+
+	resolved.ok_or_else(|| ValidationFailed::Other(format!("cannot resolve {}", input)))
+}
+
+/// Validate the candidate with the given hash and descriptor, requesting the remaining inputs
+/// through the given resolvers.
+///
+/// The validation code is checked against the descriptor before anything else is requested, so
+/// that the PoV isn't fetched for a candidate that is invalid anyway.
+async fn validate_from_resolvers(
+	validation_backend: impl ValidationBackend,
+	candidate_hash: CandidateHash,
+	descriptor: CandidateDescriptor,
+	mut resolvers: CandidateResolvers,
+	pov_checksum: Option<Hash>,
+	received_at: Instant,
+	options: &ExhaustiveOptions,
+	steps: &mut Vec<ValidationStep>,
+	metrics: &Metrics,
+) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
+	let validation_code =
+		match resolve(&mut resolvers.validation_code, candidate_hash, "validation code").await {
+			Ok(validation_code) => validation_code,
+			Err(e) => return Ok(Err(e)),
+		};
+
+	if validation_code.hash() != descriptor.validation_code_hash {
+		return Ok(Ok(ValidationResult::Invalid(InvalidCandidate::CodeHashMismatch)));
+	}
+
+	let persisted_validation_data = match resolve(
+		&mut resolvers.persisted_validation_data,
+		candidate_hash,
+		"persisted validation data",
+	).await {
+		Ok(persisted_validation_data) => persisted_validation_data,
+		Err(e) => return Ok(Err(e)),
+	};
+
+	let pov = match resolve(&mut resolvers.pov, candidate_hash, "pov").await {
+		Ok(pov) => pov,
+		Err(e) => return Ok(Err(e)),
+	};
+
+	if let Err(e) = check_pov_integrity(&pov, pov_checksum) {
+		return Ok(Err(e));
+	}
+
+	metrics.on_admission(received_at);
+
+	validate_candidate_exhaustive_explained(
+		validation_backend,
+		persisted_validation_data,
+		validation_code,
+		descriptor,
+		pov,
+		options,
+		steps,
+		metrics,
+	).await
+}
+
+/// The inputs for validating a candidate without accessing the relay-chain state.
+#[derive(Debug, Clone)]
+pub struct ValidationInputs {
+	/// The persisted validation data the candidate is based on.
+	pub persisted_validation_data: PersistedValidationData,
+	/// The validation code of the para.
+	pub validation_code: ValidationCode,
+	/// The descriptor of the candidate.
+	pub descriptor: CandidateDescriptor,
+	/// The PoV of the candidate.
+	pub pov: Arc<PoV>,
+}
+
+/// The outcome a validation is expected to have. See [`validate_expecting`].
+#[derive(Debug)]
+pub enum ExpectedOutcome {
+	/// The candidate is expected to be valid and, if specified, to produce the given head data.
+	Valid(Option<HeadData>),
+	/// The candidate is expected to be invalid and, if specified, for the given reason. Only the
+	/// kind of the reason is compared, not the details it carries.
+	Invalid(Option<InvalidCandidate>),
+}
+
+/// A report of how the actual outcome of a validation diverged from the expected one.
+#[derive(Debug)]
+pub enum OutcomeMismatch {
+	/// The validation failed for internal reasons, so there is no outcome to compare.
+	ValidationFailed(ValidationFailed),
+	/// The candidate was expected to be invalid, but is valid.
+	UnexpectedlyValid {
+		/// The outputs of the candidate.
+		outputs: CandidateCommitments,
+	},
+	/// The candidate was expected to be valid, but is invalid.
+	UnexpectedlyInvalid {
+		/// The reason the candidate is invalid for.
+		reason: InvalidCandidate,
+	},
+	/// The candidate is invalid as expected, but for another reason.
+	Reason {
+		/// The expected reason.
+		expected: InvalidCandidate,
+		/// The actual reason.
+		actual: InvalidCandidate,
+	},
+	/// The candidate is valid as expected, but produced other head data.
+	HeadData {
+		/// The expected head data.
+		expected: HeadData,
+		/// The actual head data.
+		actual: HeadData,
+	},
+	/// The candidate wasn't validated again, so there is no outcome to compare.
+	Unchanged,
+}
+
+/// Compare the outcome of a validation against the expected one.
+pub fn check_outcome(
+	actual: Result<ValidationResult, ValidationFailed>,
+	expected: ExpectedOutcome,
+) -> Result<(), OutcomeMismatch> {
+	match (actual.map_err(OutcomeMismatch::ValidationFailed)?, expected) {
+		(ValidationResult::Valid(outputs, _, _), ExpectedOutcome::Valid(expected_head_data)) => {
+			match expected_head_data {
+				Some(expected) if expected != outputs.head_data => Err(OutcomeMismatch::HeadData {
+					expected,
+					actual: outputs.head_data,
+				}),
+				_ => Ok(()),
+			}
+		}
+		(ValidationResult::Valid(outputs, _, _), ExpectedOutcome::Invalid(_)) =>
+			Err(OutcomeMismatch::UnexpectedlyValid { outputs }),
+		(ValidationResult::Invalid(reason), ExpectedOutcome::Valid(_)) =>
+			Err(OutcomeMismatch::UnexpectedlyInvalid { reason }),
+		(ValidationResult::Invalid(actual), ExpectedOutcome::Invalid(expected_reason)) => {
+			match expected_reason {
+				Some(expected) if std::mem::discriminant(&expected) != std::mem::discriminant(&actual) =>
+					Err(OutcomeMismatch::Reason { expected, actual }),
+				_ => Ok(()),
+			}
+		}
+		(ValidationResult::Unchanged, _) => Err(OutcomeMismatch::Unchanged),
+	}
+}
+
+/// Validate a candidate using the given validation host and compare the outcome against the
+/// expected one, reporting how they diverge if they do.
+///
+/// This is meant for test harnesses of parachains.
+pub async fn validate_expecting(
+	validation_host: &mut ValidationHost,
+	inputs: ValidationInputs,
+	expected: ExpectedOutcome,
+) -> Result<(), OutcomeMismatch> {
+	validate_expecting_with(validation_host, inputs, expected).await
+}
+
+async fn validate_expecting_with(
+	validation_backend: impl ValidationBackend,
+	inputs: ValidationInputs,
+	expected: ExpectedOutcome,
+) -> Result<(), OutcomeMismatch> {
+	let actual = validate_candidate_exhaustive(
+		validation_backend,
+		inputs.persisted_validation_data,
+		inputs.validation_code,
+		inputs.descriptor,
+		inputs.pov,
+		&Default::default(),
+		&Metrics::default(),
+	)
+	.await
+	.map_err(|e| OutcomeMismatch::ValidationFailed(ValidationFailed::Other(e.to_string())))?;
+
+	check_outcome(actual, expected)
+}
+
+/// Check that the validation code with the given hash, as known at the given relay-parent, compiles
+/// under the limits of validation, without executing it.
+async fn precheck_pvf(
+	sender: &mut impl SubsystemSender,
+	mut validation_backend: impl ValidationBackend,
+	relay_parent: Hash,
+	validation_code_hash: ValidationCodeHash,
+	prechecked_code: Option<&Mutex<PrecheckedCode>>,
+) -> PreCheckOutcome {
+	let (tx, rx) = oneshot::channel();
+	let validation_code = match runtime_api_request(
+		sender,
+		relay_parent,
+		RuntimeApiRequest::ValidationCodeByHash(validation_code_hash, tx),
+		rx,
+	).await {
+		Ok(Ok(Some(code))) => code,
+		res => {
+			tracing::debug!(
+				target: LOG_TARGET,
+				?relay_parent,
+				?validation_code_hash,
+				?res,
+				"Validation code to pre-check is unavailable",
+			);
+
+			return PreCheckOutcome::Failed;
+		}
+	};
+
+	prepare_validation_code(
+		&mut validation_backend,
+		validation_code_hash,
+		&validation_code,
+		prechecked_code,
+	).await
+}
+
+/// Decompress the given validation code and prepare it, without executing it. Code found valid is
+/// kept decompressed in the given cache, if any.
+async fn prepare_validation_code(
+	validation_backend: &mut impl ValidationBackend,
+	validation_code_hash: ValidationCodeHash,
+	validation_code: &ValidationCode,
+	prechecked_code: Option<&Mutex<PrecheckedCode>>,
+) -> PreCheckOutcome {
+	let raw_validation_code = match sp_maybe_compressed_blob::decompress(
+		&validation_code.0,
+		VALIDATION_CODE_BOMB_LIMIT,
+	) {
+		Ok(code) => code,
+		Err(e) => {
+			tracing::debug!(
+				target: LOG_TARGET,
+				?validation_code_hash,
+				err = ?e,
+				"Validation code to pre-check fails to decompress",
+			);
+
+			return PreCheckOutcome::Invalid;
+		}
+	};
+
+	match validation_backend.precheck_pvf(raw_validation_code.to_vec()).await {
+		Ok(()) => {
+			// The code is only cached under its hash if it actually has it, as validations look
+			// it up by the hash of the code they were provided with.
+			if let (Some(prechecked), true) =
+				(prechecked_code, validation_code.hash() == validation_code_hash)
+			{
+				prechecked.lock().await
+					.insert(validation_code_hash, Arc::new(raw_validation_code.to_vec()));
+			}
+
+			PreCheckOutcome::Valid
+		}
+		Err(PrepareError::Invalid(e)) => {
+			tracing::debug!(
+				target: LOG_TARGET,
+				?validation_code_hash,
+				err = %e,
+				"Validation code to pre-check fails to compile",
+			);
+
+			PreCheckOutcome::Invalid
+		}
+		Err(PrepareError::Failed(e)) => {
+			tracing::warn!(
+				target: LOG_TARGET,
+				?validation_code_hash,
+				err = %e,
+				"Failed to pre-check validation code",
+			);
+
+			PreCheckOutcome::Failed
+		}
+	}
+}
+
+/// Prepare the current validation code of the paras assigned to the availability cores at the
+/// given relay-parent, so that their artifacts are ready once candidates of them arrive. Validation
+/// code shared by several paras is prepared once.
+async fn prewarm_artifacts(
+	mut sender: impl SubsystemSender,
+	mut validation_backend: impl ValidationBackend,
+	relay_parent: Hash,
+) {
+	let (tx, rx) = oneshot::channel();
+	let cores = match runtime_api_request(
+		&mut sender,
+		relay_parent,
+		RuntimeApiRequest::AvailabilityCores(tx),
+		rx,
+	).await {
+		Ok(Ok(cores)) => cores,
+		res => {
+			tracing::debug!(
+				target: LOG_TARGET,
+				?relay_parent,
+				?res,
+				"Availability cores to prewarm the artifacts of are unavailable",
+			);
+
+			return;
+		}
+	};
+
+	let para_ids: BTreeSet<ParaId> = cores.iter().filter_map(|core| core.para_id()).collect();
+	let mut prepared = HashSet::new();
+	for para_id in para_ids {
+		let (tx, rx) = oneshot::channel();
+		let validation_code = match runtime_api_request(
+			&mut sender,
+			relay_parent,
+			RuntimeApiRequest::ValidationCode(para_id, OccupiedCoreAssumption::TimedOut, tx),
+			rx,
+		).await {
+			Ok(Ok(Some(code))) => code,
+			res => {
+				tracing::debug!(
+					target: LOG_TARGET,
+					?relay_parent,
+					?para_id,
+					?res,
+					"Validation code to prewarm the artifact of is unavailable",
+				);
+
+				continue;
+			}
+		};
+
+		let validation_code_hash = validation_code.hash();
+		if !prepared.insert(validation_code_hash) {
+			continue;
+		}
+
+		let outcome = prepare_validation_code(
+			&mut validation_backend,
+			validation_code_hash,
+			&validation_code,
+			None,
+		).await;
+		tracing::debug!(
+			target: LOG_TARGET,
+			?para_id,
+			?validation_code_hash,
+			?outcome,
+			"Prewarmed the artifact of validation code",
+		);
+	}
+}
+
+/// The priority of the PVF host corresponding to the requested one.
+fn pvf_priority(priority: PvfExecPriority) -> Priority {
+	match priority {
+		PvfExecPriority::Normal => Priority::Normal,
+		PvfExecPriority::Critical => Priority::Critical,
+	}
+}
+
+/// A backend executing PVFs, by default the PVF validation host. Others can be injected with
+/// [`CandidateValidationSubsystem::with_backend`], e.g. to execute a native runtime in test
+/// networks.
+#[async_trait]
+pub trait ValidationBackend {
+	/// Execute the given validation code with the given parameters.
+	async fn validate_candidate(
+		&mut self,
+		raw_validation_code: Vec<u8>,
+		params: ValidationParams,
+		priority: Priority,
+	) -> Result<WasmValidationResult, ValidationError>;
+
+	/// Prepare the given validation code without executing it.
+	async fn precheck_pvf(&mut self, raw_validation_code: Vec<u8>) -> Result<(), PrepareError>;
+
+	/// Execute the already prepared validation code with the given hash of the decompressed code.
+	/// Returns `None` if the code isn't prepared, in which case it's to be executed from the code
+	/// instead. Backends that don't keep prepared code around always do so.
+	async fn validate_candidate_prepared(
+		&mut self,
+		_code_hash: ValidationCodeHash,
+		_params: ValidationParams,
+		_priority: Priority,
+	) -> Option<Result<WasmValidationResult, ValidationError>> {
+		None
+	}
+
+	/// Execute the given validation code with the given parameters, letting it draw randomness
+	/// from the given seed. Only meant for reproducing executions in tests. Backends that can't
+	/// seed executions fail them.
+	async fn validate_candidate_seeded(
+		&mut self,
+		_raw_validation_code: Vec<u8>,
+		_params: ValidationParams,
+		_priority: Priority,
+		_seed: [u8; 32],
+	) -> Result<WasmValidationResult, ValidationError> {
+		Err(ValidationError::InternalError(UNSEEDED_BACKEND.into()))
+	}
+}
+
+/// The description of a validation backend not supporting seeded executions.
+const UNSEEDED_BACKEND: &str = "the validation backend doesn't support seeded executions";
+
+/// The description of the validation host not taking a validation.
+const PVF_HOST_UNAVAILABLE: &str = "cannot send pvf to the validation host";
+
+/// The description of the validation host dropping a validation before it concluded.
+const VALIDATION_CANCELLED: &str = "validation was cancelled";
+
+/// The failure of a validation due to the given internal error of the validation backend.
+fn internal_validation_failure(err: String) -> ValidationFailed {
+	if err == VALIDATION_CANCELLED {
+		ValidationFailed::Cancelled
+	} else if err.starts_with(PVF_HOST_UNAVAILABLE) {
+		ValidationFailed::PvfHostUnavailable(err)
+	} else {
+		ValidationFailed::Other(err)
+	}
+}
+
+#[async_trait]
+impl<B: ValidationBackend + Send + ?Sized> ValidationBackend for &'_ mut B {
+	async fn validate_candidate(
+		&mut self,
+		raw_validation_code: Vec<u8>,
+		params: ValidationParams,
+		priority: Priority,
+	) -> Result<WasmValidationResult, ValidationError> {
+		(**self).validate_candidate(raw_validation_code, params, priority).await
+	}
+
+	async fn precheck_pvf(&mut self, raw_validation_code: Vec<u8>) -> Result<(), PrepareError> {
+		(**self).precheck_pvf(raw_validation_code).await
+	}
+
+	async fn validate_candidate_prepared(
+		&mut self,
+		code_hash: ValidationCodeHash,
+		params: ValidationParams,
+		priority: Priority,
+	) -> Option<Result<WasmValidationResult, ValidationError>> {
+		(**self).validate_candidate_prepared(code_hash, params, priority).await
+	}
+
+	async fn validate_candidate_seeded(
+		&mut self,
+		raw_validation_code: Vec<u8>,
+		params: ValidationParams,
+		priority: Priority,
+		seed: [u8; 32],
+	) -> Result<WasmValidationResult, ValidationError> {
+		(**self).validate_candidate_seeded(raw_validation_code, params, priority, seed).await
+	}
+}
+
+#[async_trait]
+impl ValidationBackend for ValidationHost {
+	async fn validate_candidate(
+		&mut self,
+		raw_validation_code: Vec<u8>,
+		params: ValidationParams,
+		priority: Priority,
+	) -> Result<WasmValidationResult, ValidationError> {
+		let (tx, rx) = oneshot::channel();
+		let sent = self.execute_pvf(
+			Pvf::from_code(raw_validation_code),
+			params.encode(),
+			priority,
+			tx,
+		).await;
+
+		await_host_execution(self, sent, rx).await
+	}
+
+	async fn precheck_pvf(&mut self, raw_validation_code: Vec<u8>) -> Result<(), PrepareError> {
+		let (tx, rx) = oneshot::channel();
+		// The inherent method is named like this one, so it's spelled out.
+		if let Err(err) = ValidationHost::precheck_pvf(
+			self,
+			Pvf::from_code(raw_validation_code),
+			tx,
+		).await {
+			return Err(PrepareError::Failed(format!("cannot send pvf to the validation host: {:?}", err)));
+		}
+
+		rx.await.map_err(|_| PrepareError::Failed("pre-checking was cancelled".into()))?
+	}
+
+	async fn validate_candidate_prepared(
+		&mut self,
+		code_hash: ValidationCodeHash,
+		params: ValidationParams,
+		priority: Priority,
+	) -> Option<Result<WasmValidationResult, ValidationError>> {
+		let (tx, rx) = oneshot::channel();
+		if let Err(err) = self.execute_prepared_pvf(code_hash, params.encode(), priority, tx).await {
+			return Some(Err(ValidationError::InternalError(
+				format!("{}: {:?}", PVF_HOST_UNAVAILABLE, err),
+			)));
+		}
+
+		// The host drops the sender if the code isn't prepared.
+		rx.await.ok()
+	}
+
+	async fn validate_candidate_seeded(
+		&mut self,
+		raw_validation_code: Vec<u8>,
+		params: ValidationParams,
+		priority: Priority,
+		seed: [u8; 32],
+	) -> Result<WasmValidationResult, ValidationError> {
+		let (tx, rx) = oneshot::channel();
+		let sent = self.execute_seeded_pvf(
+			Pvf::from_code(raw_validation_code),
+			params.encode(),
+			priority,
+			seed,
+			tx,
+		).await;
+
+		await_host_execution(self, sent, rx).await
+	}
+}
+
+/// Await the result of an execution sent to the validation host, the sending having yielded `sent`.
+async fn await_host_execution(
+	host: &ValidationHost,
+	sent: Result<(), String>,
+	rx: oneshot::Receiver<Result<WasmValidationResult, ValidationError>>,
+) -> Result<WasmValidationResult, ValidationError> {
+	if let Err(err) = sent {
+		return Err(ValidationError::InternalError(
+			format!("{}: {:?}", PVF_HOST_UNAVAILABLE, err),
+		));
+	}
+
+	// The host drops the pending requests when it dies. It's restarted, so they're worth
+	// retrying, unlike the ones it cancelled.
+	rx.await.map_err(|_| if host.is_running() {
+		ValidationError::InternalError(VALIDATION_CANCELLED.into())
+	} else {
+		ValidationError::InternalError(format!("{}: the host exited", PVF_HOST_UNAVAILABLE))
+	})?
+}
+
+/// Does basic checks of a candidate. Provide the encoded PoV-block. Returns `Ok` if basic checks
+/// are passed, `Err` otherwise.
+///
+/// The checks cover the PoV size, the PoV and validation code hashes and the collator signature,
+/// without executing the PVF. They are cheap enough for rejecting obviously bad candidates
+/// before gossiping them.
+pub fn perform_basic_checks(
+	candidate: &CandidateDescriptor,
+	max_pov_size: u32,
+	pov: &PoV,
+	validation_code: &ValidationCode,
+) -> Result<(), InvalidCandidate> {
+	perform_basic_checks_with(
+		candidate,
+		max_pov_size,
+		pov,
+		validation_code,
+		|pov| pov.hash(),
+		|code| code.hash(),
+	)
+}
+
+/// Like [`perform_basic_checks`], but with the hashing of the PoV and the validation code supplied
+/// by the caller.
+///
+/// The checks short-circuit on the first failure and are ordered cheapest first, so that
+/// adversarial inputs are rejected with as little work as possible:
+///
+/// 1. The PoV size limit. The encoded size is known without reading the block data, so an
+///    oversized PoV is rejected before anything is hashed.
+/// 2. The PoV and validation code hashes, smaller blob first. Hashing is linear in the input
+///    length, and the PoV is usually, but not always, much smaller than the code.
+/// 3. The collator signature, which is the most expensive check and runs last.
+fn perform_basic_checks_with(
+	candidate: &CandidateDescriptor,
+	max_pov_size: u32,
+	pov: &PoV,
+	validation_code: &ValidationCode,
+	hash_pov: impl FnOnce(&PoV) -> Hash,
+	hash_code: impl FnOnce(&ValidationCode) -> Hash,
+) -> Result<(), InvalidCandidate> {
+	let encoded_pov_size = pov.encoded_size();
+	if encoded_pov_size > max_pov_size as usize {
+		return Err(InvalidCandidate::ParamsTooLarge(encoded_pov_size as u64));
+	}
+
+	let check_pov_hash = || {
+		if hash_pov(pov) != candidate.pov_hash {
+			return Err(InvalidCandidate::PoVHashMismatch);
+		}
+		Ok(())
+	};
+	let check_code_hash = || {
+		if hash_code(validation_code) != candidate.validation_code_hash {
+			return Err(InvalidCandidate::CodeHashMismatch);
+		}
+		Ok(())
+	};
+
+	if pov.block_data.0.len() <= validation_code.0.len() {
+		check_pov_hash()?;
+		check_code_hash()?;
+	} else {
+		check_code_hash()?;
+		check_pov_hash()?;
+	}
+
+	if let Err(()) = candidate.check_collator_signature() {
+		return Err(InvalidCandidate::BadSignature);
+	}
+
+	Ok(())
+}
+
+/// The label of the validity of the outcome of a validation in the metrics.
+fn validity_label(event: &Result<ValidationResult, ValidationFailed>) -> &'static str {
+	match event {
+		Ok(ValidationResult::Valid(_, _, _)) => "valid",
+		Ok(ValidationResult::Invalid(_)) => "invalid",
+		Ok(ValidationResult::Unchanged) => "unchanged",
+		Err(_) => "validation failure",
+	}
+}
+
+/// The label of the reason a candidate is invalid in the metrics, the name of its variant.
+fn invalid_candidate_label(reason: &InvalidCandidate) -> &'static str {
+	match reason {
+		InvalidCandidate::ExecutionError(_) => "ExecutionError",
+		InvalidCandidate::InvalidOutputs => "InvalidOutputs",
+		InvalidCandidate::Timeout => "Timeout",
+		InvalidCandidate::ParamsTooLarge(_) => "ParamsTooLarge",
+		InvalidCandidate::CodeTooLarge(_) => "CodeTooLarge",
+		InvalidCandidate::CodeDecompressionFailure => "CodeDecompressionFailure",
+		InvalidCandidate::PoVDecompressionFailure => "PoVDecompressionFailure",
+		InvalidCandidate::BadReturn => "BadReturn",
+		InvalidCandidate::BadParent => "BadParent",
+		InvalidCandidate::PoVHashMismatch => "PoVHashMismatch",
+		InvalidCandidate::BadSignature => "BadSignature",
+		InvalidCandidate::ParaHeadHashMismatch => "ParaHeadHashMismatch",
+		InvalidCandidate::CodeHashMismatch => "CodeHashMismatch",
+		InvalidCandidate::PoVSizeExceeded(_) => "PoVSizeExceeded",
+		InvalidCandidate::NonDeterministic => "NonDeterministic",
+	}
+}
+
+/// The label of the para of a validated candidate in the metrics, `unknown` if the validation
+/// concluded before the candidate was known.
+fn para_id_label(para_id: Option<ParaId>) -> String {
+	para_id.map_or_else(|| "unknown".to_owned(), |para_id| u32::from(para_id).to_string())
+}
+
+/// The buckets of the histograms of payload sizes in bytes, ranging up to the bomb limits.
+const PAYLOAD_SIZE_BUCKETS: &[f64] = &[
+	16384.0, 65536.0, 262144.0, 1048576.0, 2097152.0, 4194304.0, 8388608.0, 16777216.0, 20971520.0,
+];
+
+#[derive(Clone)]
+struct MetricsInner {
+	validation_requests: prometheus::CounterVec<prometheus::U64>,
+	labelled_validation_requests: prometheus::CounterVec<prometheus::U64>,
+	invalid_candidate_reasons: prometheus::CounterVec<prometheus::U64>,
+	validate_from_chain_state: prometheus::Histogram,
+	validate_from_exhaustive: prometheus::Histogram,
+	validate_candidate_exhaustive: prometheus::Histogram,
+	upward_messages: prometheus::HistogramVec,
+	horizontal_messages: prometheus::HistogramVec,
+	processed_downward_messages: prometheus::HistogramVec,
+	assumption_cap_reached: prometheus::Counter<prometheus::U64>,
+	admission_delay: prometheus::Histogram,
+	fetch_to_done: prometheus::Histogram,
+	pov_size: prometheus::Histogram,
+	pov_decompressed_size: prometheus::Histogram,
+	code_size: prometheus::Histogram,
+	code_decompressed_size: prometheus::Histogram,
+	code_decompression_failures_with_matching_hash: prometheus::Counter<prometheus::U64>,
+	decompression_time: prometheus::HistogramVec,
+	result_cache: prometheus::CounterVec<prometheus::U64>,
+	execution_cache: prometheus::CounterVec<prometheus::U64>,
+	readiness: prometheus::Gauge<prometheus::U64>,
+	pending_requests: prometheus::Gauge<prometheus::U64>,
+	overloaded_requests: prometheus::Counter<prometheus::U64>,
+	pvf_host_restarts: prometheus::Counter<prometheus::U64>,
+	canceled: prometheus::CounterVec<prometheus::U64>,
+}
+
+/// Accounts for a request received but not yet responded to in the metrics, until dropped.
+struct PendingRequest(prometheus::Gauge<prometheus::U64>);
+
+impl Drop for PendingRequest {
+	fn drop(&mut self) {
+		self.0.dec();
+	}
+}
+
+/// Candidate validation metrics.
+#[derive(Default, Clone)]
+pub struct Metrics(Option<MetricsInner>);
+
+impl Metrics {
+	/// Observe a served validation request for a candidate of the given para, if it is known.
+	fn on_validation_event(
+		&self,
+		para_id: Option<ParaId>,
+		event: &Result<ValidationResult, ValidationFailed>,
+	) {
+		if let Some(metrics) = &self.0 {
+			metrics.validation_requests
+				.with_label_values(&[validity_label(event), &para_id_label(para_id)])
+				.inc();
+			if let Ok(ValidationResult::Invalid(reason)) = event {
+				metrics.invalid_candidate_reasons
+					.with_label_values(&[invalid_candidate_label(reason)])
+					.inc();
+			}
+		}
+	}
+
+	/// Observe a validation under each of the given labels supplied with the request.
+	fn on_labelled_validation_event(
+		&self,
+		labels: &[(&str, &str)],
+		event: &Result<ValidationResult, ValidationFailed>,
+	) {
+		if let Some(metrics) = &self.0 {
+			for (key, value) in labels {
+				metrics.labelled_validation_requests
+					.with_label_values(&[key, value, validity_label(event)])
+					.inc();
+			}
+		}
+	}
+
+	/// Observe the number of upward and horizontal messages produced and downward messages
+	/// processed by a valid candidate.
+	fn on_commitments(&self, para_id: ParaId, commitments: &CandidateCommitments) {
+		if let Some(metrics) = &self.0 {
+			let para_id = u32::from(para_id).to_string();
+			metrics.upward_messages
+				.with_label_values(&[&para_id])
+				.observe(commitments.upward_messages.len() as f64);
+			metrics.horizontal_messages
+				.with_label_values(&[&para_id])
+				.observe(commitments.horizontal_messages.len() as f64);
+			metrics.processed_downward_messages
+				.with_label_values(&[&para_id])
+				.observe(commitments.processed_downward_messages as f64);
+		}
+	}
+
+	/// Note that no assumption matched before the configured maximum number of assumptions was
+	/// reached.
+	fn on_assumption_cap_reached(&self) {
+		if let Some(metrics) = &self.0 {
+			metrics.assumption_cap_reached.inc();
+		}
+	}
+
+	/// Observe the time from receiving a validation request to starting the validation of the
+	/// candidate.
+	fn on_admission(&self, received_at: Instant) {
+		if let Some(metrics) = &self.0 {
+			metrics.admission_delay.observe(received_at.elapsed().as_secs_f64());
+		}
+	}
+
+	/// Observe the size of the PoV of a candidate as provided, i.e. before decompressing it.
+	fn on_pov_size(&self, size: usize) {
+		if let Some(metrics) = &self.0 {
+			metrics.pov_size.observe(size as f64);
+		}
+	}
+
+	/// Observe the size of the block data of a PoV once decompressed.
+	fn on_pov_decompressed(&self, size: usize) {
+		if let Some(metrics) = &self.0 {
+			metrics.pov_decompressed_size.observe(size as f64);
+		}
+	}
+
+	/// Observe the size of validation code as provided and, if it decompressed, once decompressed.
+	fn on_code_decompression(&self, size: usize, decompressed_size: Option<usize>) {
+		if let Some(metrics) = &self.0 {
+			metrics.code_size.observe(size as f64);
+			if let Some(decompressed_size) = decompressed_size {
+				metrics.code_decompressed_size.observe(decompressed_size as f64);
+			}
+		}
+	}
+
+	/// Observe the time spent decompressing the given payload of a candidate, `code` or `pov`.
+	fn on_decompression_time(&self, payload: &str, duration: Duration) {
+		if let Some(metrics) = &self.0 {
+			metrics.decompression_time
+				.with_label_values(&[payload])
+				.observe(duration.as_secs_f64());
+		}
+	}
+
+	/// Observe the time from fetching the PoV of a candidate to completing its validation.
+	fn on_fetch_to_done(&self, fetched_at: Instant) {
+		if let Some(metrics) = &self.0 {
+			metrics.fetch_to_done.observe(fetched_at.elapsed().as_secs_f64());
+		}
+	}
+
+	/// Note that validation code matching the hash in the descriptor failed to decompress.
+	fn on_code_decompression_failure_with_matching_hash(&self) {
+		if let Some(metrics) = &self.0 {
+			metrics.code_decompression_failures_with_matching_hash.inc();
+		}
+	}
+
+	/// Note a lookup of the result of an identical earlier request.
+	fn on_result_cache_lookup(&self, hit: bool) {
+		if let Some(metrics) = &self.0 {
+			let outcome = if hit { "hit" } else { "miss" };
+			metrics.result_cache.with_label_values(&[outcome]).inc();
+		}
+	}
+
+	/// Note a lookup of the outcome of an earlier execution with the same inputs.
+	fn on_execution_cache_lookup(&self, hit: bool) {
+		if let Some(metrics) = &self.0 {
+			let outcome = if hit { "hit" } else { "miss" };
+			metrics.execution_cache.with_label_values(&[outcome]).inc();
+		}
+	}
+
+	/// Note a validation canceled for the given cause before it completed.
+	fn on_canceled(&self, cause: &str) {
+		if let Some(metrics) = &self.0 {
+			metrics.canceled.with_label_values(&[cause]).inc();
+		}
+	}
+
+	/// Set the current readiness of the subsystem, encoded as 0 for starting, 1 for ready, 2 for
+	/// degraded and 3 for unavailable.
+	fn on_readiness(&self, readiness: Readiness) {
+		if let Some(metrics) = &self.0 {
+			let value = match readiness {
+				Readiness::Starting => 0,
+				Readiness::Ready => 1,
+				Readiness::Degraded => 2,
+				Readiness::Unavailable => 3,
+			};
+			metrics.readiness.set(value);
+		}
+	}
+
+	/// Note a validation request rejected because too many validations were in flight.
+	fn on_overloaded(&self) {
+		if let Some(metrics) = &self.0 {
+			metrics.overloaded_requests.inc();
+		}
+	}
+
+	/// Note a restart of a PVF host that exited unexpectedly.
+	fn on_pvf_host_restart(&self) {
+		if let Some(metrics) = &self.0 {
+			metrics.pvf_host_restarts.inc();
+		}
+	}
+
+	/// Note a request received, which is pending until the returned guard is dropped.
+	fn on_request_received(&self) -> Option<PendingRequest> {
+		self.0.as_ref().map(|metrics| {
+			metrics.pending_requests.inc();
+			PendingRequest(metrics.pending_requests.clone())
+		})
+	}
+
+	/// Provide a timer for `validate_from_chain_state` which observes on drop.
+	fn time_validate_from_chain_state(&self) -> Option<metrics::prometheus::prometheus::HistogramTimer> {
+		self.0.as_ref().map(|metrics| metrics.validate_from_chain_state.start_timer())
+	}
+
+	/// Provide a timer for `validate_from_exhaustive` which observes on drop.
+	fn time_validate_from_exhaustive(&self) -> Option<metrics::prometheus::prometheus::HistogramTimer> {
+		self.0.as_ref().map(|metrics| metrics.validate_from_exhaustive.start_timer())
+	}
+
+	/// Provide a timer for `validate_candidate_exhaustive` which observes on drop.
+	fn time_validate_candidate_exhaustive(&self) -> Option<metrics::prometheus::prometheus::HistogramTimer> {
+		self.0.as_ref().map(|metrics| metrics.validate_candidate_exhaustive.start_timer())
+	}
+}
+
+impl metrics::Metrics for Metrics {
+	fn try_register(registry: &prometheus::Registry) -> Result<Self, prometheus::PrometheusError> {
+		let metrics = MetricsInner {
+			validation_requests: prometheus::register(
+				prometheus::CounterVec::new(
+					prometheus::Opts::new(
+						"parachain_validation_requests_total",
+						"Number of validation requests served.",
+					),
+					// The number of distinct para ids is bounded by the number of registered
+					// parachains and parathreads.
+					&["validity", "para_id"],
+				)?,
+				registry,
+			)?,
+			labelled_validation_requests: prometheus::register(
+				prometheus::CounterVec::new(
+					prometheus::Opts::new(
+						"parachain_labelled_validation_requests_total",
+						"Number of validation requests served, by the labels supplied with them.",
+					),
+					&["label", "value", "validity"],
+				)?,
+				registry,
+			)?,
+			invalid_candidate_reasons: prometheus::register(
+				prometheus::CounterVec::new(
+					prometheus::Opts::new(
+						"parachain_invalid_candidate_reasons_total",
+						"Number of candidates found invalid, by the reason they are invalid.",
+					),
+					&["reason"],
+				)?,
+				registry,
+			)?,
+			validate_from_chain_state: prometheus::register(
+				prometheus::Histogram::with_opts(
+					prometheus::HistogramOpts::new(
+						"parachain_candidate_validation_validate_from_chain_state",
+						"Time spent within `candidate_validation::validate_from_chain_state`",
+					)
+				)?,
+				registry,
+			)?,
+			validate_from_exhaustive: prometheus::register(
+				prometheus::Histogram::with_opts(
+					prometheus::HistogramOpts::new(
+						"parachain_candidate_validation_validate_from_exhaustive",
+						"Time spent within `candidate_validation::validate_from_exhaustive`",
+					)
+				)?,
+				registry,
+			)?,
+			validate_candidate_exhaustive: prometheus::register(
+				prometheus::Histogram::with_opts(
+					prometheus::HistogramOpts::new(
+						"parachain_candidate_validation_validate_candidate_exhaustive",
+						"Time spent within `candidate_validation::validate_candidate_exhaustive`",
+					)
+				)?,
+				registry,
+			)?,
+			upward_messages: prometheus::register(
+				prometheus::HistogramVec::new(
+					prometheus::HistogramOpts::new(
+						"parachain_validation_upward_messages",
+						"Number of upward messages produced by valid candidates.",
+					).buckets(vec![0.0, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0, 256.0]),
+					&["para_id"],
+				)?,
+				registry,
+			)?,
+			horizontal_messages: prometheus::register(
+				prometheus::HistogramVec::new(
+					prometheus::HistogramOpts::new(
+						"parachain_validation_horizontal_messages",
+						"Number of horizontal messages produced by valid candidates.",
+					).buckets(vec![0.0, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0, 256.0]),
+					&["para_id"],
+				)?,
+				registry,
+			)?,
+			processed_downward_messages: prometheus::register(
+				prometheus::HistogramVec::new(
+					prometheus::HistogramOpts::new(
+						"parachain_validation_processed_downward_messages",
+						"Number of downward messages processed by valid candidates.",
+					).buckets(vec![0.0, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0, 256.0]),
+					&["para_id"],
+				)?,
+				registry,
+			)?,
+			assumption_cap_reached: prometheus::register(
+				prometheus::Counter::new(
+					"parachain_validation_assumption_cap_reached_total",
+					"Number of candidates deemed to have a bad parent after trying the maximum number of assumptions.",
+				)?,
+				registry,
+			)?,
+			admission_delay: prometheus::register(
+				prometheus::Histogram::with_opts(
+					prometheus::HistogramOpts::new(
+						"parachain_validation_admission_delay",
+						"Time from receiving a validation request to starting the validation of the candidate",
+					).buckets(vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]),
+				)?,
+				registry,
+			)?,
+			fetch_to_done: prometheus::register(
+				prometheus::Histogram::with_opts(
+					prometheus::HistogramOpts::new(
+						"parachain_validation_fetch_to_done",
+						"Time from fetching the PoV of a candidate to completing its validation",
+					).buckets(vec![0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0]),
+				)?,
+				registry,
+			)?,
+			pov_size: prometheus::register(
+				prometheus::Histogram::with_opts(
+					prometheus::HistogramOpts::new(
+						"parachain_validation_pov_size",
+						"Size in bytes of the PoVs of validated candidates, before decompression.",
+					).buckets(PAYLOAD_SIZE_BUCKETS.to_vec()),
+				)?,
+				registry,
+			)?,
+			pov_decompressed_size: prometheus::register(
+				prometheus::Histogram::with_opts(
+					prometheus::HistogramOpts::new(
+						"parachain_validation_pov_decompressed_size",
+						"Size in bytes of the block data of validated candidates, after decompression.",
+					).buckets(PAYLOAD_SIZE_BUCKETS.to_vec()),
+				)?,
+				registry,
+			)?,
+			code_size: prometheus::register(
+				prometheus::Histogram::with_opts(
+					prometheus::HistogramOpts::new(
+						"parachain_validation_code_size",
+						"Size in bytes of the validation code of validated candidates, before decompression.",
+					).buckets(PAYLOAD_SIZE_BUCKETS.to_vec()),
+				)?,
+				registry,
+			)?,
+			code_decompressed_size: prometheus::register(
+				prometheus::Histogram::with_opts(
+					prometheus::HistogramOpts::new(
+						"parachain_validation_code_decompressed_size",
+						"Size in bytes of the validation code of validated candidates, after decompression.",
+					).buckets(PAYLOAD_SIZE_BUCKETS.to_vec()),
+				)?,
+				registry,
+			)?,
+			code_decompression_failures_with_matching_hash: prometheus::register(
+				prometheus::Counter::new(
+					"parachain_validation_code_decompression_failures_with_matching_hash_total",
+					"Number of times validation code matching the committed hash failed to decompress.",
+				)?,
+				registry,
+			)?,
+			decompression_time: prometheus::register(
+				prometheus::HistogramVec::new(
+					prometheus::HistogramOpts::new(
+						"parachain_validation_decompression_time",
+						"Time spent decompressing the validation code and the PoVs of candidates, by payload.",
+					).buckets(vec![0.0001, 0.0005, 0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]),
+					&["payload"],
+				)?,
+				registry,
+			)?,
+			result_cache: prometheus::register(
+				prometheus::CounterVec::new(
+					prometheus::Opts::new(
+						"parachain_validation_result_cache_total",
+						"Number of lookups of results of identical earlier requests.",
+					),
+					&["outcome"],
+				)?,
+				registry,
+			)?,
+			execution_cache: prometheus::register(
+				prometheus::CounterVec::new(
+					prometheus::Opts::new(
+						"parachain_validation_execution_cache_total",
+						"Number of lookups of outcomes of earlier executions with the same inputs.",
+					),
+					&["outcome"],
+				)?,
+				registry,
+			)?,
+			readiness: prometheus::register(
+				prometheus::Gauge::new(
+					"parachain_candidate_validation_readiness",
+					"Readiness of the subsystem: 0 starting, 1 ready, 2 degraded, 3 unavailable.",
+				)?,
+				registry,
+			)?,
+			pending_requests: prometheus::register(
+				prometheus::Gauge::new(
+					"parachain_candidate_validation_pending_requests",
+					"Number of requests received by the subsystem but not yet responded to.",
+				)?,
+				registry,
+			)?,
+			overloaded_requests: prometheus::register(
+				prometheus::Counter::new(
+					"parachain_candidate_validation_overloaded_total",
+					"Number of validation requests rejected because too many validations were in flight.",
+				)?,
+				registry,
+			)?,
+			pvf_host_restarts: prometheus::register(
+				prometheus::Counter::new(
+					"parachain_candidate_validation_pvf_host_restarts_total",
+					"Number of times a PVF validation host was restarted after exiting unexpectedly.",
+				)?,
+				registry,
+			)?,
+			canceled: prometheus::register(
+				prometheus::CounterVec::new(
+					prometheus::Opts::new(
+						"parachain_validation_canceled_total",
+						"Number of validations canceled before they completed, by cause.",
+					),
+					&["cause"],
+				)?,
+				registry,
+			)?,
+		};
+		Ok(Metrics(Some(metrics)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use polkadot_node_subsystem_test_helpers as test_helpers;
+	use polkadot_node_subsystem_util::metrics::Metrics as _;
+	use polkadot_primitives::v1::{HeadData, UpwardMessage, OutboundHrmpMessage};
+	use sp_core::testing::TaskExecutor;
+	use futures::executor;
+	use assert_matches::assert_matches;
+	use sp_keyring::Sr25519Keyring;
+	use parity_scale_codec::Decode;
+
+	/// Answer the detection of the runtime API version at the relay-parent, which precedes the
+	/// assumption checks.
+	async fn answer_runtime_api_version(
+		ctx_handle: &mut test_helpers::TestSubsystemContextHandle<CandidateValidationMessage>,
+		version: u32,
+	) {
+		assert_matches!(
+			ctx_handle.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(_, RuntimeApiRequest::Version(tx))) => {
+				let _ = tx.send(Ok(version));
+			}
+		);
+	}
+
+	async fn check_assumption_validation_data(
+		sender: &mut impl SubsystemSender,
+		descriptor: &CandidateDescriptor,
+		assumption: OccupiedCoreAssumption,
+		retry_delay: Option<Duration>,
+		prefetch_code: bool,
+		snapshot: Option<&RuntimeStateSnapshot>,
+	) -> SubsystemResult<AssumptionCheckOutcome> {
+		let pending = dispatch_assumption_check(sender, descriptor, assumption, prefetch_code, snapshot).await;
+		conclude_assumption_check(sender, descriptor, pending, retry_delay, snapshot).await
+	}
+
+	pub(crate) fn collator_sign(descriptor: &mut CandidateDescriptor, collator: Sr25519Keyring) {
+		descriptor.collator = collator.public().into();
+		let payload = polkadot_primitives::v1::collator_signature_payload(
+			&descriptor.relay_parent,
+			&descriptor.para_id,
+			&descriptor.persisted_validation_data_hash,
+			&descriptor.pov_hash,
+			&descriptor.validation_code_hash,
+		);
+
+		descriptor.signature = collator.sign(&payload[..]).into();
+		assert!(descriptor.check_collator_signature().is_ok());
+	}
+
+	#[test]
+	fn correctly_checks_included_assumption() {
+		let validation_data: PersistedValidationData = Default::default();
+		let validation_code: ValidationCode = vec![1, 2, 3].into();
+
+		let persisted_validation_data_hash = validation_data.hash();
+		let relay_parent = [2; 32].into();
+		let para_id = 5.into();
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = relay_parent;
+		candidate.persisted_validation_data_hash = persisted_validation_data_hash;
+		candidate.para_id = para_id;
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let (check_fut, check_result) = check_assumption_validation_data(
+			ctx.sender(),
+			&candidate,
+			OccupiedCoreAssumption::Included,
+			None,
+			false,
+			None,
+		).remote_handle();
+
+		let test_fut = async move {
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::PersistedValidationData(
+						p,
+						OccupiedCoreAssumption::Included,
+						tx
+					),
+				)) => {
+					assert_eq!(rp, relay_parent);
+					assert_eq!(p, para_id);
+
+					let _ = tx.send(Ok(Some(validation_data.clone())));
+				}
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::ValidationCode(p, OccupiedCoreAssumption::Included, tx)
+				)) => {
+					assert_eq!(rp, relay_parent);
+					assert_eq!(p, para_id);
+
+					let _ = tx.send(Ok(Some(validation_code.clone())));
+				}
+			);
+
+			assert_matches!(check_result.await.unwrap(), AssumptionCheckOutcome::Matches(o, v) => {
+				assert_eq!(o, validation_data);
+				assert_eq!(v, validation_code);
+			});
+		};
+
+		let test_fut = future::join(test_fut, check_fut);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn correctly_checks_timed_out_assumption() {
+		let validation_data: PersistedValidationData = Default::default();
+		let validation_code: ValidationCode = vec![1, 2, 3].into();
+
+		let persisted_validation_data_hash = validation_data.hash();
+		let relay_parent = [2; 32].into();
+		let para_id = 5.into();
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = relay_parent;
+		candidate.persisted_validation_data_hash = persisted_validation_data_hash;
+		candidate.para_id = para_id;
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let (check_fut, check_result) = check_assumption_validation_data(
+			ctx.sender(),
+			&candidate,
+			OccupiedCoreAssumption::TimedOut,
+			None,
+			false,
+			None,
+		).remote_handle();
+
+		let test_fut = async move {
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::PersistedValidationData(
+						p,
+						OccupiedCoreAssumption::TimedOut,
+						tx
+					),
+				)) => {
+					assert_eq!(rp, relay_parent);
+					assert_eq!(p, para_id);
+
+					let _ = tx.send(Ok(Some(validation_data.clone())));
+				}
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::ValidationCode(p, OccupiedCoreAssumption::TimedOut, tx)
+				)) => {
+					assert_eq!(rp, relay_parent);
+					assert_eq!(p, para_id);
+
+					let _ = tx.send(Ok(Some(validation_code.clone())));
+				}
+			);
+
+			assert_matches!(check_result.await.unwrap(), AssumptionCheckOutcome::Matches(o, v) => {
+				assert_eq!(o, validation_data);
+				assert_eq!(v, validation_code);
+			});
+		};
+
+		let test_fut = future::join(test_fut, check_fut);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn check_is_bad_request_if_no_validation_data() {
+		let validation_data: PersistedValidationData = Default::default();
+		let persisted_validation_data_hash = validation_data.hash();
+		let relay_parent = [2; 32].into();
+		let para_id = 5.into();
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = relay_parent;
+		candidate.persisted_validation_data_hash = persisted_validation_data_hash;
+		candidate.para_id = para_id;
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let (check_fut, check_result) = check_assumption_validation_data(
+			ctx.sender(),
+			&candidate,
+			OccupiedCoreAssumption::Included,
+			None,
+			false,
+			None,
+		).remote_handle();
+
+		let test_fut = async move {
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::PersistedValidationData(
+						p,
+						OccupiedCoreAssumption::Included,
+						tx
+					),
+				)) => {
+					assert_eq!(rp, relay_parent);
+					assert_eq!(p, para_id);
+
+					let _ = tx.send(Ok(None));
+				}
+			);
+
+			assert_matches!(
+				check_result.await.unwrap(),
+				AssumptionCheckOutcome::BadRequest(AssumptionCheckFailure::ValidationDataMissing)
+			);
+		};
+
+		let test_fut = future::join(test_fut, check_fut);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn check_is_bad_request_if_validation_data_request_fails() {
+		let validation_data: PersistedValidationData = Default::default();
+		let relay_parent = [2; 32].into();
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = relay_parent;
+		candidate.persisted_validation_data_hash = validation_data.hash();
+		candidate.para_id = 5.into();
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let (check_fut, check_result) = check_assumption_validation_data(
+			ctx.sender(),
+			&candidate,
+			OccupiedCoreAssumption::Included,
+			None,
+			false,
+			None,
+		).remote_handle();
+
+		let test_fut = async move {
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, tx),
+				)) => {
+					let _ = tx.send(Err(RuntimeApiError::from("failed".to_string())));
+				}
+			);
+
+			assert_matches!(
+				check_result.await.unwrap(),
+				AssumptionCheckOutcome::BadRequest(AssumptionCheckFailure::ValidationDataError)
+			);
+		};
+
+		let test_fut = future::join(test_fut, check_fut);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn retries_once_if_validation_data_is_missing() {
+		let validation_data: PersistedValidationData = Default::default();
+		let validation_code: ValidationCode = vec![1, 2, 3].into();
+		let persisted_validation_data_hash = validation_data.hash();
+		let relay_parent = [2; 32].into();
+		let para_id = 5.into();
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = relay_parent;
+		candidate.persisted_validation_data_hash = persisted_validation_data_hash;
+		candidate.para_id = para_id;
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let (check_fut, check_result) = check_assumption_validation_data(
+			ctx.sender(),
+			&candidate,
+			OccupiedCoreAssumption::Included,
+			Some(Duration::from_millis(10)),
+			false,
+			None,
+		).remote_handle();
+
+		let test_fut = async move {
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, tx),
+				)) => {
+					let _ = tx.send(Ok(None));
+				}
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, tx),
+				)) => {
+					let _ = tx.send(Ok(Some(validation_data.clone())));
+				}
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::ValidationCode(_, OccupiedCoreAssumption::Included, tx)
+				)) => {
+					let _ = tx.send(Ok(Some(validation_code.clone())));
+				}
+			);
+
+			assert_matches!(check_result.await.unwrap(), AssumptionCheckOutcome::Matches(o, v) => {
+				assert_eq!(o, validation_data);
+				assert_eq!(v, validation_code);
+			});
+		};
+
+		let test_fut = future::join(test_fut, check_fut);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn check_is_bad_request_if_no_validation_code() {
+		let validation_data: PersistedValidationData = Default::default();
+		let persisted_validation_data_hash = validation_data.hash();
+		let relay_parent = [2; 32].into();
+		let para_id = 5.into();
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = relay_parent;
+		candidate.persisted_validation_data_hash = persisted_validation_data_hash;
+		candidate.para_id = para_id;
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let (check_fut, check_result) = check_assumption_validation_data(
+			ctx.sender(),
+			&candidate,
+			OccupiedCoreAssumption::TimedOut,
+			None,
+			false,
+			None,
+		).remote_handle();
+
+		let test_fut = async move {
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::PersistedValidationData(
+						p,
+						OccupiedCoreAssumption::TimedOut,
+						tx
+					),
+				)) => {
+					assert_eq!(rp, relay_parent);
+					assert_eq!(p, para_id);
+
+					let _ = tx.send(Ok(Some(validation_data.clone())));
+				}
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::ValidationCode(p, OccupiedCoreAssumption::TimedOut, tx)
+				)) => {
+					assert_eq!(rp, relay_parent);
+					assert_eq!(p, para_id);
+
+					let _ = tx.send(Ok(None));
+				}
+			);
+
+			assert_matches!(
+				check_result.await.unwrap(),
+				AssumptionCheckOutcome::BadRequest(AssumptionCheckFailure::ValidationCodeMissing)
+			);
+		};
+
+		let test_fut = future::join(test_fut, check_fut);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn check_does_not_match() {
+		let validation_data: PersistedValidationData = Default::default();
+		let relay_parent = [2; 32].into();
+		let para_id = 5.into();
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = relay_parent;
+		candidate.persisted_validation_data_hash = [3; 32].into();
+		candidate.para_id = para_id;
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let (check_fut, check_result) = check_assumption_validation_data(
+			ctx.sender(),
+			&candidate,
+			OccupiedCoreAssumption::Included,
+			None,
+			false,
+			None,
+		).remote_handle();
+
+		let test_fut = async move {
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::PersistedValidationData(
+						p,
+						OccupiedCoreAssumption::Included,
+						tx
+					),
+				)) => {
+					assert_eq!(rp, relay_parent);
+					assert_eq!(p, para_id);
+
+					let _ = tx.send(Ok(Some(validation_data.clone())));
+				}
+			);
+
+			assert_matches!(
+				check_result.await.unwrap(),
+				AssumptionCheckOutcome::DoesNotMatch(attempted) => {
+					assert_eq!(attempted, vec![(OccupiedCoreAssumption::Included, validation_data.hash())]);
+				}
+			);
+		};
+
+		let test_fut = future::join(test_fut, check_fut);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn reports_attempted_hashes_if_no_assumption_matches() {
+		let included_data: PersistedValidationData = Default::default();
+		let timed_out_data = PersistedValidationData { relay_parent_number: 1, ..Default::default() };
+		let relay_parent = [2; 32].into();
+		let para_id = 5.into();
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = relay_parent;
+		candidate.persisted_validation_data_hash = [3; 32].into();
+		candidate.para_id = para_id;
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let mut steps = Vec::new();
+		let metrics = Metrics::default();
+		let (check_fut, check_result) = find_assumed_validation_data(
+			ctx.sender(),
+			&candidate,
+			None,
+			None,
+			false,
+			false,
+			None,
+			&mut steps,
+			&metrics,
+		).remote_handle();
+
+		let test_fut = async move {
+			answer_runtime_api_version(&mut ctx_handle, 1).await;
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, tx),
+				)) => {
+					let _ = tx.send(Ok(Some(included_data.clone())));
+				}
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::TimedOut, tx),
+				)) => {
+					let _ = tx.send(Ok(Some(timed_out_data.clone())));
+				}
+			);
+
+			assert_matches!(
+				check_result.await.unwrap(),
+				AssumptionCheckOutcome::DoesNotMatch(attempted) => {
+					assert_eq!(attempted, vec![
+						(OccupiedCoreAssumption::Included, included_data.hash()),
+						(OccupiedCoreAssumption::TimedOut, timed_out_data.hash()),
+					]);
+				}
+			);
+		};
+
+		let test_fut = future::join(test_fut, check_fut);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn free_assumption_is_tried_if_configured() {
+		let included_data: PersistedValidationData = Default::default();
+		let timed_out_data = PersistedValidationData { relay_parent_number: 1, ..Default::default() };
+		let free_data = PersistedValidationData { relay_parent_number: 2, ..Default::default() };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = [2; 32].into();
+		candidate.persisted_validation_data_hash = free_data.hash();
+		candidate.para_id = 5.into();
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let mut steps = Vec::new();
+		let metrics = Metrics::default();
+		let (check_fut, check_result) = find_assumed_validation_data(
+			ctx.sender(),
+			&candidate,
+			None,
+			None,
+			true,
+			false,
+			None,
+			&mut steps,
+			&metrics,
+		).remote_handle();
+
+		let expected_data = free_data.clone();
+		let test_fut = async move {
+			answer_runtime_api_version(&mut ctx_handle, 1).await;
+
+			for (assumption, data) in vec![
+				(OccupiedCoreAssumption::Included, &included_data),
+				(OccupiedCoreAssumption::TimedOut, &timed_out_data),
+				(OccupiedCoreAssumption::Free, &free_data),
+			] {
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::PersistedValidationData(_, a, tx),
+					)) => {
+						assert_eq!(a, assumption);
+						let _ = tx.send(Ok(Some(data.clone())));
+					}
+				);
+			}
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::ValidationCode(_, OccupiedCoreAssumption::Free, tx)
+				)) => {
+					let _ = tx.send(Ok(Some(validation_code.clone())));
+				}
+			);
+
+			assert_matches!(
+				check_result.await.unwrap(),
+				AssumptionCheckOutcome::Matches(data, code) => {
+					assert_eq!(data, free_data);
+					assert_eq!(code, validation_code);
+				}
+			);
+		};
+
+		let test_fut = future::join(test_fut, check_fut);
+		executor::block_on(test_fut);
+		assert_eq!(steps, vec![
+			ValidationStep::AssumptionMatched(OccupiedCoreAssumption::Free),
+			ValidationStep::ValidationDataFound(expected_data),
+		]);
+	}
+
+	#[test]
+	fn assumptions_are_checked_concurrently() {
+		let included_data: PersistedValidationData = Default::default();
+		let timed_out_data = PersistedValidationData { relay_parent_number: 1, ..Default::default() };
+		let validation_code = ValidationCode(vec![2; 16]);
+		let relay_parent = [2; 32].into();
+		let para_id = 5.into();
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = relay_parent;
+		candidate.persisted_validation_data_hash = timed_out_data.hash();
+		candidate.para_id = para_id;
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let mut steps = Vec::new();
+		let metrics = Metrics::default();
+		let (check_fut, check_result) = find_assumed_validation_data(
+			ctx.sender(),
+			&candidate,
+			None,
+			None,
+			false,
+			false,
+			None,
+			&mut steps,
+			&metrics,
+		).remote_handle();
+
+		let test_fut = async move {
+			answer_runtime_api_version(&mut ctx_handle, 1).await;
+
+			// Both requests are dispatched before either is answered.
+			let included_tx = assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, tx),
+				)) => tx
+			);
+
+			let timed_out_tx = assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::TimedOut, tx),
+				)) => tx
+			);
+
+			let _ = timed_out_tx.send(Ok(Some(timed_out_data.clone())));
+			let _ = included_tx.send(Ok(Some(included_data.clone())));
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::ValidationCode(_, OccupiedCoreAssumption::TimedOut, tx),
+				)) => {
+					let _ = tx.send(Ok(Some(validation_code.clone())));
+				}
+			);
+
+			assert_matches!(check_result.await.unwrap(), AssumptionCheckOutcome::Matches(o, v) => {
+				assert_eq!(o, timed_out_data);
+				assert_eq!(v, validation_code);
+			});
+		};
+
+		let test_fut = future::join(test_fut, check_fut);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn prefetched_validation_code_is_used_if_assumption_matches() {
+		let validation_data: PersistedValidationData = Default::default();
+		let validation_code: ValidationCode = vec![1, 2, 3].into();
+		let relay_parent = [2; 32].into();
+		let para_id = 5.into();
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = relay_parent;
+		candidate.persisted_validation_data_hash = validation_data.hash();
+		candidate.para_id = para_id;
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let (check_fut, check_result) = check_assumption_validation_data(
+			ctx.sender(),
+			&candidate,
+			OccupiedCoreAssumption::Included,
+			None,
+			true,
+			None,
+		).remote_handle();
+
+		let test_fut = async move {
+			let code_tx = assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::ValidationCode(p, OccupiedCoreAssumption::Included, tx)
+				)) => {
+					assert_eq!(rp, relay_parent);
+					assert_eq!(p, para_id);
+					tx
+				}
+			);
+
+			// Both requests are in flight before either is answered.
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, tx),
+				)) => {
+					let _ = tx.send(Ok(Some(validation_data.clone())));
+				}
+			);
+
+			let _ = code_tx.send(Ok(Some(validation_code.clone())));
+
+			assert_matches!(check_result.await.unwrap(), AssumptionCheckOutcome::Matches(o, v) => {
+				assert_eq!(o, validation_data);
+				assert_eq!(v, validation_code);
+			});
+		};
+
+		let test_fut = future::join(test_fut, check_fut);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn prefetched_validation_code_is_discarded_if_assumption_does_not_match() {
+		let included_data: PersistedValidationData = Default::default();
+		let timed_out_data = PersistedValidationData { relay_parent_number: 1, ..Default::default() };
+		let validation_code = ValidationCode(vec![2; 16]);
+		let relay_parent = [2; 32].into();
+		let para_id = 5.into();
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = relay_parent;
+		candidate.persisted_validation_data_hash = timed_out_data.hash();
+		candidate.para_id = para_id;
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let mut steps = Vec::new();
+		let metrics = Metrics::default();
+		let (check_fut, check_result) = find_assumed_validation_data(
+			ctx.sender(),
+			&candidate,
+			None,
+			None,
+			false,
+			true,
+			None,
+			&mut steps,
+			&metrics,
+		).remote_handle();
+
+		let test_fut = async move {
+			answer_runtime_api_version(&mut ctx_handle, 1).await;
+
+			let included_code_tx = assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::ValidationCode(_, OccupiedCoreAssumption::Included, tx),
+				)) => tx
+			);
+
+			let included_data_tx = assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, tx),
+				)) => tx
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::ValidationCode(_, OccupiedCoreAssumption::TimedOut, tx),
+				)) => {
+					let _ = tx.send(Ok(Some(validation_code.clone())));
+				}
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::TimedOut, tx),
+				)) => {
+					let _ = tx.send(Ok(Some(timed_out_data.clone())));
+				}
+			);
+
+			let _ = included_data_tx.send(Ok(Some(included_data.clone())));
+
+			assert_matches!(check_result.await.unwrap(), AssumptionCheckOutcome::Matches(o, v) => {
+				assert_eq!(o, timed_out_data);
+				assert_eq!(v, validation_code);
+			});
+
+			// The code prefetched under the mismatching assumption was discarded.
+			assert!(included_code_tx.send(Ok(Some(ValidationCode(vec![1; 16])))).is_err());
+		};
+
+		let test_fut = future::join(test_fut, check_fut);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn timed_out_assumption_match_is_surfaced() {
+		let included_data: PersistedValidationData = Default::default();
+		let timed_out_data = PersistedValidationData { relay_parent_number: 1, ..Default::default() };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.persisted_validation_data_hash = timed_out_data.hash();
+
+		let snapshot = RuntimeStateSnapshot {
+			persisted_validation_data: vec![
+				(OccupiedCoreAssumption::Included, included_data),
+				(OccupiedCoreAssumption::TimedOut, timed_out_data),
+			],
+			validation_code: vec![(OccupiedCoreAssumption::TimedOut, validation_code)],
+		};
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, _ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let mut steps = Vec::new();
+		let outcome = executor::block_on(find_assumed_validation_data(
+			ctx.sender(),
+			&candidate,
+			None,
+			None,
+			false,
+			false,
+			Some(&snapshot),
+			&mut steps,
+			&Default::default(),
+		)).unwrap();
+
+		assert_matches!(outcome, AssumptionCheckOutcome::Matches(_, _));
+		assert_eq!(matched_assumption(&steps), Some(OccupiedCoreAssumption::TimedOut));
+
+		// Nothing is surfaced if no assumption matches.
+		assert_eq!(matched_assumption(&[ValidationStep::NoAssumptionMatched]), None);
+	}
+
+	#[test]
+	fn validation_data_is_surfaced_for_invalid_candidates() {
+		let validation_data = PersistedValidationData { relay_parent_number: 1, ..Default::default() };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.persisted_validation_data_hash = validation_data.hash();
+
+		let snapshot = RuntimeStateSnapshot {
+			persisted_validation_data: vec![
+				(OccupiedCoreAssumption::Included, validation_data.clone()),
+			],
+			validation_code: vec![(OccupiedCoreAssumption::Included, validation_code)],
+		};
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, _ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let mut steps = Vec::new();
+		let result = executor::block_on(spawn_validate_from_chain_state(
+			ctx.sender(),
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InvalidCandidate(
+				WasmInvalidCandidate::HardTimeout,
+			))),
+			descriptor,
+			Arc::new(PoV { block_data: BlockData(vec![1; 32]) }),
+			None,
+			None,
+			false,
+			false,
+			Some(&snapshot),
+			false,
+			Instant::now(),
+			&ExhaustiveOptions::default(),
+			&mut steps,
+			&Metrics::default(),
+		)).unwrap();
+		assert_matches!(result, Ok(ValidationResult::Invalid(_)));
+
+		let (tx, rx) = oneshot::channel();
+		let mut options = ValidationOptions { validation_data: Some(tx), ..Default::default() };
+		report_steps(&mut options, steps, &Metrics::default());
+		assert_eq!(executor::block_on(rx).unwrap(), validation_data);
+
+		// Nothing is surfaced if no assumption matches.
+		let (tx, rx) = oneshot::channel();
+		let mut options = ValidationOptions { validation_data: Some(tx), ..Default::default() };
+		report_steps(&mut options, vec![ValidationStep::NoAssumptionMatched], &Metrics::default());
+		assert!(executor::block_on(rx).is_err());
+	}
+
+	#[test]
+	fn runtime_api_version_is_detected_per_relay_parent() {
+		let validation_data: PersistedValidationData = Default::default();
+		let validation_code = ValidationCode(vec![2; 16]);
+		let before_upgrade = Hash::repeat_byte(1);
+		let after_upgrade = Hash::repeat_byte(2);
+		let para_id = 5.into();
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		for (relay_parent, version) in vec![
+			(before_upgrade, LATEST_KNOWN_RUNTIME_API_VERSION),
+			(after_upgrade, LATEST_KNOWN_RUNTIME_API_VERSION + 1),
+		] {
+			let mut candidate = CandidateDescriptor::default();
+			candidate.relay_parent = relay_parent;
+			candidate.persisted_validation_data_hash = validation_data.hash();
+			candidate.para_id = para_id;
+
+			let mut steps = Vec::new();
+			let metrics = Metrics::default();
+			let (check_fut, check_result) = find_assumed_validation_data(
+				ctx.sender(),
+				&candidate,
+				None,
+				None,
+				false,
+				false,
+				None,
+				&mut steps,
+				&metrics,
+			).remote_handle();
+
+			let test_fut = async {
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						rp,
+						RuntimeApiRequest::Version(tx),
+					)) => {
+						assert_eq!(rp, relay_parent);
+						let _ = tx.send(Ok(version));
+					}
+				);
+
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						rp,
+						RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, tx),
+					)) => {
+						assert_eq!(rp, relay_parent);
+						let _ = tx.send(Ok(Some(validation_data.clone())));
+					}
+				);
+
+				// Both assumptions are checked at once, even though the first one matches.
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::TimedOut, _),
+					))
+				);
+
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						rp,
+						RuntimeApiRequest::ValidationCode(_, OccupiedCoreAssumption::Included, tx),
+					)) => {
+						assert_eq!(rp, relay_parent);
+						let _ = tx.send(Ok(Some(validation_code.clone())));
+					}
+				);
+
+				check_result.await.unwrap()
+			};
+
+			let (_, outcome) = executor::block_on(future::join(check_fut, test_fut));
+			assert_matches!(outcome, AssumptionCheckOutcome::Matches(data, code) => {
+				assert_eq!(data, validation_data);
+				assert_eq!(code, validation_code);
+			});
+		}
+
+		// A relay-parent not exposing the API at all isn't queried any further.
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = Hash::repeat_byte(3);
+		candidate.persisted_validation_data_hash = validation_data.hash();
+		candidate.para_id = para_id;
+
+		let mut steps = Vec::new();
+		let metrics = Metrics::default();
+		let (check_fut, check_result) = find_assumed_validation_data(
+			ctx.sender(),
+			&candidate,
+			None,
+			None,
+			false,
+			false,
+			None,
+			&mut steps,
+			&metrics,
+		).remote_handle();
+
+		let test_fut = async {
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(_, RuntimeApiRequest::Version(tx))) => {
+					let _ = tx.send(Err(RuntimeApiError::from("not exposed".to_string())));
+				}
+			);
+
+			assert_matches!(
+				check_result.await.unwrap(),
+				AssumptionCheckOutcome::BadRequest(AssumptionCheckFailure::VersionError)
+			);
+			assert!(ctx_handle.recv().now_or_never().is_none());
+		};
+
+		let _ = executor::block_on(future::join(check_fut, test_fut));
+	}
+
+	#[test]
+	fn snapshot_resolves_assumptions_like_live_state() {
+		let included_data: PersistedValidationData = Default::default();
+		let timed_out_data = PersistedValidationData { relay_parent_number: 1, ..Default::default() };
+		let validation_code = ValidationCode(vec![2; 16]);
+		let relay_parent = [2; 32].into();
+		let para_id = 5.into();
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = relay_parent;
+		candidate.persisted_validation_data_hash = timed_out_data.hash();
+		candidate.para_id = para_id;
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let live_outcome = {
+			let mut steps = Vec::new();
+			let metrics = Metrics::default();
+			let (check_fut, check_result) = find_assumed_validation_data(
+				ctx.sender(),
+				&candidate,
+				None,
+				None,
+				false,
+				false,
+				None,
+				&mut steps,
+				&metrics,
+			).remote_handle();
+
+			let test_fut = async {
+				answer_runtime_api_version(&mut ctx_handle, 1).await;
+
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, tx),
+					)) => {
+						let _ = tx.send(Ok(Some(included_data.clone())));
+					}
+				);
+
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::TimedOut, tx),
+					)) => {
+						let _ = tx.send(Ok(Some(timed_out_data.clone())));
+					}
+				);
+
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::ValidationCode(_, OccupiedCoreAssumption::TimedOut, tx),
+					)) => {
+						let _ = tx.send(Ok(Some(validation_code.clone())));
+					}
+				);
+
+				check_result.await.unwrap()
+			};
+
+			let (_, outcome) = executor::block_on(future::join(check_fut, test_fut));
+			outcome
+		};
+
+		let snapshot = RuntimeStateSnapshot {
+			persisted_validation_data: vec![
+				(OccupiedCoreAssumption::Included, included_data.clone()),
+				(OccupiedCoreAssumption::TimedOut, timed_out_data.clone()),
+			],
+			validation_code: vec![
+				(OccupiedCoreAssumption::Included, ValidationCode(vec![1; 16])),
+				(OccupiedCoreAssumption::TimedOut, validation_code.clone()),
+			],
+		};
+		let snapshot = RuntimeStateSnapshot::decode(&mut &snapshot.encode()[..]).unwrap();
+
+		let snapshot_outcome = executor::block_on(find_assumed_validation_data(
+			ctx.sender(),
+			&candidate,
+			None,
+			None,
+			false,
+			false,
+			Some(&snapshot),
+			&mut Vec::new(),
+			&Default::default(),
+		)).unwrap();
+
+		// The runtime API isn't queried when a snapshot is supplied.
+		assert!(ctx_handle.recv().now_or_never().is_none());
+
+		for outcome in vec![live_outcome, snapshot_outcome] {
+			assert_matches!(outcome, AssumptionCheckOutcome::Matches(data, code) => {
+				assert_eq!(data, timed_out_data);
+				assert_eq!(code, validation_code);
+			});
+		}
+	}
+
+	#[test]
+	fn errors_of_a_single_validation_are_surfaced_to_its_requester() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.relay_parent = [2; 32].into();
+		descriptor.persisted_validation_data_hash = validation_data.hash();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let options = ExhaustiveOptions::default();
+		let mut steps = Vec::new();
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let (validate_fut, validate_result) = spawn_validate_from_chain_state(
+			ctx.sender(),
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+				"must not be called".into(),
+			))),
+			descriptor.clone(),
+			Arc::new(pov.clone()),
+			None,
+			Some(1),
+			false,
+			false,
+			None,
+			false,
+			Instant::now(),
+			&options,
+			&mut steps,
+			&metrics,
+		).remote_handle();
+
+		let test_fut = async {
+			answer_runtime_api_version(&mut ctx_handle, 1).await;
+
+			// The runtime API drops the request.
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, _),
+				))
+			);
+
+			let err = assert_matches!(validate_result.await, Err(e) => e);
+
+			let (tx, rx) = oneshot::channel();
+			fail_on_error(&metrics, &descriptor, err, tx);
+			assert_matches!(rx.await.unwrap(), Err(ValidationFailed::RuntimeApi(_)));
+
+			// An internal error of the backend is reported like any other failed validation.
+			let res = validate_candidate_exhaustive(
+				MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+					"validation was cancelled".into(),
+				))),
+				validation_data,
+				validation_code,
+				descriptor,
+				Arc::new(pov),
+				&Default::default(),
+				&metrics,
+			).await.unwrap();
+			assert_matches!(res, Err(ValidationFailed::Cancelled));
+
+			assert_eq!(
+				metrics.0.as_ref().unwrap()
+					.validation_requests
+					.with_label_values(&["validation failure", "0"])
+					.get(),
+				1,
+			);
+		};
+
+		let test_fut = future::join(test_fut, validate_fut);
+		executor::block_on(test_fut);
+	}
+
+	/// A sender whose overseer never accepts any messages sent to it.
+	#[derive(Clone)]
+	struct SaturatedSender;
+
+	#[async_trait]
+	impl SubsystemSender for SaturatedSender {
+		async fn send_message(&mut self, _msg: AllMessages) {
+			future::pending().await
+		}
+
+		async fn send_messages<T>(&mut self, _msgs: T)
+		where
+			T: IntoIterator<Item = AllMessages> + Send,
+			T::IntoIter: Send,
+		{
+			future::pending().await
+		}
+
+		fn send_unbounded_message(&mut self, _msg: AllMessages) {}
+	}
+
+	#[test]
+	fn validation_requests_are_counted_per_para() {
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let valid = Ok(ValidationResult::Valid(Default::default(), Default::default(), Default::default()));
+		let failed = Err(ValidationFailed::Other("cannot resolve descriptor".into()));
+
+		metrics.on_validation_event(Some(5.into()), &valid);
+		metrics.on_validation_event(Some(5.into()), &valid);
+		metrics.on_validation_event(Some(6.into()), &valid);
+		metrics.on_validation_event(None, &failed);
+
+		let requests = &metrics.0.as_ref().unwrap().validation_requests;
+		assert_eq!(requests.with_label_values(&["valid", "5"]).get(), 2);
+		assert_eq!(requests.with_label_values(&["valid", "6"]).get(), 1);
+		assert_eq!(requests.with_label_values(&["validation failure", "unknown"]).get(), 1);
+	}
+
+	#[test]
+	fn supplied_labels_are_attached_to_the_metrics() {
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let mut metric_labels = MetricLabels::new(vec!["network".to_owned()].into_iter().collect());
+		let valid = Ok(ValidationResult::Valid(Default::default(), Default::default(), Default::default()));
+
+		let labels = vec![
+			("network".to_owned(), "kusama".to_owned()),
+			("request".to_owned(), "12345".to_owned()),
+		].into_iter().collect();
+		observe_labels(&metrics, &mut metric_labels, &Default::default(), &labels, &valid);
+
+		let labelled = &metrics.0.as_ref().unwrap().labelled_validation_requests;
+		assert_eq!(labelled.with_label_values(&["network", "kusama", "valid"]).get(), 1);
+		// Labels not configured to be attached to the metrics are left out.
+		assert_eq!(labelled.with_label_values(&["request", "12345", "valid"]).get(), 0);
+
+		// The number of distinct values per label is bounded.
+		for i in 0..MAX_METRIC_LABEL_VALUES {
+			let labels = vec![("network".to_owned(), format!("net-{}", i))].into_iter().collect();
+			observe_labels(&metrics, &mut metric_labels, &Default::default(), &labels, &valid);
+		}
+		assert_eq!(labelled.with_label_values(&["network", "net-0", "valid"]).get(), 1);
+		assert_eq!(labelled.with_label_values(&["network", "other", "valid"]).get(), 1);
+	}
+
+	#[test]
+	fn precheck_outcome_reflects_preparation() {
+		let compressed = sp_maybe_compressed_blob::compress(&[2; 1024], VALIDATION_CODE_BOMB_LIMIT).unwrap();
+		let valid_code = ValidationCode(compressed.clone());
+		let broken_code = ValidationCode(compressed[..compressed.len() - 4].to_vec());
+
+		let precheck = |code: Option<ValidationCode>, precheck_result: Result<(), PrepareError>| {
+			let pool = TaskExecutor::new();
+			let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+			let relay_parent = [2; 32].into();
+			let code_hash = code.as_ref().map_or_else(Default::default, |code| code.hash());
+			let backend = MockValidatorBackend {
+				result: Err(ValidationError::InternalError("must not be executed".into())),
+				precheck_result,
+			};
+
+			let (check_fut, check_result) =
+				precheck_pvf(ctx.sender(), backend, relay_parent, code_hash, None).remote_handle();
+
+			let test_fut = async move {
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						r,
+						RuntimeApiRequest::ValidationCodeByHash(h, tx),
+					)) => {
+						assert_eq!(r, relay_parent);
+						assert_eq!(h, code_hash);
+						let _ = tx.send(Ok(code));
+					}
+				);
+
+				check_result.await
+			};
+
+			executor::block_on(future::join(test_fut, check_fut)).0
+		};
+
+		assert_eq!(precheck(Some(valid_code.clone()), Ok(())), PreCheckOutcome::Valid);
+		assert_eq!(
+			precheck(Some(valid_code.clone()), Err(PrepareError::Invalid("bad wasm".into()))),
+			PreCheckOutcome::Invalid,
+		);
+		assert_eq!(
+			precheck(Some(valid_code), Err(PrepareError::Failed("worker died".into()))),
+			PreCheckOutcome::Failed,
+		);
+		// Code failing to decompress is never handed over for preparation.
+		assert_eq!(
+			precheck(Some(broken_code), Err(PrepareError::Failed("must not be prepared".into()))),
+			PreCheckOutcome::Invalid,
+		);
+		// Nothing can be said about unavailable code.
+		assert_eq!(precheck(None, Ok(())), PreCheckOutcome::Failed);
+	}
+
+	#[test]
+	fn prechecked_code_is_not_decompressed_again() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(
+			sp_maybe_compressed_blob::compress(&[2; 4096], VALIDATION_CODE_BOMB_LIMIT).unwrap(),
+		);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let config = Config { precheck_code_cache_size: Some(1024 * 1024), ..test_config() };
+		let options = ExhaustiveOptions::from_config(&config);
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let backend = || MockValidatorBackend::with_hardcoded_result(
+			Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::HardTimeout)),
+		);
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let (check_fut, check_result) = precheck_pvf(
+			ctx.sender(),
+			backend(),
+			[2; 32].into(),
+			validation_code.hash(),
+			options.prechecked_code.as_deref(),
+		).remote_handle();
+
+		let answer_fut = async {
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::ValidationCodeByHash(_, tx),
+				)) => {
+					let _ = tx.send(Ok(Some(validation_code.clone())));
+				}
+			);
+
+			check_result.await
+		};
+		let (outcome, ()) = executor::block_on(future::join(answer_fut, check_fut));
+		assert_eq!(outcome, PreCheckOutcome::Valid);
+
+		let v = executor::block_on(validate_candidate_exhaustive(
+			backend(),
+			validation_data,
+			validation_code,
+			descriptor,
+			Arc::new(pov),
+			&options,
+			&metrics,
+		))
+		.unwrap();
+		assert_matches!(v, Ok(ValidationResult::Invalid(InvalidCandidate::Timeout)));
+
+		// The code was served from the cache populated by the pre-check.
+		assert_eq!(metrics.0.as_ref().unwrap().code_decompressed_size.get_sample_count(), 0);
+	}
+
+	#[test]
+	fn prechecked_code_is_bounded_in_size() {
+		let mut prechecked = PrecheckedCode::new(10);
+		prechecked.insert(ValidationCodeHash::from(Hash::repeat_byte(1)), Arc::new(vec![1; 4]));
+		prechecked.insert(ValidationCodeHash::from(Hash::repeat_byte(2)), Arc::new(vec![2; 4]));
+		assert!(prechecked.get(&Hash::repeat_byte(1).into()).is_some());
+
+		// The code used least recently is evicted first.
+		prechecked.insert(ValidationCodeHash::from(Hash::repeat_byte(3)), Arc::new(vec![3; 4]));
+		assert!(prechecked.get(&Hash::repeat_byte(2).into()).is_none());
+		assert!(prechecked.get(&Hash::repeat_byte(1).into()).is_some());
+		assert_eq!(prechecked.size, 8);
+
+		// Code exceeding the limit on its own isn't kept.
+		prechecked.insert(ValidationCodeHash::from(Hash::repeat_byte(4)), Arc::new(vec![4; 11]));
+		assert!(prechecked.get(&Hash::repeat_byte(4).into()).is_none());
+	}
+
+	#[test]
+	fn saturated_overseer_fails_the_validation_rather_than_stalling() {
+		let (tx, rx) = oneshot::channel::<Result<Option<u32>, RuntimeApiError>>();
+		let sent = executor::block_on(send_with_timeout(
+			&mut SaturatedSender,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				Default::default(),
+				RuntimeApiRequest::Version(tx),
+			)),
+			Duration::from_millis(10),
+		));
+		assert_matches!(&sent, Err(ValidationFailed::Other(e)) if e == "overseer send timed out");
+
+		// The dropped request cancels its response channel.
+		assert!(executor::block_on(rx).is_err());
+
+		// The failure reaches the requester as it is.
+		let metrics = Metrics::default();
+		let (tx, rx) = oneshot::channel();
+		fail_on_error(
+			&metrics,
+			&CandidateDescriptor::default(),
+			SubsystemError::with_origin("candidate-validation", sent.unwrap_err()),
+			tx,
+		);
+		assert_matches!(
+			executor::block_on(rx).unwrap(),
+			Err(ValidationFailed::Other(e)) if e == "overseer send timed out"
+		);
+	}
+
+	#[test]
+	fn essential_validations_dont_wait_for_a_saturated_overseer() {
+		let send = |source| {
+			let (tx, _rx) = oneshot::channel::<Result<u32, RuntimeApiError>>();
+			executor::block_on(send_with_timeout(
+				&mut ValidationSender::new(SaturatedSender, source),
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					Default::default(),
+					RuntimeApiRequest::Version(tx),
+				)),
+				Duration::from_millis(10),
+			))
+		};
+
+		assert_matches!(send(ValidationSource::ApprovalVoting), Ok(()));
+		assert_matches!(send(ValidationSource::DisputeParticipation), Ok(()));
+		assert_matches!(send(ValidationSource::Backing), Err(ValidationFailed::Other(_)));
+		assert_matches!(send(ValidationSource::Other), Err(ValidationFailed::Other(_)));
+	}
+
+	#[test]
+	fn only_tries_up_to_the_maximum_number_of_assumptions() {
+		let included_data: PersistedValidationData = Default::default();
+		let relay_parent = [2; 32].into();
+		let para_id = 5.into();
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.relay_parent = relay_parent;
+		descriptor.persisted_validation_data_hash = [3; 32].into();
+		descriptor.para_id = para_id;
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let options = ExhaustiveOptions::default();
+		let mut steps = Vec::new();
+		let metrics = Metrics::default();
+		let (validate_fut, validate_result) = spawn_validate_from_chain_state(
+			ctx.sender(),
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+				"must not be called".into(),
+			))),
+			descriptor,
+			Arc::new(PoV { block_data: BlockData(Vec::new()) }),
+			None,
+			Some(1),
+			false,
+			false,
+			None,
+			false,
+			Instant::now(),
+			&options,
+			&mut steps,
+			&metrics,
+		).remote_handle();
+
+		let test_fut = async move {
+			answer_runtime_api_version(&mut ctx_handle, 1).await;
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, tx),
+				)) => {
+					let _ = tx.send(Ok(Some(included_data.clone())));
+				}
+			);
+
+			assert_matches!(
+				validate_result.await,
+				Ok(Ok(ValidationResult::Invalid(InvalidCandidate::BadParent)))
+			);
+
+			// No further assumption is tried.
+			assert!(ctx_handle.recv().now_or_never().is_none());
+		};
+
+		let test_fut = future::join(test_fut, validate_fut);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn input_digest_is_stable_and_distinguishes_inputs() {
+		let validation_code = ValidationCode(vec![2; 16]);
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.para_id = 5.into();
+
+		let digest = InputDigest::new(&validation_code, &pov, &descriptor);
+		assert_eq!(digest, InputDigest::new(&validation_code.clone(), &pov.clone(), &descriptor.clone()));
+
+		assert_ne!(digest, InputDigest::new(&ValidationCode(vec![3; 16]), &pov, &descriptor));
+		assert_ne!(
+			digest,
+			InputDigest::new(&validation_code, &PoV { block_data: BlockData(vec![2; 32]) }, &descriptor),
+		);
+
+		let mut other_descriptor = descriptor.clone();
+		other_descriptor.para_id = 6.into();
+		assert_ne!(digest, InputDigest::new(&validation_code, &pov, &other_descriptor));
+	}
+
+	#[test]
+	fn validations_are_dispatched_round_robin() {
+		let mut hosts = HostPool::new(vec![
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError("a".into()))),
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError("b".into()))),
+		]);
+
+		let dispatched = (0..4)
+			.map(|_| {
+				let host = hosts.next();
+				let params = ValidationParams {
+					parent_head: Default::default(),
+					block_data: BlockData(Vec::new()),
+					relay_parent_number: 0,
+					relay_parent_storage_root: Default::default(),
+				};
+				let res = executor::block_on(host.validate_candidate(Vec::new(), params, Priority::Normal));
+				assert_matches!(res, Err(ValidationError::InternalError(e)) => e)
+			})
+			.collect::<Vec<_>>();
+
+		assert_eq!(dispatched, vec!["a", "b", "a", "b"]);
+	}
+
+	pub(crate) fn test_config() -> Config {
+		Config {
+			artifacts_cache_path: "/tmp/artifacts".into(),
+			program_path: "/usr/bin/polkadot".into(),
+			worker_idle_timeout: None,
+			max_workers: None,
+			execution_timeout: DEFAULT_EXECUTION_TIMEOUT,
+			approval_execution_timeout: DEFAULT_APPROVAL_EXECUTION_TIMEOUT,
+			preparation_timeout: DEFAULT_PREPARATION_TIMEOUT,
+			prewarm_on_startup: false,
+			max_artifacts_cache_size: None,
+			execution_memory_limit: None,
+			validation_data_retry_delay: None,
+			published_head_data_limit: None,
+			rate_limits: HashMap::new(),
+			defer_output_checks: false,
+			max_assumptions_to_try: None,
+			check_free_assumption: false,
+			prefetch_validation_code: false,
+			dispute_policy: None,
+			unclaimed_results_capacity: None,
+			execution_cache_size: None,
+			precheck_code_cache_size: None,
+			pvf_hosts: 1,
+			max_concurrent_validations: 1,
+			shutdown_grace_period: Duration::from_millis(100),
+			pov_preview_len: 0,
+			span_log: None,
+			max_preparation_cost: None,
+			pov_decompression_timeout: None,
+			validation_code_bomb_limit: None,
+			max_compressed_code_size: None,
+			pov_bomb_limit: None,
+			max_concurrent_decompressions: None,
+			worker_respawn_backoff: None,
+			pvf_host_restart_backoff: None,
+			ambiguous_worker_death_retries: DEFAULT_AMBIGUOUS_WORKER_DEATH_RETRIES,
+			differential_execution: false,
+			disabled_host_functions: Vec::new(),
+			metric_labels: HashSet::new(),
+			active_validator_check: None,
+			keystore: None,
+			record_sink: Arc::new(NoopRecordSink),
+		}
+	}
+
+	#[test]
+	fn worker_idle_timeout_is_forwarded_to_host() {
+		let mut config = test_config();
+		assert_eq!(pvf_host_config(&config).execute_worker_idle_timeout, None);
+
+		config.pvf_hosts = 2;
+		let pvf_configs = pvf_host_configs(&config);
+		assert_eq!(pvf_configs.len(), 2);
+		assert_ne!(pvf_configs[0].cache_path, pvf_configs[1].cache_path);
+		config.pvf_hosts = 1;
+
+		config.worker_idle_timeout = Some(Duration::from_secs(60));
+		assert_eq!(
+			pvf_host_config(&config).execute_worker_idle_timeout,
+			Some(Duration::from_secs(60)),
+		);
+
+		config.disabled_host_functions = vec!["ext_logging_log_version_1".into()];
+		assert_eq!(
+			pvf_host_config(&config).execute_disabled_host_functions,
+			vec!["ext_logging_log_version_1".to_owned()],
+		);
+
+		config.max_artifacts_cache_size = Some(1 << 30);
+		assert_eq!(pvf_host_config(&config).max_artifacts_cache_size, Some(1 << 30));
+
+		let default_workers = pvf_host_config(&config).execute_workers_max_num;
+		config.max_workers = Some(default_workers + 4);
+		assert_eq!(pvf_host_config(&config).execute_workers_max_num, default_workers + 4);
+	}
+
+	#[test]
+	fn exited_pvf_host_is_restarted() {
+		let (exits_tx, mut exits) = mpsc::unbounded();
+		executor::block_on(supervise_pvf_host(1, future::ready(()), exits_tx.clone()));
+		assert_eq!(exits.try_next().unwrap(), Some(1));
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, _ctx_handle) =
+			test_helpers::make_subsystem_context::<CandidateValidationMessage, _>(pool);
+		let config = Config {
+			artifacts_cache_path: std::env::temp_dir().join("candidate-validation-host-restart"),
+			..test_config()
+		};
+
+		// Dropping the task of the host simulates its death.
+		let (validation_host, task) = polkadot_node_core_pvf::start(pvf_host_config(&config));
+		drop(task);
+		let mut hosts = HostPool::new(vec![validation_host]);
+		assert!(!hosts.hosts[0].is_running());
+
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		assert_matches!(executor::block_on(
+			restart_pvf_host(&mut ctx, &config, &mut hosts, 0, &exits_tx, &metrics),
+		), Ok(()));
+		assert!(hosts.hosts[0].is_running());
+		assert_eq!(metrics.0.as_ref().unwrap().pvf_host_restarts.get(), 1);
+	}
+
+	#[test]
+	fn pvf_host_restarts_back_off_and_are_bounded() {
+		let backoff = polkadot_node_core_pvf::RespawnBackoff {
+			initial: Duration::from_secs(1),
+			max: Duration::from_secs(3),
+		};
+
+		let mut restarts = 0;
+		let delays = (0..MAX_PVF_HOST_RESTARTS)
+			.map(|_| pvf_host_exited(0, &mut restarts, Some(backoff)).unwrap())
+			.collect::<Vec<_>>();
+		assert_eq!(
+			delays,
+			vec![Duration::from_secs(1), Duration::from_secs(2), Duration::from_secs(3)],
+		);
+
+		assert_matches!(
+			pvf_host_exited(0, &mut restarts, Some(backoff)),
+			Err(SubsystemError::Context(_))
+		);
+		assert_eq!(restarts, MAX_PVF_HOST_RESTARTS);
+
+		let mut restarts = 0;
+		assert_eq!(pvf_host_exited(0, &mut restarts, None).unwrap(), Duration::from_secs(0));
+	}
+
+	#[test]
+	fn limits_reflect_config() {
+		let config = Config {
+			worker_idle_timeout: Some(Duration::from_secs(30)),
+			execution_timeout: Duration::from_secs(6),
+			approval_execution_timeout: Duration::from_secs(24),
+			preparation_timeout: Duration::from_secs(20),
+			execution_memory_limit: Some(64 * 1024 * 1024),
+			..test_config()
+		};
+
+		assert_eq!(validation_limits(&config), ValidationLimits {
+			validation_code_bomb_limit: VALIDATION_CODE_BOMB_LIMIT,
+			pov_bomb_limit: POV_BOMB_LIMIT,
+			execution_timeout: Duration::from_secs(6),
+			worker_idle_timeout: Some(Duration::from_secs(30)),
+			execution_memory_limit: Some(64 * 1024 * 1024),
+		});
+		assert_eq!(pvf_host_config(&config).execute_memory_limit, Some(64 * 1024 * 1024));
+		// The hosts must not cut short the longest allotted execution.
+		assert_eq!(pvf_host_config(&config).execute_timeout, Duration::from_secs(24));
+		assert_eq!(pvf_host_config(&config).prepare_timeout, Duration::from_secs(20));
+	}
+
+	#[test]
+	fn injected_backend_serves_validations() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		// No PVF host is started, so the configured program path is never used.
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			Config { program_path: "/nonexistent".into(), ..test_config() },
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+			Metrics::default(),
+		).start(ctx);
+
+		let test_fut = async move {
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateFromExhaustive(
+					validation_data,
+					validation_code,
+					descriptor,
+					Arc::new(pov),
+					Default::default(),
+					tx,
+				),
+			}).await;
+			assert_matches!(rx.await.unwrap(), Ok(ValidationResult::Valid(_, _, _)));
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::FlushArtifactCache(tx),
+			}).await;
+			assert_eq!(rx.await.unwrap(), 0);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn resolving_the_descriptor_doesnt_block_other_requests() {
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			test_config(),
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+				"must not be called".into(),
+			))),
+			Metrics::default(),
+		).start(ctx);
+
+		let test_fut = async move {
+			let (descriptor_tx, mut descriptor_rx) = mpsc::channel(1);
+			let resolvers = CandidateResolvers {
+				descriptor: descriptor_tx,
+				validation_code: mpsc::channel(1).0,
+				persisted_validation_data: mpsc::channel(1).0,
+				pov: mpsc::channel(1).0,
+			};
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateFromHash(
+					CandidateHash(Hash::repeat_byte(1)),
+					resolvers,
+					Default::default(),
+					tx,
+				),
+			}).await;
+			let (_, resolve_tx) = descriptor_rx.next().await.unwrap();
+
+			// The subsystem answers other requests while the descriptor is being resolved.
+			let (limits_tx, limits_rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::GetLimits(limits_tx),
+			}).await;
+			assert!(limits_rx.await.is_ok());
+
+			let _ = resolve_tx.send(None);
+			assert_matches!(
+				rx.await.unwrap(),
+				Err(ValidationFailed::Other(e)) if e == "cannot resolve descriptor"
+			);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn pre_checking_doesnt_block_other_requests() {
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			test_config(),
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+				"must not be called".into(),
+			))),
+			Metrics::default(),
+		).start(ctx);
+
+		let test_fut = async move {
+			let validation_code = ValidationCode(vec![2; 16]);
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::PreCheck(
+					Hash::repeat_byte(1),
+					validation_code.hash(),
+					tx,
+				),
+			}).await;
+			let code_tx = assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::ValidationCodeByHash(_, tx),
+				)) => tx
+			);
+
+			// The subsystem answers other requests while the code is being fetched.
+			let (limits_tx, limits_rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::GetLimits(limits_tx),
+			}).await;
+			assert!(limits_rx.await.is_ok());
+
+			let _ = code_tx.send(Ok(Some(validation_code)));
+			assert_eq!(rx.await.unwrap(), PreCheckOutcome::Valid);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn basic_check_rejects_a_pov_hash_mismatch_without_executing() {
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = Hash::repeat_byte(1);
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			test_config(),
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+				"must not be called".into(),
+			))),
+			Metrics::default(),
+		).start(ctx);
+
+		let test_fut = async move {
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::BasicCheck(
+					descriptor,
+					Arc::new(pov),
+					validation_code,
+					1024,
+					tx,
+				),
+			}).await;
+			assert_matches!(rx.await.unwrap(), Err(InvalidCandidate::PoVHashMismatch));
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn validations_are_in_flight_simultaneously() {
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			Config { max_concurrent_validations: 2, ..test_config() },
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+				"must not be called".into(),
+			))),
+			Metrics::default(),
+		).start(ctx);
+
+		let test_fut = async move {
+			let mut responses = Vec::new();
+			for relay_parent in vec![Hash::repeat_byte(1), Hash::repeat_byte(2)] {
+				let mut descriptor = CandidateDescriptor::default();
+				descriptor.relay_parent = relay_parent;
+
+				let (tx, rx) = oneshot::channel();
+				ctx_handle.send(FromOverseer::Communication {
+					msg: CandidateValidationMessage::ValidateFromChainState(
+						descriptor,
+						Arc::new(PoV { block_data: BlockData(Vec::new()) }),
+						Default::default(),
+						tx,
+					),
+				}).await;
+				responses.push(rx);
+			}
+
+			// Both validations query the runtime before either of them is answered.
+			let mut relay_parents = Vec::new();
+			for _ in 0..2 {
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						relay_parent,
+						RuntimeApiRequest::Version(_),
+					)) => relay_parents.push(relay_parent)
+				);
+			}
+			relay_parents.sort();
+			assert_eq!(relay_parents, vec![Hash::repeat_byte(1), Hash::repeat_byte(2)]);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+			drop(responses);
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn requests_beyond_the_concurrency_limit_are_rejected() {
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			Config { max_concurrent_validations: 1, ..test_config() },
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+				"must not be called".into(),
+			))),
+			metrics.clone(),
+		).start(ctx);
+
+		let test_fut = async move {
+			let validate = || {
+				let (tx, rx) = oneshot::channel();
+				let msg = CandidateValidationMessage::ValidateFromChainState(
+					CandidateDescriptor::default(),
+					Arc::new(PoV { block_data: BlockData(Vec::new()) }),
+					Default::default(),
+					tx,
+				);
+				(FromOverseer::Communication { msg }, rx)
+			};
+
+			// The first validation stays in flight while it waits for the runtime.
+			let (msg, _first) = validate();
+			ctx_handle.send(msg).await;
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(_, RuntimeApiRequest::Version(_)))
+			);
+
+			let (msg, second) = validate();
+			ctx_handle.send(msg).await;
+			assert_matches!(second.await.unwrap(), Err(ValidationFailed::Overloaded));
+			assert_eq!(metrics.0.as_ref().unwrap().overloaded_requests.get(), 1);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn essential_requests_beyond_the_concurrency_limit_are_queued() {
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			Config { max_concurrent_validations: 1, ..test_config() },
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+				"must not be called".into(),
+			))),
+			metrics.clone(),
+		).start(ctx);
+
+		let test_fut = async move {
+			let validate = |source| {
+				let (tx, rx) = oneshot::channel();
+				let msg = CandidateValidationMessage::ValidateFromChainState(
+					CandidateDescriptor::default(),
+					Arc::new(PoV { block_data: BlockData(Vec::new()) }),
+					ValidationOptions { source, ..Default::default() },
+					tx,
+				);
+				(FromOverseer::Communication { msg }, rx)
+			};
+
+			let (msg, first) = validate(ValidationSource::Backing);
+			ctx_handle.send(msg).await;
+			let version_tx = assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(_, RuntimeApiRequest::Version(tx))) => tx
+			);
+
+			// Requests for approval voting and dispute participation wait for the first validation.
+			let (msg, _approval) = validate(ValidationSource::ApprovalVoting);
+			ctx_handle.send(msg).await;
+			let (msg, _dispute) = validate(ValidationSource::DisputeParticipation);
+			ctx_handle.send(msg).await;
+
+			drop(version_tx);
+			assert!(first.await.is_ok());
+
+			// The approval is let in flight once the first validation completed, while the dispute
+			// waits for it in turn.
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(_, RuntimeApiRequest::Version(_)))
+			);
+			assert_eq!(metrics.0.as_ref().unwrap().overloaded_requests.get(), 0);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn artifacts_of_the_paras_assigned_to_cores_are_prewarmed_on_startup() {
+		use polkadot_primitives::v1::{CoreState, ScheduledCore};
+		use polkadot_subsystem::{ActivatedLeaf, ActiveLeavesUpdate, LeafStatus, jaeger};
+
+		/// A backend reporting the validation code it prepares.
+		struct PrepareProbe(mpsc::UnboundedSender<Vec<u8>>);
+
+		#[async_trait]
+		impl ValidationBackend for PrepareProbe {
+			async fn validate_candidate(
+				&mut self,
+				_raw_validation_code: Vec<u8>,
+				_params: ValidationParams,
+				_priority: Priority,
+			) -> Result<WasmValidationResult, ValidationError> {
+				Err(ValidationError::InternalError("must not be called".into()))
+			}
+
+			async fn precheck_pvf(&mut self, raw_validation_code: Vec<u8>) -> Result<(), PrepareError> {
+				let _ = self.0.unbounded_send(raw_validation_code);
+				Ok(())
+			}
+		}
+
+		let relay_parent = Hash::repeat_byte(1);
+		let scheduled = |para_id: u32| CoreState::Scheduled(ScheduledCore {
+			para_id: para_id.into(),
+			collator: None,
+		});
+		// Paras 1 and 3 share their validation code.
+		let code_of = |para_id: ParaId| {
+			if para_id == ParaId::from(2) { ValidationCode(vec![2; 16]) } else { ValidationCode(vec![1; 16]) }
+		};
+
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let (prepared_tx, prepared_rx) = mpsc::unbounded();
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			Config { prewarm_on_startup: true, ..test_config() },
+			PrepareProbe(prepared_tx),
+			Metrics::default(),
+		).start(ctx);
+
+		let test_fut = async move {
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::start_work(ActivatedLeaf {
+					hash: relay_parent,
+					number: 1,
+					status: LeafStatus::Fresh,
+					span: Arc::new(jaeger::Span::Disabled),
+				}),
+			))).await;
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::AvailabilityCores(tx),
+				)) => {
+					assert_eq!(rp, relay_parent);
+					let _ = tx.send(Ok(vec![scheduled(3), CoreState::Free, scheduled(1), scheduled(2)]));
+				}
+			);
+
+			for expected_para_id in vec![1u32, 2, 3] {
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						rp,
+						RuntimeApiRequest::ValidationCode(para_id, OccupiedCoreAssumption::TimedOut, tx),
+					)) => {
+						assert_eq!(rp, relay_parent);
+						assert_eq!(para_id, ParaId::from(expected_para_id));
+						let _ = tx.send(Ok(Some(code_of(para_id))));
+					}
+				);
+			}
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+
+		let prepared: Vec<_> = executor::block_on(prepared_rx.collect());
+		assert_eq!(prepared, vec![vec![1; 16], vec![2; 16]]);
+	}
+
+	#[test]
+	fn candidates_of_a_batch_share_the_decompressed_code() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(
+			sp_maybe_compressed_blob::compress(&[2; 4096], VALIDATION_CODE_BOMB_LIMIT).unwrap(),
+		);
+
+		let candidates = (1..=2u8)
+			.map(|i| {
+				let pov = PoV { block_data: BlockData(vec![i; 32]) };
+
+				let mut descriptor = CandidateDescriptor::default();
+				descriptor.para_id = u32::from(i).into();
+				descriptor.pov_hash = pov.hash();
+				descriptor.para_head = head_data.hash();
+				descriptor.validation_code_hash = validation_code.hash();
+				collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+				(validation_data.clone(), validation_code.clone(), descriptor, Arc::new(pov))
+			})
+			.collect();
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			Config { max_concurrent_validations: 2, ..test_config() },
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+			metrics.clone(),
+		).start(ctx);
+
+		let test_fut = async move {
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateBatch(candidates, tx),
+			}).await;
+
+			let results = rx.await.unwrap();
+			assert_eq!(results.len(), 2);
+			for result in results {
+				assert_matches!(result, Ok(ValidationResult::Valid(_, _, _)));
+			}
+
+			let inner = metrics.0.as_ref().unwrap();
+			assert_eq!(inner.code_decompressed_size.get_sample_count(), 1);
+			assert_eq!(inner.pov_decompressed_size.get_sample_count(), 2);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn validation_is_cancelled_once_its_requester_hangs_up() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let (started_tx, started_rx) = oneshot::channel();
+		let (dropped_tx, dropped_rx) = oneshot::channel();
+
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			test_config(),
+			StalledBackend { started: Some(started_tx), dropped: Some(dropped_tx) },
+			metrics.clone(),
+		).start(ctx);
+
+		let test_fut = async move {
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateFromExhaustive(
+					validation_data,
+					validation_code,
+					descriptor,
+					Arc::new(pov),
+					Default::default(),
+					tx,
+				),
+			}).await;
+
+			started_rx.await.unwrap();
+			drop(rx);
+
+			// The execution is dropped rather than awaited in vain.
+			assert!(dropped_rx.await.is_err());
+			assert_eq!(
+				metrics.0.as_ref().unwrap().canceled.with_label_values(&["requester_dropped"]).get(),
+				1,
+			);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn identical_validations_in_flight_are_coalesced() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = Arc::new(PoV { block_data: BlockData(vec![1; 32]) });
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let (started_tx, started_rx) = oneshot::channel();
+		let (release_tx, release_rx) = oneshot::channel();
+		let executions = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			test_config(),
+			GatedBackend {
+				started: Some(started_tx),
+				release: release_rx.shared(),
+				executions: executions.clone(),
+				result: Err(ValidationError::InvalidCandidate(
+					WasmInvalidCandidate::WorkerReportedError("trap".into()),
+				)),
+			},
+			Metrics::default(),
+		).start(ctx);
+
+		let test_fut = async move {
+			let validate = |tx| FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateFromExhaustive(
+					validation_data.clone(),
+					validation_code.clone(),
+					descriptor.clone(),
+					pov.clone(),
+					Default::default(),
+					tx,
+				),
+			};
+
+			let (tx, first_rx) = oneshot::channel();
+			ctx_handle.send(validate(tx)).await;
+			started_rx.await.unwrap();
+
+			// Let in even though the only validation allowed in flight is running already.
+			let (tx, second_rx) = oneshot::channel();
+			ctx_handle.send(validate(tx)).await;
+			let (limits_tx, limits_rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::GetLimits(limits_tx),
+			}).await;
+			let _ = limits_rx.await.unwrap();
+
+			// The first requester hanging up doesn't cancel the validation the second awaits.
+			drop(first_rx);
+			release_tx.send(()).unwrap();
+
+			assert_matches!(
+				second_rx.await,
+				Ok(Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError(_))))
+			);
+			assert_eq!(executions.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn validation_is_aborted_once_its_relay_parent_is_deactivated() {
+		use polkadot_subsystem::{ActivatedLeaf, LeafStatus, jaeger};
+
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
+		let relay_parent = Hash::repeat_byte(1);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.relay_parent = relay_parent;
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let (started_tx, started_rx) = oneshot::channel();
+		let (dropped_tx, dropped_rx) = oneshot::channel();
+
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			test_config(),
+			StalledBackend { started: Some(started_tx), dropped: Some(dropped_tx) },
+			metrics.clone(),
+		).start(ctx);
+
+		let test_fut = async move {
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::start_work(ActivatedLeaf {
+					hash: relay_parent,
+					number: 1,
+					status: LeafStatus::Fresh,
+					span: Arc::new(jaeger::Span::Disabled),
+				}),
+			))).await;
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateFromExhaustive(
+					validation_data,
+					validation_code,
+					descriptor,
+					Arc::new(pov),
+					Default::default(),
+					tx,
+				),
+			}).await;
+
+			started_rx.await.unwrap();
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::stop_work(relay_parent),
+			))).await;
+
+			assert_matches!(rx.await, Ok(Err(ValidationFailed::Cancelled)));
+			assert!(dropped_rx.await.is_err());
+			assert_eq!(
+				metrics.0.as_ref().unwrap().canceled.with_label_values(&["relay_parent_deactivated"]).get(),
+				1,
+			);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn only_validations_for_backing_are_aborted_once_their_relay_parent_is_superseded() {
+		use polkadot_subsystem::{ActivatedLeaf, LeafStatus, jaeger};
+
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = Arc::new(PoV { block_data: BlockData(vec![1; 32]) });
+		let validation_code = ValidationCode(vec![2; 16]);
+		let relay_parent = Hash::repeat_byte(1);
+		let child = Hash::repeat_byte(2);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.relay_parent = relay_parent;
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let (started_tx, started_rx) = oneshot::channel();
+		let (release_tx, release_rx) = oneshot::channel();
+
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			Config { max_concurrent_validations: 2, ..test_config() },
+			GatedBackend {
+				started: Some(started_tx),
+				release: release_rx.shared(),
+				executions: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+				result: Err(ValidationError::InvalidCandidate(
+					WasmInvalidCandidate::WorkerReportedError("trap".into()),
+				)),
+			},
+			Metrics::default(),
+		).start(ctx);
+
+		let test_fut = async move {
+			let leaf = |hash, number| ActivatedLeaf {
+				hash,
+				number,
+				status: LeafStatus::Fresh,
+				span: Arc::new(jaeger::Span::Disabled),
+			};
+			let validate = |source, tx| FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateFromExhaustive(
+					validation_data.clone(),
+					validation_code.clone(),
+					descriptor.clone(),
+					pov.clone(),
+					ValidationOptions::new(source),
+					tx,
+				),
+			};
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::start_work(leaf(relay_parent, 1)),
+			))).await;
+
+			let (tx, approval_rx) = oneshot::channel();
+			ctx_handle.send(validate(ValidationSource::ApprovalVoting, tx)).await;
+			started_rx.await.unwrap();
+
+			let (tx, backing_rx) = oneshot::channel();
+			ctx_handle.send(validate(ValidationSource::Backing, tx)).await;
+
+			// The child of the relay-parent is imported, superseding it as a leaf.
+			let mut update = ActiveLeavesUpdate::start_work(leaf(child, 2));
+			update.deactivated.push(relay_parent);
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(update))).await;
+
+			// The backing job on the relay-parent concluded, so its validation is aborted, whereas the
+			// one for approval survives.
+			assert_matches!(backing_rx.await, Ok(Err(ValidationFailed::Cancelled)));
+
+			release_tx.send(()).unwrap();
+			assert_matches!(
+				approval_rx.await,
+				Ok(Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError(_))))
+			);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn only_validations_for_backing_on_active_leaves_are_aborted() {
+		use polkadot_subsystem::{ActivatedLeaf, LeafStatus, jaeger};
+
+		let leaf = Hash::repeat_byte(1);
+		let mut aborts = RelayParentAborts::default();
+		aborts.on_active_leaves(&ActiveLeavesUpdate::start_work(ActivatedLeaf {
+			hash: leaf,
+			number: 1,
+			status: LeafStatus::Fresh,
+			span: Arc::new(jaeger::Span::Disabled),
+		}));
+
+		let abort = aborts.signal(leaf, ExecutionKind::Backing).unwrap();
+		assert!(aborts.signal(leaf, ExecutionKind::Approval).is_none());
+		assert!(aborts.signal(leaf, ExecutionKind::Dispute).is_none());
+		assert!(aborts.signal(Hash::repeat_byte(2), ExecutionKind::Backing).is_none());
+
+		assert!(abort.clone().now_or_never().is_none());
+		aborts.on_active_leaves(&ActiveLeavesUpdate::stop_work(leaf));
+		assert_matches!(abort.now_or_never(), Some(Ok(())));
+		assert!(aborts.signal(leaf, ExecutionKind::Backing).is_none());
+	}
+
+	#[test]
+	fn pending_requester_is_answered_on_conclude() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let (started_tx, started_rx) = oneshot::channel();
+		let (dropped_tx, dropped_rx) = oneshot::channel();
+
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			test_config(),
+			StalledBackend { started: Some(started_tx), dropped: Some(dropped_tx) },
+			Metrics::default(),
+		).start(ctx);
+
+		let test_fut = async move {
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateFromExhaustive(
+					validation_data,
+					validation_code,
+					descriptor,
+					Arc::new(pov),
+					Default::default(),
+					tx,
+				),
+			}).await;
+
+			started_rx.await.unwrap();
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+
+			// The execution outlasting the grace period is cancelled, but its requester still
+			// hears back.
+			assert_matches!(rx.await, Ok(Err(ValidationFailed::ShuttingDown)));
+			assert!(dropped_rx.await.is_err());
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn validation_completing_within_the_grace_period_is_answered_on_conclude() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			Config { shutdown_grace_period: Duration::from_secs(10), ..test_config() },
+			DelayedBackend {
+				delay: Duration::from_millis(10),
+				result: Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::HardTimeout)),
+			},
+			Metrics::default(),
+		).start(ctx);
+
+		let test_fut = async move {
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateFromExhaustive(
+					validation_data,
+					validation_code,
+					descriptor,
+					Arc::new(pov),
+					Default::default(),
+					tx,
+				),
+			}).await;
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+
+			assert_matches!(rx.await, Ok(Ok(ValidationResult::Invalid(InvalidCandidate::Timeout))));
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn unclaimed_result_is_published_when_served() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = Arc::new(PoV { block_data: BlockData(vec![1; 32]) });
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let subsystem = CandidateValidationSubsystem::with_backend(
+			Config { unclaimed_results_capacity: Some(1), ..test_config() },
+			DelayedBackend {
+				delay: Duration::from_millis(50),
+				result: Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::HardTimeout)),
+			},
+			Metrics::default(),
+		).start(ctx);
+
+		let test_fut = async move {
+			let validate = |tx| FromOverseer::Communication {
+				msg: CandidateValidationMessage::ValidateFromExhaustive(
+					validation_data.clone(),
+					validation_code.clone(),
+					descriptor.clone(),
+					pov.clone(),
+					Default::default(),
+					tx,
+				),
+			};
+
+			let (outcomes_tx, mut outcomes) = mpsc::channel(4);
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::SubscribeOutcomes(outcomes_tx),
+			}).await;
+
+			// Hang up once the validation is admitted, which the answer to a later request
+			// confirms.
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(validate(tx)).await;
+			let (limits_tx, limits_rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::GetLimits(limits_tx),
+			}).await;
+			let _ = limits_rx.await.unwrap();
+			drop(rx);
+			assert!(outcomes.next().await.is_some());
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(validate(tx)).await;
+			assert_matches!(rx.await, Ok(Ok(ValidationResult::Invalid(InvalidCandidate::Timeout))));
+
+			// The served result is published like the one it was kept from.
+			let served = outcomes.next().await.unwrap();
+			assert_matches!(served.outcome, PublishedOutcome::Invalid { .. });
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+
+	#[test]
+	fn published_head_data_is_truncated() {
+		let head_data = HeadData(vec![7; 1024]);
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.para_id = 5.into();
+
+		let commitments = CandidateCommitments {
+			head_data: head_data.clone(),
+			..Default::default()
+		};
+		let result = Ok(ValidationResult::Valid(commitments, Default::default(), Default::default()));
+
+		let (tx, mut rx) = mpsc::channel(1);
+		let mut subscribers = vec![tx];
+		publish_outcome(&mut subscribers, &descriptor, &result, Some(32), None);
+
+		let event = rx.try_next().unwrap().unwrap();
+		assert_eq!(event.para_id, descriptor.para_id);
+		assert_eq!(event.outcome, PublishedOutcome::Valid {
+			head_data: HeadData(vec![7; 32]),
+			head_data_hash: head_data.hash(),
+		});
+
+		assert_matches!(result, Ok(ValidationResult::Valid(outputs, _, _)) => {
+			assert_eq!(outputs.head_data, head_data);
+		});
+
+		drop(rx);
+		publish_outcome(&mut subscribers, &descriptor, &Err(ValidationFailed::Other("x".into())), None, None);
+		assert!(subscribers.is_empty());
+	}
+
+	#[test]
+	fn completed_validations_are_recorded_to_the_sink() {
+		#[derive(Default)]
+		struct MemorySink(std::sync::Mutex<Vec<ValidationOutcomeEvent>>);
+
+		impl ValidationRecordSink for MemorySink {
+			fn record(&self, outcome: &ValidationOutcomeEvent) {
+				self.0.lock().unwrap().push(outcome.clone());
+			}
+		}
+
+		let head_data = HeadData(vec![7; 1024]);
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.para_id = 5.into();
+
+		let commitments = CandidateCommitments {
+			head_data: head_data.clone(),
+			..Default::default()
+		};
+
+		let sink = MemorySink::default();
+		let valid = Ok(ValidationResult::Valid(commitments, Default::default(), Default::default()));
+		let invalid = Ok(ValidationResult::Invalid(InvalidCandidate::BadReturn));
+		record_outcome(&sink, &descriptor, &valid, None);
+		record_outcome(&sink, &descriptor, &invalid, None);
+
+		let records = sink.0.into_inner().unwrap();
+		assert_eq!(records.len(), 2);
+		assert!(records.iter().all(|record| record.para_id == descriptor.para_id));
+		assert_eq!(records[0].outcome, PublishedOutcome::Valid {
+			head_data: head_data.clone(),
+			head_data_hash: head_data.hash(),
+		});
+		assert_matches!(records[1].outcome, PublishedOutcome::Invalid { .. });
+	}
+
+	#[test]
+	fn repeated_identical_request_is_unchanged() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+		let validation_code = ValidationCode(vec![2; 16]);
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let descriptor = CandidateDescriptor::default();
+		let candidate = BlakeTwo256::hash_of(&descriptor);
+
+		let key = exhaustive_request_key(&validation_data, &validation_code, &descriptor, &pov);
+		let mut last_validated = LastValidatedInputs::new();
+		assert!(!last_validated.is_unchanged(&candidate, &key));
+
+		last_validated.note(candidate, key);
+
+		// The identical request is resent.
+		let resent_key = exhaustive_request_key(
+			&validation_data.clone(),
+			&validation_code.clone(),
+			&descriptor.clone(),
+			&pov.clone(),
+		);
+		assert!(last_validated.is_unchanged(&candidate, &resent_key));
+
+		// The validation code of the candidate changed.
+		let other_code = ValidationCode(vec![3; 16]);
+		let changed_key = exhaustive_request_key(&validation_data, &other_code, &descriptor, &pov);
+		assert!(!last_validated.is_unchanged(&candidate, &changed_key));
+
+		// Only the latest inputs of the candidate are remembered.
+		last_validated.note(candidate, changed_key);
+		assert!(last_validated.is_unchanged(&candidate, &changed_key));
+		assert!(!last_validated.is_unchanged(&candidate, &key));
+
+		let res = Ok(ValidationResult::Unchanged);
+		assert_eq!(outcome_event(&descriptor, &res, None, None).outcome, PublishedOutcome::Unchanged);
+	}
+
+	#[test]
+	fn unclaimed_results_are_served_to_identical_requests() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+		let validation_code = ValidationCode(vec![2; 16]);
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let descriptor = CandidateDescriptor::default();
+
+		let key = exhaustive_request_key(&validation_data, &validation_code, &descriptor, &pov);
+		let mut unclaimed = UnclaimedResults::new(1);
+
+		// The requester hung up, so the result is kept.
+		unclaimed.insert(key, ValidationResult::Invalid(InvalidCandidate::BadReturn));
+
+		let other_pov = PoV { block_data: BlockData(vec![2; 32]) };
+		let other_key = exhaustive_request_key(&validation_data, &validation_code, &descriptor, &other_pov);
+		assert!(unclaimed.take(&other_key).is_none());
+
+		// The identical request is resent.
+		let resent_key = exhaustive_request_key(
+			&validation_data.clone(),
+			&validation_code.clone(),
+			&descriptor.clone(),
+			&pov.clone(),
+		);
+		assert_matches!(
+			unclaimed.take(&resent_key),
+			Some(ValidationResult::Invalid(InvalidCandidate::BadReturn))
+		);
+		assert!(unclaimed.take(&resent_key).is_none());
+
+		// The oldest result is evicted once the capacity is reached.
+		unclaimed.insert(key, ValidationResult::Invalid(InvalidCandidate::BadReturn));
+		unclaimed.insert(other_key, ValidationResult::Invalid(InvalidCandidate::Timeout));
+		assert!(unclaimed.take(&key).is_none());
+		assert!(unclaimed.take(&other_key).is_some());
+	}
+
+	#[test]
+	fn requests_are_pending_until_their_guards_are_dropped() {
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let gauge = || metrics.0.as_ref().unwrap().pending_requests.get();
+
+		let first = metrics.on_request_received();
+		let second = metrics.on_request_received();
+		assert_eq!(gauge(), 2);
+
+		drop(first);
+		assert_eq!(gauge(), 1);
+
+		drop(second);
+		assert_eq!(gauge(), 0);
+
+		// Without registered metrics, there is nothing to account for.
+		assert!(Metrics::default().on_request_received().is_none());
+	}
+
+	#[test]
+	fn readiness_moves_to_ready_once_serving_validations() {
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let gauge = || metrics.0.as_ref().unwrap().readiness.get();
+		let valid = Ok(ValidationResult::Valid(Default::default(), Default::default(), Default::default()));
+		let failed = Err(ValidationFailed::Other("worker died".into()));
+
+		let mut readiness = ReadinessTracker::new();
+		assert_eq!(readiness.current(), Readiness::Starting);
+
+		readiness.on_hosts_started(&metrics);
+		readiness.on_validation(&valid, &metrics);
+		assert_eq!(readiness.current(), Readiness::Ready);
+		assert_eq!(gauge(), 1);
+
+		// Most of the recent validations failing degrades the subsystem.
+		readiness.on_validation(&failed, &metrics);
+		readiness.on_validation(&failed, &metrics);
+		assert_eq!(readiness.current(), Readiness::Degraded);
+		assert_eq!(gauge(), 2);
+
+		for _ in 0..READINESS_WINDOW {
+			readiness.on_validation(&valid, &metrics);
+		}
+		assert_eq!(readiness.current(), Readiness::Ready);
+
+		readiness.on_hosts_checked(false, &metrics);
+		assert_eq!(readiness.current(), Readiness::Unavailable);
+		assert_eq!(gauge(), 3);
+	}
+
+	#[test]
+	fn corrupted_pov_fails_integrity_check() {
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let checksum = pov_integrity_checksum(&pov);
+
+		assert!(check_pov_integrity(&pov, None).is_ok());
+		assert!(check_pov_integrity(&pov, Some(checksum)).is_ok());
+
+		let mut corrupted = pov.clone();
+		corrupted.block_data.0[7] ^= 0xff;
+		assert_matches!(
+			check_pov_integrity(&corrupted, Some(checksum)),
+			Err(ValidationFailed::Other(e)) if e == "pov integrity checksum mismatch"
+		);
+	}
+
+	/// Counts the events emitted within `f` that match the given filter.
+	fn count_events(filter: fn(&tracing::Event<'_>) -> bool, f: impl FnOnce()) -> usize {
+		use std::sync::atomic::{AtomicUsize, Ordering};
+		use tracing::{span, Event, Metadata, Subscriber};
+
+		struct CountEvents {
+			filter: fn(&Event<'_>) -> bool,
+			count: Arc<AtomicUsize>,
+		}
+
+		impl Subscriber for CountEvents {
+			fn enabled(&self, _: &Metadata<'_>) -> bool {
+				true
+			}
+
+			fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+				span::Id::from_u64(1)
+			}
+
+			fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+			fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+			fn event(&self, event: &Event<'_>) {
+				if (self.filter)(event) {
+					self.count.fetch_add(1, Ordering::SeqCst);
+				}
+			}
+
+			fn enter(&self, _: &span::Id) {}
+
+			fn exit(&self, _: &span::Id) {}
+		}
+
+		let count = Arc::new(AtomicUsize::new(0));
+		tracing::subscriber::with_default(CountEvents { filter, count: count.clone() }, f);
+		count.load(Ordering::SeqCst)
+	}
+
+	#[test]
+	fn validations_are_traced_in_spans_closed_on_early_returns() {
+		use std::collections::HashMap as StdHashMap;
+		use std::sync::Mutex as StdMutex;
+		use tracing::{span, Event, Metadata, Subscriber};
+
+		#[derive(Default)]
+		struct Spans {
+			names: Vec<&'static str>,
+			handles: StdHashMap<u64, usize>,
+			closed: usize,
+			depth: usize,
+			events_inside: usize,
+			events_outside: usize,
+		}
+
+		struct RecordSpans(Arc<StdMutex<Spans>>);
+
+		impl Subscriber for RecordSpans {
+			fn enabled(&self, _: &Metadata<'_>) -> bool {
+				true
+			}
+
+			fn new_span(&self, attributes: &span::Attributes<'_>) -> span::Id {
+				let mut spans = self.0.lock().unwrap();
+				spans.names.push(attributes.metadata().name());
+				let id = spans.names.len() as u64;
+				let _ = spans.handles.insert(id, 1);
+				span::Id::from_u64(id)
+			}
+
+			fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+			fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+			fn event(&self, _: &Event<'_>) {
+				let mut spans = self.0.lock().unwrap();
+				if spans.depth > 0 {
+					spans.events_inside += 1;
+				} else {
+					spans.events_outside += 1;
+				}
+			}
+
+			fn enter(&self, _: &span::Id) {
+				self.0.lock().unwrap().depth += 1;
+			}
+
+			fn exit(&self, _: &span::Id) {
+				self.0.lock().unwrap().depth -= 1;
+			}
+
+			fn clone_span(&self, id: &span::Id) -> span::Id {
+				*self.0.lock().unwrap().handles.get_mut(&id.into_u64()).unwrap() += 1;
+				id.clone()
+			}
+
+			fn try_close(&self, id: span::Id) -> bool {
+				let mut spans = self.0.lock().unwrap();
+				let handles = spans.handles.get_mut(&id.into_u64()).unwrap();
+				*handles -= 1;
+				let closed = *handles == 0;
+				if closed {
+					spans.closed += 1;
+				}
+				closed
+			}
+		}
+
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		// The descriptor isn't signed, so the candidate is found invalid before it is executed.
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+
+		let spans = Arc::new(StdMutex::new(Spans::default()));
+		let v = tracing::subscriber::with_default(RecordSpans(spans.clone()), || {
+			executor::block_on(validate_candidate_exhaustive(
+				MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+					"must not be executed".into(),
+				))),
+				validation_data,
+				validation_code,
+				descriptor,
+				Arc::new(pov),
+				&Default::default(),
+				&Metrics::default(),
+			))
+		});
+		assert_matches!(v, Ok(Ok(ValidationResult::Invalid(InvalidCandidate::BadSignature))));
+
+		let spans = spans.lock().unwrap();
+		assert_eq!(spans.names, vec!["validate-candidate-exhaustive"]);
+		assert_eq!(spans.closed, spans.names.len());
+		assert_eq!(spans.depth, 0);
+		assert!(spans.events_inside > 0);
+		assert_eq!(spans.events_outside, 0);
+	}
+
+	#[test]
+	fn only_verbose_validations_emit_trace_events() {
+		let descriptor = CandidateDescriptor::default();
+		let result = Ok(ValidationResult::Invalid(InvalidCandidate::BadReturn));
+
+		let events = |verbose| count_events(
+			|event| {
+				let metadata = event.metadata();
+				metadata.target() == VERBOSE_LOG_TARGET && *metadata.level() == tracing::Level::TRACE
+			},
+			|| {
+				trace_validation_start(verbose, &descriptor);
+				trace_validation_outcome(verbose, &descriptor, &result);
+			},
+		);
+
+		assert_eq!(events(false), 0);
+		assert_eq!(events(true), 2);
+	}
+
+	#[test]
+	fn pov_preview_is_logged_on_decompression_failure() {
+		let pov = PoV { block_data: BlockData(vec![0xab; 64]) };
+		let failure = Ok(ValidationResult::Invalid(InvalidCandidate::PoVDecompressionFailure));
+		let other = Ok(ValidationResult::Invalid(InvalidCandidate::BadReturn));
+
+		let previews = |preview_len, result: &Result<ValidationResult, ValidationFailed>| count_events(
+			|event| event.metadata().fields().field("pov_preview").is_some(),
+			|| log_pov_preview(&pov, preview_len, result),
+		);
+
+		assert_eq!(previews(0, &failure), 0);
+		assert_eq!(previews(8, &other), 0);
+		assert_eq!(previews(8, &failure), 1);
+	}
+
+	#[test]
+	fn validation_of_dropped_requester_is_canceled() {
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let canceled = || {
+			metrics.0.as_ref().unwrap().canceled.with_label_values(&["requester_dropped"]).get()
+		};
+
+		let (tx, rx) = oneshot::channel();
+		assert!(!requester_dropped(&metrics, &tx));
+		assert_eq!(canceled(), 0);
+
+		drop(rx);
+		assert!(requester_dropped(&metrics, &tx));
+		assert_eq!(canceled(), 1);
+	}
+
+	#[test]
+	fn result_cache_lookups_are_counted() {
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let mut unclaimed = UnclaimedResults::new(2);
+
+		let key = Hash::repeat_byte(1);
+		unclaimed.insert(key, ValidationResult::Invalid(InvalidCandidate::BadReturn));
+
+		assert!(unclaimed.lookup(&key, &metrics).is_some());
+		assert!(unclaimed.lookup(&Hash::repeat_byte(2), &metrics).is_none());
+		assert!(unclaimed.lookup(&key, &metrics).is_none());
+
+		let result_cache = &metrics.0.as_ref().unwrap().result_cache;
+		assert_eq!(result_cache.with_label_values(&["hit"]).get(), 1);
+		assert_eq!(result_cache.with_label_values(&["miss"]).get(), 2);
+	}
+
+	#[test]
+	fn verdict_codes_match_the_documented_ones() {
+		let codes = vec![
+			(InvalidCandidate::ExecutionError("x".into()), 1),
+			(InvalidCandidate::InvalidOutputs, 2),
+			(InvalidCandidate::Timeout, 3),
+			(InvalidCandidate::ParamsTooLarge(1), 4),
+			(InvalidCandidate::CodeTooLarge(1), 5),
+			(InvalidCandidate::CodeDecompressionFailure, 6),
+			(InvalidCandidate::PoVDecompressionFailure, 7),
+			(InvalidCandidate::BadReturn, 8),
+			(InvalidCandidate::BadParent, 9),
+			(InvalidCandidate::PoVHashMismatch, 10),
+			(InvalidCandidate::BadSignature, 11),
+			(InvalidCandidate::ParaHeadHashMismatch, 12),
+			(InvalidCandidate::CodeHashMismatch, 13),
+			(InvalidCandidate::PoVSizeExceeded(1), 14),
+			(InvalidCandidate::NonDeterministic, 15),
+		];
+
+		for (reason, code) in codes {
+			assert_eq!(reason.verdict_code(), code, "{:?}", reason);
+			assert_eq!(ValidationResult::Invalid(reason).verdict_code(), code);
+		}
+
+		let valid = ValidationResult::Valid(Default::default(), Default::default(), Default::default());
+		assert_eq!(valid.verdict_code(), 0);
+		assert_eq!(ValidationResult::Unchanged.verdict_code(), -2);
+
+		let failures = vec![
+			(ValidationFailed::Other("x".into()), -1),
+			(ValidationFailed::RuntimeApi("x".into()), -3),
+			(ValidationFailed::PvfHostUnavailable("x".into()), -4),
+			(ValidationFailed::Cancelled, -5),
+			(ValidationFailed::AssumptionCheck(AssumptionCheckFailure::VersionError), -6),
+			(ValidationFailed::OutputsCheck, -7),
+			(ValidationFailed::Overloaded, -8),
+			(ValidationFailed::ShuttingDown, -9),
+		];
+
+		for (failure, code) in failures {
+			assert_eq!(failure.verdict_code(), code, "{:?}", failure);
+		}
+	}
+
+	#[test]
+	fn validation_failures_are_structured_but_display_as_before() {
+		assert_eq!(
+			ValidationFailed::AssumptionCheck(AssumptionCheckFailure::ValidationDataMissing).to_string(),
+			"Validation failed with \"Assumption Check: Bad request\"",
+		);
+		assert_eq!(
+			ValidationFailed::OutputsCheck.to_string(),
+			"Validation failed with \"Check Validation Outputs: Bad request\"",
+		);
+		assert_eq!(
+			ValidationFailed::Other("rate limited".into()).to_string(),
+			"Validation failed with \"rate limited\"",
+		);
+
+		assert_eq!(internal_validation_failure(VALIDATION_CANCELLED.into()), ValidationFailed::Cancelled);
+		assert_eq!(ValidationFailed::Cancelled.description(), VALIDATION_CANCELLED);
+		assert_matches!(
+			internal_validation_failure(format!("{}: Disconnected", PVF_HOST_UNAVAILABLE)),
+			ValidationFailed::PvfHostUnavailable(_)
+		);
+		assert_eq!(
+			internal_validation_failure("artifact deserialization".into()),
+			ValidationFailed::Other("artifact deserialization".into()),
+		);
+	}
+
+	#[test]
+	fn every_invalid_candidate_reason_has_its_own_label() {
+		let reasons = vec![
+			InvalidCandidate::ExecutionError("x".into()),
+			InvalidCandidate::InvalidOutputs,
+			InvalidCandidate::Timeout,
+			InvalidCandidate::ParamsTooLarge(1),
+			InvalidCandidate::CodeTooLarge(1),
+			InvalidCandidate::CodeDecompressionFailure,
+			InvalidCandidate::PoVDecompressionFailure,
+			InvalidCandidate::BadReturn,
+			InvalidCandidate::BadParent,
+			InvalidCandidate::PoVHashMismatch,
+			InvalidCandidate::BadSignature,
+			InvalidCandidate::ParaHeadHashMismatch,
+			InvalidCandidate::CodeHashMismatch,
+			InvalidCandidate::PoVSizeExceeded(1),
+			InvalidCandidate::NonDeterministic,
+		];
+
+		// Verdict codes are assigned consecutively, so this covers every reason there is.
+		let codes: Vec<_> = reasons.iter().map(|reason| reason.verdict_code()).collect();
+		assert_eq!(codes, (1..=reasons.len() as i32).collect::<Vec<_>>());
+
+		let labels: HashSet<_> = reasons.iter().map(invalid_candidate_label).collect();
+		assert_eq!(labels.len(), reasons.len());
+		assert_eq!(invalid_candidate_label(&InvalidCandidate::Timeout), "Timeout");
+
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		metrics.on_validation_event(
+			None,
+			&Ok(ValidationResult::Invalid(InvalidCandidate::PoVHashMismatch)),
+		);
+		metrics.on_validation_event(None, &Ok(ValidationResult::Unchanged));
+
+		let reasons = &metrics.0.as_ref().unwrap().invalid_candidate_reasons;
+		assert_eq!(reasons.with_label_values(&["PoVHashMismatch"]).get(), 1);
+		assert_eq!(reasons.with_label_values(&["Timeout"]).get(), 0);
+	}
+
+	#[test]
+	fn outcome_events_are_timestamped() {
+		let descriptor = CandidateDescriptor::default();
+		let result = Err(ValidationFailed::Other("x".into()));
+
+		let before = SystemTime::now();
+		let first = outcome_event(&descriptor, &result, None, None);
+		let second = outcome_event(&descriptor, &result, None, None);
+
+		assert!(first.completed_at >= before);
+		assert!(second.completed_at >= first.completed_at);
+	}
+
+	#[test]
+	fn invalid_outcomes_carry_the_configured_dispute_severity() {
+		let policy: DisputePolicy = Arc::new(|reason: &InvalidCandidate| match reason {
+			InvalidCandidate::Timeout => DisputeSeverity::Ignore,
+			InvalidCandidate::BadSignature => DisputeSeverity::Escalate,
+			_ => DisputeSeverity::Dispute,
+		});
+
+		let descriptor = CandidateDescriptor::default();
+		let severity = |reason: InvalidCandidate, policy: Option<&DisputePolicy>| {
+			let event = outcome_event(&descriptor, &Ok(ValidationResult::Invalid(reason)), None, policy);
+			assert_matches!(event.outcome, PublishedOutcome::Invalid { severity, .. } => severity)
+		};
+
+		assert_eq!(severity(InvalidCandidate::Timeout, Some(&policy)), DisputeSeverity::Ignore);
+		assert_eq!(severity(InvalidCandidate::BadSignature, Some(&policy)), DisputeSeverity::Escalate);
+		assert_eq!(severity(InvalidCandidate::BadReturn, Some(&policy)), DisputeSeverity::Dispute);
+		assert_eq!(severity(InvalidCandidate::Timeout, None), DisputeSeverity::Dispute);
+	}
+
+	#[test]
+	fn requests_are_admitted_by_the_same_checks() {
+		let mut limits = HashMap::new();
+		limits.insert(
+			ValidationSource::Backing,
+			RateLimit { max_requests: 1, window: Duration::from_secs(60) },
+		);
+		let mut rate_limiter = RateLimiter::new(limits);
+		let options = ValidationOptions::new(ValidationSource::Backing);
+		let metrics = Metrics::default();
+		let (tx, rx) = oneshot::channel::<()>();
+
+		// An overloaded request doesn't count towards the rate limit of its source.
+		assert_matches!(
+			admit(&metrics, 1, 2, 2, &mut rate_limiter, None, &options, None, &tx),
+			Admission::Rejected(ValidationFailed::Overloaded)
+		);
+
+		// Nor does a batch of more validations than there is room for.
+		assert_matches!(
+			admit(&metrics, 2, 1, 2, &mut rate_limiter, None, &options, None, &tx),
+			Admission::Rejected(ValidationFailed::Overloaded)
+		);
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let corrupted_options = ValidationOptions {
+			pov_checksum: Some(Hash::repeat_byte(1)),
+			..ValidationOptions::new(ValidationSource::Backing)
+		};
+		assert_matches!(
+			admit(&metrics, 1, 0, 2, &mut rate_limiter, None, &corrupted_options, Some(&pov), &tx),
+			Admission::Rejected(ValidationFailed::Other(e)) if e == "pov integrity checksum mismatch"
+		);
+
+		// Nor does a request declined as the node isn't an active validator.
+		let inactive: ActiveValidatorCheck = Arc::new(|| false);
+		assert_matches!(
+			admit(&metrics, 1, 0, 2, &mut rate_limiter, Some(&inactive), &options, None, &tx),
+			Admission::Rejected(ValidationFailed::Other(e)) if e == "node not active validator"
+		);
+
+		assert_matches!(
+			admit(&metrics, 1, 1, 2, &mut rate_limiter, None, &options, Some(&pov), &tx),
+			Admission::Admitted(_)
+		);
+		assert_matches!(
+			admit(&metrics, 1, 1, 2, &mut rate_limiter, None, &options, Some(&pov), &tx),
+			Admission::Rejected(ValidationFailed::Other(e)) if e == "rate limited"
+		);
+
+		drop(rx);
+		assert_matches!(
+			admit(&metrics, 1, 0, 2, &mut rate_limiter, None, &Default::default(), None, &tx),
+			Admission::RequesterDropped
+		);
+
+		// A request whose requester hung up doesn't count towards the rate limit either.
+		let mut rate_limiter = RateLimiter::new(rate_limiter.limits);
+		assert_matches!(
+			admit(&metrics, 1, 0, 2, &mut rate_limiter, None, &options, None, &tx),
+			Admission::RequesterDropped
+		);
+		let (tx, _rx) = oneshot::channel::<()>();
+		assert_matches!(
+			admit(&metrics, 1, 0, 2, &mut rate_limiter, None, &options, None, &tx),
+			Admission::Admitted(_)
+		);
+	}
+
+	#[test]
+	fn rate_limits_are_applied_per_source() {
+		let mut limits = HashMap::new();
+		limits.insert(
+			ValidationSource::Backing,
+			RateLimit { max_requests: 2, window: Duration::from_secs(1) },
+		);
+		let mut rate_limiter = RateLimiter::new(limits);
+
+		let now = Instant::now();
+		assert!(rate_limiter.try_acquire(ValidationSource::Backing, now));
+		assert!(rate_limiter.try_acquire(ValidationSource::Backing, now));
+		assert!(!rate_limiter.try_acquire(ValidationSource::Backing, now));
+
+		// Other sources proceed unaffected.
+		for _ in 0..10 {
+			assert!(rate_limiter.try_acquire(ValidationSource::Other, now));
+		}
+
+		// The limit is reset in the next window.
+		let later = now + Duration::from_secs(1);
+		assert!(rate_limiter.try_acquire(ValidationSource::Backing, later));
+	}
+
+	#[test]
+	fn essential_sources_are_never_rate_limited() {
+		let limit = RateLimit { max_requests: 1, window: Duration::from_secs(60) };
+		let mut limits = HashMap::new();
+		limits.insert(ValidationSource::ApprovalVoting, limit);
+		limits.insert(ValidationSource::DisputeParticipation, limit);
+		let mut rate_limiter = RateLimiter::new(limits);
+
+		let now = Instant::now();
+		for _ in 0..10 {
+			assert!(rate_limiter.try_acquire(ValidationSource::ApprovalVoting, now));
+			assert!(rate_limiter.try_acquire(ValidationSource::DisputeParticipation, now));
+		}
+	}
+
+	struct MockValidatorBackend {
+		result: Result<WasmValidationResult, ValidationError>,
+		precheck_result: Result<(), PrepareError>,
+	}
+
+	impl MockValidatorBackend {
+		fn with_hardcoded_result(result: Result<WasmValidationResult, ValidationError>) -> Self {
+			Self {
+				result,
+				precheck_result: Ok(()),
+			}
+		}
+	}
+
+	#[async_trait]
+	impl ValidationBackend for MockValidatorBackend {
+		async fn validate_candidate(
+			&mut self,
+			_raw_validation_code: Vec<u8>,
+			_params: ValidationParams,
+			_priority: Priority,
+		) -> Result<WasmValidationResult, ValidationError> {
+			self.result.clone()
+		}
+
+		async fn precheck_pvf(&mut self, _raw_validation_code: Vec<u8>) -> Result<(), PrepareError> {
+			self.precheck_result.clone()
+		}
+	}
+
+	/// A backend yielding the given result after the given delay.
+	struct DelayedBackend {
+		delay: Duration,
+		result: Result<WasmValidationResult, ValidationError>,
+	}
+
+	#[async_trait]
+	impl ValidationBackend for DelayedBackend {
+		async fn validate_candidate(
+			&mut self,
+			_raw_validation_code: Vec<u8>,
+			_params: ValidationParams,
+			_priority: Priority,
+		) -> Result<WasmValidationResult, ValidationError> {
+			futures_timer::Delay::new(self.delay).await;
+			self.result.clone()
+		}
+
+		async fn precheck_pvf(&mut self, _raw_validation_code: Vec<u8>) -> Result<(), PrepareError> {
+			Ok(())
+		}
+	}
+
+	/// A backend whose executions never conclude, reporting when the first one starts and when it
+	/// is dropped.
+	struct StalledBackend {
+		started: Option<oneshot::Sender<()>>,
+		dropped: Option<oneshot::Sender<()>>,
+	}
+
+	#[async_trait]
+	impl ValidationBackend for StalledBackend {
+		async fn validate_candidate(
+			&mut self,
+			_raw_validation_code: Vec<u8>,
+			_params: ValidationParams,
+			_priority: Priority,
+		) -> Result<WasmValidationResult, ValidationError> {
+			if let Some(started) = self.started.take() {
+				let _ = started.send(());
+			}
+
+			// Hung up once the execution is dropped.
+			let _dropped = self.dropped.take();
+			future::pending().await
+		}
+
+		async fn precheck_pvf(&mut self, _raw_validation_code: Vec<u8>) -> Result<(), PrepareError> {
+			Ok(())
+		}
+	}
+
+	/// A backend whose executions conclude with the given result only once released, reporting
+	/// when the first one starts and counting them.
+	struct GatedBackend {
+		started: Option<oneshot::Sender<()>>,
+		release: future::Shared<oneshot::Receiver<()>>,
+		executions: Arc<std::sync::atomic::AtomicUsize>,
+		result: Result<WasmValidationResult, ValidationError>,
+	}
+
+	#[async_trait]
+	impl ValidationBackend for GatedBackend {
+		async fn validate_candidate(
+			&mut self,
+			_raw_validation_code: Vec<u8>,
+			_params: ValidationParams,
+			_priority: Priority,
+		) -> Result<WasmValidationResult, ValidationError> {
+			let _ = self.executions.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+			if let Some(started) = self.started.take() {
+				let _ = started.send(());
+			}
+
+			let _ = self.release.clone().await;
+			self.result.clone()
+		}
+
+		async fn precheck_pvf(&mut self, _raw_validation_code: Vec<u8>) -> Result<(), PrepareError> {
+			Ok(())
+		}
+	}
+
+	/// A backend yielding the given results in turn, one per execution.
+	struct SequencedBackend {
+		results: VecDeque<Result<WasmValidationResult, ValidationError>>,
+		executions: Arc<std::sync::atomic::AtomicUsize>,
+	}
+
+	#[async_trait]
+	impl ValidationBackend for SequencedBackend {
+		async fn validate_candidate(
+			&mut self,
+			_raw_validation_code: Vec<u8>,
+			_params: ValidationParams,
+			_priority: Priority,
+		) -> Result<WasmValidationResult, ValidationError> {
+			let _ = self.executions.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+			self.results.pop_front().expect("executed more often than expected")
+		}
+
+		async fn precheck_pvf(&mut self, _raw_validation_code: Vec<u8>) -> Result<(), PrepareError> {
+			Ok(())
+		}
+	}
+
+	/// A backend reporting the priority it was asked to execute the PVF with.
+	struct PriorityProbe {
+		priority: Option<oneshot::Sender<Priority>>,
+		result: Result<WasmValidationResult, ValidationError>,
+	}
+
+	#[async_trait]
+	impl ValidationBackend for PriorityProbe {
+		async fn validate_candidate(
+			&mut self,
+			_raw_validation_code: Vec<u8>,
+			_params: ValidationParams,
+			priority: Priority,
+		) -> Result<WasmValidationResult, ValidationError> {
+			if let Some(tx) = self.priority.take() {
+				let _ = tx.send(priority);
+			}
+			self.result.clone()
+		}
+
+		async fn precheck_pvf(&mut self, _raw_validation_code: Vec<u8>) -> Result<(), PrepareError> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn candidate_validation_ok_is_ok() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let check = perform_basic_checks(
+			&descriptor,
+			validation_data.max_pov_size,
+			&pov,
+			&validation_code,
+		);
+		assert!(check.is_ok());
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: Some(vec![2, 2, 2].into()),
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let v = executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+			validation_data.clone(),
+			validation_code,
+			descriptor,
+			Arc::new(pov),
+			&Default::default(),
+			&Default::default(),
+		))
+		.unwrap()
+		.unwrap();
+
+		assert_matches!(v, ValidationResult::Valid(outputs, used_validation_data, _) => {
+			assert_eq!(outputs.head_data, HeadData(vec![1, 1, 1]));
+			assert_eq!(outputs.upward_messages, Vec::<UpwardMessage>::new());
+			assert_eq!(outputs.horizontal_messages, Vec::new());
+			assert_eq!(outputs.new_validation_code, Some(vec![2, 2, 2].into()));
+			assert_eq!(outputs.hrmp_watermark, 0);
+			assert_eq!(used_validation_data, validation_data);
+		});
+	}
+
+	#[test]
+	fn decompressions_beyond_the_limit_wait_for_a_permit() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let permits = Arc::new(Semaphore::new(1));
+		let options = ExhaustiveOptions {
+			decompression_permits: Some(permits.clone()),
+			..Default::default()
+		};
+
+		executor::block_on(async move {
+			// Another candidate is being decompressed, taking up the only permit.
+			let held_permit = permits.acquire().await;
+
+			let mut steps = Vec::new();
+			let validation = validate_candidate_exhaustive_explained(
+				MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+				validation_data,
+				validation_code,
+				descriptor,
+				Arc::new(pov),
+				&options,
+				&mut steps,
+				&Default::default(),
+			);
+			futures::pin_mut!(validation);
+
+			assert!(futures::poll!(&mut validation).is_pending());
+			assert!(futures::poll!(&mut validation).is_pending());
+
+			drop(held_permit);
+
+			assert_matches!(validation.await, Ok(Ok(ValidationResult::Valid(_, _, _))));
+			assert!(steps.iter().any(|step| matches!(step, ValidationStep::PoVDecompressed { .. })));
+
+			// The permit is given back once the decompressions are done.
+			assert!(permits.try_acquire().is_some());
+		});
+	}
+
+	#[test]
+	fn pov_sizes_are_observed() {
+		let validation_data = PersistedValidationData { max_pov_size: 4096, ..Default::default() };
+
+		let block_data = sp_maybe_compressed_blob::compress(&[1; 4096], POV_BOMB_LIMIT).unwrap();
+		let pov = PoV { block_data: BlockData(block_data) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let pov_size = pov.encoded_size();
+		let v = executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+			validation_data,
+			validation_code,
+			descriptor,
+			Arc::new(pov),
+			&Default::default(),
+			&metrics,
+		));
+		assert_matches!(v, Ok(Ok(ValidationResult::Valid(_, _, _))));
+
+		let inner = metrics.0.as_ref().unwrap();
+		assert_eq!(inner.pov_size.get_sample_count(), 1);
+		assert_eq!(inner.pov_size.get_sample_sum(), pov_size as f64);
+		assert_eq!(inner.pov_decompressed_size.get_sample_count(), 1);
+		assert_eq!(inner.pov_decompressed_size.get_sample_sum(), 4096.0);
+		assert_eq!(inner.decompression_time.with_label_values(&["pov"]).get_sample_count(), 1);
+	}
+
+	#[test]
+	fn validation_code_sizes_are_observed() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(
+			sp_maybe_compressed_blob::compress(&[2; 4096], VALIDATION_CODE_BOMB_LIMIT).unwrap(),
+		);
+		let code_size = validation_code.0.len();
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let v = executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+			validation_data,
+			validation_code,
+			descriptor,
+			Arc::new(pov),
+			&Default::default(),
+			&metrics,
+		));
+		assert_matches!(v, Ok(Ok(ValidationResult::Valid(_, _, _))));
+
+		let inner = metrics.0.as_ref().unwrap();
+		assert_eq!(inner.code_size.get_sample_count(), 1);
+		assert_eq!(inner.code_size.get_sample_sum(), code_size as f64);
+		assert_eq!(inner.code_decompressed_size.get_sample_count(), 1);
+		assert_eq!(inner.code_decompressed_size.get_sample_sum(), 4096.0);
+		assert_eq!(inner.decompression_time.with_label_values(&["code"]).get_sample_count(), 1);
+	}
+
+	#[test]
+	fn valid_result_carries_the_hash_of_the_executed_code() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(
+			sp_maybe_compressed_blob::compress(&[2; 16], VALIDATION_CODE_BOMB_LIMIT).unwrap(),
+		);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let v = executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+			validation_data,
+			validation_code.clone(),
+			descriptor,
+			Arc::new(pov),
+			&Default::default(),
+			&Default::default(),
+		));
+
+		// The hash is the one of the code as provided, i.e. before decompressing it.
+		assert_matches!(v, Ok(Ok(ValidationResult::Valid(_, _, code_hash))) => {
+			assert_eq!(code_hash, validation_code.hash());
+		});
+	}
+
+	#[test]
+	fn execution_timeout_depends_on_the_kind_of_validation() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let defaults = ExhaustiveOptions {
+			backing_execution_timeout: Some(Duration::from_millis(50)),
+			approval_execution_timeout: Some(Duration::from_secs(10)),
+			..Default::default()
+		};
+		let validate = |execution_kind: ExecutionKind| {
+			executor::block_on(validate_candidate_exhaustive(
+				DelayedBackend {
+					delay: Duration::from_millis(500),
+					result: Ok(validation_result.clone()),
+				},
+				validation_data.clone(),
+				validation_code.clone(),
+				descriptor.clone(),
+				Arc::new(pov.clone()),
+				&defaults.clone().with_execution_kind(execution_kind),
+				&Default::default(),
+			))
+		};
+
+		assert_matches!(
+			validate(ExecutionKind::Backing),
+			Ok(Ok(ValidationResult::Invalid(InvalidCandidate::Timeout)))
+		);
+		assert_matches!(validate(ExecutionKind::Approval), Ok(Ok(ValidationResult::Valid(..))));
+		assert_matches!(validate(ExecutionKind::Dispute), Ok(Ok(ValidationResult::Valid(..))));
+	}
+
+	#[test]
+	fn executions_with_the_same_inputs_are_served_from_the_cache() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let options = ExhaustiveOptions {
+			execution_cache: Some(Arc::new(Mutex::new(ExecutionCache::new(1 << 20)))),
+			..Default::default()
+		};
+		let pov = Arc::new(pov);
+		let validate = |
+			descriptor: CandidateDescriptor,
+			results: Vec<Result<WasmValidationResult, ValidationError>>,
+		| {
+			let executions = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+			let backend = SequencedBackend { results: results.into(), executions: executions.clone() };
+			let v = executor::block_on(validate_candidate_exhaustive(
+				backend,
+				validation_data.clone(),
+				validation_code.clone(),
+				descriptor,
+				pov.clone(),
+				&options,
+				&metrics,
+			));
+			(v, executions.load(std::sync::atomic::Ordering::SeqCst))
+		};
+
+		let (v, executions) = validate(descriptor.clone(), vec![Ok(validation_result)]);
+		assert_matches!(v, Ok(Ok(ValidationResult::Valid(_, _, _))));
+		assert_eq!(executions, 1);
+
+		let (v, executions) = validate(descriptor.clone(), Vec::new());
+		assert_matches!(v, Ok(Ok(ValidationResult::Valid(_, _, _))));
+		assert_eq!(executions, 0);
+
+		// The cached outputs are still checked against the descriptor at hand.
+		let mut other_descriptor = descriptor;
+		other_descriptor.para_head = Hash::repeat_byte(9);
+		collator_sign(&mut other_descriptor, Sr25519Keyring::Alice);
+		let (v, executions) = validate(other_descriptor, Vec::new());
+		assert_matches!(v, Ok(Ok(ValidationResult::Invalid(InvalidCandidate::ParaHeadHashMismatch))));
+		assert_eq!(executions, 0);
+
+		let execution_cache = &metrics.0.as_ref().unwrap().execution_cache;
+		assert_eq!(execution_cache.with_label_values(&["hit"]).get(), 2);
+		assert_eq!(execution_cache.with_label_values(&["miss"]).get(), 1);
+	}
+
+	#[test]
+	fn differing_outputs_of_a_differential_execution_are_non_deterministic() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = Arc::new(PoV { block_data: BlockData(vec![1; 32]) });
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let outputs = |hrmp_watermark| -> Result<WasmValidationResult, ValidationError> {
+			Ok(WasmValidationResult {
+				head_data: head_data.clone(),
+				new_validation_code: None,
+				upward_messages: Vec::new(),
+				horizontal_messages: Vec::new(),
+				processed_downward_messages: 0,
+				hrmp_watermark,
+			})
+		};
+
+		let options = ExhaustiveOptions { differential_execution: true, ..Default::default() };
+		let validate = |results: Vec<Result<WasmValidationResult, ValidationError>>| {
+			let executions = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+			let backend = SequencedBackend { results: results.into(), executions: executions.clone() };
+			let v = executor::block_on(validate_candidate_exhaustive(
+				backend,
+				validation_data.clone(),
+				validation_code.clone(),
+				descriptor.clone(),
+				pov.clone(),
+				&options,
+				&Default::default(),
+			));
+			(v, executions.load(std::sync::atomic::Ordering::SeqCst))
+		};
+
+		let (v, executions) = validate(vec![outputs(0), outputs(0)]);
+		assert_matches!(v, Ok(Ok(ValidationResult::Valid(_, _, _))));
+		assert_eq!(executions, 2);
+
+		let (v, executions) = validate(vec![outputs(0), outputs(1)]);
+		assert_matches!(v, Ok(Ok(ValidationResult::Invalid(InvalidCandidate::NonDeterministic))));
+		assert_eq!(executions, 2);
+
+		// A candidate found invalid isn't executed again.
+		let trap = Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::WorkerReportedError(
+			"trap".into(),
+		)));
+		let (v, executions) = validate(vec![trap]);
+		assert_matches!(v, Ok(Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError(_)))));
+		assert_eq!(executions, 1);
+	}
+
+	#[test]
+	fn execution_cache_is_bounded_and_keeps_deterministic_outcomes_only() {
+		let outcome = |head: u8| -> Result<WasmValidationResult, ValidationError> {
+			Ok(WasmValidationResult {
+				head_data: HeadData(vec![head; 100]),
+				new_validation_code: None,
+				upward_messages: Vec::new(),
+				horizontal_messages: Vec::new(),
+				processed_downward_messages: 0,
+				hrmp_watermark: 0,
+			})
+		};
+		let key = |i: u8| (ValidationCode(vec![i]).hash(), Hash::repeat_byte(i), Hash::repeat_byte(0));
+
+		let size = outcome(0).unwrap().encoded_size();
+		let mut cache = ExecutionCache::new(2 * size);
+		cache.insert(key(1), outcome(1));
+		cache.insert(key(2), outcome(2));
+
+		// Using the first outcome makes the second one the least recently used.
+		assert!(cache.get(&key(1)).is_some());
+		cache.insert(key(3), outcome(3));
+		assert!(cache.get(&key(2)).is_none());
+		assert!(cache.get(&key(1)).is_some());
+		assert!(cache.get(&key(3)).is_some());
+		assert_eq!(cache.size, 2 * size);
+
+		// Outcomes depending on the circumstances of the execution aren't kept.
+		cache.insert(key(4), Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::HardTimeout)));
+		cache.insert(key(5), Err(ValidationError::InternalError("broken pipe".into())));
+		assert!(cache.get(&key(4)).is_none());
+		assert!(cache.get(&key(5)).is_none());
+
+		cache.insert(
+			key(6),
+			Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::WorkerReportedError("trap".into()))),
+		);
+		assert!(cache.get(&key(6)).is_some());
+		assert_eq!(cache.outcomes.back().unwrap().2, std::mem::size_of::<ValidationError>() + 4);
+	}
+
+	#[test]
+	fn execution_is_retried_after_ambiguous_worker_death() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let ambiguous_death = || Err(ValidationError::InvalidCandidate(
+			WasmInvalidCandidate::AmbigiousWorkerDeath,
+		));
+		let options = ExhaustiveOptions { ambiguous_worker_death_retries: 2, ..Default::default() };
+		let pov = Arc::new(pov);
+		let validate = |results: Vec<Result<WasmValidationResult, ValidationError>>| {
+			let executions = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+			let backend = SequencedBackend { results: results.into(), executions: executions.clone() };
+			let v = executor::block_on(validate_candidate_exhaustive(
+				backend,
+				validation_data.clone(),
+				validation_code.clone(),
+				descriptor.clone(),
+				pov.clone(),
+				&options,
+				&Default::default(),
+			));
+			(v, executions.load(std::sync::atomic::Ordering::SeqCst))
+		};
+
+		// A death followed by a successful execution.
+		let (v, executions) = validate(vec![ambiguous_death(), Ok(validation_result.clone())]);
+		assert_matches!(v, Ok(Ok(ValidationResult::Valid(_, _, _))));
+		assert_eq!(executions, 2);
+
+		// Only once all attempts died is the candidate invalid.
+		let (v, executions) = validate(vec![ambiguous_death(), ambiguous_death(), ambiguous_death()]);
+		assert_matches!(
+			v,
+			Ok(Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError(e)))) if e == "ambigious worker death"
+		);
+		assert_eq!(executions, 3);
+
+		// Other errors aren't retried.
+		let (v, executions) = validate(vec![Err(ValidationError::InvalidCandidate(
+			WasmInvalidCandidate::HardTimeout,
+		))]);
+		assert_matches!(v, Ok(Ok(ValidationResult::Invalid(InvalidCandidate::Timeout))));
+		assert_eq!(executions, 1);
+
+		// Every death is noted for the health of the host, whether retried or not.
+		let recent_deaths = executor::block_on(options.worker_deaths.lock()).recent(Instant::now());
+		assert_eq!(recent_deaths, 4);
+	}
+
+	#[test]
+	fn worker_deaths_are_forgotten_after_a_while() {
+		let start = Instant::now();
+		let mut deaths = WorkerDeaths::default();
+		deaths.note(start);
+		deaths.note(start + Duration::from_secs(60));
+
+		assert_eq!(deaths.recent(start + Duration::from_secs(60)), 2);
+		assert_eq!(deaths.recent(start + RECENT_WORKER_DEATHS_PERIOD + Duration::from_secs(1)), 1);
+		assert_eq!(deaths.recent(start + RECENT_WORKER_DEATHS_PERIOD + Duration::from_secs(61)), 0);
+	}
+
+	#[test]
+	fn requested_priority_is_forwarded_to_the_backend() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let pov = Arc::new(pov);
+		let defaults = ExhaustiveOptions::default();
+		for (options, expected) in vec![
+			(defaults.clone(), Priority::Normal),
+			(defaults.with_priority(PvfExecPriority::Critical), Priority::Critical),
+		] {
+			let (tx, rx) = oneshot::channel();
+			let backend = PriorityProbe { priority: Some(tx), result: Ok(validation_result.clone()) };
+
+			let v = executor::block_on(validate_candidate_exhaustive(
+				backend,
+				validation_data.clone(),
+				validation_code.clone(),
+				descriptor.clone(),
+				pov.clone(),
+				&options,
+				&Default::default(),
+			));
+			assert_matches!(v, Ok(Ok(ValidationResult::Valid(_, _, _))));
+			assert_eq!(executor::block_on(rx).unwrap(), expected);
+		}
+	}
+
+	#[test]
+	fn message_counts_of_valid_candidate_are_observed() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.para_id = 5.into();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: vec![vec![1], vec![2], vec![3]],
+			horizontal_messages: vec![
+				OutboundHrmpMessage { recipient: 6.into(), data: vec![4] },
+				OutboundHrmpMessage { recipient: 7.into(), data: vec![5] },
+			],
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+
+		let v = executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+			validation_data,
+			validation_code,
+			descriptor,
+			Arc::new(pov),
+			&Default::default(),
+			&metrics,
+		))
+		.unwrap();
+
+		assert_matches!(v, Ok(ValidationResult::Valid(_, _, _)));
+
+		let inner = metrics.0.as_ref().unwrap();
+		let upward = inner.upward_messages.with_label_values(&["5"]);
+		let horizontal = inner.horizontal_messages.with_label_values(&["5"]);
+		assert_eq!(upward.get_sample_count(), 1);
+		assert_eq!(upward.get_sample_sum(), 3.0);
+		assert_eq!(horizontal.get_sample_count(), 1);
+		assert_eq!(horizontal.get_sample_sum(), 2.0);
+	}
+
+	#[test]
+	fn completed_validation_appends_span_record() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.para_id = 5.into();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let dir = tempfile::tempdir().unwrap();
+		let span_log = SpanLogConfig { path: dir.path().join("spans.log"), max_file_size: 1 << 20 };
+
+		let v = executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+			validation_data,
+			validation_code,
+			descriptor,
+			Arc::new(pov),
+			&ExhaustiveOptions { span_log: Some(span_log.clone()), ..Default::default() },
+			&Default::default(),
+		))
+		.unwrap();
+		assert_matches!(v, Ok(ValidationResult::Valid(_, _, _)));
+
+		let log = std::fs::read_to_string(&span_log.path).unwrap();
+		let records = log.lines().collect::<Vec<_>>();
+		assert_eq!(records.len(), 1);
+
+		let fields = records[0]
+			.split(' ')
+			.map(|field| {
+				let mut kv = field.splitn(2, '=');
+				(kv.next().unwrap(), kv.next().unwrap())
+			})
+			.collect::<HashMap<_, _>>();
+
+		assert_eq!(fields["para_id"], "5");
+		assert_eq!(fields["outcome"], "valid");
+		for phase in &[
+			"basic_checks_us",
+			"code_decompression_us",
+			"pov_decompression_us",
+			"execution_us",
+			"total_us",
+		] {
+			assert!(fields[phase].parse::<u128>().is_ok(), "{} is not a duration", phase);
+		}
+
+		// The file is rotated once it exceeds its maximum size.
+		let span_log = SpanLogConfig { max_file_size: 1, ..span_log };
+		append_span_record(&span_log, "next").unwrap();
+		assert_eq!(std::fs::read_to_string(&span_log.path).unwrap(), "next\n");
+		assert_eq!(std::fs::read_to_string(dir.path().join("spans.log.1")).unwrap(), log);
+	}
+
+	#[test]
+	fn candidate_is_validated_from_resolved_inputs() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+		let candidate_hash = CandidateHash(Hash::repeat_byte(7));
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let (descriptor_tx, mut descriptor_rx) = mpsc::channel(1);
+		let (code_tx, mut code_rx) = mpsc::channel(1);
+		let (data_tx, mut data_rx) = mpsc::channel(1);
+		let (pov_tx, mut pov_rx) = mpsc::channel(1);
+		let mut resolvers = CandidateResolvers {
+			descriptor: descriptor_tx,
+			validation_code: code_tx,
+			persisted_validation_data: data_tx,
+			pov: pov_tx,
+		};
+
+		let validate_fut = async move {
+			let descriptor = resolve(&mut resolvers.descriptor, candidate_hash, "descriptor")
+				.await
+				.unwrap();
+
+			validate_from_resolvers(
+				MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+				candidate_hash,
+				descriptor,
+				resolvers,
+				None,
+				Instant::now(),
+				&Default::default(),
+				&mut Vec::new(),
+				&Default::default(),
+			).await
+		};
+
+		let resolve_fut = async move {
+			let (hash, tx) = descriptor_rx.next().await.unwrap();
+			assert_eq!(hash, candidate_hash);
+			let _ = tx.send(Some(descriptor));
+
+			let (hash, tx) = code_rx.next().await.unwrap();
+			assert_eq!(hash, candidate_hash);
+			let _ = tx.send(Some(validation_code));
+
+			let (hash, tx) = data_rx.next().await.unwrap();
+			assert_eq!(hash, candidate_hash);
+			let _ = tx.send(Some(validation_data));
+
+			let (hash, tx) = pov_rx.next().await.unwrap();
+			assert_eq!(hash, candidate_hash);
+			let _ = tx.send(Some(Arc::new(pov)));
+		};
+
+		let (v, ()) = executor::block_on(future::join(validate_fut, resolve_fut));
+		assert_matches!(v, Ok(Ok(ValidationResult::Valid(_, _, _))));
+	}
+
+	#[test]
+	fn processed_downward_messages_are_observed() {
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let commitments = CandidateCommitments {
+			processed_downward_messages: 7,
+			..Default::default()
+		};
+
+		metrics.on_commitments(5.into(), &commitments);
+
+		let processed = metrics.0.as_ref().unwrap().processed_downward_messages.with_label_values(&["5"]);
+		assert_eq!(processed.get_sample_count(), 1);
+		assert_eq!(processed.get_sample_sum(), 7.0);
+	}
+
+	#[test]
+	fn validate_expecting_reports_mismatches() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let inputs = ValidationInputs {
+			persisted_validation_data: validation_data,
+			validation_code,
+			descriptor,
+			pov: Arc::new(pov),
+		};
+		let validation_result = WasmValidationResult {
+			head_data: head_data.clone(),
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let r = executor::block_on(validate_expecting_with(
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result.clone())),
+			inputs.clone(),
+			ExpectedOutcome::Valid(Some(head_data.clone())),
+		));
+		assert_matches!(r, Ok(()));
+
+		let r = executor::block_on(validate_expecting_with(
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result.clone())),
+			inputs.clone(),
+			ExpectedOutcome::Invalid(None),
+		));
+		assert_matches!(r, Err(OutcomeMismatch::UnexpectedlyValid { outputs }) => {
+			assert_eq!(outputs.head_data, head_data);
+		});
+
+		let r = executor::block_on(validate_expecting_with(
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+			inputs.clone(),
+			ExpectedOutcome::Valid(Some(HeadData(vec![2, 2, 2]))),
+		));
+		assert_matches!(r, Err(OutcomeMismatch::HeadData { expected, actual }) => {
+			assert_eq!(expected, HeadData(vec![2, 2, 2]));
+			assert_eq!(actual, head_data);
+		});
+
+		let r = executor::block_on(validate_expecting_with(
+			MockValidatorBackend::with_hardcoded_result(
+				Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::HardTimeout)),
+			),
+			inputs,
+			ExpectedOutcome::Invalid(Some(InvalidCandidate::BadReturn)),
+		));
+		assert_matches!(
+			r,
+			Err(OutcomeMismatch::Reason {
+				expected: InvalidCandidate::BadReturn,
+				actual: InvalidCandidate::Timeout,
+			})
+		);
+	}
+
+	#[test]
+	fn admission_delay_is_observed() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.persisted_validation_data_hash = validation_data.hash();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let delay = Duration::from_millis(50);
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let test_fut = async move {
+			let options = ExhaustiveOptions::default();
+			let mut steps = Vec::new();
+			let (validate_fut, validate_result) = spawn_validate_from_chain_state(
+				ctx.sender(),
+				MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+					"irrelevant".into(),
+				))),
+				descriptor,
+				Arc::new(pov),
+				None,
+				None,
+				false,
+				false,
+				None,
+				false,
+				Instant::now(),
+				&options,
+				&mut steps,
+				&metrics,
+			).remote_handle();
+
+			let handle_fut = async {
+				// The request is held up while looking up the validation data.
+				futures_timer::Delay::new(delay).await;
+
+				answer_runtime_api_version(&mut ctx_handle, 1).await;
+
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, tx),
+					)) => {
+						let _ = tx.send(Ok(Some(validation_data.clone())));
+					}
+				);
+
+				// Both assumptions are checked at once, even though the first one matches.
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::TimedOut, _),
+					))
+				);
+
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::ValidationCode(_, OccupiedCoreAssumption::Included, tx)
+					)) => {
+						let _ = tx.send(Ok(Some(validation_code.clone())));
+					}
+				);
+			};
+
+			let (_, ()) = future::join(validate_fut, handle_fut).await;
+			assert_matches!(validate_result.await, Ok(Err(ValidationFailed::Other(_))));
+
+			let admission_delay = &metrics.0.as_ref().unwrap().admission_delay;
+			assert_eq!(admission_delay.get_sample_count(), 1);
+			assert!(admission_delay.get_sample_sum() >= delay.as_secs_f64());
+		};
+
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn fetch_to_done_gap_is_observed() {
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let gap = Duration::from_millis(50);
+		let fetched_at = Instant::now();
+
+		executor::block_on(futures_timer::Delay::new(gap));
+		metrics.on_fetch_to_done(fetched_at);
+
+		let fetch_to_done = &metrics.0.as_ref().unwrap().fetch_to_done;
+		assert_eq!(fetch_to_done.get_sample_count(), 1);
+		assert!(fetch_to_done.get_sample_sum() >= gap.as_secs_f64());
+	}
+
+	#[test]
+	fn streamed_pov_validates_like_buffered_pov() {
+		async fn answer_chain_state_requests(
+			ctx_handle: &mut test_helpers::TestSubsystemContextHandle<CandidateValidationMessage>,
+			validation_data: PersistedValidationData,
+			validation_code: ValidationCode,
+		) {
+			answer_runtime_api_version(ctx_handle, 1).await;
+
+			assert_matches!(
+				ctx_handle.recv().await,
 				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					rp,
-					RuntimeApiRequest::PersistedValidationData(
-						p,
-						OccupiedCoreAssumption::TimedOut,
-						tx
-					),
+					_,
+					RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, tx),
 				)) => {
-					assert_eq!(rp, relay_parent);
-					assert_eq!(p, para_id);
+					let _ = tx.send(Ok(Some(validation_data)));
+				}
+			);
+
+			// Both assumptions are checked at once, even though the first one matches.
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::TimedOut, _),
+				))
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::ValidationCode(_, OccupiedCoreAssumption::Included, tx)
+				)) => {
+					let _ = tx.send(Ok(Some(validation_code)));
+				}
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::CheckValidationOutputs(_, _, tx),
+				)) => {
+					let _ = tx.send(Ok(true));
+				}
+			);
+		}
+
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData((0..32).collect()) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.persisted_validation_data_hash = validation_data.hash();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = || WasmValidationResult {
+			head_data: head_data.clone(),
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let test_fut = async move {
+			let options = ExhaustiveOptions::default();
+			let metrics = Metrics::default();
+
+			let mut buffered_steps = Vec::new();
+			let (validate_fut, validate_result) = spawn_validate_from_chain_state(
+				ctx.sender(),
+				MockValidatorBackend::with_hardcoded_result(Ok(validation_result())),
+				descriptor.clone(),
+				Arc::new(pov.clone()),
+				None,
+				None,
+				false,
+				false,
+				None,
+				false,
+				Instant::now(),
+				&options,
+				&mut buffered_steps,
+				&metrics,
+			).remote_handle();
+
+			let handle_fut = answer_chain_state_requests(
+				&mut ctx_handle,
+				validation_data.clone(),
+				validation_code.clone(),
+			);
+			let (_, ()) = future::join(validate_fut, handle_fut).await;
+			let buffered = validate_result.await;
+
+			let (mut chunk_tx, chunk_rx) = mpsc::channel(1);
+			let feed_fut = async move {
+				for chunk in pov.block_data.0.chunks(8) {
+					chunk_tx.send(chunk.to_vec()).await.unwrap();
+				}
+			};
+
+			let mut streamed_steps = Vec::new();
+			let (validate_fut, validate_result) = validate_from_chain_state_with(
+				ctx.sender(),
+				MockValidatorBackend::with_hardcoded_result(Ok(validation_result())),
+				descriptor,
+				assemble_pov(chunk_rx, POV_BOMB_LIMIT, None),
+				None,
+				None,
+				false,
+				false,
+				None,
+				false,
+				Instant::now(),
+				&options,
+				&mut streamed_steps,
+				&metrics,
+			).remote_handle();
+
+			let handle_fut = answer_chain_state_requests(&mut ctx_handle, validation_data, validation_code);
+			let (_, (), ()) = future::join3(validate_fut, handle_fut, feed_fut).await;
+			let streamed = validate_result.await;
+
+			assert_matches!(
+				(buffered, streamed),
+				(
+					Ok(Ok(ValidationResult::Valid(buffered_outputs, buffered_data, _))),
+					Ok(Ok(ValidationResult::Valid(streamed_outputs, streamed_data, _))),
+				) => {
+					assert_eq!(buffered_outputs, streamed_outputs);
+					assert_eq!(buffered_data, streamed_data);
+				}
+			);
+			assert_eq!(buffered_steps, streamed_steps);
+		};
+
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn streamed_pov_larger_than_the_limit_is_invalid() {
+		let (mut chunk_tx, chunk_rx) = mpsc::channel(4);
+		for _ in 0..3 {
+			chunk_tx.try_send(vec![1; 8]).unwrap();
+		}
+		drop(chunk_tx);
+
+		assert_matches!(
+			executor::block_on(assemble_pov(chunk_rx, 16, None)),
+			Err(Ok(ValidationResult::Invalid(InvalidCandidate::ParamsTooLarge(24))))
+		);
+	}
 
-					let _ = tx.send(Ok(Some(validation_data.clone())));
-				}
-			);
+	#[test]
+	fn explain_report_covers_each_step_of_successful_validation() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
 
-			assert_matches!(
-				ctx_handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					rp,
-					RuntimeApiRequest::ValidationCode(p, OccupiedCoreAssumption::TimedOut, tx)
-				)) => {
-					assert_eq!(rp, relay_parent);
-					assert_eq!(p, para_id);
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
 
-					let _ = tx.send(Ok(Some(validation_code.clone())));
-				}
-			);
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.persisted_validation_data_hash = validation_data.hash();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
 
-			assert_matches!(check_result.await.unwrap(), AssumptionCheckOutcome::Matches(o, v) => {
-				assert_eq!(o, validation_data);
-				assert_eq!(v, validation_code);
-			});
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let test_fut = async move {
+			let mut steps = Vec::new();
+			let options = ExhaustiveOptions::default();
+			let metrics = Metrics::default();
+			let fingerprint = BlakeTwo256::hash_of(&validation_result);
+			let (validate_fut, validate_result) = spawn_validate_from_chain_state(
+				ctx.sender(),
+				MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+				descriptor,
+				Arc::new(pov),
+				None,
+				None,
+				false,
+				false,
+				None,
+				false,
+				Instant::now(),
+				&options,
+				&mut steps,
+				&metrics,
+			).remote_handle();
+
+			let handle_fut = async {
+				answer_runtime_api_version(&mut ctx_handle, 1).await;
+
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, tx),
+					)) => {
+						let _ = tx.send(Ok(Some(validation_data.clone())));
+					}
+				);
+
+				// Both assumptions are checked at once, even though the first one matches.
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::TimedOut, _),
+					))
+				);
+
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::ValidationCode(_, OccupiedCoreAssumption::Included, tx)
+					)) => {
+						let _ = tx.send(Ok(Some(validation_code.clone())));
+					}
+				);
+
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::CheckValidationOutputs(_, _, tx),
+					)) => {
+						let _ = tx.send(Ok(true));
+					}
+				);
+			};
+
+			let (_, ()) = future::join(validate_fut, handle_fut).await;
+			assert_matches!(validate_result.await, Ok(Ok(ValidationResult::Valid(_, _, _))));
+
+			assert_eq!(steps, vec![
+				ValidationStep::AssumptionMatched(OccupiedCoreAssumption::Included),
+				ValidationStep::ValidationDataFound(validation_data),
+				ValidationStep::BasicChecks(None),
+				ValidationStep::CodeDecompressed { compressed_size: 16, decompressed_size: Some(16) },
+				ValidationStep::PoVDecompressed { compressed_size: 32, decompressed_size: Some(32) },
+				ValidationStep::Executed(None),
+				ValidationStep::Fingerprinted(fingerprint),
+				ValidationStep::OutputsChecked(true),
+			]);
 		};
 
-		let test_fut = future::join(test_fut, check_fut);
 		executor::block_on(test_fut);
 	}
 
 	#[test]
-	fn check_is_bad_request_if_no_validation_data() {
-		let validation_data: PersistedValidationData = Default::default();
-		let persisted_validation_data_hash = validation_data.hash();
+	fn output_checks_are_deferred_to_block_boundary() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
 		let relay_parent = [2; 32].into();
 		let para_id = 5.into();
 
-		let mut candidate = CandidateDescriptor::default();
-		candidate.relay_parent = relay_parent;
-		candidate.persisted_validation_data_hash = persisted_validation_data_hash;
-		candidate.para_id = para_id;
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.relay_parent = relay_parent;
+		descriptor.para_id = para_id;
+		descriptor.persisted_validation_data_hash = validation_data.hash();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
 
 		let pool = TaskExecutor::new();
 		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
 
-		let (check_fut, check_result) = check_assumption_validation_data(
-			&mut ctx,
-			&candidate,
-			OccupiedCoreAssumption::Included,
-		).remote_handle();
+		let test_fut = async move {
+			let options = ExhaustiveOptions::default();
+			let mut steps = Vec::new();
+			let metrics = Metrics::default();
+			let (validate_fut, validate_result) = spawn_validate_from_chain_state(
+				ctx.sender(),
+				MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+				descriptor,
+				Arc::new(pov),
+				None,
+				None,
+				false,
+				false,
+				None,
+				true,
+				Instant::now(),
+				&options,
+				&mut steps,
+				&metrics,
+			).remote_handle();
+
+			let handle_fut = async {
+				answer_runtime_api_version(&mut ctx_handle, 1).await;
+
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::Included, tx),
+					)) => {
+						let _ = tx.send(Ok(Some(validation_data.clone())));
+					}
+				);
+
+				// Both assumptions are checked at once, even though the first one matches.
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::PersistedValidationData(_, OccupiedCoreAssumption::TimedOut, _),
+					))
+				);
+
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::ValidationCode(_, OccupiedCoreAssumption::Included, tx)
+					)) => {
+						let _ = tx.send(Ok(Some(validation_code.clone())));
+					}
+				);
+			};
+
+			let (_, ()) = future::join(validate_fut, handle_fut).await;
+
+			// The result is returned without checking the outputs.
+			let outputs = assert_matches!(
+				validate_result.await,
+				Ok(Ok(ValidationResult::Valid(outputs, _, _))) => outputs
+			);
+
+			let (callback, verdict) = oneshot::channel();
+			let checks = vec![DeferredOutputCheck { relay_parent, para_id, outputs, callback }];
+			let (resolve_fut, resolve_result) = resolve_deferred_output_checks(ctx.sender(), checks)
+				.remote_handle();
+
+			let handle_fut = async {
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						rp,
+						RuntimeApiRequest::CheckValidationOutputs(p, _, tx),
+					)) => {
+						assert_eq!(rp, relay_parent);
+						assert_eq!(p, para_id);
+						let _ = tx.send(Ok(false));
+					}
+				);
+			};
+
+			let (_, ()) = future::join(resolve_fut, handle_fut).await;
+			assert_matches!(resolve_result.await, Ok(()));
+			assert_matches!(verdict.await, Ok(Ok(false)));
+		};
+
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn outputs_of_exhaustive_validations_are_checked_on_request() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 16]);
+		let relay_parent = [2; 32].into();
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.relay_parent = relay_parent;
+		descriptor.para_id = 5.into();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		// Exhaustive callers may not have a live relay-parent, so outputs aren't checked by default.
+		assert!(!polkadot_subsystem::messages::ValidationOptions::default().check_outputs);
+
+		let valid = executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+			validation_data,
+			validation_code,
+			descriptor.clone(),
+			Arc::new(pov),
+			&Default::default(),
+			&Metrics::default(),
+		)).unwrap();
+		assert_matches!(valid, Ok(ValidationResult::Valid(_, _, _)));
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
 
 		let test_fut = async move {
-			assert_matches!(
-				ctx_handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					rp,
-					RuntimeApiRequest::PersistedValidationData(
-						p,
-						OccupiedCoreAssumption::Included,
-						tx
-					),
-				)) => {
-					assert_eq!(rp, relay_parent);
-					assert_eq!(p, para_id);
+			for accepted in vec![true, false] {
+				let mut steps = Vec::new();
+				let (check_fut, check_result) =
+					check_validation_outputs(ctx.sender(), &descriptor, valid.clone(), &mut steps)
+						.remote_handle();
+
+				let handle_fut = async {
+					assert_matches!(
+						ctx_handle.recv().await,
+						AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+							rp,
+							RuntimeApiRequest::CheckValidationOutputs(p, _, tx),
+						)) => {
+							assert_eq!(rp, relay_parent);
+							assert_eq!(p, descriptor.para_id);
+							let _ = tx.send(Ok(accepted));
+						}
+					);
+				};
 
-					let _ = tx.send(Ok(None));
+				let (_, ()) = future::join(check_fut, handle_fut).await;
+				if accepted {
+					assert_matches!(check_result.await, Ok(Ok(ValidationResult::Valid(_, _, _))));
+				} else {
+					assert_matches!(
+						check_result.await,
+						Ok(Ok(ValidationResult::Invalid(InvalidCandidate::InvalidOutputs)))
+					);
 				}
+				assert_eq!(steps, vec![ValidationStep::OutputsChecked(accepted)]);
+			}
+
+			// Other results have no outputs to check.
+			let invalid = Ok(ValidationResult::Invalid(InvalidCandidate::BadReturn));
+			let mut steps = Vec::new();
+			assert_matches!(
+				check_validation_outputs(ctx.sender(), &descriptor, invalid, &mut steps).await,
+				Ok(Ok(ValidationResult::Invalid(InvalidCandidate::BadReturn)))
 			);
+			assert!(steps.is_empty());
+		};
 
-			assert_matches!(check_result.await.unwrap(), AssumptionCheckOutcome::BadRequest);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn relay_parent_in_the_future_is_bad_parent() {
+		let validation_data = PersistedValidationData {
+			max_pov_size: 1024,
+			relay_parent_number: 11,
+			..Default::default()
 		};
 
-		let test_fut = future::join(test_fut, check_fut);
-		executor::block_on(test_fut);
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let v = executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+				"must not be called".into(),
+			))),
+			validation_data,
+			validation_code,
+			descriptor,
+			Arc::new(pov),
+			&ExhaustiveOptions { best_known_number: Some(10), ..Default::default() },
+			&Default::default(),
+		))
+		.unwrap();
+
+		assert_matches!(v, Ok(ValidationResult::Invalid(InvalidCandidate::BadParent)));
+	}
+
+	#[test]
+	fn candidate_validation_bad_return_is_invalid() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let check = perform_basic_checks(
+			&descriptor,
+			validation_data.max_pov_size,
+			&pov,
+			&validation_code,
+		);
+		assert!(check.is_ok());
+
+		let v = executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(
+				Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::AmbigiousWorkerDeath))
+			),
+			validation_data,
+			validation_code,
+			descriptor,
+			Arc::new(pov),
+			&Default::default(),
+			&Default::default(),
+		))
+		.unwrap()
+		.unwrap();
+
+		assert_matches!(v, ValidationResult::Invalid(InvalidCandidate::ExecutionError(_)));
+	}
+
+	#[test]
+	fn candidate_validation_timeout_is_internal_error() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let check = perform_basic_checks(
+			&descriptor,
+			validation_data.max_pov_size,
+			&pov,
+			&validation_code,
+		);
+		assert!(check.is_ok());
+
+		let v = executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(
+				Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::HardTimeout)),
+			),
+			validation_data,
+			validation_code,
+			descriptor,
+			Arc::new(pov),
+			&Default::default(),
+			&Default::default(),
+		))
+		.unwrap();
+
+		assert_matches!(v, Ok(ValidationResult::Invalid(InvalidCandidate::Timeout)));
+	}
+
+	#[test]
+	fn candidate_validation_memory_limit_breach_is_an_internal_failure() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let v = executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(
+				Err(ValidationError::InternalError("the execution exceeded the memory limit".into())),
+			),
+			validation_data,
+			validation_code,
+			descriptor,
+			Arc::new(pov),
+			&Default::default(),
+			&Default::default(),
+		))
+		.unwrap();
+
+		// The breach isn't deterministic, so it mustn't render the candidate invalid.
+		assert_matches!(v, Err(ValidationFailed::Other(_)));
 	}
 
 	#[test]
-	fn check_is_bad_request_if_no_validation_code() {
-		let validation_data: PersistedValidationData = Default::default();
-		let persisted_validation_data_hash = validation_data.hash();
-		let relay_parent = [2; 32].into();
-		let para_id = 5.into();
-
-		let mut candidate = CandidateDescriptor::default();
-		candidate.relay_parent = relay_parent;
-		candidate.persisted_validation_data_hash = persisted_validation_data_hash;
-		candidate.para_id = para_id;
+	fn identical_executions_have_identical_fingerprints() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
 
-		let pool = TaskExecutor::new();
-		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
 
-		let (check_fut, check_result) = check_assumption_validation_data(
-			&mut ctx,
-			&candidate,
-			OccupiedCoreAssumption::TimedOut,
-		).remote_handle();
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
 
-		let test_fut = async move {
-			assert_matches!(
-				ctx_handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					rp,
-					RuntimeApiRequest::PersistedValidationData(
-						p,
-						OccupiedCoreAssumption::TimedOut,
-						tx
-					),
-				)) => {
-					assert_eq!(rp, relay_parent);
-					assert_eq!(p, para_id);
+		let validation_result = |head_data: HeadData| WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
 
-					let _ = tx.send(Ok(Some(validation_data.clone())));
-				}
-			);
+		let fingerprint = |result: WasmValidationResult| {
+			let mut steps = Vec::new();
+			let _ = executor::block_on(validate_candidate_exhaustive_explained(
+				MockValidatorBackend::with_hardcoded_result(Ok(result)),
+				validation_data.clone(),
+				validation_code.clone(),
+				descriptor.clone(),
+				Arc::new(pov.clone()),
+				&Default::default(),
+				&mut steps,
+				&Default::default(),
+			))
+			.unwrap();
 
-			assert_matches!(
-				ctx_handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					rp,
-					RuntimeApiRequest::ValidationCode(p, OccupiedCoreAssumption::TimedOut, tx)
-				)) => {
-					assert_eq!(rp, relay_parent);
-					assert_eq!(p, para_id);
+			execution_fingerprint(&steps)
+		};
 
-					let _ = tx.send(Ok(None));
-				}
-			);
+		let first = fingerprint(validation_result(HeadData(vec![1, 1, 1])));
+		let second = fingerprint(validation_result(HeadData(vec![1, 1, 1])));
+		let diverging = fingerprint(validation_result(HeadData(vec![2, 2, 2])));
 
-			assert_matches!(check_result.await.unwrap(), AssumptionCheckOutcome::BadRequest);
-		};
+		assert!(first.is_some());
+		assert_eq!(first, second);
+		assert_ne!(first, diverging);
 
-		let test_fut = future::join(test_fut, check_fut);
-		executor::block_on(test_fut);
+		// Nothing is surfaced for a failed execution.
+		let mut steps = Vec::new();
+		let _ = executor::block_on(validate_candidate_exhaustive_explained(
+			MockValidatorBackend::with_hardcoded_result(
+				Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::HardTimeout)),
+			),
+			validation_data,
+			validation_code,
+			descriptor,
+			Arc::new(pov),
+			&Default::default(),
+			&mut steps,
+			&Default::default(),
+		))
+		.unwrap();
+		assert_eq!(execution_fingerprint(&steps), None);
 	}
 
 	#[test]
-	fn check_does_not_match() {
-		let validation_data: PersistedValidationData = Default::default();
-		let relay_parent = [2; 32].into();
-		let para_id = 5.into();
-
-		let mut candidate = CandidateDescriptor::default();
-		candidate.relay_parent = relay_parent;
-		candidate.persisted_validation_data_hash = [3; 32].into();
-		candidate.para_id = para_id;
+	fn candidate_calling_disabled_host_function_is_invalid() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
 
-		let pool = TaskExecutor::new();
-		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
 
-		let (check_fut, check_result) = check_assumption_validation_data(
-			&mut ctx,
-			&candidate,
-			OccupiedCoreAssumption::Included,
-		).remote_handle();
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
 
-		let test_fut = async move {
-			assert_matches!(
-				ctx_handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					rp,
-					RuntimeApiRequest::PersistedValidationData(
-						p,
-						OccupiedCoreAssumption::Included,
-						tx
-					),
-				)) => {
-					assert_eq!(rp, relay_parent);
-					assert_eq!(p, para_id);
+		// This is how the worker reports a call to a host function withheld from the PVF.
+		let error = "execute: call to a missing function ext_logging_log_version_1";
+		let v = executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(
+				Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::WorkerReportedError(
+					error.into(),
+				))),
+			),
+			validation_data,
+			validation_code,
+			descriptor,
+			Arc::new(pov),
+			&Default::default(),
+			&Default::default(),
+		))
+		.unwrap();
 
-					let _ = tx.send(Ok(Some(validation_data.clone())));
-				}
-			);
+		assert_matches!(
+			v,
+			Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError(e))) if e == error
+		);
+	}
 
-			assert_matches!(check_result.await.unwrap(), AssumptionCheckOutcome::DoesNotMatch);
-		};
+	#[test]
+	fn oversized_pov_is_rejected_without_hashing() {
+		let pov = PoV { block_data: BlockData(vec![1; 2048]) };
+		let validation_code = ValidationCode(vec![2; 16]);
 
-		let test_fut = future::join(test_fut, check_fut);
-		executor::block_on(test_fut);
+		let check = perform_basic_checks_with(
+			&CandidateDescriptor::default(),
+			1024,
+			&pov,
+			&validation_code,
+			|_| panic!("the PoV must not be hashed"),
+			|_| panic!("the validation code must not be hashed"),
+		);
+		assert_matches!(check, Err(InvalidCandidate::ParamsTooLarge(_)));
 	}
 
-	struct MockValidatorBackend {
-		result: Result<WasmValidationResult, ValidationError>,
-	}
+	#[test]
+	fn smaller_blob_is_hashed_first() {
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 64]);
 
-	impl MockValidatorBackend {
-		fn with_hardcoded_result(result: Result<WasmValidationResult, ValidationError>) -> Self {
-			Self {
-				result,
-			}
-		}
-	}
+		// Both hashes mismatch, so the first check to run determines the error.
+		let check = perform_basic_checks_with(
+			&CandidateDescriptor::default(),
+			1024,
+			&pov,
+			&validation_code,
+			|pov| pov.hash(),
+			|_| panic!("the larger blob must not be hashed"),
+		);
+		assert_matches!(check, Err(InvalidCandidate::PoVHashMismatch));
 
-	#[async_trait]
-	impl ValidationBackend for MockValidatorBackend {
-		async fn validate_candidate(
-			&mut self,
-			_raw_validation_code: Vec<u8>,
-			_params: ValidationParams
-		) -> Result<WasmValidationResult, ValidationError> {
-			self.result.clone()
-		}
+		let validation_code = ValidationCode(vec![2; 16]);
+		let check = perform_basic_checks_with(
+			&CandidateDescriptor::default(),
+			1024,
+			&pov,
+			&validation_code,
+			|_| panic!("the larger blob must not be hashed"),
+			|code| code.hash(),
+		);
+		assert_matches!(check, Err(InvalidCandidate::CodeHashMismatch));
 	}
 
 	#[test]
-	fn candidate_validation_ok_is_ok() {
+	fn candidate_validation_code_mismatch_is_invalid() {
 		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
 
 		let pov = PoV { block_data: BlockData(vec![1; 32]) };
-		let head_data = HeadData(vec![1, 1, 1]);
 		let validation_code = ValidationCode(vec![2; 16]);
 
 		let mut descriptor = CandidateDescriptor::default();
 		descriptor.pov_hash = pov.hash();
-		descriptor.para_head = head_data.hash();
-		descriptor.validation_code_hash = validation_code.hash();
+		descriptor.validation_code_hash = ValidationCode(vec![1; 16]).hash();
 		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
 
 		let check = perform_basic_checks(
@@ -930,11 +10659,91 @@ mod tests {
 			&pov,
 			&validation_code,
 		);
-		assert!(check.is_ok());
+		assert_matches!(check, Err(InvalidCandidate::CodeHashMismatch));
+
+		let v = executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(
+				Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::HardTimeout)),
+			),
+			validation_data,
+			validation_code,
+			descriptor,
+			Arc::new(pov),
+			&Default::default(),
+			&Default::default(),
+		))
+		.unwrap()
+		.unwrap();
+
+		assert_matches!(v, ValidationResult::Invalid(InvalidCandidate::CodeHashMismatch));
+	}
+
+	#[test]
+	fn compressed_code_works() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+
+		let raw_code = vec![2u8; 16];
+		let validation_code = sp_maybe_compressed_blob::compress(
+			&raw_code,
+			VALIDATION_CODE_BOMB_LIMIT,
+		)
+			.map(ValidationCode)
+			.unwrap();
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let v = executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
+			validation_data,
+			validation_code,
+			descriptor,
+			Arc::new(pov),
+			&Default::default(),
+			&Default::default(),
+		))
+		.unwrap();
+
+		assert_matches!(v, Ok(ValidationResult::Valid(_, _, _)));
+	}
+
+	#[test]
+	fn code_decompression_failure_is_invalid() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+
+		let raw_code = vec![2u8; VALIDATION_CODE_BOMB_LIMIT + 1];
+		let validation_code = sp_maybe_compressed_blob::compress(
+			&raw_code,
+			VALIDATION_CODE_BOMB_LIMIT + 1,
+		)
+			.map(ValidationCode)
+			.unwrap();
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
 
 		let validation_result = WasmValidationResult {
 			head_data,
-			new_validation_code: Some(vec![2, 2, 2].into()),
+			new_validation_code: None,
 			upward_messages: Vec::new(),
 			horizontal_messages: Vec::new(),
 			processed_downward_messages: 0,
@@ -943,146 +10752,398 @@ mod tests {
 
 		let v = executor::block_on(validate_candidate_exhaustive(
 			MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
-			validation_data.clone(),
+			validation_data,
 			validation_code,
 			descriptor,
 			Arc::new(pov),
 			&Default::default(),
+			&Default::default(),
 		))
-		.unwrap()
 		.unwrap();
 
-		assert_matches!(v, ValidationResult::Valid(outputs, used_validation_data) => {
-			assert_eq!(outputs.head_data, HeadData(vec![1, 1, 1]));
-			assert_eq!(outputs.upward_messages, Vec::<UpwardMessage>::new());
-			assert_eq!(outputs.horizontal_messages, Vec::new());
-			assert_eq!(outputs.new_validation_code, Some(vec![2, 2, 2].into()));
-			assert_eq!(outputs.hrmp_watermark, 0);
-			assert_eq!(used_validation_data, validation_data);
-		});
+		assert_matches!(
+			v,
+			Ok(ValidationResult::Invalid(InvalidCandidate::CodeDecompressionFailure))
+		);
 	}
 
 	#[test]
-	fn candidate_validation_bad_return_is_invalid() {
+	fn broken_code_matching_the_committed_hash_is_flagged() {
 		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
-
 		let pov = PoV { block_data: BlockData(vec![1; 32]) };
-		let validation_code = ValidationCode(vec![2; 16]);
+
+		// A compressed blob cut short: it carries the compression prefix, but its payload is broken.
+		let compressed = sp_maybe_compressed_blob::compress(&[2u8; 1024], VALIDATION_CODE_BOMB_LIMIT)
+			.unwrap();
+		let validation_code = ValidationCode(compressed[..compressed.len() - 4].to_vec());
 
 		let mut descriptor = CandidateDescriptor::default();
 		descriptor.pov_hash = pov.hash();
 		descriptor.validation_code_hash = validation_code.hash();
 		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
 
-		let check = perform_basic_checks(
-			&descriptor,
-			validation_data.max_pov_size,
-			&pov,
-			&validation_code,
-		);
-		assert!(check.is_ok());
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
 
 		let v = executor::block_on(validate_candidate_exhaustive(
-			MockValidatorBackend::with_hardcoded_result(
-				Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::AmbigiousWorkerDeath))
-			),
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+				"must not be called".into(),
+			))),
 			validation_data,
 			validation_code,
 			descriptor,
 			Arc::new(pov),
 			&Default::default(),
+			&metrics,
 		))
-		.unwrap()
 		.unwrap();
 
-		assert_matches!(v, ValidationResult::Invalid(InvalidCandidate::ExecutionError(_)));
+		assert_matches!(
+			v,
+			Ok(ValidationResult::Invalid(InvalidCandidate::CodeDecompressionFailure))
+		);
+		assert_eq!(
+			metrics.0.as_ref().unwrap().code_decompression_failures_with_matching_hash.get(),
+			1,
+		);
 	}
 
 	#[test]
-	fn candidate_validation_timeout_is_internal_error() {
+	fn decompressed_code_is_checked_against_the_known_hash() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+
+		let raw_code = vec![2u8; 1024];
+		let validation_code = sp_maybe_compressed_blob::compress(&raw_code, VALIDATION_CODE_BOMB_LIMIT)
+			.map(ValidationCode)
+			.unwrap();
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let validate = |decompressed_code_hash| executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result.clone())),
+			validation_data.clone(),
+			validation_code.clone(),
+			descriptor.clone(),
+			Arc::new(pov.clone()),
+			&ExhaustiveOptions::default().with_decompressed_code_hash(decompressed_code_hash),
+			&Metrics::default(),
+		)).unwrap();
+
+		assert_matches!(validate(None), Ok(ValidationResult::Valid(_, _, _)));
+		assert_matches!(
+			validate(Some(BlakeTwo256::hash(&raw_code))),
+			Ok(ValidationResult::Valid(_, _, _))
+		);
+		// The compressed code is the one committed to, so the candidate isn't blamed for a mismatch.
+		assert_matches!(
+			validate(Some(BlakeTwo256::hash(&validation_code.0))),
+			Err(ValidationFailed::Other(e)) if e == "decompressed validation code hash mismatch"
+		);
+	}
+
+	#[test]
+	fn prepared_artifact_is_executed_by_hash() {
+		/// A backend with the code of the given hash prepared, counting the executions from code.
+		struct PreparedProbe {
+			prepared: ValidationCodeHash,
+			result: WasmValidationResult,
+			executed_by_hash: Vec<ValidationCodeHash>,
+			executed_by_code: usize,
+		}
+
+		#[async_trait]
+		impl ValidationBackend for PreparedProbe {
+			async fn validate_candidate(
+				&mut self,
+				_raw_validation_code: Vec<u8>,
+				_params: ValidationParams,
+				_priority: Priority,
+			) -> Result<WasmValidationResult, ValidationError> {
+				self.executed_by_code += 1;
+				Ok(self.result.clone())
+			}
+
+			async fn precheck_pvf(&mut self, _raw_validation_code: Vec<u8>) -> Result<(), PrepareError> {
+				Ok(())
+			}
+
+			async fn validate_candidate_prepared(
+				&mut self,
+				code_hash: ValidationCodeHash,
+				_params: ValidationParams,
+				_priority: Priority,
+			) -> Option<Result<WasmValidationResult, ValidationError>> {
+				self.executed_by_hash.push(code_hash);
+				if code_hash == self.prepared {
+					Some(Ok(self.result.clone()))
+				} else {
+					None
+				}
+			}
+		}
+
 		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+
+		let raw_code = vec![2u8; 1024];
+		let validation_code = sp_maybe_compressed_blob::compress(&raw_code, VALIDATION_CODE_BOMB_LIMIT)
+			.map(ValidationCode)
+			.unwrap();
+		let artifact_hash = ValidationCodeHash::from(BlakeTwo256::hash(&raw_code));
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let mut backend = PreparedProbe {
+			prepared: artifact_hash,
+			result: WasmValidationResult {
+				head_data,
+				new_validation_code: None,
+				upward_messages: Vec::new(),
+				horizontal_messages: Vec::new(),
+				processed_downward_messages: 0,
+				hrmp_watermark: 0,
+			},
+			executed_by_hash: Vec::new(),
+			executed_by_code: 0,
+		};
+
+		let validate = |backend: &mut PreparedProbe, prepared_artifact| executor::block_on(validate_candidate_exhaustive(
+			backend,
+			validation_data.clone(),
+			validation_code.clone(),
+			descriptor.clone(),
+			Arc::new(pov.clone()),
+			&ExhaustiveOptions::default().with_prepared_artifact(prepared_artifact),
+			&Metrics::default(),
+		)).unwrap();
+
+		assert_matches!(validate(&mut backend, Some(artifact_hash)), Ok(ValidationResult::Valid(_, _, _)));
+		assert_eq!(backend.executed_by_hash, vec![artifact_hash]);
+		assert_eq!(backend.executed_by_code, 0);
+
+		// The artifact being gone, the PVF is executed from its code.
+		backend.prepared = ValidationCodeHash::from(Hash::repeat_byte(1));
+		assert_matches!(validate(&mut backend, Some(artifact_hash)), Ok(ValidationResult::Valid(_, _, _)));
+		assert_eq!(backend.executed_by_hash, vec![artifact_hash, artifact_hash]);
+		assert_eq!(backend.executed_by_code, 1);
+
+		// An artifact of other code than the candidate's is never executed.
+		let other_hash = ValidationCodeHash::from(Hash::repeat_byte(2));
+		backend.prepared = other_hash;
+		assert_matches!(validate(&mut backend, Some(other_hash)), Ok(ValidationResult::Valid(_, _, _)));
+		assert_eq!(backend.executed_by_hash, vec![artifact_hash, artifact_hash]);
+		assert_eq!(backend.executed_by_code, 2);
+	}
 
+	#[test]
+	fn seeded_executions_are_reproducible() {
+		/// A backend whose PVF sends the seed it's executed with upwards, if any.
+		struct SeedEcho {
+			head_data: HeadData,
+		}
+
+		impl SeedEcho {
+			fn outputs(&self, upward_messages: Vec<Vec<u8>>) -> WasmValidationResult {
+				WasmValidationResult {
+					head_data: self.head_data.clone(),
+					new_validation_code: None,
+					upward_messages,
+					horizontal_messages: Vec::new(),
+					processed_downward_messages: 0,
+					hrmp_watermark: 0,
+				}
+			}
+		}
+
+		#[async_trait]
+		impl ValidationBackend for SeedEcho {
+			async fn validate_candidate(
+				&mut self,
+				_raw_validation_code: Vec<u8>,
+				_params: ValidationParams,
+				_priority: Priority,
+			) -> Result<WasmValidationResult, ValidationError> {
+				Ok(self.outputs(Vec::new()))
+			}
+
+			async fn precheck_pvf(&mut self, _raw_validation_code: Vec<u8>) -> Result<(), PrepareError> {
+				Ok(())
+			}
+
+			async fn validate_candidate_seeded(
+				&mut self,
+				_raw_validation_code: Vec<u8>,
+				_params: ValidationParams,
+				_priority: Priority,
+				seed: [u8; 32],
+			) -> Result<WasmValidationResult, ValidationError> {
+				Ok(self.outputs(vec![seed.to_vec()]))
+			}
+		}
+
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
 		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
 		let validation_code = ValidationCode(vec![2; 16]);
 
 		let mut descriptor = CandidateDescriptor::default();
 		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
 		descriptor.validation_code_hash = validation_code.hash();
 		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
 
-		let check = perform_basic_checks(
-			&descriptor,
-			validation_data.max_pov_size,
-			&pov,
-			&validation_code,
-		);
-		assert!(check.is_ok());
+		let mut backend = SeedEcho { head_data };
+		// Seeded executions must bypass the cache, or the first outcome would be served to all.
+		let options = ExhaustiveOptions {
+			execution_cache: Some(Arc::new(Mutex::new(ExecutionCache::new(1 << 20)))),
+			..Default::default()
+		};
+
+		let mut validate = |execution_seed| {
+			let v = executor::block_on(validate_candidate_exhaustive(
+				&mut backend,
+				validation_data.clone(),
+				validation_code.clone(),
+				descriptor.clone(),
+				Arc::new(pov.clone()),
+				&options.clone().with_execution_seed(execution_seed),
+				&Metrics::default(),
+			)).unwrap();
+
+			match v {
+				Ok(ValidationResult::Valid(outputs, _, _)) => outputs.upward_messages,
+				v => panic!("unexpected outcome of a seeded validation: {:?}", v),
+			}
+		};
+
+		let unseeded = validate(None);
+		let seeded = validate(Some([1; 32]));
+		assert_eq!(seeded, vec![vec![1; 32]]);
+		assert_eq!(validate(Some([1; 32])), seeded);
+		assert_ne!(validate(Some([2; 32])), seeded);
+		assert_eq!(validate(None), unseeded);
+		assert_ne!(unseeded, seeded);
+	}
+
+	#[test]
+	fn seeded_executions_fail_on_backends_without_seeding() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let validation_code = ValidationCode(vec![2; 16]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
 
 		let v = executor::block_on(validate_candidate_exhaustive(
-			MockValidatorBackend::with_hardcoded_result(
-				Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::HardTimeout)),
-			),
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+				"must not be called".into(),
+			))),
 			validation_data,
 			validation_code,
 			descriptor,
 			Arc::new(pov),
+			&ExhaustiveOptions::default().with_execution_seed(Some([1; 32])),
 			&Default::default(),
 		))
 		.unwrap();
 
-		assert_matches!(v, Ok(ValidationResult::Invalid(InvalidCandidate::Timeout)));
+		assert_matches!(v, Err(ValidationFailed::Other(e)) if e == UNSEEDED_BACKEND);
 	}
 
 	#[test]
-	fn candidate_validation_code_mismatch_is_invalid() {
+	fn code_above_preparation_cost_limit_is_rejected_before_preparation() {
 		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
-
 		let pov = PoV { block_data: BlockData(vec![1; 32]) };
-		let validation_code = ValidationCode(vec![2; 16]);
+
+		let raw_code = vec![2u8; 1024];
+		let validation_code = sp_maybe_compressed_blob::compress(&raw_code, VALIDATION_CODE_BOMB_LIMIT)
+			.map(ValidationCode)
+			.unwrap();
 
 		let mut descriptor = CandidateDescriptor::default();
 		descriptor.pov_hash = pov.hash();
-		descriptor.validation_code_hash = ValidationCode(vec![1; 16]).hash();
+		descriptor.validation_code_hash = validation_code.hash();
 		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
 
-		let check = perform_basic_checks(
-			&descriptor,
-			validation_data.max_pov_size,
-			&pov,
-			&validation_code,
-		);
-		assert_matches!(check, Err(InvalidCandidate::CodeHashMismatch));
+		// The compressed code is small, but the limit applies to the decompressed one.
+		assert!(estimate_preparation_cost(&validation_code.0) < 512);
 
 		let v = executor::block_on(validate_candidate_exhaustive(
-			MockValidatorBackend::with_hardcoded_result(
-				Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::HardTimeout)),
-			),
+			MockValidatorBackend::with_hardcoded_result(Err(ValidationError::InternalError(
+				"must not be called".into(),
+			))),
 			validation_data,
 			validation_code,
 			descriptor,
 			Arc::new(pov),
+			&ExhaustiveOptions { max_preparation_cost: Some(512), ..Default::default() },
 			&Default::default(),
 		))
-		.unwrap()
 		.unwrap();
 
-		assert_matches!(v, ValidationResult::Invalid(InvalidCandidate::CodeHashMismatch));
+		assert_matches!(
+			v,
+			Err(ValidationFailed::Other(e)) if e == "preparation cost exceeds policy"
+		);
 	}
 
 	#[test]
-	fn compressed_code_works() {
-		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
-		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+	fn slow_pov_decompression_times_out() {
+		let slow = || {
+			std::thread::sleep(Duration::from_millis(200));
+			vec![1u8; 32]
+		};
+
+		let res = executor::block_on(run_blocking_with_timeout(slow, Duration::from_millis(10)));
+		assert!(res.is_none());
+
+		let res = executor::block_on(run_blocking_with_timeout(slow, Duration::from_secs(5)));
+		assert_eq!(res, Some(vec![1u8; 32]));
+
+		let pov = Arc::new(PoV { block_data: BlockData(vec![1; 32]) });
+		let res = executor::block_on(decompress_pov(&pov, POV_BOMB_LIMIT, Some(Duration::from_secs(5))));
+		assert_matches!(res, Some(Ok(block_data)) => assert_eq!(block_data, vec![1; 32]));
+	}
+
+	#[test]
+	fn pov_decompression_failure_is_invalid() {
+		let validation_data = PersistedValidationData {
+			max_pov_size: POV_BOMB_LIMIT as u32,
+			..Default::default()
+		 };
 		let head_data = HeadData(vec![1, 1, 1]);
 
-		let raw_code = vec![2u8; 16];
-		let validation_code = sp_maybe_compressed_blob::compress(
-			&raw_code,
-			VALIDATION_CODE_BOMB_LIMIT,
+		let raw_block_data = vec![2u8; POV_BOMB_LIMIT + 1];
+		let pov = sp_maybe_compressed_blob::compress(
+			&raw_block_data,
+			POV_BOMB_LIMIT + 1,
 		)
-			.map(ValidationCode)
+			.map(|raw| PoV { block_data: BlockData(raw) })
 			.unwrap();
 
+		let validation_code = ValidationCode(vec![2; 16]);
+
 		let mut descriptor = CandidateDescriptor::default();
 		descriptor.pov_hash = pov.hash();
 		descriptor.para_head = head_data.hash();
@@ -1105,25 +11166,29 @@ mod tests {
 			descriptor,
 			Arc::new(pov),
 			&Default::default(),
+			&Default::default(),
 		))
 		.unwrap();
 
-		assert_matches!(v, Ok(ValidationResult::Valid(_, _)));
+		assert_matches!(
+			v,
+			Ok(ValidationResult::Invalid(InvalidCandidate::PoVDecompressionFailure))
+		);
 	}
 
 	#[test]
-	fn code_decompression_failure_is_invalid() {
+	fn pov_decompressing_beyond_the_max_pov_size_is_invalid() {
 		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
-		let pov = PoV { block_data: BlockData(vec![1; 32]) };
 		let head_data = HeadData(vec![1, 1, 1]);
 
-		let raw_code = vec![2u8; VALIDATION_CODE_BOMB_LIMIT + 1];
-		let validation_code = sp_maybe_compressed_blob::compress(
-			&raw_code,
-			VALIDATION_CODE_BOMB_LIMIT + 1,
-		)
-			.map(ValidationCode)
+		// Compresses to a fraction of the maximum PoV size, while decompressing far beyond it.
+		let raw_block_data = vec![2u8; 1024 * 1024];
+		let pov = sp_maybe_compressed_blob::compress(&raw_block_data, POV_BOMB_LIMIT)
+			.map(|raw| PoV { block_data: BlockData(raw) })
 			.unwrap();
+		assert!(pov.encoded_size() < 1024);
+
+		let validation_code = ValidationCode(vec![2; 16]);
 
 		let mut descriptor = CandidateDescriptor::default();
 		descriptor.pov_hash = pov.hash();
@@ -1147,21 +11212,61 @@ mod tests {
 			descriptor,
 			Arc::new(pov),
 			&Default::default(),
+			&Default::default(),
 		))
 		.unwrap();
 
 		assert_matches!(
 			v,
-			Ok(ValidationResult::Invalid(InvalidCandidate::CodeDecompressionFailure))
+			Ok(ValidationResult::Invalid(InvalidCandidate::PoVSizeExceeded(size))) if size == 1024 * 1024
 		);
 	}
 
 	#[test]
-	fn pov_decompression_failure_is_invalid() {
+	fn code_above_the_compressed_size_limit_is_rejected_before_decompression() {
+		let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+		let head_data = HeadData(vec![1, 1, 1]);
+		let validation_code = ValidationCode(vec![2; 64]);
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		descriptor.para_head = head_data.hash();
+		descriptor.validation_code_hash = validation_code.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validation_result = WasmValidationResult {
+			head_data,
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		let metrics = Metrics::try_register(&prometheus::Registry::new()).unwrap();
+		let validate = |max_compressed_code_size| executor::block_on(validate_candidate_exhaustive(
+			MockValidatorBackend::with_hardcoded_result(Ok(validation_result.clone())),
+			validation_data.clone(),
+			validation_code.clone(),
+			descriptor.clone(),
+			Arc::new(pov.clone()),
+			&ExhaustiveOptions::from_config(&Config { max_compressed_code_size, ..test_config() }),
+			&metrics,
+		)).unwrap();
+
+		assert_matches!(validate(Some(64)), Ok(ValidationResult::Valid(_, _, _)));
+		assert_matches!(validate(Some(63)), Ok(ValidationResult::Invalid(InvalidCandidate::CodeTooLarge(64))));
+		// Only the valid candidate's code was decompressed.
+		assert_eq!(metrics.0.as_ref().unwrap().code_size.get_sample_count(), 1);
+	}
+
+	#[test]
+	fn pov_decompressing_within_a_raised_limit_is_valid() {
 		let validation_data = PersistedValidationData {
-			max_pov_size: POV_BOMB_LIMIT as u32,
+			max_pov_size: POV_BOMB_LIMIT as u32 + 1,
 			..Default::default()
-		 };
+		};
 		let head_data = HeadData(vec![1, 1, 1]);
 
 		let raw_block_data = vec![2u8; POV_BOMB_LIMIT + 1];
@@ -1189,19 +11294,116 @@ mod tests {
 			hrmp_watermark: 0,
 		};
 
+		let config = Config { pov_bomb_limit: Some(POV_BOMB_LIMIT + 1), ..test_config() };
+		assert_eq!(validation_limits(&config).pov_bomb_limit, POV_BOMB_LIMIT + 1);
+
 		let v = executor::block_on(validate_candidate_exhaustive(
 			MockValidatorBackend::with_hardcoded_result(Ok(validation_result)),
 			validation_data,
 			validation_code,
 			descriptor,
 			Arc::new(pov),
+			&ExhaustiveOptions::from_config(&config),
 			&Default::default(),
 		))
 		.unwrap();
 
+		assert_matches!(v, Ok(ValidationResult::Valid(_, _, _)));
+	}
+
+	#[test]
+	fn validation_proof_verifies_against_the_validator_and_the_candidate() {
+		use polkadot_primitives::v1::ValidatorId;
+		use sp_application_crypto::AppKey;
+		use sp_keystore::SyncCryptoStore;
+
+		let keystore: SyncCryptoStorePtr = Arc::new(sp_keystore::testing::KeyStore::new());
+		let validator: ValidatorId = SyncCryptoStore::sr25519_generate_new(
+			&*keystore,
+			ValidatorId::ID,
+			Some(&Sr25519Keyring::Alice.to_seed()),
+		).unwrap().into();
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.validation_code_hash = ValidationCode(vec![1, 2, 3]).hash();
+		let commitments = CandidateCommitments {
+			head_data: HeadData(vec![1, 1, 1]),
+			..Default::default()
+		};
+		let candidate_hash = CandidateReceipt {
+			descriptor: descriptor.clone(),
+			commitments_hash: commitments.hash(),
+		}.hash();
+		let other_candidate_hash = CandidateHash(Hash::repeat_byte(1));
+		let valid = || Ok(ValidationResult::Valid(
+			commitments.clone(),
+			Default::default(),
+			descriptor.validation_code_hash,
+		));
+
+		let request_proof = |
+			candidate_hash: CandidateHash,
+			result: Result<ValidationResult, ValidationFailed>,
+		| {
+			let (tx, rx) = oneshot::channel();
+			let request = ProofRequest { candidate_hash, validator: validator.clone(), response: tx };
+			executor::block_on(send_validation_proof(Some(&keystore), &descriptor, Some(request), &result));
+			executor::block_on(rx).ok()
+		};
+
+		let proof = request_proof(candidate_hash, valid()).unwrap();
+		let proof = ValidationProof::decode(&mut &proof.encode()[..]).unwrap();
+		assert!(proof.is_valid());
+		assert_eq!(proof.validation_code_hash(), &descriptor.validation_code_hash);
+		assert_eq!(proof.check(candidate_hash, &validator), Ok(()));
+		assert!(proof.check(other_candidate_hash, &validator).is_err());
+		assert!(proof.check(candidate_hash, &Sr25519Keyring::Bob.public().into()).is_err());
+
+		// A valid result is only vouched for if it belongs to the requested candidate.
+		assert!(request_proof(other_candidate_hash, valid()).is_none());
+
+		let proof = request_proof(
+			candidate_hash,
+			Ok(ValidationResult::Invalid(InvalidCandidate::BadReturn)),
+		).unwrap();
+		assert!(!proof.is_valid());
+		assert_eq!(proof.check(candidate_hash, &validator), Ok(()));
+
+		assert!(request_proof(candidate_hash, Err(ValidationFailed::Other("failed".into()))).is_none());
+	}
+
+	#[test]
+	fn backing_validations_are_declined_while_inactive() {
+		let inactive: ActiveValidatorCheck = Arc::new(|| false);
+		let active: ActiveValidatorCheck = Arc::new(|| true);
+
+		let backing = ValidationOptions::new(ValidationSource::Backing);
+		assert!(declined_while_inactive(Some(&inactive), &backing));
+		assert!(!declined_while_inactive(Some(&active), &backing));
+		assert!(!declined_while_inactive(None, &backing));
+
+		// Approval and dispute work is served regardless of its priority.
+		for source in vec![ValidationSource::ApprovalVoting, ValidationSource::DisputeParticipation] {
+			let options = ValidationOptions {
+				priority: PvfExecPriority::Normal,
+				..ValidationOptions::new(source)
+			};
+			assert!(!declined_while_inactive(Some(&inactive), &options));
+		}
+
+		// As is any validation executed for approval or dispute purposes.
+		let options = ValidationOptions {
+			execution_kind: ExecutionKind::Dispute,
+			..Default::default()
+		};
+		assert!(!declined_while_inactive(Some(&inactive), &options));
+
+		let (tx, _rx) = oneshot::channel::<()>();
+		let mut rate_limiter = RateLimiter::new(HashMap::new());
+		let options = ValidationOptions::default();
 		assert_matches!(
-			v,
-			Ok(ValidationResult::Invalid(InvalidCandidate::PoVDecompressionFailure))
+			admit(&Metrics::default(), 1, 0, 1, &mut rate_limiter, Some(&inactive), &options, None, &tx),
+			Admission::Rejected(ValidationFailed::Other(e)) if e == "node not active validator"
 		);
 	}
 }
@@ -0,0 +1,178 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A validation backend running native validation functions of parachains in-process instead of
+//! executing their validation code, for local development.
+
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+
+use async_trait::async_trait;
+
+use polkadot_primitives::v1::{ValidationCode, ValidationCodeHash};
+use polkadot_parachain::primitives::{ValidationParams, ValidationResult as WasmValidationResult};
+use polkadot_node_core_pvf::{
+	ValidationError, InvalidCandidate as WasmInvalidCandidate, Priority, PrepareError,
+};
+
+use super::ValidationBackend;
+
+/// The native counterpart of the `validate_block` function of a parachain.
+pub type NativeValidationFunction = fn(ValidationParams) -> WasmValidationResult;
+
+/// A [`ValidationBackend`] calling the native validation function registered for the validation
+/// code of a candidate, rather than compiling and executing the code. Plugged in with
+/// [`CandidateValidationSubsystem::with_backend`](super::CandidateValidationSubsystem::with_backend),
+/// it spares `--dev` setups the compilation of the code of parachains under development.
+///
+/// The functions are registered by the hash of the decompressed validation code, which is the
+/// validation code hash of code that isn't compressed. Code without a registered function can't be
+/// validated, and a function panicking renders the candidate invalid, like a trapping
+/// `validate_block` does.
+#[derive(Clone, Default)]
+pub struct NativeValidationBackend {
+	functions: HashMap<ValidationCodeHash, NativeValidationFunction>,
+}
+
+impl NativeValidationBackend {
+	/// Create a backend calling the given functions for the validation code with their hashes.
+	pub fn new(functions: HashMap<ValidationCodeHash, NativeValidationFunction>) -> Self {
+		NativeValidationBackend { functions }
+	}
+
+	fn function(&self, raw_validation_code: Vec<u8>) -> Option<NativeValidationFunction> {
+		self.functions.get(&ValidationCode(raw_validation_code).hash()).copied()
+	}
+}
+
+#[async_trait]
+impl ValidationBackend for NativeValidationBackend {
+	async fn validate_candidate(
+		&mut self,
+		raw_validation_code: Vec<u8>,
+		params: ValidationParams,
+		_priority: Priority,
+	) -> Result<WasmValidationResult, ValidationError> {
+		let function = self.function(raw_validation_code).ok_or_else(|| {
+			ValidationError::InternalError("no native validation function registered".into())
+		})?;
+
+		panic::catch_unwind(AssertUnwindSafe(|| function(params))).map_err(|_| {
+			ValidationError::InvalidCandidate(WasmInvalidCandidate::WorkerReportedError(
+				"native validation function panicked".into(),
+			))
+		})
+	}
+
+	async fn precheck_pvf(&mut self, raw_validation_code: Vec<u8>) -> Result<(), PrepareError> {
+		self.function(raw_validation_code)
+			.map(|_| ())
+			.ok_or_else(|| PrepareError::Failed("no native validation function registered".into()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{CandidateValidationSubsystem, Metrics, tests::{collator_sign, test_config}};
+	use polkadot_node_subsystem_test_helpers as test_helpers;
+	use polkadot_node_primitives::{BlockData, InvalidCandidate, PoV, ValidationResult};
+	use polkadot_primitives::v1::{CandidateDescriptor, HeadData, PersistedValidationData};
+	use polkadot_subsystem::{
+		Subsystem, FromOverseer, OverseerSignal, messages::CandidateValidationMessage,
+	};
+	use futures::{channel::oneshot, executor, future};
+	use assert_matches::assert_matches;
+	use sp_core::testing::TaskExecutor;
+	use sp_keyring::Sr25519Keyring;
+	use std::sync::Arc;
+
+	/// The validation function of a parachain whose head is the block data of its last block.
+	fn echo(params: ValidationParams) -> WasmValidationResult {
+		WasmValidationResult {
+			head_data: HeadData(params.block_data.0),
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: params.relay_parent_number,
+		}
+	}
+
+	fn panicking(_: ValidationParams) -> WasmValidationResult {
+		panic!("bad block")
+	}
+
+	#[test]
+	fn echo_parachain_validates_end_to_end() {
+		let echo_code = ValidationCode(vec![1; 16]);
+		let panicking_code = ValidationCode(vec![2; 16]);
+		let unknown_code = ValidationCode(vec![3; 16]);
+
+		let backend = NativeValidationBackend::new(vec![
+			(echo_code.hash(), echo as NativeValidationFunction),
+			(panicking_code.hash(), panicking as NativeValidationFunction),
+		].into_iter().collect());
+
+		let pool = TaskExecutor::new();
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+		let subsystem =
+			CandidateValidationSubsystem::with_backend(test_config(), backend, Metrics::default())
+				.start(ctx);
+
+		let test_fut = async move {
+			let validation_data = PersistedValidationData { max_pov_size: 1024, ..Default::default() };
+			let pov = PoV { block_data: BlockData(vec![4, 2]) };
+
+			for validation_code in vec![echo_code, panicking_code, unknown_code] {
+				let mut descriptor = CandidateDescriptor::default();
+				descriptor.pov_hash = pov.hash();
+				descriptor.para_head = HeadData(vec![4, 2]).hash();
+				descriptor.validation_code_hash = validation_code.hash();
+				collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+				let (tx, rx) = oneshot::channel();
+				ctx_handle.send(FromOverseer::Communication {
+					msg: CandidateValidationMessage::ValidateFromExhaustive(
+						validation_data.clone(),
+						validation_code.clone(),
+						descriptor,
+						Arc::new(pov.clone()),
+						Default::default(),
+						tx,
+					),
+				}).await;
+
+				let outcome = rx.await.unwrap();
+				match validation_code.0[0] {
+					1 => assert_matches!(outcome, Ok(ValidationResult::Valid(outputs, _, _)) => {
+						assert_eq!(outputs.head_data, HeadData(vec![4, 2]));
+					}),
+					2 => assert_matches!(
+						outcome,
+						Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError(_)))
+					),
+					_ => assert_matches!(outcome, Err(_)),
+				}
+			}
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		let (result, ()) = executor::block_on(future::join(subsystem.future, test_fut));
+		assert_matches!(result, Ok(()));
+	}
+}
@@ -29,7 +29,8 @@ use polkadot_node_subsystem::{
 	messages::{
 		AllMessages, AvailabilityRecoveryMessage, AvailabilityStoreMessage,
 		CandidateValidationMessage, DisputeCoordinatorMessage, DisputeParticipationMessage,
-		RuntimeApiMessage, RuntimeApiRequest,
+		RuntimeApiMessage, RuntimeApiRequest, ValidationFailed, ValidationOptions,
+		ValidationSource,
 	},
 	ActiveLeavesUpdate, FromOverseer, OverseerSignal, SpawnedSubsystem, Subsystem,
 	SubsystemContext, SubsystemError,
@@ -93,6 +94,8 @@ pub enum ParticipationError {
 	MissingAvailableData(CandidateHash),
 	#[error("Failed to recover validation code for candidate {0}")]
 	MissingValidationCode(CandidateHash),
+	#[error("Failed to validate candidate {0}: {1}")]
+	ValidationFailed(CandidateHash, ValidationFailed),
 }
 
 impl Error {
@@ -275,6 +278,7 @@ async fn participate(
 			validation_code,
 			candidate_receipt.descriptor.clone(),
 			available_data.pov,
+			ValidationOptions::new(ValidationSource::DisputeParticipation),
 			validation_tx,
 		)
 		.into(),
@@ -284,15 +288,10 @@ async fn participate(
 	// we cast votes (either positive or negative) depending on the outcome of
 	// the validation and if valid, whether the commitments hash matches
 	match validation_rx.await? {
+		// the validation couldn't be completed, e.g. due to the node being overloaded or shutting
+		// down, which says nothing about the candidate, so we don't vote on it
 		Err(err) => {
-			tracing::warn!(
-				target: LOG_TARGET,
-				"Candidate {:?} validation failed with: {:?}",
-				candidate_receipt.hash(),
-				err,
-			);
-
-			cast_invalid_vote(ctx, candidate_hash, candidate_receipt, session).await;
+			return Err(ParticipationError::ValidationFailed(candidate_hash, err).into());
 		}
 		Ok(ValidationResult::Invalid(invalid)) => {
 			tracing::warn!(
@@ -304,7 +303,7 @@ async fn participate(
 
 			cast_invalid_vote(ctx, candidate_hash, candidate_receipt, session).await;
 		}
-		Ok(ValidationResult::Valid(commitments, _)) => {
+		Ok(ValidationResult::Valid(commitments, _, _)) => {
 			if commitments.hash() != candidate_receipt.commitments_hash {
 				tracing::warn!(
 					target: LOG_TARGET,
@@ -318,6 +317,13 @@ async fn participate(
 				cast_valid_vote(ctx, candidate_hash, candidate_receipt, session).await;
 			}
 		}
+		Ok(ValidationResult::Unchanged) => {
+			tracing::warn!(
+				target: LOG_TARGET,
+				"Candidate {:?} validation reported an unchanged outcome without asking for it",
+				candidate_hash,
+			);
+		}
 	}
 
 	Ok(())
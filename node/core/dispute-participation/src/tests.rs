@@ -288,7 +288,7 @@ fn cast_invalid_vote_if_validation_fails_or_is_invalid() {
 			assert_matches!(
 				virtual_overseer.recv().await,
 				AllMessages::CandidateValidation(
-					CandidateValidationMessage::ValidateFromExhaustive(_, _, _, _, tx)
+					CandidateValidationMessage::ValidateFromExhaustive(_, _, _, _, _, tx)
 				) => {
 					tx.send(Ok(ValidationResult::Invalid(InvalidCandidate::Timeout))).unwrap();
 				},
@@ -324,13 +324,13 @@ fn cast_invalid_vote_if_validation_passes_but_commitments_dont_match() {
 			assert_matches!(
 				virtual_overseer.recv().await,
 				AllMessages::CandidateValidation(
-					CandidateValidationMessage::ValidateFromExhaustive(_, _, _, _, tx)
+					CandidateValidationMessage::ValidateFromExhaustive(_, _, _, _, _, tx)
 				) => {
 					let mut commitments = CandidateCommitments::default();
 					// this should lead to a commitments hash mismatch
 					commitments.processed_downward_messages = 42;
 
-					tx.send(Ok(ValidationResult::Valid(commitments, Default::default()))).unwrap();
+					tx.send(Ok(ValidationResult::Valid(commitments, Default::default(), Default::default()))).unwrap();
 				},
 				"overseer did not receive candidate validation message",
 			);
@@ -364,9 +364,9 @@ fn cast_valid_vote_if_validation_passes() {
 			assert_matches!(
 				virtual_overseer.recv().await,
 				AllMessages::CandidateValidation(
-					CandidateValidationMessage::ValidateFromExhaustive(_, _, _, _, tx)
+					CandidateValidationMessage::ValidateFromExhaustive(_, _, _, _, _, tx)
 				) => {
-					tx.send(Ok(ValidationResult::Valid(Default::default(), Default::default()))).unwrap();
+					tx.send(Ok(ValidationResult::Valid(Default::default(), Default::default(), Default::default()))).unwrap();
 				},
 				"overseer did not receive candidate validation message",
 			);
@@ -401,9 +401,9 @@ fn failure_to_store_available_data_does_not_preclude_participation() {
 			assert_matches!(
 				virtual_overseer.recv().await,
 				AllMessages::CandidateValidation(
-					CandidateValidationMessage::ValidateFromExhaustive(_, _, _, _, tx)
+					CandidateValidationMessage::ValidateFromExhaustive(_, _, _, _, _, tx)
 				) => {
-					tx.send(Err(ValidationFailed("fail".to_string()))).unwrap();
+					tx.send(Ok(ValidationResult::Invalid(InvalidCandidate::Timeout))).unwrap();
 				},
 				"overseer did not receive candidate validation message",
 			);
@@ -423,3 +423,39 @@ fn failure_to_store_available_data_does_not_preclude_participation() {
 		})
 	});
 }
+
+#[test]
+fn no_vote_if_validation_cannot_be_completed() {
+	let failures = vec![
+		ValidationFailed::Overloaded,
+		ValidationFailed::ShuttingDown,
+		ValidationFailed::Cancelled,
+		ValidationFailed::PvfHostUnavailable("host exited".to_string()),
+		ValidationFailed::Other("internal error".to_string()),
+	];
+
+	for failure in failures {
+		test_harness(|mut virtual_overseer| {
+			Box::pin(async move {
+				activate_leaf(&mut virtual_overseer, 10).await;
+				participate(&mut virtual_overseer).await;
+				recover_available_data(&mut virtual_overseer).await;
+				fetch_validation_code(&mut virtual_overseer).await;
+				store_available_data(&mut virtual_overseer, true).await;
+
+				assert_matches!(
+					virtual_overseer.recv().await,
+					AllMessages::CandidateValidation(
+						CandidateValidationMessage::ValidateFromExhaustive(_, _, _, _, _, tx)
+					) => {
+						tx.send(Err(failure)).unwrap();
+					},
+					"overseer did not receive candidate validation message",
+				);
+
+				// the harness checks that no vote is issued afterwards
+				virtual_overseer
+			})
+		});
+	}
+}
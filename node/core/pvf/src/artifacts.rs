@@ -21,6 +21,7 @@ use async_std::{
 use polkadot_parachain::primitives::ValidationCodeHash;
 use std::{
 	collections::HashMap,
+	sync::Arc,
 	time::{Duration, SystemTime},
 };
 use parity_scale_codec::{Encode, Decode};
@@ -97,14 +98,22 @@ pub enum ArtifactState {
 		/// This is updated when we get the heads up for this artifact or when we just discover
 		/// this file.
 		last_time_needed: SystemTime,
+		/// The size of the artifact on disk, in bytes.
+		size: u64,
 	},
 	/// A task to prepare this artifact is scheduled.
 	Preparing,
 }
 
+/// A token held by an execution of an artifact for as long as it's queued or running. An artifact
+/// with a lease outstanding is never removed from the table.
+#[derive(Debug, Clone, Default)]
+pub struct ArtifactLease(Arc<()>);
+
 /// A container of all known artifact ids and their states.
 pub struct Artifacts {
 	artifacts: HashMap<ArtifactId, ArtifactState>,
+	leases: HashMap<ArtifactId, ArtifactLease>,
 }
 
 impl Artifacts {
@@ -118,16 +127,29 @@ impl Artifacts {
 		let _ = async_std::fs::remove_dir_all(cache_path).await;
 		let _ = async_std::fs::create_dir_all(cache_path).await;
 
-		Self { artifacts: HashMap::new() }
+		Self { artifacts: HashMap::new(), leases: HashMap::new() }
 	}
 
 	#[cfg(test)]
 	pub(crate) fn empty() -> Self {
 		Self {
 			artifacts: HashMap::new(),
+			leases: HashMap::new(),
 		}
 	}
 
+	/// Returns a lease on the given artifact, which keeps it in the table until dropped.
+	pub fn lease(&mut self, artifact_id: &ArtifactId) -> ArtifactLease {
+		self.leases.entry(artifact_id.clone()).or_default().clone()
+	}
+
+	/// Forgets the leases which were all dropped and returns whether the given artifact is still
+	/// leased.
+	fn is_leased(&mut self, artifact_id: &ArtifactId) -> bool {
+		self.leases.retain(|_, lease| Arc::strong_count(&lease.0) > 1);
+		self.leases.contains_key(artifact_id)
+	}
+
 	/// Returns the state of the given artifact by its ID.
 	pub fn artifact_state_mut(&mut self, artifact_id: &ArtifactId) -> Option<&mut ArtifactState> {
 		self.artifacts.get_mut(artifact_id)
@@ -150,14 +172,51 @@ impl Artifacts {
 	/// This function must be used only for brand new artifacts and should never be used for
 	/// replacing existing ones.
 	#[cfg(test)]
-	pub fn insert_prepared(&mut self, artifact_id: ArtifactId, last_time_needed: SystemTime) {
+	pub fn insert_prepared(
+		&mut self,
+		artifact_id: ArtifactId,
+		last_time_needed: SystemTime,
+		size: u64,
+	) {
 		// See the precondition.
 		always!(self
 			.artifacts
-			.insert(artifact_id, ArtifactState::Prepared { last_time_needed })
+			.insert(artifact_id, ArtifactState::Prepared { last_time_needed, size })
 			.is_none());
 	}
 
+	/// Returns the total size, in bytes, of the prepared artifacts.
+	pub fn prepared_size(&self) -> u64 {
+		self.artifacts
+			.values()
+			.map(|state| match state {
+				ArtifactState::Prepared { size, .. } => *size,
+				ArtifactState::Preparing => 0,
+			})
+			.sum()
+	}
+
+	/// Remove and retrieve all the prepared artifacts from the table. The artifacts that are being
+	/// prepared or are leased are left untouched.
+	pub fn remove_prepared(&mut self) -> Vec<ArtifactId> {
+		let prepared = self
+			.artifacts
+			.iter()
+			.filter(|(_, state)| matches!(state, ArtifactState::Prepared { .. }))
+			.map(|(id, _)| id.clone())
+			.collect::<Vec<_>>();
+		let to_remove = prepared
+			.into_iter()
+			.filter(|id| !self.is_leased(id))
+			.collect::<Vec<_>>();
+
+		for artifact in &to_remove {
+			self.artifacts.remove(artifact);
+		}
+
+		to_remove
+	}
+
 	/// Remove and retrieve the artifacts from the table that are older than the supplied Time-To-Live.
 	pub fn prune(&mut self, artifact_ttl: Duration) -> Vec<ArtifactId> {
 		let now = SystemTime::now();
@@ -183,6 +242,45 @@ impl Artifacts {
 
 		to_remove
 	}
+
+	/// Remove and retrieve the least recently needed prepared artifacts from the table until their
+	/// total size doesn't exceed `max_size` bytes. Leased artifacts are never removed, so the total
+	/// size may remain above `max_size` until their leases are dropped.
+	pub fn prune_to_size(&mut self, max_size: u64) -> Vec<ArtifactId> {
+		let mut total_size = self.prepared_size();
+		if total_size <= max_size {
+			return Vec::new()
+		}
+
+		let mut prepared = self
+			.artifacts
+			.iter()
+			.filter_map(|(id, state)| match *state {
+				ArtifactState::Prepared { last_time_needed, size } =>
+					Some((last_time_needed, size, id.clone())),
+				ArtifactState::Preparing => None,
+			})
+			.collect::<Vec<_>>();
+		prepared.sort_by_key(|(last_time_needed, _, _)| *last_time_needed);
+
+		let mut to_remove = vec![];
+		for (_, size, id) in prepared {
+			if total_size <= max_size {
+				break
+			}
+			if self.is_leased(&id) {
+				continue
+			}
+			total_size -= size;
+			to_remove.push(id);
+		}
+
+		for artifact in &to_remove {
+			self.artifacts.remove(artifact);
+		}
+
+		to_remove
+	}
 }
 
 #[cfg(test)]
@@ -190,7 +288,10 @@ mod tests {
 	use async_std::path::Path;
 	use super::{Artifacts, ArtifactId};
 	use sp_core::H256;
-	use std::str::FromStr;
+	use std::{
+		str::FromStr,
+		time::{Duration, SystemTime},
+	};
 
 	#[test]
 	fn from_file_name() {
@@ -221,6 +322,62 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn pruning_to_size_removes_the_least_recently_needed_first() {
+		let artifact_id = |n: u64| ArtifactId::new(H256::from_low_u64_be(n).into());
+		let now = SystemTime::now();
+
+		let mut artifacts = Artifacts::empty();
+		artifacts.insert_prepared(artifact_id(1), now - Duration::from_secs(30), 100);
+		artifacts.insert_prepared(artifact_id(2), now - Duration::from_secs(10), 100);
+		artifacts.insert_prepared(artifact_id(3), now - Duration::from_secs(20), 100);
+		artifacts.insert_preparing(artifact_id(4));
+
+		assert!(artifacts.prune_to_size(300).is_empty());
+		assert_eq!(artifacts.prune_to_size(150), vec![artifact_id(1), artifact_id(3)]);
+		assert_eq!(artifacts.prepared_size(), 100);
+		assert!(artifacts.artifact_state_mut(&artifact_id(2)).is_some());
+		assert!(artifacts.artifact_state_mut(&artifact_id(4)).is_some());
+	}
+
+	#[test]
+	fn leased_artifacts_are_never_pruned() {
+		let artifact_id = |n: u64| ArtifactId::new(H256::from_low_u64_be(n).into());
+		let now = SystemTime::now();
+
+		let mut artifacts = Artifacts::empty();
+		artifacts.insert_prepared(artifact_id(1), now - Duration::from_secs(30), 100);
+		artifacts.insert_prepared(artifact_id(2), now - Duration::from_secs(20), 100);
+		artifacts.insert_prepared(artifact_id(3), now - Duration::from_secs(10), 100);
+
+		let lease = artifacts.lease(&artifact_id(1));
+		assert_eq!(artifacts.prune_to_size(200), vec![artifact_id(2)]);
+
+		// Dropping one lease leaves the others in force.
+		drop(artifacts.lease(&artifact_id(1)));
+		assert_eq!(artifacts.prune_to_size(100), vec![artifact_id(3)]);
+
+		drop(lease);
+		assert_eq!(artifacts.prune_to_size(0), vec![artifact_id(1)]);
+	}
+
+	#[test]
+	fn leased_artifacts_are_not_flushed() {
+		let artifact_id = |n: u64| ArtifactId::new(H256::from_low_u64_be(n).into());
+		let now = SystemTime::now();
+
+		let mut artifacts = Artifacts::empty();
+		artifacts.insert_prepared(artifact_id(1), now, 100);
+		artifacts.insert_prepared(artifact_id(2), now, 100);
+
+		let lease = artifacts.lease(&artifact_id(1));
+		assert_eq!(artifacts.remove_prepared(), vec![artifact_id(2)]);
+		assert_eq!(artifacts.prepared_size(), 100);
+
+		drop(lease);
+		assert_eq!(artifacts.remove_prepared(), vec![artifact_id(1)]);
+	}
+
 	#[test]
 	fn artifacts_removes_cache_on_startup() {
 		let fake_cache_path = async_std::task::block_on(async move { crate::worker_common::tmpfile("test-cache").await.unwrap() });
@@ -23,6 +23,16 @@ pub enum ValidationError {
 	InternalError(String),
 }
 
+/// An error raised while preparing a PVF, as reported to the requests for pre-checking it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrepareError {
+	/// The PVF failed the prevalidation or the compilation. The string contains the error message.
+	Invalid(String),
+	/// The PVF couldn't be prepared for reasons that can't be attributed to it, e.g. the worker
+	/// preparing it died or its artifact couldn't be read.
+	Failed(String),
+}
+
 /// A description of an error raised during executing a PVF and can be attributed to the combination
 /// of the candidate [`polkadot_parachain::primitives::ValidationParams`] and the PVF.
 #[derive(Debug, Clone)]
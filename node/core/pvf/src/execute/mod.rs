@@ -23,5 +23,5 @@
 mod queue;
 mod worker;
 
-pub use queue::{ToQueue, start};
-pub use worker::worker_entrypoint;
+pub use queue::{ToQueue, RespawnBackoff, start};
+pub use worker::{worker_entrypoint, spawn as spawn_worker, EXECUTION_TIMEOUT};
@@ -17,16 +17,17 @@
 //! A queue that handles requests for PVF execution.
 
 use crate::{
+	artifacts::ArtifactLease,
 	worker_common::{IdleWorker, WorkerHandle},
 	host::ResultSender,
 	LOG_TARGET, InvalidCandidate, ValidationError,
 };
 use super::worker::Outcome;
-use std::{collections::VecDeque, fmt, time::Duration};
+use std::{collections::VecDeque, fmt, time::{Duration, Instant}};
 use futures::{
 	Future, FutureExt,
 	channel::mpsc,
-	future::BoxFuture,
+	future::{self, BoxFuture},
 	stream::{FuturesUnordered, StreamExt as _},
 };
 use async_std::path::PathBuf;
@@ -34,23 +35,54 @@ use slotmap::HopSlotMap;
 
 slotmap::new_key_type! { struct Worker; }
 
+/// The schedule by which spawning execute workers is delayed while they keep dying. Each
+/// consecutive death doubles the delay, starting from `initial` and capped at `max`. A job
+/// completing without the worker dying resets the schedule.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RespawnBackoff {
+	/// The delay after the first death.
+	pub initial: Duration,
+	/// The maximum delay.
+	pub max: Duration,
+}
+
+impl RespawnBackoff {
+	/// The delay before spawning again after the given number of consecutive deaths.
+	pub fn delay(&self, consecutive_deaths: u32) -> Duration {
+		if consecutive_deaths == 0 {
+			return Duration::from_secs(0);
+		}
+
+		let factor = 1u32.checked_shl(consecutive_deaths - 1).unwrap_or(u32::MAX);
+		self.initial.checked_mul(factor).map_or(self.max, |delay| delay.min(self.max))
+	}
+}
+
 #[derive(Debug)]
 pub enum ToQueue {
 	Enqueue {
 		artifact_path: PathBuf,
+		/// Keeps the artifact from being removed until the job is done with it.
+		lease: ArtifactLease,
 		params: Vec<u8>,
+		/// The seed the PVF draws its randomness from, if it may draw any.
+		execution_seed: Option<[u8; 32]>,
 		result_tx: ResultSender,
 	},
 }
 
 struct ExecuteJob {
 	artifact_path: PathBuf,
+	lease: ArtifactLease,
 	params: Vec<u8>,
+	execution_seed: Option<[u8; 32]>,
 	result_tx: ResultSender,
 }
 
 struct WorkerData {
 	idle: Option<IdleWorker>,
+	/// The moment the worker became idle the last time. Only meaningful if `idle` is `Some`.
+	idle_since: Instant,
 	handle: WorkerHandle,
 }
 
@@ -92,11 +124,32 @@ impl Workers {
 			.idle
 			.take()
 	}
+
+	/// Remove the workers that have been idle for longer than the given timeout. Dropping the
+	/// handle kills the worker process.
+	fn cull_idle(&mut self, idle_timeout: Duration) {
+		let mut to_remove = vec![];
+		for (worker, data) in self.running.iter() {
+			if data.idle.is_some() && data.idle_since.elapsed() >= idle_timeout {
+				to_remove.push(worker);
+			}
+		}
+		for w in to_remove {
+			tracing::debug!(
+				target: LOG_TARGET,
+				"culling an execute worker idle for more than {:?}",
+				idle_timeout,
+			);
+			let _ = self.running.remove(w);
+		}
+	}
 }
 
 enum QueueEvent {
 	Spawn((IdleWorker, WorkerHandle)),
 	StartWork(Worker, Outcome, ResultSender),
+	/// The requester of the job the worker was busy with hung up before the job completed.
+	Cancelled(Worker),
 }
 
 type Mux = FuturesUnordered<BoxFuture<'static, QueueEvent>>;
@@ -107,6 +160,14 @@ struct Queue {
 
 	program_path: PathBuf,
 	spawn_timeout: Duration,
+	idle_timeout: Option<Duration>,
+	respawn_backoff: Option<RespawnBackoff>,
+	memory_limit: Option<u64>,
+	disabled_host_functions: Vec<String>,
+	execution_timeout: Duration,
+
+	/// The number of workers that died in a row, without a job completing in between.
+	consecutive_deaths: u32,
 
 	/// The queue of jobs that are waiting for a worker to pick up.
 	queue: VecDeque<ExecuteJob>,
@@ -119,11 +180,22 @@ impl Queue {
 		program_path: PathBuf,
 		worker_capacity: usize,
 		spawn_timeout: Duration,
+		idle_timeout: Option<Duration>,
+		respawn_backoff: Option<RespawnBackoff>,
+		memory_limit: Option<u64>,
+		disabled_host_functions: Vec<String>,
+		execution_timeout: Duration,
 		to_queue_rx: mpsc::Receiver<ToQueue>,
 	) -> Self {
 		Self {
 			program_path,
 			spawn_timeout,
+			idle_timeout,
+			respawn_backoff,
+			memory_limit,
+			disabled_host_functions,
+			execution_timeout,
+			consecutive_deaths: 0,
 			to_queue_rx,
 			queue: VecDeque::new(),
 			mux: Mux::new(),
@@ -136,6 +208,12 @@ impl Queue {
 	}
 
 	async fn run(mut self) {
+		let mut cull_pulse = match self.idle_timeout {
+			Some(idle_timeout) => crate::host::pulse_every(idle_timeout).boxed(),
+			None => futures::stream::pending().boxed(),
+		}
+		.fuse();
+
 		loop {
 			futures::select! {
 				to_queue = self.to_queue_rx.next() => {
@@ -146,6 +224,11 @@ impl Queue {
 					}
 				}
 				ev = self.mux.select_next_some() => handle_mux(&mut self, ev).await,
+				() = cull_pulse.select_next_some() => {
+					if let Some(idle_timeout) = self.idle_timeout {
+						self.workers.cull_idle(idle_timeout);
+					}
+				}
 			}
 
 			purge_dead(&mut self.workers).await;
@@ -169,13 +252,17 @@ async fn purge_dead(workers: &mut Workers) {
 fn handle_to_queue(queue: &mut Queue, to_queue: ToQueue) {
 	let ToQueue::Enqueue {
 		artifact_path,
+		lease,
 		params,
+		execution_seed,
 		result_tx,
 	} = to_queue;
 
 	let job = ExecuteJob {
 		artifact_path,
+		lease,
 		params,
+		execution_seed,
 		result_tx,
 	};
 
@@ -196,16 +283,45 @@ async fn handle_mux(queue: &mut Queue, event: QueueEvent) {
 
 			let worker = queue.workers.running.insert(WorkerData {
 				idle: Some(idle),
+				idle_since: Instant::now(),
 				handle,
 			});
 
-			if let Some(job) = queue.queue.pop_front() {
+			if let Some(job) = next_job(&mut queue.queue) {
 				assign(queue, worker, job);
 			}
 		}
 		QueueEvent::StartWork(worker, outcome, result_tx) => {
 			handle_job_finish(queue, worker, outcome, result_tx);
 		}
+		QueueEvent::Cancelled(worker) => handle_job_cancelled(queue, worker),
+	}
+}
+
+/// Takes the next job off the queue, dropping the jobs whose requesters hung up meanwhile.
+fn next_job(queue: &mut VecDeque<ExecuteJob>) -> Option<ExecuteJob> {
+	while let Some(job) = queue.pop_front() {
+		if !job.result_tx.is_canceled() {
+			return Some(job);
+		}
+	}
+
+	None
+}
+
+/// Kills the worker busy with a job nobody awaits anymore, so that it doesn't keep executing in
+/// vain, and requests a replacement if there are jobs waiting.
+fn handle_job_cancelled(queue: &mut Queue, worker: Worker) {
+	tracing::debug!(
+		target: LOG_TARGET,
+		"killing an execute worker whose job was cancelled",
+	);
+
+	// Dropping the handle kills the worker process.
+	let _ = queue.workers.running.remove(worker);
+
+	if !queue.queue.is_empty() {
+		spawn_extra_worker(queue);
 	}
 }
 
@@ -233,6 +349,14 @@ fn handle_job_finish(queue: &mut Queue, worker: Worker, outcome: Outcome, result
 			Some(idle_worker),
 			Err(ValidationError::InternalError(err)),
 		),
+		// The peak memory of the worker also depends on what the allocator made of the earlier
+		// executions, so a breach can't be attributed to the candidate.
+		Outcome::MemoryLimitExceeded { idle_worker } => (
+			Some(idle_worker),
+			Err(ValidationError::InternalError(
+				"the execution exceeded the memory limit".to_string(),
+			)),
+		),
 		Outcome::HardTimeout => (
 			None,
 			Err(ValidationError::InvalidCandidate(
@@ -259,16 +383,20 @@ fn handle_job_finish(queue: &mut Queue, worker: Worker, outcome: Outcome, result
 	// - if the `idle_worker` token was consumed, all the metadata pertaining to that worker should
 	//   be removed.
 	if let Some(idle_worker) = idle_worker {
+		queue.consecutive_deaths = 0;
+
 		if let Some(data) = queue.workers.running.get_mut(worker) {
 			data.idle = Some(idle_worker);
+			data.idle_since = Instant::now();
 
-			if let Some(job) = queue.queue.pop_front() {
+			if let Some(job) = next_job(&mut queue.queue) {
 				assign(queue, worker, job);
 			}
 		}
 	} else {
 		// Note it's possible that the worker was purged already by `purge_dead`
 		queue.workers.running.remove(worker);
+		queue.consecutive_deaths = queue.consecutive_deaths.saturating_add(1);
 
 		if !queue.queue.is_empty() {
 			// The worker has died and we still have work we have to do. Request an extra worker.
@@ -280,15 +408,28 @@ fn handle_job_finish(queue: &mut Queue, worker: Worker, outcome: Outcome, result
 }
 
 fn spawn_extra_worker(queue: &mut Queue) {
+	let delay = queue.respawn_backoff
+		.map_or(Duration::from_secs(0), |backoff| backoff.delay(queue.consecutive_deaths));
+
 	queue
 		.mux
-		.push(spawn_worker_task(queue.program_path.clone(), queue.spawn_timeout).boxed());
+		.push(spawn_worker_task(queue.program_path.clone(), queue.spawn_timeout, delay).boxed());
 	queue.workers.spawn_inflight += 1;
 }
 
-async fn spawn_worker_task(program_path: PathBuf, spawn_timeout: Duration) -> QueueEvent {
+async fn spawn_worker_task(program_path: PathBuf, spawn_timeout: Duration, delay: Duration) -> QueueEvent {
 	use futures_timer::Delay;
 
+	if delay > Duration::from_secs(0) {
+		tracing::debug!(
+			target: LOG_TARGET,
+			"delaying spawning an execute worker by {:?} after consecutive worker deaths",
+			delay,
+		);
+
+		Delay::new(delay).await;
+	}
+
 	loop {
 		match super::worker::spawn(&program_path, spawn_timeout).await {
 			Ok((idle, handle)) => break QueueEvent::Spawn((idle, handle)),
@@ -318,10 +459,30 @@ fn assign(queue: &mut Queue, worker: Worker, job: ExecuteJob) {
 			thus claim_idle cannot return None;
 			qed."
 		);
+	let memory_limit = queue.memory_limit;
+	let disabled_host_functions = queue.disabled_host_functions.clone();
+	let execution_timeout = queue.execution_timeout;
 	queue.mux.push(
 		async move {
-			let outcome = super::worker::start_work(idle, job.artifact_path, job.params).await;
-			QueueEvent::StartWork(worker, outcome, job.result_tx)
+			let mut result_tx = job.result_tx;
+			// The artifact is no longer needed once the work is done, whatever its outcome.
+			let _lease = job.lease;
+			let work = super::worker::start_work(
+				idle,
+				job.artifact_path,
+				job.params,
+				job.execution_seed,
+				memory_limit,
+				&disabled_host_functions,
+				execution_timeout,
+			);
+			futures::pin_mut!(work);
+
+			let outcome = match future::select(work, result_tx.cancellation()).await {
+				future::Either::Left((outcome, _)) => outcome,
+				future::Either::Right(((), _)) => return QueueEvent::Cancelled(worker),
+			};
+			QueueEvent::StartWork(worker, outcome, result_tx)
 		}
 		.boxed(),
 	);
@@ -331,14 +492,73 @@ pub fn start(
 	program_path: PathBuf,
 	worker_capacity: usize,
 	spawn_timeout: Duration,
+	idle_timeout: Option<Duration>,
+	respawn_backoff: Option<RespawnBackoff>,
+	memory_limit: Option<u64>,
+	disabled_host_functions: Vec<String>,
+	execution_timeout: Duration,
 ) -> (mpsc::Sender<ToQueue>, impl Future<Output = ()>) {
 	let (to_queue_tx, to_queue_rx) = mpsc::channel(20);
 	let run = Queue::new(
 		program_path,
 		worker_capacity,
 		spawn_timeout,
+		idle_timeout,
+		respawn_backoff,
+		memory_limit,
+		disabled_host_functions,
+		execution_timeout,
 		to_queue_rx,
 	)
 	.run();
 	(to_queue_tx, run)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn respawn_delay_grows_with_consecutive_deaths_up_to_cap() {
+		let backoff = RespawnBackoff {
+			initial: Duration::from_millis(100),
+			max: Duration::from_secs(1),
+		};
+
+		let delays = (0..6).map(|deaths| backoff.delay(deaths)).collect::<Vec<_>>();
+		assert_eq!(delays, vec![
+			Duration::from_millis(0),
+			Duration::from_millis(100),
+			Duration::from_millis(200),
+			Duration::from_millis(400),
+			Duration::from_millis(800),
+			Duration::from_secs(1),
+		]);
+
+		// The cap holds no matter how long the crash loop goes on.
+		assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(1));
+	}
+
+	#[test]
+	fn jobs_whose_requesters_hung_up_are_skipped() {
+		let job = |params: u8| {
+			let (result_tx, result_rx) = futures::channel::oneshot::channel();
+			let job = ExecuteJob {
+				artifact_path: PathBuf::from("/artifact"),
+				lease: ArtifactLease::default(),
+				params: vec![params],
+				execution_seed: None,
+				result_tx,
+			};
+			(job, result_rx)
+		};
+
+		let (cancelled, cancelled_rx) = job(1);
+		drop(cancelled_rx);
+		let (live, _live_rx) = job(2);
+
+		let mut jobs = VecDeque::from(vec![cancelled, live]);
+		assert_eq!(next_job(&mut jobs).map(|job| job.params), Some(vec![2]));
+		assert!(next_job(&mut jobs).is_none());
+	}
+}
@@ -20,7 +20,7 @@ use crate::{
 	executor_intf::TaskExecutor,
 	worker_common::{
 		IdleWorker, SpawnErr, WorkerHandle, bytes_to_path, framed_recv, framed_send, path_to_bytes,
-		spawn_with_program_path, worker_event_loop,
+		recv_handshake, send_handshake, spawn_with_program_path, worker_event_loop,
 	},
 };
 use std::time::{Duration, Instant};
@@ -34,7 +34,8 @@ use futures_timer::Delay;
 use polkadot_parachain::primitives::ValidationResult;
 use parity_scale_codec::{Encode, Decode};
 
-const EXECUTION_TIMEOUT: Duration = Duration::from_secs(3);
+/// The time allotted for a single PVF execution, unless configured otherwise.
+pub const EXECUTION_TIMEOUT: Duration = Duration::from_secs(3);
 
 /// Spawns a new worker with the given program path that acts as the worker and the spawn timeout.
 ///
@@ -43,13 +44,17 @@ pub async fn spawn(
 	program_path: &Path,
 	spawn_timeout: Duration,
 ) -> Result<(IdleWorker, WorkerHandle), SpawnErr> {
-	spawn_with_program_path(
+	let (mut idle_worker, handle) = spawn_with_program_path(
 		"execute",
 		program_path,
 		&["execute-worker"],
 		spawn_timeout,
 	)
-	.await
+	.await?;
+
+	recv_handshake(&mut idle_worker.stream, spawn_timeout).await?;
+
+	Ok((idle_worker, handle))
 }
 
 /// Outcome of PVF execution.
@@ -73,6 +78,11 @@ pub enum Outcome {
 		err: String,
 		idle_worker: IdleWorker,
 	},
+	/// The execution used more memory than the configured ceiling. The worker is ready for
+	/// another job.
+	MemoryLimitExceeded {
+		idle_worker: IdleWorker,
+	},
 	/// The execution time exceeded the hard limit. The worker is terminated.
 	HardTimeout,
 	/// An I/O error happened during communication with the worker. This may mean that the worker
@@ -82,10 +92,26 @@ pub enum Outcome {
 
 /// Given the idle token of a worker and parameters of work, communicates with the worker and
 /// returns the outcome.
+///
+/// If `memory_limit` is set, the worker reports the execution as exceeding it if the peak
+/// resident memory of the worker grows by more than that many bytes during the execution. The
+/// limit is only measured on Linux and ignored elsewhere.
+///
+/// If `execution_seed` is set, the PVF may draw randomness through the offchain `random_seed` host
+/// function, which yields that seed. Otherwise the host function is withheld from the PVF.
+///
+/// The host functions named in `disabled_host_functions` are withheld from the PVF.
+///
+/// An execution that doesn't conclude within `execution_timeout` is reported as
+/// [`Outcome::HardTimeout`].
 pub async fn start_work(
 	worker: IdleWorker,
 	artifact_path: PathBuf,
 	validation_params: Vec<u8>,
+	execution_seed: Option<[u8; 32]>,
+	memory_limit: Option<u64>,
+	disabled_host_functions: &[String],
+	execution_timeout: Duration,
 ) -> Outcome {
 	let IdleWorker { mut stream, pid } = worker;
 
@@ -96,7 +122,14 @@ pub async fn start_work(
 		artifact_path.display(),
 	);
 
-	if send_request(&mut stream, &artifact_path, &validation_params).await.is_err() {
+	if send_request(
+		&mut stream,
+		&artifact_path,
+		&validation_params,
+		execution_seed,
+		memory_limit,
+		disabled_host_functions,
+	).await.is_err() {
 		return Outcome::IoErr;
 	}
 
@@ -107,7 +140,7 @@ pub async fn start_work(
 				Ok(response) => response,
 			}
 		},
-		_ = Delay::new(EXECUTION_TIMEOUT).fuse() => return Outcome::HardTimeout,
+		_ = Delay::new(execution_timeout).fuse() => return Outcome::HardTimeout,
 	};
 
 	match response {
@@ -127,6 +160,9 @@ pub async fn start_work(
 			err,
 			idle_worker: IdleWorker { stream, pid },
 		},
+		Response::MemoryLimitExceeded => Outcome::MemoryLimitExceeded {
+			idle_worker: IdleWorker { stream, pid },
+		},
 	}
 }
 
@@ -134,12 +170,20 @@ async fn send_request(
 	stream: &mut UnixStream,
 	artifact_path: &Path,
 	validation_params: &[u8],
+	execution_seed: Option<[u8; 32]>,
+	memory_limit: Option<u64>,
+	disabled_host_functions: &[String],
 ) -> io::Result<()> {
 	framed_send(stream, path_to_bytes(artifact_path)).await?;
-	framed_send(stream, validation_params).await
+	framed_send(stream, validation_params).await?;
+	framed_send(stream, &execution_seed.encode()).await?;
+	framed_send(stream, &memory_limit.encode()).await?;
+	framed_send(stream, &disabled_host_functions.encode()).await
 }
 
-async fn recv_request(stream: &mut UnixStream) -> io::Result<(PathBuf, Vec<u8>)> {
+async fn recv_request(
+	stream: &mut UnixStream,
+) -> io::Result<(PathBuf, Vec<u8>, Option<[u8; 32]>, Option<u64>, Vec<String>)> {
 	let artifact_path = framed_recv(stream).await?;
 	let artifact_path = bytes_to_path(&artifact_path).ok_or_else(|| {
 		io::Error::new(
@@ -148,7 +192,29 @@ async fn recv_request(stream: &mut UnixStream) -> io::Result<(PathBuf, Vec<u8>)>
 		)
 	})?;
 	let params = framed_recv(stream).await?;
-	Ok((artifact_path, params))
+	let execution_seed = framed_recv(stream).await?;
+	let execution_seed = Option::<[u8; 32]>::decode(&mut &execution_seed[..]).map_err(|e| {
+		io::Error::new(
+			io::ErrorKind::Other,
+			format!("execute pvf recv_request: execution seed decode error: {:?}", e),
+		)
+	})?;
+	let memory_limit = framed_recv(stream).await?;
+	let memory_limit = Option::<u64>::decode(&mut &memory_limit[..]).map_err(|e| {
+		io::Error::new(
+			io::ErrorKind::Other,
+			format!("execute pvf recv_request: memory limit decode error: {:?}", e),
+		)
+	})?;
+	let disabled_host_functions = framed_recv(stream).await?;
+	let disabled_host_functions = Vec::<String>::decode(&mut &disabled_host_functions[..])
+		.map_err(|e| {
+			io::Error::new(
+				io::ErrorKind::Other,
+				format!("execute pvf recv_request: disabled host functions decode error: {:?}", e),
+			)
+		})?;
+	Ok((artifact_path, params, execution_seed, memory_limit, disabled_host_functions))
 }
 
 async fn send_response(stream: &mut UnixStream, response: Response) -> io::Result<()> {
@@ -173,6 +239,7 @@ enum Response {
 	},
 	InvalidCandidate(String),
 	InternalError(String),
+	MemoryLimitExceeded,
 }
 
 impl Response {
@@ -195,15 +262,24 @@ pub fn worker_entrypoint(socket_path: &str) {
 				format!("cannot create task executor: {}", e),
 			)
 		})?;
+		send_handshake(&mut stream).await?;
 		loop {
-			let (artifact_path, params) = recv_request(&mut stream).await?;
+			let (artifact_path, params, execution_seed, memory_limit, disabled_host_functions) =
+				recv_request(&mut stream).await?;
 			tracing::debug!(
 				target: LOG_TARGET,
 				worker_pid = %std::process::id(),
 				"worker: validating artifact {}",
 				artifact_path.display(),
 			);
-			let response = validate_using_artifact(&artifact_path, &params, &executor).await;
+			let response = validate_using_artifact(
+				&artifact_path,
+				&params,
+				execution_seed,
+				memory_limit,
+				&disabled_host_functions,
+				&executor,
+			).await;
 			send_response(&mut stream, response).await?;
 		}
 	});
@@ -212,6 +288,9 @@ pub fn worker_entrypoint(socket_path: &str) {
 async fn validate_using_artifact(
 	artifact_path: &Path,
 	params: &[u8],
+	execution_seed: Option<[u8; 32]>,
+	memory_limit: Option<u64>,
+	disabled_host_functions: &[String],
 	spawner: &TaskExecutor,
 ) -> Response {
 	let artifact_bytes = match async_std::fs::read(artifact_path).await {
@@ -244,14 +323,42 @@ async fn validate_using_artifact(
 		Artifact::Compiled { compiled_artifact } => compiled_artifact,
 	};
 
+	let baseline_memory = match memory_limit {
+		Some(_) if cfg!(target_os = "linux") => match reset_peak_resident_memory() {
+			Err(e) => return Response::InternalError(format!("memory measurement: {:?}", e)),
+			Ok(baseline) => Some(baseline),
+		},
+		_ => None,
+	};
+
 	let validation_started_at = Instant::now();
+	let execution_result = unsafe {
+		// SAFETY: this should be safe since the compiled artifact passed here comes from the
+		//         file created by the prepare workers. These files are obtained by calling
+		//         [`executor_intf::prepare`].
+		crate::executor_intf::execute(
+			compiled_artifact,
+			params,
+			execution_seed,
+			disabled_host_functions,
+			spawner.clone(),
+		)
+	};
+
+	// The breach takes precedence over the outcome of the execution, which may well have failed
+	// because of the memory pressure.
+	if let (Some(limit), Some(baseline)) = (memory_limit, baseline_memory) {
+		match peak_resident_memory() {
+			Err(e) => return Response::InternalError(format!("memory measurement: {:?}", e)),
+			Ok(peak) if peak.saturating_sub(baseline) > limit => {
+				return Response::MemoryLimitExceeded;
+			}
+			Ok(_) => {}
+		}
+	}
+
 	let descriptor_bytes =
-		match unsafe {
-			// SAFETY: this should be safe since the compiled artifact passed here comes from the
-			//         file created by the prepare workers. These files are obtained by calling
-			//         [`executor_intf::prepare`].
-			crate::executor_intf::execute(compiled_artifact, params, spawner.clone())
-		 } {
+		match execution_result {
 			Err(err) => {
 				return Response::format_invalid("execute", &err.to_string());
 			}
@@ -275,3 +382,23 @@ async fn validate_using_artifact(
 		duration_ms,
 	}
 }
+
+/// Resets the peak resident memory of this process to the current resident memory and returns it,
+/// in bytes.
+fn reset_peak_resident_memory() -> std::io::Result<u64> {
+	std::fs::write("/proc/self/clear_refs", "5")?;
+	peak_resident_memory()
+}
+
+/// Returns the peak resident memory of this process since the last reset, in bytes.
+fn peak_resident_memory() -> std::io::Result<u64> {
+	let status = std::fs::read_to_string("/proc/self/status")?;
+	status
+		.lines()
+		.find_map(|line| line.strip_prefix("VmHWM:"))
+		.and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+		.map(|kb| kb * 1024)
+		.ok_or_else(|| {
+			std::io::Error::new(std::io::ErrorKind::Other, "VmHWM is missing from /proc/self/status")
+		})
+}
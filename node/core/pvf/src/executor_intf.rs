@@ -57,6 +57,12 @@ pub fn prepare(blob: RuntimeBlob) -> Result<Vec<u8>, sc_executor_common::error::
 /// Executes the given PVF in the form of a compiled artifact and returns the result of execution
 /// upon success.
 ///
+/// If `execution_seed` is set, the PVF may draw randomness through the offchain `random_seed` host
+/// function, which yields that seed. This is only meant for reproducing executions in tests.
+///
+/// The host functions named in `disabled_host_functions` are withheld from the PVF. Since missing
+/// imports are allowed, the PVF still instantiates, but calling any of them fails the execution.
+///
 /// # Safety
 ///
 /// The compiled artifact must be produced with [`prepare`]. Not following this guidance can lead
@@ -64,12 +70,21 @@ pub fn prepare(blob: RuntimeBlob) -> Result<Vec<u8>, sc_executor_common::error::
 pub unsafe fn execute(
 	compiled_artifact: &[u8],
 	params: &[u8],
+	execution_seed: Option<[u8; 32]>,
+	disabled_host_functions: &[String],
 	spawner: impl sp_core::traits::SpawnNamed + 'static,
 ) -> Result<Vec<u8>, sc_executor_common::error::Error> {
 	let mut extensions = sp_externalities::Extensions::new();
 
 	extensions.register(sp_core::traits::TaskExecutorExt::new(spawner));
 	extensions.register(sp_core::traits::ReadRuntimeVersionExt::new(ReadRuntimeVersion));
+	if let Some(seed) = execution_seed {
+		// Only the `random_seed` host function is exposed, so the rest of the offchain
+		// externalities stay out of reach of the PVF.
+		let (offchain, state) = sp_core::offchain::testing::TestOffchainExt::new();
+		state.write().seed = seed;
+		extensions.register(sp_core::offchain::OffchainWorkerExt::new(offchain));
+	}
 
 	let mut ext = ValidationExternalities(extensions);
 
@@ -77,7 +92,7 @@ pub unsafe fn execute(
 		let runtime = sc_executor_wasmtime::create_runtime_from_artifact(
 			compiled_artifact,
 			CONFIG,
-			HostFunctions::host_functions(),
+			enabled_host_functions(execution_seed.is_some(), disabled_host_functions),
 		)?;
 		runtime
 			.new_instance()?
@@ -85,6 +100,26 @@ pub unsafe fn execute(
 	})?
 }
 
+/// The host function through which seeded executions draw their randomness.
+const RANDOM_SEED_HOST_FUNCTION: &str = "ext_offchain_random_seed_version_1";
+
+/// The host functions exposed to PVFs, except for the ones with the given names. The `random_seed`
+/// host function is only exposed to seeded executions.
+fn enabled_host_functions(
+	seeded: bool,
+	disabled: &[String],
+) -> Vec<&'static dyn sp_wasm_interface::Function> {
+	let random_seed = sp_io::offchain::HostFunctions::host_functions()
+		.into_iter()
+		.filter(|function| seeded && function.name() == RANDOM_SEED_HOST_FUNCTION);
+
+	HostFunctions::host_functions()
+		.into_iter()
+		.chain(random_seed)
+		.filter(|function| !disabled.iter().any(|name| name == function.name()))
+		.collect()
+}
+
 type HostFunctions = (
 	sp_io::misc::HostFunctions,
 	sp_io::crypto::HostFunctions,
@@ -273,3 +308,30 @@ impl sp_core::traits::ReadRuntimeVersion for ReadRuntimeVersion {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn disabled_host_functions_are_withheld() {
+		let all = enabled_host_functions(false, &[]);
+		let disabled = all[0].name().to_owned();
+
+		let enabled = enabled_host_functions(false, &[disabled.clone()]);
+		assert_eq!(enabled.len(), all.len() - 1);
+		assert!(enabled.iter().all(|function| function.name() != disabled));
+	}
+
+	#[test]
+	fn random_seed_is_only_exposed_to_seeded_executions() {
+		let exposes_random_seed = |seeded| {
+			enabled_host_functions(seeded, &[])
+				.iter()
+				.any(|function| function.name() == RANDOM_SEED_HOST_FUNCTION)
+		};
+
+		assert!(!exposes_random_seed(false));
+		assert!(exposes_random_seed(true));
+	}
+}
@@ -21,9 +21,10 @@
 //! [`ValidationHost`], that allows communication with that event-loop.
 
 use crate::{
-	Priority, Pvf, ValidationError,
-	artifacts::{Artifacts, ArtifactState, ArtifactId},
+	Priority, Pvf, ValidationError, PrepareError,
+	artifacts::{Artifact, Artifacts, ArtifactState, ArtifactId},
 	execute, prepare,
+	worker_common::SpawnErr,
 };
 use std::{
 	collections::HashMap,
@@ -33,7 +34,7 @@ use always_assert::never;
 use async_std::{
 	path::{Path, PathBuf},
 };
-use polkadot_parachain::primitives::ValidationResult;
+use polkadot_parachain::primitives::{ValidationCodeHash, ValidationResult};
 use futures::{
 	Future, FutureExt, SinkExt, StreamExt,
 	channel::{mpsc, oneshot},
@@ -42,6 +43,9 @@ use futures::{
 /// An alias to not spell the type for the oneshot sender for the PVF execution result.
 pub(crate) type ResultSender = oneshot::Sender<Result<ValidationResult, ValidationError>>;
 
+/// An alias to not spell the type for the oneshot sender for the PVF preparation result.
+pub type PrepareResultSender = oneshot::Sender<Result<(), PrepareError>>;
+
 /// A handle to the async process serving the validation host requests.
 #[derive(Clone)]
 pub struct ValidationHost {
@@ -49,6 +53,11 @@ pub struct ValidationHost {
 }
 
 impl ValidationHost {
+	/// Returns whether the validation host is still running, i.e. can accept requests.
+	pub fn is_running(&self) -> bool {
+		!self.to_host_tx.is_closed()
+	}
+
 	/// Execute PVF with the given code, params and priority. The result of execution will be sent
 	/// to the provided result sender.
 	///
@@ -68,6 +77,60 @@ impl ValidationHost {
 				pvf,
 				params,
 				priority,
+				execution_seed: None,
+				result_tx,
+			})
+			.await
+			.map_err(|_| "the inner loop hung up".to_string())
+	}
+
+	/// Like [`ValidationHost::execute_pvf`], but the PVF may draw randomness through the offchain
+	/// `random_seed` host function, which yields the given seed.
+	///
+	/// This is meant for reproducing executions in tests only: PVFs have no access to randomness
+	/// in consensus and the outcome of a seeded execution must never be acted upon.
+	///
+	/// Returns an error if the request cannot be sent to the validation host, i.e. if it shut down.
+	pub async fn execute_seeded_pvf(
+		&mut self,
+		pvf: Pvf,
+		params: Vec<u8>,
+		priority: Priority,
+		execution_seed: [u8; 32],
+		result_tx: ResultSender,
+	) -> Result<(), String> {
+		self.to_host_tx
+			.send(ToHost::ExecutePvf {
+				pvf,
+				params,
+				priority,
+				execution_seed: Some(execution_seed),
+				result_tx,
+			})
+			.await
+			.map_err(|_| "the inner loop hung up".to_string())
+	}
+
+	/// Execute the already prepared PVF whose code has the given hash, with the given params and
+	/// priority. The result of execution will be sent to the provided result sender.
+	///
+	/// Unlike [`ValidationHost::execute_pvf`], this doesn't prepare the PVF if it isn't known. The
+	/// result sender is dropped without a result in that case, so that the caller can fall back to
+	/// executing the PVF from its code. A PVF that is being prepared is executed once prepared.
+	///
+	/// Returns an error if the request cannot be sent to the validation host, i.e. if it shut down.
+	pub async fn execute_prepared_pvf(
+		&mut self,
+		code_hash: ValidationCodeHash,
+		params: Vec<u8>,
+		priority: Priority,
+		result_tx: ResultSender,
+	) -> Result<(), String> {
+		self.to_host_tx
+			.send(ToHost::ExecutePreparedPvf {
+				code_hash,
+				params,
+				priority,
 				result_tx,
 			})
 			.await
@@ -86,6 +149,36 @@ impl ValidationHost {
 			.await
 			.map_err(|_| "the inner loop hung up".to_string())
 	}
+
+	/// Prepare the given PVF without executing it, e.g. for pre-checking it. The outcome of the
+	/// preparation will be sent to the provided result sender once it is known. If the PVF is
+	/// already prepared, the outcome of the earlier preparation is sent.
+	///
+	/// Returns an error if the request cannot be sent to the validation host, i.e. if it shut down.
+	pub async fn precheck_pvf(
+		&mut self,
+		pvf: Pvf,
+		result_tx: PrepareResultSender,
+	) -> Result<(), String> {
+		self.to_host_tx
+			.send(ToHost::PrecheckPvf { pvf, result_tx })
+			.await
+			.map_err(|_| "the inner loop hung up".to_string())
+	}
+
+	/// Sends a signal to the validation host requesting to delete all the prepared artifacts. The
+	/// number of deleted artifacts will be sent to the provided result sender.
+	///
+	/// The artifacts with executions queued or running are kept. The others will be prepared again
+	/// the next time they are needed.
+	///
+	/// Returns an error if the request cannot be sent to the validation host, i.e. if it shut down.
+	pub async fn flush_artifacts(&mut self, result_tx: oneshot::Sender<usize>) -> Result<(), String> {
+		self.to_host_tx
+			.send(ToHost::FlushArtifacts { result_tx })
+			.await
+			.map_err(|_| "the inner loop hung up".to_string())
+	}
 }
 
 enum ToHost {
@@ -93,11 +186,25 @@ enum ToHost {
 		pvf: Pvf,
 		params: Vec<u8>,
 		priority: Priority,
+		execution_seed: Option<[u8; 32]>,
+		result_tx: ResultSender,
+	},
+	ExecutePreparedPvf {
+		code_hash: ValidationCodeHash,
+		params: Vec<u8>,
+		priority: Priority,
 		result_tx: ResultSender,
 	},
 	HeadsUp {
 		active_pvfs: Vec<Pvf>,
 	},
+	PrecheckPvf {
+		pvf: Pvf,
+		result_tx: PrepareResultSender,
+	},
+	FlushArtifacts {
+		result_tx: oneshot::Sender<usize>,
+	},
 }
 
 /// Configuration for the validation host.
@@ -119,6 +226,31 @@ pub struct Config {
 	pub execute_worker_spawn_timeout: Duration,
 	/// The maximum number of execute workers that can run at the same time.
 	pub execute_workers_max_num: usize,
+	/// The time after which an execute worker that hasn't executed anything is terminated. The
+	/// worker is respawned on demand. `None` means idle workers are kept around indefinitely.
+	pub execute_worker_idle_timeout: Option<Duration>,
+	/// The schedule by which respawning execute workers is delayed while they keep dying, e.g.
+	/// due to a PVF crashing them. `None` respawns them right away.
+	pub execute_worker_respawn_backoff: Option<crate::RespawnBackoff>,
+	/// The amount of memory, in bytes, a single PVF execution may use on top of what the worker
+	/// used before it. Exceeding it fails the execution with [`ValidationError::InternalError`], as
+	/// the measurement isn't precise enough to render the candidate invalid. Only supported on
+	/// Linux and ignored elsewhere. `None` doesn't limit the memory.
+	pub execute_memory_limit: Option<u64>,
+	/// The names of the host functions withheld from PVFs, as imported by them, e.g.
+	/// `ext_logging_log_version_1`. A PVF calling any of them fails its execution, rendering the
+	/// candidate invalid.
+	pub execute_disabled_host_functions: Vec<String>,
+	/// The time allotted for preparing a PVF. A PVF that doesn't prepare in time can't be
+	/// executed. Defaults to [`crate::COMPILATION_TIMEOUT`].
+	pub prepare_timeout: Duration,
+	/// The time allotted for a single PVF execution. Exceeding it results in
+	/// [`crate::InvalidCandidate::HardTimeout`]. Defaults to [`crate::EXECUTION_TIMEOUT`].
+	pub execute_timeout: Duration,
+	/// The maximum total size, in bytes, of the prepared artifacts kept in the cache. Once it's
+	/// exceeded, the least recently needed artifacts are pruned. `None` only prunes the artifacts
+	/// that weren't needed for a day.
+	pub max_artifacts_cache_size: Option<u64>,
 }
 
 impl Config {
@@ -137,10 +269,40 @@ impl Config {
 			execute_worker_program_path: program_path,
 			execute_worker_spawn_timeout: Duration::from_secs(3),
 			execute_workers_max_num: 5,
+			execute_worker_idle_timeout: None,
+			execute_worker_respawn_backoff: None,
+			execute_memory_limit: None,
+			execute_disabled_host_functions: Vec::new(),
+			prepare_timeout: crate::COMPILATION_TIMEOUT,
+			execute_timeout: crate::EXECUTION_TIMEOUT,
+			max_artifacts_cache_size: None,
 		}
 	}
 }
 
+/// Spawns a worker of each kind with the given program path and checks that both are compatible
+/// with this host, returning a descriptive error otherwise.
+pub async fn check_worker_compatibility(
+	program_path: std::path::PathBuf,
+	spawn_timeout: Duration,
+) -> Result<(), String> {
+	let program_path = PathBuf::from(program_path);
+	let describe = |kind: &str, err: SpawnErr| match err {
+		SpawnErr::Handshake(err) => err,
+		err => format!("incompatible worker binary: failed to spawn {} worker: {:?}", kind, err),
+	};
+
+	// The workers are shut down as soon as their handles are dropped.
+	let _ = execute::spawn_worker(&program_path, spawn_timeout)
+		.await
+		.map_err(|err| describe("an execute", err))?;
+	let _ = prepare::spawn_worker(&program_path, spawn_timeout)
+		.await
+		.map_err(|err| describe("a prepare", err))?;
+
+	Ok(())
+}
+
 /// Start the validation host.
 ///
 /// Returns a [handle][`ValidationHost`] to the started validation host and the future. The future
@@ -154,10 +316,18 @@ pub fn start(config: Config) -> (ValidationHost, impl Future<Output = ()>) {
 
 	let validation_host = ValidationHost { to_host_tx };
 
+	if config.execute_memory_limit.is_some() && !cfg!(target_os = "linux") {
+		tracing::warn!(
+			target: crate::LOG_TARGET,
+			"the execution memory limit is only supported on Linux and is ignored",
+		);
+	}
+
 	let (to_prepare_pool, from_prepare_pool, run_prepare_pool) = prepare::start_pool(
 		config.prepare_worker_program_path.clone(),
 		config.cache_path.clone(),
 		config.prepare_worker_spawn_timeout,
+		config.prepare_timeout,
 	);
 
 	let (to_prepare_queue_tx, from_prepare_queue_rx, run_prepare_queue) = prepare::start_queue(
@@ -172,6 +342,11 @@ pub fn start(config: Config) -> (ValidationHost, impl Future<Output = ()>) {
 		config.execute_worker_program_path.to_owned(),
 		config.execute_workers_max_num,
 		config.execute_worker_spawn_timeout,
+		config.execute_worker_idle_timeout,
+		config.execute_worker_respawn_backoff,
+		config.execute_memory_limit,
+		config.execute_disabled_host_functions.clone(),
+		config.execute_timeout,
 	);
 
 	let (to_sweeper_tx, to_sweeper_rx) = mpsc::channel(100);
@@ -192,6 +367,7 @@ pub fn start(config: Config) -> (ValidationHost, impl Future<Output = ()>) {
 				cache_path: config.cache_path,
 				cleanup_pulse_interval: Duration::from_secs(3600),
 				artifact_ttl: Duration::from_secs(3600 * 24),
+				max_artifacts_cache_size: config.max_artifacts_cache_size,
 				artifacts,
 				to_host_rx,
 				to_prepare_queue_tx,
@@ -199,6 +375,7 @@ pub fn start(config: Config) -> (ValidationHost, impl Future<Output = ()>) {
 				to_execute_queue_tx,
 				to_sweeper_tx,
 				awaiting_prepare: AwaitingPrepare::default(),
+				awaiting_precheck: AwaitingPrecheck::default(),
 			},
 			run_prepare_pool,
 			run_prepare_queue,
@@ -216,6 +393,7 @@ pub fn start(config: Config) -> (ValidationHost, impl Future<Output = ()>) {
 #[derive(Debug)]
 struct PendingExecutionRequest {
 	params: Vec<u8>,
+	execution_seed: Option<[u8; 32]>,
 	result_tx: ResultSender,
 }
 
@@ -225,11 +403,17 @@ struct PendingExecutionRequest {
 struct AwaitingPrepare(HashMap<ArtifactId, Vec<PendingExecutionRequest>>);
 
 impl AwaitingPrepare {
-	fn add(&mut self, artifact_id: ArtifactId, params: Vec<u8>, result_tx: ResultSender) {
+	fn add(
+		&mut self,
+		artifact_id: ArtifactId,
+		params: Vec<u8>,
+		execution_seed: Option<[u8; 32]>,
+		result_tx: ResultSender,
+	) {
 		self.0
 			.entry(artifact_id)
 			.or_default()
-			.push(PendingExecutionRequest { params, result_tx });
+			.push(PendingExecutionRequest { params, execution_seed, result_tx });
 	}
 
 	fn take(&mut self, artifact_id: &ArtifactId) -> Vec<PendingExecutionRequest> {
@@ -237,10 +421,26 @@ impl AwaitingPrepare {
 	}
 }
 
+/// A mapping from an artifact ID which is in preparation state to the senders of the pre-checking
+/// requests awaiting the outcome of its preparation.
+#[derive(Default)]
+struct AwaitingPrecheck(HashMap<ArtifactId, Vec<PrepareResultSender>>);
+
+impl AwaitingPrecheck {
+	fn add(&mut self, artifact_id: ArtifactId, result_tx: PrepareResultSender) {
+		self.0.entry(artifact_id).or_default().push(result_tx);
+	}
+
+	fn take(&mut self, artifact_id: &ArtifactId) -> Vec<PrepareResultSender> {
+		self.0.remove(artifact_id).unwrap_or_default()
+	}
+}
+
 struct Inner {
 	cache_path: PathBuf,
 	cleanup_pulse_interval: Duration,
 	artifact_ttl: Duration,
+	max_artifacts_cache_size: Option<u64>,
 	artifacts: Artifacts,
 
 	to_host_rx: mpsc::Receiver<ToHost>,
@@ -252,6 +452,7 @@ struct Inner {
 	to_sweeper_tx: mpsc::Sender<PathBuf>,
 
 	awaiting_prepare: AwaitingPrepare,
+	awaiting_precheck: AwaitingPrecheck,
 }
 
 #[derive(Debug)]
@@ -262,6 +463,7 @@ async fn run(
 		cache_path,
 		cleanup_pulse_interval,
 		artifact_ttl,
+		max_artifacts_cache_size,
 		mut artifacts,
 		to_host_rx,
 		from_prepare_queue_rx,
@@ -269,6 +471,7 @@ async fn run(
 		mut to_execute_queue_tx,
 		mut to_sweeper_tx,
 		mut awaiting_prepare,
+		mut awaiting_precheck,
 	}: Inner,
 	prepare_pool: impl Future<Output = ()> + Unpin,
 	prepare_queue: impl Future<Output = ()> + Unpin,
@@ -336,7 +539,9 @@ async fn run(
 					&mut artifacts,
 					&mut to_prepare_queue_tx,
 					&mut to_execute_queue_tx,
+					&mut to_sweeper_tx,
 					&mut awaiting_prepare,
+					&mut awaiting_precheck,
 					to_host,
 				)
 				.await);
@@ -358,7 +563,10 @@ async fn run(
 					&cache_path,
 					&mut artifacts,
 					&mut to_execute_queue_tx,
+					&mut to_sweeper_tx,
 					&mut awaiting_prepare,
+					&mut awaiting_precheck,
+					max_artifacts_cache_size,
 					artifact_id,
 				).await);
 			},
@@ -371,7 +579,9 @@ async fn handle_to_host(
 	artifacts: &mut Artifacts,
 	prepare_queue: &mut mpsc::Sender<prepare::ToQueue>,
 	execute_queue: &mut mpsc::Sender<execute::ToQueue>,
+	sweeper_tx: &mut mpsc::Sender<PathBuf>,
 	awaiting_prepare: &mut AwaitingPrepare,
+	awaiting_precheck: &mut AwaitingPrecheck,
 	to_host: ToHost,
 ) -> Result<(), Fatal> {
 	match to_host {
@@ -379,6 +589,7 @@ async fn handle_to_host(
 			pvf,
 			params,
 			priority,
+			execution_seed,
 			result_tx,
 		} => {
 			handle_execute_pvf(
@@ -390,6 +601,26 @@ async fn handle_to_host(
 				pvf,
 				params,
 				priority,
+				execution_seed,
+				result_tx,
+			)
+			.await?;
+		}
+		ToHost::ExecutePreparedPvf {
+			code_hash,
+			params,
+			priority,
+			result_tx,
+		} => {
+			handle_execute_prepared_pvf(
+				cache_path,
+				artifacts,
+				prepare_queue,
+				execute_queue,
+				awaiting_prepare,
+				ArtifactId::new(code_hash),
+				params,
+				priority,
 				result_tx,
 			)
 			.await?;
@@ -397,6 +628,20 @@ async fn handle_to_host(
 		ToHost::HeadsUp { active_pvfs } => {
 			handle_heads_up(artifacts, prepare_queue, active_pvfs).await?;
 		}
+		ToHost::PrecheckPvf { pvf, result_tx } => {
+			handle_precheck_pvf(
+				cache_path,
+				artifacts,
+				prepare_queue,
+				awaiting_precheck,
+				pvf,
+				result_tx,
+			)
+			.await?;
+		}
+		ToHost::FlushArtifacts { result_tx } => {
+			handle_flush_artifacts(cache_path, sweeper_tx, artifacts, result_tx).await?;
+		}
 	}
 
 	Ok(())
@@ -411,6 +656,7 @@ async fn handle_execute_pvf(
 	pvf: Pvf,
 	params: Vec<u8>,
 	priority: Priority,
+	execution_seed: Option<[u8; 32]>,
 	result_tx: ResultSender,
 ) -> Result<(), Fatal> {
 	let artifact_id = pvf.as_artifact_id();
@@ -418,7 +664,7 @@ async fn handle_execute_pvf(
 	if let Some(state) = artifacts.artifact_state_mut(&artifact_id) {
 		match state {
 			ArtifactState::Prepared {
-				ref mut last_time_needed,
+				ref mut last_time_needed, ..
 			} => {
 				*last_time_needed = SystemTime::now();
 
@@ -426,7 +672,9 @@ async fn handle_execute_pvf(
 					execute_queue,
 					execute::ToQueue::Enqueue {
 						artifact_path: artifact_id.path(cache_path),
+						lease: artifacts.lease(&artifact_id),
 						params,
+						execution_seed,
 						result_tx,
 					},
 				)
@@ -442,7 +690,7 @@ async fn handle_execute_pvf(
 				)
 				.await?;
 
-				awaiting_prepare.add(artifact_id, params, result_tx);
+				awaiting_prepare.add(artifact_id, params, execution_seed, result_tx);
 			}
 		}
 	} else {
@@ -451,12 +699,121 @@ async fn handle_execute_pvf(
 		artifacts.insert_preparing(artifact_id.clone());
 		send_prepare(prepare_queue, prepare::ToQueue::Enqueue { priority, pvf }).await?;
 
-		awaiting_prepare.add(artifact_id, params, result_tx);
+		awaiting_prepare.add(artifact_id, params, execution_seed, result_tx);
 	}
 
 	return Ok(());
 }
 
+async fn handle_execute_prepared_pvf(
+	cache_path: &Path,
+	artifacts: &mut Artifacts,
+	prepare_queue: &mut mpsc::Sender<prepare::ToQueue>,
+	execute_queue: &mut mpsc::Sender<execute::ToQueue>,
+	awaiting_prepare: &mut AwaitingPrepare,
+	artifact_id: ArtifactId,
+	params: Vec<u8>,
+	priority: Priority,
+	result_tx: ResultSender,
+) -> Result<(), Fatal> {
+	match artifacts.artifact_state_mut(&artifact_id) {
+		Some(ArtifactState::Prepared {
+			ref mut last_time_needed, ..
+		}) => {
+			*last_time_needed = SystemTime::now();
+
+			send_execute(
+				execute_queue,
+				execute::ToQueue::Enqueue {
+					artifact_path: artifact_id.path(cache_path),
+					lease: artifacts.lease(&artifact_id),
+					params,
+					execution_seed: None,
+					result_tx,
+				},
+			)
+			.await?;
+		}
+		Some(ArtifactState::Preparing) => {
+			send_prepare(
+				prepare_queue,
+				prepare::ToQueue::Amend {
+					priority,
+					artifact_id: artifact_id.clone(),
+				},
+			)
+			.await?;
+
+			awaiting_prepare.add(artifact_id, params, None, result_tx);
+		}
+		None => {
+			// Without the code the artifact can't be prepared. Dropping the result sender lets
+			// the requester know to execute the PVF from its code instead.
+			drop(result_tx);
+		}
+	}
+
+	Ok(())
+}
+
+async fn handle_precheck_pvf(
+	cache_path: &Path,
+	artifacts: &mut Artifacts,
+	prepare_queue: &mut mpsc::Sender<prepare::ToQueue>,
+	awaiting_precheck: &mut AwaitingPrecheck,
+	pvf: Pvf,
+	result_tx: PrepareResultSender,
+) -> Result<(), Fatal> {
+	let artifact_id = pvf.as_artifact_id();
+
+	if let Some(state) = artifacts.artifact_state_mut(&artifact_id) {
+		match state {
+			ArtifactState::Prepared {
+				ref mut last_time_needed, ..
+			} => {
+				*last_time_needed = SystemTime::now();
+
+				let outcome = read_prepare_outcome(&artifact_id.path(cache_path)).await;
+				let _ = result_tx.send(outcome);
+			}
+			ArtifactState::Preparing => {
+				// Pre-checking is not urgent, so the priority isn't amended.
+				awaiting_precheck.add(artifact_id, result_tx);
+			}
+		}
+	} else {
+		artifacts.insert_preparing(artifact_id.clone());
+		send_prepare(
+			prepare_queue,
+			prepare::ToQueue::Enqueue {
+				priority: Priority::Normal,
+				pvf,
+			},
+		)
+		.await?;
+
+		awaiting_precheck.add(artifact_id, result_tx);
+	}
+
+	Ok(())
+}
+
+/// Read the outcome of the preparation from the artifact at the given path.
+async fn read_prepare_outcome(artifact_path: &Path) -> Result<(), PrepareError> {
+	let bytes = async_std::fs::read(artifact_path)
+		.await
+		.map_err(|e| PrepareError::Failed(format!("failed to read the artifact: {:?}", e)))?;
+
+	match Artifact::deserialize(&bytes) {
+		Ok(Artifact::Compiled { .. }) => Ok(()),
+		Ok(Artifact::PrevalidationErr(msg)) | Ok(Artifact::PreparationErr(msg)) =>
+			Err(PrepareError::Invalid(msg)),
+		Ok(Artifact::DidntMakeIt) =>
+			Err(PrepareError::Failed("the preparation worker didn't make it".to_string())),
+		Err(e) => Err(PrepareError::Failed(format!("failed to decode the artifact: {}", e))),
+	}
+}
+
 async fn handle_heads_up(
 	artifacts: &mut Artifacts,
 	prepare_queue: &mut mpsc::Sender<prepare::ToQueue>,
@@ -500,9 +857,16 @@ async fn handle_prepare_done(
 	cache_path: &Path,
 	artifacts: &mut Artifacts,
 	execute_queue: &mut mpsc::Sender<execute::ToQueue>,
+	sweeper_tx: &mut mpsc::Sender<PathBuf>,
 	awaiting_prepare: &mut AwaitingPrepare,
+	awaiting_precheck: &mut AwaitingPrecheck,
+	max_artifacts_cache_size: Option<u64>,
 	artifact_id: ArtifactId,
 ) -> Result<(), Fatal> {
+	// Lease the artifact for the pending executions. Holding the lease until we're done also keeps
+	// the artifact from being evicted to make room for itself.
+	let lease = artifacts.lease(&artifact_id);
+
 	// Make some sanity checks and extract the current state.
 	let state = match artifacts.artifact_state_mut(&artifact_id) {
 		None => {
@@ -530,7 +894,7 @@ async fn handle_prepare_done(
 	// to be prepared.
 	let artifact_path = artifact_id.path(&cache_path);
 	let pending_requests = awaiting_prepare.take(&artifact_id);
-	for PendingExecutionRequest { params, result_tx } in pending_requests {
+	for PendingExecutionRequest { params, execution_seed, result_tx } in pending_requests {
 		if result_tx.is_canceled() {
 			// Preparation could've taken quite a bit of time and the requester may be not interested
 			// in execution anymore, in which case we just skip the request.
@@ -541,18 +905,39 @@ async fn handle_prepare_done(
 			execute_queue,
 			execute::ToQueue::Enqueue {
 				artifact_path: artifact_path.clone(),
+				lease: lease.clone(),
 				params,
+				execution_seed,
 				result_tx,
 			},
 		)
 		.await?;
 	}
 
+	let pending_prechecks = awaiting_precheck.take(&artifact_id);
+	if !pending_prechecks.is_empty() {
+		let outcome = read_prepare_outcome(&artifact_path).await;
+		for result_tx in pending_prechecks {
+			let _ = result_tx.send(outcome.clone());
+		}
+	}
+
 	// Now consider the artifact prepared.
+	let size = async_std::fs::metadata(&artifact_path).await.map(|m| m.len()).unwrap_or(0);
 	*state = ArtifactState::Prepared {
 		last_time_needed: SystemTime::now(),
+		size,
 	};
 
+	// Make room for the new artifact by evicting the ones that weren't needed for the longest time.
+	// Neither the new artifact nor the ones with executions queued or running are evicted.
+	if let Some(max_size) = max_artifacts_cache_size {
+		for artifact_id in artifacts.prune_to_size(max_size) {
+			let artifact_path = artifact_id.path(cache_path);
+			sweeper_tx.send(artifact_path).await.map_err(|_| Fatal)?;
+		}
+	}
+
 	Ok(())
 }
 
@@ -585,6 +970,24 @@ async fn handle_cleanup_pulse(
 	Ok(())
 }
 
+async fn handle_flush_artifacts(
+	cache_path: &Path,
+	sweeper_tx: &mut mpsc::Sender<PathBuf>,
+	artifacts: &mut Artifacts,
+	result_tx: oneshot::Sender<usize>,
+) -> Result<(), Fatal> {
+	let to_remove = artifacts.remove_prepared();
+	let removed = to_remove.len();
+	for artifact_id in to_remove {
+		let artifact_path = artifact_id.path(cache_path);
+		sweeper_tx.send(artifact_path).await.map_err(|_| Fatal)?;
+	}
+
+	let _ = result_tx.send(removed);
+
+	Ok(())
+}
+
 /// A simple task which sole purpose is to delete files thrown at it.
 async fn sweeper_task(mut sweeper_rx: mpsc::Receiver<PathBuf>) {
 	loop {
@@ -598,7 +1001,7 @@ async fn sweeper_task(mut sweeper_rx: mpsc::Receiver<PathBuf>) {
 }
 
 /// A stream that yields a pulse continuously at a given interval.
-fn pulse_every(interval: std::time::Duration) -> impl futures::Stream<Item = ()> {
+pub(crate) fn pulse_every(interval: std::time::Duration) -> impl futures::Stream<Item = ()> {
 	futures::stream::unfold(interval, {
 		|interval| async move {
 			futures_timer::Delay::new(interval).await;
@@ -642,6 +1045,7 @@ mod tests {
 	struct Builder {
 		cleanup_pulse_interval: Duration,
 		artifact_ttl: Duration,
+		max_artifacts_cache_size: Option<u64>,
 		artifacts: Artifacts,
 	}
 
@@ -651,6 +1055,7 @@ mod tests {
 				// these are selected high to not interfere in tests in which pruning is irrelevant.
 				cleanup_pulse_interval: Duration::from_secs(3600),
 				artifact_ttl: Duration::from_secs(3600),
+				max_artifacts_cache_size: None,
 
 				artifacts: Artifacts::empty(),
 			}
@@ -677,6 +1082,7 @@ mod tests {
 			Builder {
 				cleanup_pulse_interval,
 				artifact_ttl,
+				max_artifacts_cache_size,
 				artifacts,
 			}: Builder,
 		) -> Self {
@@ -695,6 +1101,7 @@ mod tests {
 					cache_path,
 					cleanup_pulse_interval,
 					artifact_ttl,
+					max_artifacts_cache_size,
 					artifacts,
 					to_host_rx,
 					to_prepare_queue_tx,
@@ -702,6 +1109,7 @@ mod tests {
 					to_execute_queue_tx,
 					to_sweeper_tx,
 					awaiting_prepare: AwaitingPrepare::default(),
+					awaiting_precheck: AwaitingPrecheck::default(),
 				},
 				mk_dummy_loop(),
 				mk_dummy_loop(),
@@ -826,8 +1234,8 @@ mod tests {
 		let mut builder = Builder::default();
 		builder.cleanup_pulse_interval = Duration::from_millis(100);
 		builder.artifact_ttl = Duration::from_millis(500);
-		builder.artifacts.insert_prepared(artifact_id(1), mock_now);
-		builder.artifacts.insert_prepared(artifact_id(2), mock_now);
+		builder.artifacts.insert_prepared(artifact_id(1), mock_now, 0);
+		builder.artifacts.insert_prepared(artifact_id(2), mock_now, 0);
 		let mut test = builder.build();
 		let mut host = test.host_handle();
 
@@ -853,6 +1261,123 @@ mod tests {
 		test.poll_ensure_to_sweeper_is_empty().await;
 	}
 
+	#[async_std::test]
+	async fn flushing_artifacts() {
+		let mut builder = Builder::default();
+		builder.artifacts.insert_prepared(artifact_id(1), SystemTime::now(), 0);
+		builder.artifacts.insert_prepared(artifact_id(2), SystemTime::now(), 0);
+		let mut test = builder.build();
+		let mut host = test.host_handle();
+
+		let (result_tx, result_rx) = oneshot::channel();
+		host.flush_artifacts(result_tx).await.unwrap();
+
+		let to_sweeper_rx = &mut test.to_sweeper_rx;
+		run_until(
+			&mut test.run,
+			async {
+				let removed = vec![
+					to_sweeper_rx.next().await.unwrap(),
+					to_sweeper_rx.next().await.unwrap(),
+				];
+				assert!(removed.contains(&artifact_path(1)));
+				assert!(removed.contains(&artifact_path(2)));
+			}
+			.boxed(),
+		)
+		.await;
+		assert_eq!(result_rx.await.unwrap(), 2);
+
+		// The next execution prepares the artifact afresh.
+		let (result_tx, _result_rx) = oneshot::channel();
+		host.execute_pvf(
+			Pvf::from_discriminator(1),
+			b"pvf1".to_vec(),
+			Priority::Normal,
+			result_tx,
+		)
+		.await
+		.unwrap();
+
+		assert_matches!(
+			test.poll_and_recv_to_prepare_queue().await,
+			prepare::ToQueue::Enqueue { .. }
+		);
+		test.poll_ensure_to_execute_queue_is_empty().await;
+	}
+
+	#[async_std::test]
+	async fn artifacts_in_use_are_neither_evicted_nor_flushed() {
+		let mut builder = Builder::default();
+		builder.max_artifacts_cache_size = Some(0);
+		builder.artifacts.insert_prepared(
+			artifact_id(1),
+			SystemTime::now() - Duration::from_secs(30),
+			100,
+		);
+		builder.artifacts.insert_prepared(
+			artifact_id(2),
+			SystemTime::now() - Duration::from_secs(20),
+			100,
+		);
+		let mut test = builder.build();
+		let mut host = test.host_handle();
+
+		let (result_tx, _result_rx_pvf_1) = oneshot::channel();
+		host.execute_prepared_pvf(
+			Pvf::from_discriminator(1).code_hash,
+			b"pvf1".to_vec(),
+			Priority::Normal,
+			result_tx,
+		)
+		.await
+		.unwrap();
+		let execution_pvf_1 = test.poll_and_recv_to_execute_queue().await;
+
+		let (result_tx, _result_rx_pvf_3) = oneshot::channel();
+		host.execute_pvf(
+			Pvf::from_discriminator(3),
+			b"pvf3".to_vec(),
+			Priority::Normal,
+			result_tx,
+		)
+		.await
+		.unwrap();
+		assert_matches!(
+			test.poll_and_recv_to_prepare_queue().await,
+			prepare::ToQueue::Enqueue { .. }
+		);
+		test.from_prepare_queue_tx
+			.send(prepare::FromQueue::Prepared(artifact_id(3)))
+			.await
+			.unwrap();
+		let execution_pvf_3 = test.poll_and_recv_to_execute_queue().await;
+
+		// Only the artifact which isn't executed is evicted to make room for the new one.
+		let to_sweeper_rx = &mut test.to_sweeper_rx;
+		run_until(
+			&mut test.run,
+			async {
+				assert_eq!(to_sweeper_rx.next().await.unwrap(), artifact_path(2));
+			}
+			.boxed(),
+		)
+		.await;
+		test.poll_ensure_to_sweeper_is_empty().await;
+
+		let (result_tx, result_rx) = oneshot::channel();
+		host.flush_artifacts(result_tx).await.unwrap();
+		assert_eq!(run_until(&mut test.run, result_rx.boxed()).await.unwrap(), 0);
+
+		// Once the executions conclude, their artifacts can be removed.
+		drop(execution_pvf_1);
+		drop(execution_pvf_3);
+
+		let (result_tx, result_rx) = oneshot::channel();
+		host.flush_artifacts(result_tx).await.unwrap();
+		assert_eq!(run_until(&mut test.run, result_rx.boxed()).await.unwrap(), 2);
+	}
+
 	#[async_std::test]
 	async fn amending_priority() {
 		let mut test = Builder::default().build();
@@ -1008,6 +1533,81 @@ mod tests {
 		);
 	}
 
+	#[async_std::test]
+	async fn execute_prepared_pvf_requests() {
+		let mut builder = Builder::default();
+		builder.artifacts.insert_prepared(artifact_id(1), SystemTime::now(), 0);
+		let mut test = builder.build();
+		let mut host = test.host_handle();
+
+		let (result_tx, _result_rx) = oneshot::channel();
+		host.execute_prepared_pvf(
+			Pvf::from_discriminator(1).code_hash,
+			b"pvf1".to_vec(),
+			Priority::Normal,
+			result_tx,
+		)
+		.await
+		.unwrap();
+
+		assert_matches!(
+			test.poll_and_recv_to_execute_queue().await,
+			execute::ToQueue::Enqueue { artifact_path: path, .. } if path == artifact_path(1)
+		);
+
+		// An unknown PVF is neither prepared nor executed, leaving the requester without a result.
+		let (result_tx, result_rx) = oneshot::channel();
+		host.execute_prepared_pvf(
+			Pvf::from_discriminator(2).code_hash,
+			b"pvf2".to_vec(),
+			Priority::Normal,
+			result_tx,
+		)
+		.await
+		.unwrap();
+
+		test.poll_ensure_to_execute_queue_is_empty().await;
+		assert_matches!(result_rx.await, Err(oneshot::Canceled));
+	}
+
+	#[async_std::test]
+	async fn precheck_pvf_requests() {
+		let mut test = Builder::default().build();
+		let mut host = test.host_handle();
+
+		let (result_tx, result_rx) = oneshot::channel();
+		host.precheck_pvf(Pvf::from_discriminator(1024), result_tx).await.unwrap();
+
+		assert_matches!(
+			test.poll_and_recv_to_prepare_queue().await,
+			prepare::ToQueue::Enqueue { priority: Priority::Normal, .. }
+		);
+
+		let artifact_path = artifact_path(1024);
+		async_std::fs::write(&artifact_path, Artifact::PreparationErr("boom".into()).serialize())
+			.await
+			.unwrap();
+
+		test.from_prepare_queue_tx
+			.send(prepare::FromQueue::Prepared(artifact_id(1024)))
+			.await
+			.unwrap();
+
+		let outcome = run_until(&mut test.run, result_rx.boxed()).await.unwrap();
+		assert_eq!(outcome, Err(PrepareError::Invalid("boom".into())));
+
+		// Nothing is executed, only prepared.
+		test.poll_ensure_to_execute_queue_is_empty().await;
+
+		// The outcome of a prepared PVF is known right away.
+		let (result_tx, result_rx) = oneshot::channel();
+		host.precheck_pvf(Pvf::from_discriminator(1024), result_tx).await.unwrap();
+		let outcome = run_until(&mut test.run, result_rx.boxed()).await.unwrap();
+		assert_eq!(outcome, Err(PrepareError::Invalid("boom".into())));
+
+		let _ = async_std::fs::remove_file(artifact_path).await;
+	}
+
 	#[async_std::test]
 	async fn cancellation() {
 		let mut test = Builder::default().build();
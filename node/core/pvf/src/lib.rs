@@ -88,13 +88,16 @@ mod worker_common;
 #[doc(hidden)]
 pub mod testing;
 
-pub use error::{ValidationError, InvalidCandidate};
+pub use error::{ValidationError, InvalidCandidate, PrepareError};
 pub use priority::Priority;
 pub use pvf::Pvf;
 
-pub use host::{start, Config, ValidationHost};
+pub use host::{start, check_worker_compatibility, Config, ValidationHost, PrepareResultSender};
 
 pub use execute::worker_entrypoint as execute_worker_entrypoint;
+pub use execute::{RespawnBackoff, EXECUTION_TIMEOUT};
 pub use prepare::worker_entrypoint as prepare_worker_entrypoint;
+pub use prepare::COMPILATION_TIMEOUT;
+pub use worker_common::WORKER_VERSION;
 
 const LOG_TARGET: &str = "parachain::pvf";
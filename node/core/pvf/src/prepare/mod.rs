@@ -28,4 +28,4 @@ mod worker;
 
 pub use queue::{ToQueue, FromQueue, start as start_queue};
 pub use pool::start as start_pool;
-pub use worker::worker_entrypoint;
+pub use worker::{worker_entrypoint, spawn as spawn_worker, COMPILATION_TIMEOUT};
@@ -106,6 +106,7 @@ struct Pool {
 	program_path: PathBuf,
 	cache_path: PathBuf,
 	spawn_timeout: Duration,
+	compilation_timeout: Duration,
 	to_pool: mpsc::Receiver<ToPool>,
 	from_pool: mpsc::UnboundedSender<FromPool>,
 	spawned: HopSlotMap<Worker, WorkerData>,
@@ -120,6 +121,7 @@ async fn run(
 		program_path,
 		cache_path,
 		spawn_timeout,
+		compilation_timeout,
 		to_pool,
 		mut from_pool,
 		mut spawned,
@@ -145,6 +147,7 @@ async fn run(
 					&program_path,
 					&cache_path,
 					spawn_timeout,
+					compilation_timeout,
 					&mut spawned,
 					&mut mux,
 					to_pool,
@@ -186,6 +189,7 @@ fn handle_to_pool(
 	program_path: &Path,
 	cache_path: &Path,
 	spawn_timeout: Duration,
+	compilation_timeout: Duration,
 	spawned: &mut HopSlotMap<Worker, WorkerData>,
 	mux: &mut Mux,
 	to_pool: ToPool,
@@ -209,7 +213,8 @@ fn handle_to_pool(
 							code,
 							cache_path.to_owned(),
 							artifact_path,
-							background_priority
+							background_priority,
+							compilation_timeout,
 						)
 						.boxed(),
 					);
@@ -265,9 +270,16 @@ async fn start_work_task(
 	cache_path: PathBuf,
 	artifact_path: PathBuf,
 	background_priority: bool,
+	compilation_timeout: Duration,
 ) -> PoolEvent {
-	let outcome =
-		worker::start_work(idle, code, &cache_path, artifact_path, background_priority).await;
+	let outcome = worker::start_work(
+		idle,
+		code,
+		&cache_path,
+		artifact_path,
+		background_priority,
+		compilation_timeout,
+	).await;
 	PoolEvent::StartWork(worker, outcome)
 }
 
@@ -329,6 +341,7 @@ pub fn start(
 	program_path: PathBuf,
 	cache_path: PathBuf,
 	spawn_timeout: Duration,
+	compilation_timeout: Duration,
 ) -> (
 	mpsc::Sender<ToPool>,
 	mpsc::UnboundedReceiver<FromPool>,
@@ -341,6 +354,7 @@ pub fn start(
 		program_path,
 		cache_path,
 		spawn_timeout,
+		compilation_timeout,
 		to_pool: to_pool_rx,
 		from_pool: from_pool_tx,
 		spawned: HopSlotMap::with_capacity_and_key(20),
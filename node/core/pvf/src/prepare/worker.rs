@@ -19,7 +19,7 @@ use crate::{
 	artifacts::Artifact,
 	worker_common::{
 		IdleWorker, SpawnErr, WorkerHandle, bytes_to_path, framed_recv, framed_send, path_to_bytes,
-		spawn_with_program_path, tmpfile_in, worker_event_loop,
+		recv_handshake, send_handshake, spawn_with_program_path, tmpfile_in, worker_event_loop,
 	},
 };
 use async_std::{
@@ -34,7 +34,8 @@ use std::{sync::Arc, time::Duration};
 const NICENESS_BACKGROUND: i32 = 10;
 const NICENESS_FOREGROUND: i32 = 0;
 
-const COMPILATION_TIMEOUT: Duration = Duration::from_secs(10);
+/// The time allotted for preparing a PVF, unless configured otherwise.
+pub const COMPILATION_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Spawns a new worker with the given program path that acts as the worker and the spawn timeout.
 ///
@@ -43,13 +44,17 @@ pub async fn spawn(
 	program_path: &Path,
 	spawn_timeout: Duration,
 ) -> Result<(IdleWorker, WorkerHandle), SpawnErr> {
-	spawn_with_program_path(
+	let (mut idle_worker, handle) = spawn_with_program_path(
 		"prepare",
 		program_path,
 		&["prepare-worker"],
 		spawn_timeout,
 	)
-	.await
+	.await?;
+
+	recv_handshake(&mut idle_worker.stream, spawn_timeout).await?;
+
+	Ok((idle_worker, handle))
 }
 
 pub enum Outcome {
@@ -67,12 +72,15 @@ pub enum Outcome {
 
 /// Given the idle token of a worker and parameters of work, communicates with the worker and
 /// returns the outcome.
+///
+/// A preparation that doesn't conclude within `compilation_timeout` leaves no usable artifact.
 pub async fn start_work(
 	worker: IdleWorker,
 	code: Arc<Vec<u8>>,
 	cache_path: &Path,
 	artifact_path: PathBuf,
 	background_priority: bool,
+	compilation_timeout: Duration,
 ) -> Outcome {
 	let IdleWorker { mut stream, pid } = worker;
 
@@ -162,7 +170,7 @@ pub async fn start_work(
 					}
 				}
 			},
-			_ = Delay::new(COMPILATION_TIMEOUT).fuse() => Selected::Deadline,
+			_ = Delay::new(compilation_timeout).fuse() => Selected::Deadline,
 		};
 
 		match selected {
@@ -274,6 +282,7 @@ fn renice(pid: u32, niceness: i32) {
 /// the path to the socket used to communicate with the host.
 pub fn worker_entrypoint(socket_path: &str) {
 	worker_event_loop("prepare", socket_path, |mut stream| async move {
+		send_handshake(&mut stream).await?;
 		loop {
 			let (code, dest) = recv_request(&mut stream).await?;
 
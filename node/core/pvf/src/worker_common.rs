@@ -35,6 +35,10 @@ use std::{
 };
 use pin_project::pin_project;
 
+/// The version a worker reports to the host right after connecting. The host only accepts workers
+/// reporting the same version as itself.
+pub const WORKER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
 /// This is publicly exposed only for integration tests.
 #[doc(hidden)]
 pub async fn spawn_with_program_path(
@@ -179,6 +183,8 @@ pub enum SpawnErr {
 	ProcessSpawn,
 	/// The deadline alloted for the worker spawning and connecting to the socket has elapsed.
 	AcceptTimeout,
+	/// The worker didn't complete the handshake or it reported an incompatible version.
+	Handshake(String),
 }
 
 /// This is a representation of a potentially running worker. Drop it and the process will be killed.
@@ -297,3 +303,66 @@ pub async fn framed_recv(r: &mut (impl AsyncRead + Unpin)) -> io::Result<Vec<u8>
 	r.read_exact(&mut buf).await?;
 	Ok(buf)
 }
+
+/// Report the version of the worker to the host. This is the first thing a worker sends.
+pub async fn send_handshake(stream: &mut UnixStream) -> io::Result<()> {
+	framed_send(stream, WORKER_VERSION.as_bytes()).await
+}
+
+/// Wait for a freshly spawned worker to report its version, failing if it doesn't within the spawn
+/// timeout or reports a version other than [`WORKER_VERSION`].
+pub async fn recv_handshake(stream: &mut UnixStream, spawn_timeout: Duration) -> Result<(), SpawnErr> {
+	futures::select! {
+		version = framed_recv(stream).fuse() => {
+			let version = version.map_err(|e| {
+				SpawnErr::Handshake(format!("incompatible worker binary: handshake failed: {:?}", e))
+			})?;
+			check_version(&String::from_utf8_lossy(&version)).map_err(SpawnErr::Handshake)
+		},
+		_ = Delay::new(spawn_timeout).fuse() => {
+			Err(SpawnErr::Handshake("incompatible worker binary: no handshake".to_string()))
+		},
+	}
+}
+
+fn check_version(version: &str) -> Result<(), String> {
+	if version != WORKER_VERSION {
+		return Err(format!(
+			"incompatible worker binary: expected v{}, got v{}",
+			WORKER_VERSION,
+			version,
+		));
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use assert_matches::assert_matches;
+
+	#[async_std::test]
+	async fn handshake_checks_the_worker_version() {
+		let (mut host, mut worker) = UnixStream::pair().unwrap();
+		let timeout = Duration::from_secs(1);
+
+		send_handshake(&mut worker).await.unwrap();
+		assert_matches!(recv_handshake(&mut host, timeout).await, Ok(()));
+
+		framed_send(&mut worker, b"0.0.0-mismatch").await.unwrap();
+		assert_matches!(
+			recv_handshake(&mut host, timeout).await,
+			Err(SpawnErr::Handshake(e)) if e == format!(
+				"incompatible worker binary: expected v{}, got v0.0.0-mismatch",
+				WORKER_VERSION,
+			)
+		);
+
+		// A worker not reporting its version at all, like one of an older binary, is rejected too.
+		assert_matches!(
+			recv_handshake(&mut host, Duration::from_millis(10)).await,
+			Err(SpawnErr::Handshake(e)) if e == "incompatible worker binary: no handshake"
+		);
+	}
+}
@@ -95,6 +95,41 @@ async fn terminates_on_timeout() {
 	}
 }
 
+#[async_std::test]
+async fn terminates_on_configured_timeout() {
+	let execute_timeout = std::time::Duration::from_millis(500);
+	let host = TestHost::new_with_config(|cfg| cfg.execute_timeout = execute_timeout);
+
+	// Prepare the PVF upfront, so that only the execution is timed.
+	let (result_tx, result_rx) = futures::channel::oneshot::channel();
+	host.host
+		.lock()
+		.await
+		.precheck_pvf(Pvf::from_code(halt::wasm_binary_unwrap().to_vec()), result_tx)
+		.await
+		.unwrap();
+	result_rx.await.unwrap().unwrap();
+
+	let start = std::time::Instant::now();
+	let result = host
+		.validate_candidate(
+			halt::wasm_binary_unwrap(),
+			ValidationParams {
+				block_data: BlockData(Vec::new()),
+				parent_head: Default::default(),
+				relay_parent_number: 1,
+				relay_parent_storage_root: Default::default(),
+			},
+		)
+		.await;
+
+	match result {
+		Err(ValidationError::InvalidCandidate(InvalidCandidate::HardTimeout)) => {}
+		r => panic!("{:?}", r),
+	}
+	assert!(start.elapsed() < polkadot_node_core_pvf::EXECUTION_TIMEOUT);
+}
+
 #[async_std::test]
 async fn parallel_execution() {
 	let host = TestHost::new();
@@ -41,6 +41,7 @@ const SESSION_INFO_CACHE_SIZE: usize = 64 * 1024;
 const DMQ_CONTENTS_CACHE_SIZE: usize = 64 * 1024;
 const INBOUND_HRMP_CHANNELS_CACHE_SIZE: usize = 64 * 1024;
 const CURRENT_BABE_EPOCH_CACHE_SIZE: usize = 64 * 1024;
+const VERSION_CACHE_SIZE: usize = 4 * 1024;
 
 struct ResidentSizeOf<T>(T);
 
@@ -84,6 +85,7 @@ pub(crate) struct RequestResultCache {
 	dmq_contents: MemoryLruCache<(Hash, ParaId), ResidentSizeOf<Vec<InboundDownwardMessage<BlockNumber>>>>,
 	inbound_hrmp_channels_contents: MemoryLruCache<(Hash, ParaId), ResidentSizeOf<BTreeMap<ParaId, Vec<InboundHrmpMessage<BlockNumber>>>>>,
 	current_babe_epoch: MemoryLruCache<Hash, DoesNotAllocate<Epoch>>,
+	version: MemoryLruCache<Hash, ResidentSizeOf<u32>>,
 }
 
 impl Default for RequestResultCache {
@@ -104,6 +106,7 @@ impl Default for RequestResultCache {
 			dmq_contents: MemoryLruCache::new(DMQ_CONTENTS_CACHE_SIZE),
 			inbound_hrmp_channels_contents: MemoryLruCache::new(INBOUND_HRMP_CHANNELS_CACHE_SIZE),
 			current_babe_epoch: MemoryLruCache::new(CURRENT_BABE_EPOCH_CACHE_SIZE),
+			version: MemoryLruCache::new(VERSION_CACHE_SIZE),
 		}
 	}
 }
@@ -228,6 +231,14 @@ impl RequestResultCache {
 	pub(crate) fn cache_current_babe_epoch(&mut self, relay_parent: Hash, epoch: Epoch) {
 		self.current_babe_epoch.insert(relay_parent, DoesNotAllocate(epoch));
 	}
+
+	pub(crate) fn version(&mut self, relay_parent: &Hash) -> Option<&u32> {
+		self.version.get(relay_parent).map(|v| &v.0)
+	}
+
+	pub(crate) fn cache_version(&mut self, relay_parent: Hash, version: u32) {
+		self.version.insert(relay_parent, ResidentSizeOf(version));
+	}
 }
 
 pub(crate) enum RequestResult {
@@ -246,4 +257,5 @@ pub(crate) enum RequestResult {
 	DmqContents(Hash, ParaId, Vec<InboundDownwardMessage<BlockNumber>>),
 	InboundHrmpChannelsContents(Hash, ParaId, BTreeMap<ParaId, Vec<InboundHrmpMessage<BlockNumber>>>),
 	CurrentBabeEpoch(Hash, Epoch),
+	Version(Hash, u32),
 }
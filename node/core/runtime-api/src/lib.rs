@@ -33,7 +33,7 @@ use polkadot_subsystem::{
 use polkadot_node_subsystem_util::metrics::{self, prometheus};
 use polkadot_primitives::v1::{Block, BlockId, Hash, ParachainHost};
 
-use sp_api::ProvideRuntimeApi;
+use sp_api::{ApiExt, ProvideRuntimeApi};
 use sp_authority_discovery::AuthorityDiscoveryApi;
 use sp_core::traits::SpawnNamed;
 use sp_consensus_babe::BabeApi;
@@ -136,6 +136,8 @@ impl<Client> RuntimeApiSubsystem<Client> where
 				self.requests_cache.cache_inbound_hrmp_channel_contents((relay_parent, para_id), contents),
 			CurrentBabeEpoch(relay_parent, epoch) =>
 				self.requests_cache.cache_current_babe_epoch(relay_parent, epoch),
+			Version(relay_parent, version) =>
+				self.requests_cache.cache_version(relay_parent, version),
 		}
 	}
 
@@ -204,6 +206,9 @@ impl<Client> RuntimeApiSubsystem<Client> where
 			Request::CurrentBabeEpoch(sender) =>
 				query!(current_babe_epoch(), sender)
 					.map(|sender| Request::CurrentBabeEpoch(sender)),
+			Request::Version(sender) =>
+				query!(version(), sender)
+					.map(|sender| Request::Version(sender)),
 		}
 	}
 
@@ -353,6 +358,18 @@ where
 		Request::DmqContents(id, sender) => query!(DmqContents, dmq_contents(id), sender),
 		Request::InboundHrmpChannelsContents(id, sender) => query!(InboundHrmpChannelsContents, inbound_hrmp_channels_contents(id), sender),
 		Request::CurrentBabeEpoch(sender) => query!(CurrentBabeEpoch, current_epoch(), sender),
+		Request::Version(sender) => {
+			let api = client.runtime_api();
+			let res = api.api_version::<dyn ParachainHost<Block>>(&BlockId::Hash(relay_parent))
+				.map_err(|e| RuntimeApiError::from(format!("{:?}", e)))
+				.and_then(|version| version.ok_or_else(|| {
+					RuntimeApiError::from("the parachain host runtime API is not exposed".to_string())
+				}));
+			metrics.on_request(res.is_ok());
+			let _ = sender.send(res.clone());
+
+			res.ok().map(|version| RequestResult::Version(relay_parent, version))
+		}
 	}
 }
 
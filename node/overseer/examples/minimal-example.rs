@@ -68,6 +68,7 @@ impl Subsystem1 {
 					PoV {
 						block_data: BlockData(Vec::new()),
 					}.into(),
+					Default::default(),
 					tx,
 				)
 			)).await;
@@ -79,6 +79,7 @@ impl<C> Subsystem<C> for TestSubsystem2
 									PoV {
 										block_data: BlockData(Vec::new()),
 									}.into(),
+									Default::default(),
 									tx,
 								)
 							)
@@ -840,7 +841,7 @@ impl<C, M> Subsystem<C> for CounterSubsystem
 fn test_candidate_validation_msg() -> CandidateValidationMessage {
 	let (sender, _) = oneshot::channel();
 	let pov = Arc::new(PoV { block_data: BlockData(Vec::new()) });
-	CandidateValidationMessage::ValidateFromChainState(Default::default(), pov, sender)
+	CandidateValidationMessage::ValidateFromChainState(Default::default(), pov, Default::default(), sender)
 }
 
 fn test_candidate_backing_msg() -> CandidateBackingMessage {
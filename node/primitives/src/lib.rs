@@ -30,6 +30,7 @@ use futures::Future;
 use parity_scale_codec::{Decode, Encode};
 use sp_keystore::{CryptoStore, SyncCryptoStorePtr, Error as KeystoreError};
 use sp_application_crypto::AppKey;
+use runtime_primitives::traits::AppVerify;
 
 pub use sp_core::traits::SpawnNamed;
 pub use sp_consensus_babe::{
@@ -41,7 +42,7 @@ use polkadot_primitives::v1::{
 	CompactStatement, EncodeAs, Hash, HashT, HeadData, Id as ParaId, OutboundHrmpMessage,
 	PersistedValidationData, Signed, UncheckedSigned, UpwardMessage, ValidationCode,
 	ValidatorIndex, ValidatorSignature, ValidDisputeStatementKind, InvalidDisputeStatementKind,
-	CandidateReceipt, ValidatorId, SessionIndex, DisputeStatement,
+	CandidateReceipt, ValidatorId, SessionIndex, DisputeStatement, ValidationCodeHash,
 };
 
 pub use polkadot_parachain::primitives::BlockData;
@@ -128,7 +129,7 @@ pub type SignedFullStatement = Signed<Statement, CompactStatement>;
 pub type UncheckedSignedFullStatement = UncheckedSigned<Statement, CompactStatement>;
 
 /// Candidate invalidity details
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum InvalidCandidate {
 	/// Failed to execute.`validate_block`. This includes function panicking.
 	ExecutionError(String),
@@ -156,16 +157,81 @@ pub enum InvalidCandidate {
 	ParaHeadHashMismatch,
 	/// Validation code hash does not match.
 	CodeHashMismatch,
+	/// PoV decompresses to more than the maximum PoV size.
+	PoVSizeExceeded(u64),
+	/// Executing the PVF twice yields different outputs.
+	NonDeterministic,
+}
+
+impl InvalidCandidate {
+	/// The stable numeric code of the reason, for consumers on the other side of an IPC boundary.
+	///
+	/// The codes are positive and never reassigned, new reasons get new codes:
+	///
+	/// | Reason                     | Code |
+	/// |----------------------------|------|
+	/// | `ExecutionError`           | 1    |
+	/// | `InvalidOutputs`           | 2    |
+	/// | `Timeout`                  | 3    |
+	/// | `ParamsTooLarge`           | 4    |
+	/// | `CodeTooLarge`             | 5    |
+	/// | `CodeDecompressionFailure` | 6    |
+	/// | `PoVDecompressionFailure`  | 7    |
+	/// | `BadReturn`                | 8    |
+	/// | `BadParent`                | 9    |
+	/// | `PoVHashMismatch`          | 10   |
+	/// | `BadSignature`             | 11   |
+	/// | `ParaHeadHashMismatch`     | 12   |
+	/// | `CodeHashMismatch`         | 13   |
+	/// | `PoVSizeExceeded`          | 14   |
+	/// | `NonDeterministic`         | 15   |
+	pub fn verdict_code(&self) -> i32 {
+		match self {
+			InvalidCandidate::ExecutionError(_) => 1,
+			InvalidCandidate::InvalidOutputs => 2,
+			InvalidCandidate::Timeout => 3,
+			InvalidCandidate::ParamsTooLarge(_) => 4,
+			InvalidCandidate::CodeTooLarge(_) => 5,
+			InvalidCandidate::CodeDecompressionFailure => 6,
+			InvalidCandidate::PoVDecompressionFailure => 7,
+			InvalidCandidate::BadReturn => 8,
+			InvalidCandidate::BadParent => 9,
+			InvalidCandidate::PoVHashMismatch => 10,
+			InvalidCandidate::BadSignature => 11,
+			InvalidCandidate::ParaHeadHashMismatch => 12,
+			InvalidCandidate::CodeHashMismatch => 13,
+			InvalidCandidate::PoVSizeExceeded(_) => 14,
+			InvalidCandidate::NonDeterministic => 15,
+		}
+	}
 }
 
 /// Result of the validation of the candidate.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ValidationResult {
-	/// Candidate is valid. The validation process yields these outputs and the persisted validation
-	/// data used to form inputs.
-	Valid(CandidateCommitments, PersistedValidationData),
+	/// Candidate is valid. The validation process yields these outputs, the persisted validation
+	/// data used to form inputs and the hash of the validation code that was executed.
+	Valid(CandidateCommitments, PersistedValidationData, ValidationCodeHash),
 	/// Candidate is invalid.
 	Invalid(InvalidCandidate),
+	/// The inputs of the candidate are identical to the ones it was last validated with, so it
+	/// wasn't validated again. Only returned to requests explicitly asking for it.
+	Unchanged,
+}
+
+impl ValidationResult {
+	/// The stable numeric code of the verdict: `0` if the candidate is valid, otherwise the
+	/// [`InvalidCandidate::verdict_code`] of the reason it is invalid for.
+	///
+	/// Negative codes are reserved for results that don't carry a verdict: validations that failed
+	/// for internal reasons and [`ValidationResult::Unchanged`], which is `-2`.
+	pub fn verdict_code(&self) -> i32 {
+		match self {
+			ValidationResult::Valid(_, _, _) => 0,
+			ValidationResult::Invalid(reason) => reason.verdict_code(),
+			ValidationResult::Unchanged => -2,
+		}
+	}
 }
 
 /// A Proof-of-Validity
@@ -405,3 +471,113 @@ impl SignedDisputeStatement {
 		self.session_index
 	}
 }
+
+/// A compact proof of a validator having validated a candidate, which can be gossiped as evidence
+/// of the validation and verified by anyone knowing the public key of the validator.
+///
+/// Its serialized form is its SCALE encoding: the candidate hash, the outcome of the validation
+/// (`true` if the candidate is valid), the hash of the validation code the candidate was validated
+/// against, the public key of the validator and its signature, in that order. The signed payload
+/// is described by [`ValidationProof::payload_data`].
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct ValidationProof {
+	candidate_hash: CandidateHash,
+	valid: bool,
+	validation_code_hash: ValidationCodeHash,
+	validator_public: ValidatorId,
+	validator_signature: ValidatorSignature,
+}
+
+impl ValidationProof {
+	/// Get the payload signed by the validator: the SCALE encoding of the magic `b"PVAL"`, followed
+	/// by the candidate hash, the outcome of the validation and the hash of the validation code.
+	pub fn payload_data(
+		candidate_hash: CandidateHash,
+		valid: bool,
+		validation_code_hash: ValidationCodeHash,
+	) -> Vec<u8> {
+		const MAGIC: [u8; 4] = *b"PVAL";
+
+		(MAGIC, candidate_hash, valid, validation_code_hash).encode()
+	}
+
+	/// Sign a proof of the given outcome of the validation of a candidate with the given keystore
+	/// and key. Returns `None` if the key is not in the keystore.
+	pub async fn sign(
+		keystore: &SyncCryptoStorePtr,
+		candidate_hash: CandidateHash,
+		valid: bool,
+		validation_code_hash: ValidationCodeHash,
+		validator_public: ValidatorId,
+	) -> Result<Option<Self>, KeystoreError> {
+		let data = Self::payload_data(candidate_hash, valid, validation_code_hash);
+		let signature = CryptoStore::sign_with(
+			&**keystore,
+			ValidatorId::ID,
+			&validator_public.clone().into(),
+			&data,
+		).await?;
+
+		let signature = match signature {
+			Some(sig) => sig.try_into().map_err(|_| KeystoreError::KeyNotSupported(ValidatorId::ID))?,
+			None => return Ok(None),
+		};
+
+		Ok(Some(Self {
+			candidate_hash,
+			valid,
+			validation_code_hash,
+			validator_public,
+			validator_signature: signature,
+		}))
+	}
+
+	/// Check that this proof is about the given candidate and signed by the given validator.
+	pub fn check(
+		&self,
+		candidate_hash: CandidateHash,
+		validator_public: &ValidatorId,
+	) -> Result<(), ()> {
+		if self.candidate_hash != candidate_hash || &self.validator_public != validator_public {
+			return Err(());
+		}
+
+		self.check_signature()
+	}
+
+	/// Check the signature of this proof against the public key it carries.
+	pub fn check_signature(&self) -> Result<(), ()> {
+		let payload = Self::payload_data(self.candidate_hash, self.valid, self.validation_code_hash);
+
+		if self.validator_signature.verify(&payload[..], &self.validator_public) {
+			Ok(())
+		} else {
+			Err(())
+		}
+	}
+
+	/// Access the hash of the candidate the proof is about.
+	pub fn candidate_hash(&self) -> &CandidateHash {
+		&self.candidate_hash
+	}
+
+	/// Whether the candidate was found to be valid.
+	pub fn is_valid(&self) -> bool {
+		self.valid
+	}
+
+	/// Access the hash of the validation code the candidate was validated against.
+	pub fn validation_code_hash(&self) -> &ValidationCodeHash {
+		&self.validation_code_hash
+	}
+
+	/// Access the public key of the validator that signed the proof.
+	pub fn validator_public(&self) -> &ValidatorId {
+		&self.validator_public
+	}
+
+	/// Access the signature of the validator.
+	pub fn validator_signature(&self) -> &ValidatorSignature {
+		&self.validator_signature
+	}
+}
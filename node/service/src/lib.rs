@@ -652,6 +652,50 @@ pub fn new_full<RuntimeApi, Executor, OverseerGenerator>(
 			None => std::env::current_exe()?,
 			Some(p) => p,
 		},
+		worker_idle_timeout: None,
+		max_workers: None,
+		execution_timeout: polkadot_node_core_candidate_validation::DEFAULT_EXECUTION_TIMEOUT,
+		approval_execution_timeout:
+			polkadot_node_core_candidate_validation::DEFAULT_APPROVAL_EXECUTION_TIMEOUT,
+		preparation_timeout: polkadot_node_core_candidate_validation::DEFAULT_PREPARATION_TIMEOUT,
+		prewarm_on_startup: false,
+		max_artifacts_cache_size: None,
+		execution_memory_limit: None,
+		validation_data_retry_delay: None,
+		published_head_data_limit: None,
+		rate_limits: Default::default(),
+		defer_output_checks: false,
+		max_assumptions_to_try: None,
+		check_free_assumption: true,
+		prefetch_validation_code: false,
+		dispute_policy: None,
+		unclaimed_results_capacity: None,
+		execution_cache_size: None,
+		precheck_code_cache_size: None,
+		pvf_hosts: 1,
+		max_concurrent_validations:
+			polkadot_node_core_candidate_validation::DEFAULT_MAX_CONCURRENT_VALIDATIONS,
+		shutdown_grace_period: polkadot_node_core_candidate_validation::DEFAULT_SHUTDOWN_GRACE_PERIOD,
+		pov_preview_len: 0,
+		span_log: None,
+		max_preparation_cost: None,
+		pov_decompression_timeout: None,
+		validation_code_bomb_limit: None,
+		max_compressed_code_size: None,
+		pov_bomb_limit: None,
+		max_concurrent_decompressions: None,
+		worker_respawn_backoff: None,
+		pvf_host_restart_backoff: Some(
+			polkadot_node_core_candidate_validation::DEFAULT_PVF_HOST_RESTART_BACKOFF,
+		),
+		ambiguous_worker_death_retries:
+			polkadot_node_core_candidate_validation::DEFAULT_AMBIGUOUS_WORKER_DEATH_RETRIES,
+		differential_execution: false,
+		disabled_host_functions: Vec::new(),
+		metric_labels: Default::default(),
+		active_validator_check: None,
+		keystore: Some(keystore_container.sync_keystore()),
+		record_sink: Arc::new(polkadot_node_core_candidate_validation::NoopRecordSink),
 	};
 
 	let chain_spec = config.chain_spec.cloned_box();
@@ -22,9 +22,14 @@
 //!
 //! Subsystems' APIs are defined separately from their implementation, leading to easier mocking.
 
-use std::{collections::btree_map::BTreeMap, sync::Arc};
+use std::{
+	collections::btree_map::BTreeMap,
+	sync::Arc,
+	time::{Duration, Instant, SystemTime},
+};
 
 use futures::channel::{mpsc, oneshot};
+use parity_scale_codec::{Decode, Encode};
 use thiserror::Error;
 
 pub use sc_network::IfDisconnected;
@@ -37,16 +42,17 @@ use polkadot_node_network_protocol::{
 use polkadot_node_primitives::{
 	approval::{BlockApprovalMeta, IndirectAssignmentCert, IndirectSignedApprovalVote},
 	AvailableData, BabeEpoch, CandidateVotes, CollationGenerationConfig, ErasureChunk, PoV,
-	SignedDisputeStatement, SignedFullStatement, ValidationResult,
+	InvalidCandidate, SignedDisputeStatement, SignedFullStatement, ValidationProof,
+	ValidationResult,
 };
 use polkadot_primitives::v1::{
-	AuthorityDiscoveryId, BackedCandidate, BlockNumber, CandidateDescriptor, CandidateEvent,
-	CandidateHash, CandidateIndex, CandidateReceipt, CollatorId, CommittedCandidateReceipt,
-	CoreState, GroupIndex, GroupRotationInfo, Hash, Header as BlockHeader, Id as ParaId,
-	InboundDownwardMessage, InboundHrmpMessage, MultiDisputeStatementSet, OccupiedCoreAssumption,
-	PersistedValidationData, SessionIndex, SessionInfo, SignedAvailabilityBitfield,
-	SignedAvailabilityBitfields, ValidationCode, ValidationCodeHash, ValidatorId, ValidatorIndex,
-	ValidatorSignature,
+	AuthorityDiscoveryId, BackedCandidate, BlakeTwo256, BlockNumber, CandidateDescriptor,
+	CandidateEvent, CandidateHash, CandidateIndex, CandidateReceipt, CollatorId,
+	CommittedCandidateReceipt, CoreState, GroupIndex, GroupRotationInfo, Hash, HashT, HeadData,
+	Header as BlockHeader, Id as ParaId, InboundDownwardMessage, InboundHrmpMessage,
+	MultiDisputeStatementSet, OccupiedCoreAssumption, PersistedValidationData, SessionIndex,
+	SessionInfo, SignedAvailabilityBitfield, SignedAvailabilityBitfields, ValidationCode,
+	ValidationCodeHash, ValidatorId, ValidatorIndex, ValidatorSignature,
 };
 use polkadot_procmacro_subsystem_dispatch_gen::subsystem_dispatch_gen;
 use polkadot_statement_table::v1::Misbehavior;
@@ -86,9 +92,462 @@ impl BoundToRelayParent for CandidateBackingMessage {
 }
 
 /// Blanket error for validation failing for internal reasons.
-#[derive(Debug, Error)]
-#[error("Validation failed with {0:?}")]
-pub struct ValidationFailed(pub String);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationFailed {
+	/// A request to the runtime API couldn't be served.
+	RuntimeApi(String),
+	/// The validation couldn't be handed to the PVF validation host.
+	PvfHostUnavailable(String),
+	/// The validation was cancelled before it concluded.
+	Cancelled,
+	/// The runtime API request for checking the occupied core assumptions of the candidate failed.
+	AssumptionCheck(AssumptionCheckFailure),
+	/// The runtime API request for checking the outputs of the candidate failed.
+	OutputsCheck,
+	/// Too many validations were in flight to take on another one. The requester should back off.
+	Overloaded,
+	/// The subsystem concluded before the validation did, e.g. as the node is shutting down. This
+	/// says nothing about the candidate, so it must not be taken for a verdict on it.
+	ShuttingDown,
+	/// Any other internal reason, described by the string.
+	Other(String),
+}
+
+impl std::fmt::Display for ValidationFailed {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "Validation failed with {:?}", self.description())
+	}
+}
+
+impl std::error::Error for ValidationFailed {}
+
+impl ValidationFailed {
+	/// The description of the reason the validation failed.
+	pub fn description(&self) -> &str {
+		match self {
+			ValidationFailed::RuntimeApi(e)
+			| ValidationFailed::PvfHostUnavailable(e)
+			| ValidationFailed::Other(e) => e,
+			ValidationFailed::Cancelled => "validation was cancelled",
+			ValidationFailed::AssumptionCheck(_) => "Assumption Check: Bad request",
+			ValidationFailed::OutputsCheck => "Check Validation Outputs: Bad request",
+			ValidationFailed::Overloaded => "too many validations in flight",
+			ValidationFailed::ShuttingDown => "the subsystem is shutting down",
+		}
+	}
+
+	/// The stable numeric code of a validation that failed for internal reasons. It is negative,
+	/// so that it can't be confused with the [`ValidationResult::verdict_code`] of an outcome, and
+	/// skips `-2`, which is [`ValidationResult::Unchanged`].
+	///
+	/// | Reason               | Code |
+	/// |----------------------|------|
+	/// | `Other`              | -1   |
+	/// | `RuntimeApi`         | -3   |
+	/// | `PvfHostUnavailable` | -4   |
+	/// | `Cancelled`          | -5   |
+	/// | `AssumptionCheck`    | -6   |
+	/// | `OutputsCheck`       | -7   |
+	/// | `Overloaded`         | -8   |
+	/// | `ShuttingDown`       | -9   |
+	pub fn verdict_code(&self) -> i32 {
+		match self {
+			ValidationFailed::Other(_) => -1,
+			ValidationFailed::RuntimeApi(_) => -3,
+			ValidationFailed::PvfHostUnavailable(_) => -4,
+			ValidationFailed::Cancelled => -5,
+			ValidationFailed::AssumptionCheck(_) => -6,
+			ValidationFailed::OutputsCheck => -7,
+			ValidationFailed::Overloaded => -8,
+			ValidationFailed::ShuttingDown => -9,
+		}
+	}
+}
+
+/// The runtime API request a check of the occupied core assumptions of a candidate failed on, and
+/// whether it returned an error or nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssumptionCheckFailure {
+	/// The version of the parachain host runtime API of the relay-parent couldn't be requested.
+	VersionError,
+	/// No persisted validation data was returned for the para under the assumption.
+	ValidationDataMissing,
+	/// The request for the persisted validation data returned an error.
+	ValidationDataError,
+	/// No validation code was returned for the para under the matching assumption.
+	ValidationCodeMissing,
+	/// The request for the validation code returned an error.
+	ValidationCodeError,
+}
+
+/// The subsystem a validation request originates from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValidationSource {
+	/// The candidate backing subsystem.
+	Backing,
+	/// The approval voting subsystem.
+	ApprovalVoting,
+	/// The dispute participation subsystem.
+	DisputeParticipation,
+	/// Any other or an unspecified source.
+	Other,
+}
+
+impl Default for ValidationSource {
+	fn default() -> Self {
+		Self::Other
+	}
+}
+
+/// The kind of validation a candidate is executed for, selecting the time allotted for the
+/// execution of its PVF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionKind {
+	/// Validation for backing, which has a tight time budget.
+	Backing,
+	/// Validation for approval checking, which can afford a longer execution.
+	Approval,
+	/// Validation for participating in a dispute, which can afford a longer execution, so that an
+	/// honest but slow candidate isn't concluded to be invalid.
+	Dispute,
+}
+
+impl Default for ExecutionKind {
+	fn default() -> Self {
+		Self::Backing
+	}
+}
+
+impl From<ValidationSource> for ExecutionKind {
+	fn from(source: ValidationSource) -> Self {
+		match source {
+			ValidationSource::Backing | ValidationSource::Other => Self::Backing,
+			ValidationSource::ApprovalVoting => Self::Approval,
+			ValidationSource::DisputeParticipation => Self::Dispute,
+		}
+	}
+}
+
+/// The priority with which the PVF of a candidate is executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PvfExecPriority {
+	/// For validations that need to be done pretty quick, but don't hold up anything else.
+	Normal,
+	/// For validations on the critical path, which are executed ahead of the others.
+	Critical,
+}
+
+impl Default for PvfExecPriority {
+	fn default() -> Self {
+		Self::Normal
+	}
+}
+
+/// Per-request options of a validation request.
+#[derive(Debug, Default)]
+pub struct ValidationOptions {
+	/// The subsystem the request originates from.
+	pub source: ValidationSource,
+	/// If provided and the subsystem is configured to defer output checks, a valid result of a
+	/// `ValidateFromChainState` request is returned before checking the outputs against the
+	/// acceptance criteria. The outcome of that check is sent here once it is performed at the
+	/// next block boundary: `true` if the outputs are acceptable.
+	pub deferred_output_check: Option<oneshot::Sender<Result<bool, ValidationFailed>>>,
+	/// If provided, a `ValidateFromChainState` request is served from this snapshot of the
+	/// runtime state instead of querying the runtime API, e.g. to replay a past validation.
+	pub state_snapshot: Option<RuntimeStateSnapshot>,
+	/// If provided, the PoV is checked against this checksum, as computed by
+	/// [`pov_integrity_checksum`] when the PoV was fetched, before it is decompressed.
+	pub pov_checksum: Option<Hash>,
+	/// Whether to emit detailed trace-level events about this validation. These are logged under
+	/// a dedicated target, so that it can be enabled permanently without flooding the logs with
+	/// details of other validations.
+	pub verbose: bool,
+	/// If provided, a report of every step taken while validating the candidate is sent here once
+	/// the validation concluded. No report is sent for results served without validating the
+	/// candidate again.
+	pub explain: Option<oneshot::Sender<Vec<ValidationStep>>>,
+	/// Whether to answer with [`ValidationResult::Unchanged`] instead of validating the candidate
+	/// again, if its inputs are identical to the ones of the last request for it that asked for
+	/// this. Only honoured by `ValidateFromExhaustive` requests, whose inputs are all known upfront.
+	pub if_changed: bool,
+	/// If provided, the occupied core assumption under which the persisted validation data of a
+	/// candidate validated from chain state was found is sent here. `Included` means the
+	/// candidate builds on a predecessor pending availability on the core of its para, whereas
+	/// `TimedOut` means it builds on the head of its para as of the relay-parent, like a
+	/// candidate scheduled onto a free core does. Dropped without an answer if no assumption
+	/// matched.
+	pub matched_assumption: Option<oneshot::Sender<OccupiedCoreAssumption>>,
+	/// If provided, the persisted validation data a candidate validated from chain state was
+	/// found to be based on is sent here, whatever the outcome of its validation, so that an
+	/// invalid candidate can be diagnosed against the data it was validated with. Dropped without
+	/// an answer if no assumption matched.
+	pub validation_data: Option<oneshot::Sender<PersistedValidationData>>,
+	/// The time the PoV of the candidate was fetched at, if known. The time from then until the
+	/// validation completed is observed, exposing the latency of the whole pipeline rather than
+	/// just of the validation.
+	pub fetched_at: Option<Instant>,
+	/// If provided, the fingerprint of the execution of the PVF is sent here once it succeeded.
+	/// Identical executions yield identical fingerprints, so comparing them across validators
+	/// spots non-deterministic execution. Dropped without an answer if the PVF wasn't executed
+	/// successfully.
+	pub fingerprint: Option<oneshot::Sender<Hash>>,
+	/// The priority with which the PVF is executed. Requests that don't care are executed with
+	/// [`PvfExecPriority::Normal`].
+	pub priority: PvfExecPriority,
+	/// The kind of validation the candidate is executed for. [`ValidationOptions::new`] derives it
+	/// from the source of the request.
+	pub execution_kind: ExecutionKind,
+	/// Labels describing the request, e.g. `network=kusama`. They are logged along with the outcome
+	/// of the validation, and those the subsystem is configured to do so for are attached to the
+	/// metrics. Keep them few, as they are copied for every observation.
+	pub labels: BTreeMap<String, String>,
+	/// If provided and the subsystem is configured with a keystore, a proof of the outcome of the
+	/// validation signed by the requested validator key is sent here once the validation
+	/// concluded. Dropped without an answer if no outcome was concluded, the key is not in the
+	/// keystore, or the candidate turned out not to have the requested hash.
+	pub proof: Option<ProofRequest>,
+	/// Whether to check the outputs of a valid candidate against the acceptance criteria of the
+	/// runtime at its relay-parent, as is always done for `ValidateFromChainState` requests. Only
+	/// honoured by `ValidateFromExhaustive` requests, whose callers may not have a live
+	/// relay-parent, so it is off by default.
+	pub check_outputs: bool,
+	/// If provided, the validation code is checked to decompress to code with this BLAKE2-256
+	/// hash, e.g. as known from an earlier validation with the same code, before it is executed.
+	/// A mismatch fails the validation rather than rendering the candidate invalid, as the code
+	/// itself is checked against the hash committed to by the candidate.
+	pub decompressed_code_hash: Option<Hash>,
+	/// If provided, the hash of the decompressed validation code of a PVF prepared before, e.g.
+	/// when pre-checking it. The PVF is then executed from its prepared artifact rather than
+	/// handed to the validation host as code, falling back to the latter if the artifact is gone.
+	/// Ignored if the validation code doesn't decompress to code with this hash. Only honoured by
+	/// `ValidateFromExhaustive` requests.
+	pub prepared_artifact: Option<ValidationCodeHash>,
+	/// If provided, the PVF may draw randomness through the offchain `random_seed` host function,
+	/// which yields this seed, so that executions with the same seed are reproducible. This is a
+	/// testing affordance only: PVFs have no access to randomness in consensus, so the outcome of
+	/// a seeded validation must never be acted upon. Seeded validations are neither served from
+	/// nor remembered by the caches of the subsystem.
+	pub execution_seed: Option<[u8; 32]>,
+}
+
+/// A request for a signed [`ValidationProof`] of the outcome of a validation.
+#[derive(Debug)]
+pub struct ProofRequest {
+	/// The hash of the candidate being validated. Valid candidates are only vouched for if their
+	/// commitments match it.
+	pub candidate_hash: CandidateHash,
+	/// The key of the validator to sign the proof with.
+	pub validator: ValidatorId,
+	/// The channel the proof is sent to.
+	pub response: oneshot::Sender<ValidationProof>,
+}
+
+/// A step taken while validating a candidate, as reported to requests asking for an explanation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationStep {
+	/// The persisted validation data the candidate is based on was found under the given occupied
+	/// core assumption.
+	AssumptionMatched(OccupiedCoreAssumption),
+	/// The persisted validation data the candidate is based on, as found under the matching
+	/// occupied core assumption.
+	ValidationDataFound(PersistedValidationData),
+	/// None of the attempted occupied core assumptions yields the persisted validation data the
+	/// candidate is based on.
+	NoAssumptionMatched,
+	/// The basic checks of the candidate were performed. Contains the reason the candidate failed
+	/// them for, if it did.
+	BasicChecks(Option<String>),
+	/// The validation code was decompressed. The decompressed size is `None` if it failed.
+	CodeDecompressed {
+		/// The size of the validation code as provided.
+		compressed_size: usize,
+		/// The size of the decompressed validation code.
+		decompressed_size: Option<usize>,
+	},
+	/// The PoV was decompressed. The decompressed size is `None` if it failed or timed out.
+	PoVDecompressed {
+		/// The size of the block data as provided.
+		compressed_size: usize,
+		/// The size of the decompressed block data.
+		decompressed_size: Option<usize>,
+	},
+	/// The PVF was executed. Contains the error reported by the execution, if any.
+	Executed(Option<String>),
+	/// The execution of the PVF succeeded with the given fingerprint: the hash of the canonical
+	/// encoding of the outputs it produced.
+	Fingerprinted(Hash),
+	/// The outputs of the candidate were checked against the acceptance criteria. Contains whether
+	/// they are acceptable.
+	OutputsChecked(bool),
+}
+
+/// Compute the checksum of the raw block data of a PoV, used for verifying its integrity after
+/// it was transported.
+pub fn pov_integrity_checksum(pov: &PoV) -> Hash {
+	BlakeTwo256::hash(&pov.block_data.0)
+}
+
+impl ValidationOptions {
+	/// Create the options for a request originating from the given source.
+	pub fn new(source: ValidationSource) -> Self {
+		ValidationOptions { source, execution_kind: source.into(), ..Default::default() }
+	}
+}
+
+/// A snapshot of the runtime state of the relay parent of a candidate, as far as it is relevant
+/// for validating the candidate.
+///
+/// It can be serialized in order to replay the validation of a candidate at a later point.
+#[derive(Debug, Clone, Default, PartialEq, Encode, Decode)]
+pub struct RuntimeStateSnapshot {
+	/// The persisted validation data of the para under each captured occupied core assumption.
+	pub persisted_validation_data: Vec<(OccupiedCoreAssumption, PersistedValidationData)>,
+	/// The validation code of the para under each captured occupied core assumption.
+	pub validation_code: Vec<(OccupiedCoreAssumption, ValidationCode)>,
+}
+
+impl RuntimeStateSnapshot {
+	/// The captured persisted validation data under the given assumption, if any.
+	pub fn persisted_validation_data(
+		&self,
+		assumption: OccupiedCoreAssumption,
+	) -> Option<&PersistedValidationData> {
+		self.persisted_validation_data
+			.iter()
+			.find(|(a, _)| *a == assumption)
+			.map(|(_, d)| d)
+	}
+
+	/// The captured validation code under the given assumption, if any.
+	pub fn validation_code(&self, assumption: OccupiedCoreAssumption) -> Option<&ValidationCode> {
+		self.validation_code
+			.iter()
+			.find(|(a, _)| *a == assumption)
+			.map(|(_, c)| c)
+	}
+}
+
+/// The effective limits the candidate validation subsystem is running with.
+///
+/// Note that the maximum PoV size is not part of these, since it is a parameter of the
+/// relay-chain and is taken from the `PersistedValidationData` of each candidate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationLimits {
+	/// The maximum size of the decompressed validation code.
+	pub validation_code_bomb_limit: usize,
+	/// The maximum size of the decompressed PoV.
+	pub pov_bomb_limit: usize,
+	/// The time allotted for a single PVF execution.
+	pub execution_timeout: Duration,
+	/// The time after which an idle execution worker is terminated, if any.
+	pub worker_idle_timeout: Option<Duration>,
+	/// The amount of memory in bytes a single PVF execution may use, if limited.
+	pub execution_memory_limit: Option<u64>,
+}
+
+/// The readiness of the candidate validation subsystem to serve validations, meant for health
+/// checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Readiness {
+	/// The validation hosts are being started.
+	Starting,
+	/// Validations are being served.
+	Ready,
+	/// Validations are being served, but many of the recent ones failed, e.g. due to failing
+	/// workers.
+	Degraded,
+	/// No validation host is running, so no validations can be served.
+	Unavailable,
+}
+
+/// The health of the PVF validation host behind the candidate validation subsystem, meant for
+/// alerting on failing hosts before backing suffers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationHealth {
+	/// Whether any validation host is running.
+	pub host_running: bool,
+	/// The number of execution workers that died ambiguously within the last ten minutes.
+	pub recent_worker_deaths: usize,
+}
+
+/// An event describing a completed validation, published to the outcome subscribers of the
+/// candidate validation subsystem.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationOutcomeEvent {
+	/// The para the candidate belongs to.
+	pub para_id: ParaId,
+	/// The relay-parent the candidate is based on.
+	pub relay_parent: Hash,
+	/// The hash of the candidate's PoV.
+	pub pov_hash: Hash,
+	/// The outcome of the validation.
+	pub outcome: PublishedOutcome,
+	/// The wall-clock time at which the validation completed.
+	pub completed_at: SystemTime,
+}
+
+/// How severely an invalid candidate should be treated by the dispute logic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisputeSeverity {
+	/// The invalidity doesn't warrant raising a dispute.
+	Ignore,
+	/// A dispute should be raised.
+	Dispute,
+	/// A dispute should be raised and escalated with priority.
+	Escalate,
+}
+
+impl Default for DisputeSeverity {
+	fn default() -> Self {
+		Self::Dispute
+	}
+}
+
+/// The outcome of a validation as published to the outcome subscribers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PublishedOutcome {
+	/// The candidate is valid. The head data may be truncated, depending on the subsystem
+	/// configuration, while `head_data_hash` always refers to the full head data.
+	Valid {
+		/// The produced head data, possibly truncated.
+		head_data: HeadData,
+		/// The hash of the full produced head data.
+		head_data_hash: Hash,
+	},
+	/// The candidate is invalid.
+	Invalid {
+		/// The description of the reason.
+		reason: String,
+		/// The severity of the invalidity according to the configured dispute policy.
+		severity: DisputeSeverity,
+	},
+	/// The candidate could not be validated for internal reasons.
+	Failed(String),
+	/// The candidate wasn't validated again, since its inputs are unchanged.
+	Unchanged,
+}
+
+/// A request to resolve an input of the candidate with the given hash. Answered with `None` if the
+/// input can't be resolved.
+pub type ResolveRequest<T> = (CandidateHash, oneshot::Sender<Option<T>>);
+
+/// The channels through which the candidate validation subsystem requests the inputs of a
+/// candidate it was only given the hash of.
+///
+/// Each input is requested only once it is needed, so that later inputs aren't fetched if the
+/// candidate is already known to be invalid.
+#[derive(Debug)]
+pub struct CandidateResolvers {
+	/// Resolves the descriptor of the candidate.
+	pub descriptor: mpsc::Sender<ResolveRequest<CandidateDescriptor>>,
+	/// Resolves the validation code the candidate is validated against.
+	pub validation_code: mpsc::Sender<ResolveRequest<ValidationCode>>,
+	/// Resolves the persisted validation data the candidate is based on.
+	pub persisted_validation_data: mpsc::Sender<ResolveRequest<PersistedValidationData>>,
+	/// Resolves the PoV of the candidate.
+	pub pov: mpsc::Sender<ResolveRequest<Arc<PoV>>>,
+}
 
 /// Messages received by the Validation subsystem.
 ///
@@ -112,6 +571,19 @@ pub enum CandidateValidationMessage {
 	ValidateFromChainState(
 		CandidateDescriptor,
 		Arc<PoV>,
+		ValidationOptions,
+		oneshot::Sender<Result<ValidationResult, ValidationFailed>>,
+	),
+	/// Like `ValidateFromChainState`, but with the block data of the PoV arriving in chunks
+	/// through the receiver, ending once its sender is dropped.
+	///
+	/// The PoV is assembled while the validation data is fetched, and renders the candidate
+	/// invalid as soon as it grows larger than the PoV size limit. The integrity checksum of the
+	/// options, if any, is checked once the PoV is assembled.
+	ValidateFromChainStateStreamed(
+		CandidateDescriptor,
+		mpsc::Receiver<Vec<u8>>,
+		ValidationOptions,
 		oneshot::Sender<Result<ValidationResult, ValidationFailed>>,
 	),
 	/// Validate a candidate with provided, exhaustive parameters for validation.
@@ -128,16 +600,91 @@ pub enum CandidateValidationMessage {
 		ValidationCode,
 		CandidateDescriptor,
 		Arc<PoV>,
+		ValidationOptions,
+		oneshot::Sender<Result<ValidationResult, ValidationFailed>>,
+	),
+	/// Validate a candidate known only by its hash, with the exhaustive parameters for validation
+	/// requested through the provided resolvers.
+	///
+	/// Like `ValidateFromExhaustive`, this doesn't involve acceptance criteria checking. If an
+	/// input can't be resolved, an error is returned.
+	ValidateFromHash(
+		CandidateHash,
+		CandidateResolvers,
+		ValidationOptions,
 		oneshot::Sender<Result<ValidationResult, ValidationFailed>>,
 	),
+	/// Validate several candidates with provided, exhaustive parameters for validation, like
+	/// `ValidateFromExhaustive` with the default options.
+	///
+	/// Validation code shared by several candidates of the batch is only decompressed once. The
+	/// results are returned in the order of the candidates.
+	ValidateBatch(
+		Vec<(PersistedValidationData, ValidationCode, CandidateDescriptor, Arc<PoV>)>,
+		oneshot::Sender<Vec<Result<ValidationResult, ValidationFailed>>>,
+	),
+	/// Perform only the basic checks of a candidate against the given maximum PoV size, without
+	/// decompressing anything or executing the PVF.
+	///
+	/// This is cheap enough for rejecting obviously malformed candidates, e.g. before gossiping
+	/// them. Passing the checks doesn't imply the candidate is valid.
+	BasicCheck(
+		CandidateDescriptor,
+		Arc<PoV>,
+		ValidationCode,
+		u32,
+		oneshot::Sender<Result<(), InvalidCandidate>>,
+	),
+	/// Get the limits the subsystem is currently running with.
+	GetLimits(oneshot::Sender<ValidationLimits>),
+	/// Get the current readiness of the subsystem to serve validations.
+	GetReadiness(oneshot::Sender<Readiness>),
+	/// Get the current health of the validation host.
+	QueryHealth(oneshot::Sender<ValidationHealth>),
+	/// Subscribe to the outcomes of all subsequent validations.
+	///
+	/// Events are dropped for subscribers that don't keep up, and the subscription ends when the
+	/// receiver is dropped.
+	SubscribeOutcomes(mpsc::Sender<ValidationOutcomeEvent>),
+	/// Delete all prepared PVF artifacts, so that they are prepared afresh when needed next. The
+	/// artifacts of executions queued or running are kept.
+	///
+	/// Responds with the number of removed artifacts.
+	FlushArtifactCache(oneshot::Sender<usize>),
+	/// Check that the validation code with the given hash, as known at the given relay-parent,
+	/// compiles under the limits of validation, without executing it. This is used for voting on
+	/// upgrades of the validation code.
+	PreCheck(Hash, ValidationCodeHash, oneshot::Sender<PreCheckOutcome>),
+}
+
+/// The outcome of pre-checking validation code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreCheckOutcome {
+	/// The validation code compiles.
+	Valid,
+	/// The validation code fails to decompress or to compile.
+	Invalid,
+	/// The validation code couldn't be checked, e.g. because it isn't available at the
+	/// relay-parent or the preparation worker died. This doesn't say anything about its validity.
+	Failed,
 }
 
 impl CandidateValidationMessage {
 	/// If the current variant contains the relay parent hash, return it.
 	pub fn relay_parent(&self) -> Option<Hash> {
 		match self {
-			Self::ValidateFromChainState(_, _, _) => None,
-			Self::ValidateFromExhaustive(_, _, _, _, _) => None,
+			Self::ValidateFromChainState(_, _, _, _) => None,
+			Self::ValidateFromChainStateStreamed(_, _, _, _) => None,
+			Self::ValidateFromExhaustive(_, _, _, _, _, _) => None,
+			Self::ValidateFromHash(_, _, _, _) => None,
+			Self::ValidateBatch(_, _) => None,
+			Self::BasicCheck(_, _, _, _, _) => None,
+			Self::GetLimits(_) => None,
+			Self::GetReadiness(_) => None,
+			Self::QueryHealth(_) => None,
+			Self::SubscribeOutcomes(_) => None,
+			Self::FlushArtifactCache(_) => None,
+			Self::PreCheck(relay_parent, _, _) => Some(*relay_parent),
 		}
 	}
 }
@@ -551,6 +1098,9 @@ pub enum RuntimeApiRequest {
 	),
 	/// Get information about the BABE epoch the block was included in.
 	CurrentBabeEpoch(RuntimeApiSender<BabeEpoch>),
+	/// Get the version of the `ParachainHost` runtime API exposed by the block. This fails if the
+	/// runtime of the block doesn't expose the API at all.
+	Version(RuntimeApiSender<u32>),
 }
 
 /// A message to the Runtime API subsystem.